@@ -0,0 +1,129 @@
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use pngme_lib::{
+    builder::PngBuilder,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    events::{parse_events, PngEvent, PngVisitor, Visit},
+    png::{ChunkReader, ParseOptions, Png, PngParseError},
+};
+
+/// A tiny image, a large one, and a small one carrying lots of little chunks,
+/// the way a png full of hidden messages would.
+fn inputs() -> Vec<(&'static str, Vec<u8>)> {
+    let small = PngBuilder::new(16, 16).noise().build().unwrap();
+    let large = PngBuilder::new(1024, 1024).noise().build().unwrap();
+
+    let mut many_chunks = small.clone();
+    let chunk_type = ChunkType::new_private("bnch").unwrap();
+    for index in 0..10_000u32 {
+        many_chunks.insert_chunk_ordered(Chunk::new(chunk_type, index.to_be_bytes().repeat(4)));
+    }
+
+    vec![
+        ("small", small.as_bytes()),
+        ("large", large.as_bytes()),
+        ("many_chunks", many_chunks.as_bytes()),
+    ]
+}
+
+/// Looks at every event and nothing else.
+struct Counter(usize);
+
+impl PngVisitor for Counter {
+    type Error = PngParseError;
+
+    fn visit(&mut self, event: PngEvent<'_>) -> Result<Visit, Self::Error> {
+        if let PngEvent::ChunkHeader { .. } = event {
+            self.0 += 1;
+        }
+        Ok(Visit::Continue)
+    }
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for (name, bytes) in inputs() {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("png", name), &bytes, |b, bytes| {
+            b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("png_no_crc", name), &bytes, |b, bytes| {
+            let options = ParseOptions {
+                verify_crc: false,
+                ..ParseOptions::default()
+            };
+            b.iter(|| Png::parse(black_box(bytes), options).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("chunk_reader", name),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    ChunkReader::new(black_box(bytes.as_slice()))
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap()
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("events", name), &bytes, |b, bytes| {
+            b.iter(|| {
+                let mut counter = Counter(0);
+                parse_events(black_box(bytes.as_slice()), &mut counter).unwrap();
+                counter.0
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc");
+    let chunk_type = ChunkType::new_private("bnch").unwrap();
+
+    for size in [64, 64 * 1024, 8 * 1024 * 1024] {
+        let data: Vec<u8> = (0..size).map(|index| index as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        // Chunk::new computes the crc, and the copy it takes is made outside
+        // the measurement
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |data| Chunk::new(chunk_type, data),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+
+    for (name, bytes) in inputs() {
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("as_bytes", name), &png, |b, png| {
+            b.iter(|| black_box(png).as_bytes())
+        });
+        group.bench_with_input(BenchmarkId::new("write_to", name), &png, |b, png| {
+            let mut buffer = Vec::with_capacity(bytes.len());
+            b.iter(|| {
+                buffer.clear();
+                black_box(png).write_to(&mut buffer).unwrap();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse, crc, serialize);
+criterion_main!(benches);
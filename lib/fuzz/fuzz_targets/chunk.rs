@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme_lib::chunk::Chunk;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(chunk) = Chunk::try_from(data) {
+        // A chunk only parses if it used up every byte, with a matching crc
+        assert_eq!(chunk.as_bytes(), data);
+        assert_eq!(chunk.length(), data.len() - 12);
+    }
+});
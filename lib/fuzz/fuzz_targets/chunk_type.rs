@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme_lib::chunk_type::ChunkType;
+use std::str::FromStr;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(chunk_type) = ChunkType::from_str(text) {
+            assert_eq!(chunk_type.to_string(), text);
+        }
+        let _ = ChunkType::new_private(text);
+    }
+
+    if let Ok(bytes) = <[u8; 4]>::try_from(data) {
+        if let Ok(chunk_type) = ChunkType::try_from(bytes) {
+            assert_eq!(chunk_type.bytes(), bytes);
+            assert_eq!(
+                ChunkType::from_str(&chunk_type.to_string()).ok(),
+                Some(chunk_type)
+            );
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme_lib::png::{ParseOptions, Png};
+
+fuzz_target!(|input: (ParseOptions, &[u8])| {
+    let (options, data) = input;
+
+    if let Ok((png, _warnings)) = Png::parse(data, options) {
+        // Whatever was kept has to parse again, with nothing left to skip
+        let (reparsed, warnings) = Png::parse(&png.as_bytes(), options).unwrap();
+        assert_eq!(reparsed, png);
+        assert!(warnings.is_empty());
+
+        let _ = png.validate();
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme_lib::png::Png;
+
+fuzz_target!(|png: Png| {
+    let bytes = png.as_bytes();
+    assert_eq!(Png::try_from(bytes.as_slice()).unwrap(), png);
+});
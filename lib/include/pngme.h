@@ -0,0 +1,65 @@
+/*
+ * C interface to pngme_lib, built as a shared library with
+ * `cargo rustc --release --features ffi --crate-type cdylib`.
+ *
+ * Every function returns a PNGME_* status code. Memory written to an out
+ * parameter belongs to the caller and must be released with the matching
+ * pngme_*_free function. Inputs are only borrowed for the duration of a call.
+ */
+
+#ifndef PNGME_H
+#define PNGME_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define PNGME_OK 0
+#define PNGME_ERROR_NULL_POINTER 1
+#define PNGME_ERROR_INVALID_PNG 2
+#define PNGME_ERROR_INVALID_CHUNK_TYPE 3
+#define PNGME_ERROR_CHUNK_NOT_FOUND 4
+#define PNGME_ERROR_DECOMPRESSION 5
+#define PNGME_ERROR_PANIC 6
+
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} PngmeBuffer;
+
+typedef struct {
+    uint8_t chunk_type[4];
+    uint32_t length;
+    uint32_t crc;
+} PngmeChunk;
+
+/* Stores `message` in a new chunk of type `chunk_type` and writes the
+ * resulting png to `out`. */
+int32_t pngme_encode(const uint8_t *png, size_t png_len, const char *chunk_type,
+                     const uint8_t *message, size_t message_len, PngmeBuffer *out);
+
+/* Writes the message in the first chunk of type `chunk_type` to `out`,
+ * decompressed if needed. */
+int32_t pngme_decode(const uint8_t *png, size_t png_len, const char *chunk_type,
+                     PngmeBuffer *out);
+
+/* Writes an array describing every chunk of the png to `out` and its length
+ * to `count`. */
+int32_t pngme_list_chunks(const uint8_t *png, size_t png_len, PngmeChunk **out,
+                          size_t *count);
+
+void pngme_buffer_free(PngmeBuffer buffer);
+
+void pngme_chunks_free(PngmeChunk *chunks, size_t count);
+
+/* A static description of a status code, never to be freed. */
+const char *pngme_error_message(int32_t code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PNGME_H */
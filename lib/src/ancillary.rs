@@ -0,0 +1,420 @@
+use fehler::{throw, throws};
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::{consts, ChunkType},
+    ihdr::{ColorType, Ihdr},
+    image::Image,
+};
+
+#[derive(Debug, Error)]
+pub enum AncillaryError {
+    #[error("expected a `{expected}` chunk but got `{actual}`")]
+    UnexpectedChunkType { expected: String, actual: String },
+
+    #[error("`{chunk_type}` chunk for {color_type} images can't be {length} bytes")]
+    InvalidLength {
+        chunk_type: ChunkType,
+        color_type: ColorType,
+        length: usize,
+    },
+
+    #[error("{color_type} images take {expected} values here, got {actual}")]
+    InvalidChannelCount {
+        color_type: ColorType,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("significant bits must be from 1 to {max}, got {bits}")]
+    InvalidSignificantBits { bits: u8, max: u8 },
+
+    #[error("background value {value} doesn't fit in {bit_depth} bits")]
+    InvalidBackground { value: u16, bit_depth: u8 },
+
+    #[error("hIST chunk data must be 1 to 256 entries of 2 bytes, got {0} bytes")]
+    InvalidHistogramLength(usize),
+
+    #[error("hIST chunks are only for indexed images, not {0} ones")]
+    NotIndexed(ColorType),
+}
+
+const MAX_ENTRIES: usize = 256;
+
+#[throws(AncillaryError)]
+fn check_chunk_type(chunk: &Chunk, expected: ChunkType) {
+    if *chunk.chunk_type() != expected {
+        throw!(AncillaryError::UnexpectedChunkType {
+            expected: expected.to_string(),
+            actual: chunk.chunk_type().to_string()
+        })
+    }
+}
+
+fn read_u16(data: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([data[index * 2], data[index * 2 + 1]])
+}
+
+/// Names of the channels that sBIT has a value for. Indexed images describe
+/// the red, green and blue of their palette.
+pub(crate) fn channel_names(color_type: ColorType) -> &'static [&'static str] {
+    match color_type {
+        ColorType::Grayscale => &["gray"],
+        ColorType::GrayscaleAlpha => &["gray", "alpha"],
+        ColorType::Rgb | ColorType::Indexed => &["red", "green", "blue"],
+        ColorType::Rgba => &["red", "green", "blue", "alpha"],
+    }
+}
+
+/// How many bits of each sample were significant in the original image,
+/// stored in the `sBIT` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignificantBits {
+    color_type: ColorType,
+    bits: Vec<u8>,
+}
+
+impl SignificantBits {
+    /// Takes one value per channel, in the order the channels are stored.
+    #[throws(AncillaryError)]
+    pub fn new(bits: Vec<u8>, header: &Ihdr) -> Self {
+        let color_type = header.color_type();
+        let expected = channel_names(color_type).len();
+        if bits.len() != expected {
+            throw!(AncillaryError::InvalidChannelCount {
+                color_type,
+                expected,
+                actual: bits.len()
+            })
+        }
+
+        // Palette entries are always 8 bits, whatever the index depth
+        let max = match color_type {
+            ColorType::Indexed => 8,
+            _ => header.bit_depth(),
+        };
+        if let Some(&bits) = bits.iter().find(|&&bits| !(1..=max).contains(&bits)) {
+            throw!(AncillaryError::InvalidSignificantBits { bits, max })
+        }
+
+        SignificantBits { color_type, bits }
+    }
+
+    #[throws(AncillaryError)]
+    pub fn parse(chunk: &Chunk, header: &Ihdr) -> Self {
+        check_chunk_type(chunk, consts::sBIT)?;
+
+        let data = chunk.data();
+        if data.len() != channel_names(header.color_type()).len() {
+            throw!(AncillaryError::InvalidLength {
+                chunk_type: consts::sBIT,
+                color_type: header.color_type(),
+                length: data.len()
+            })
+        }
+
+        SignificantBits::new(data.to_vec(), header)?
+    }
+
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(consts::sBIT, self.bits.clone())
+    }
+}
+
+impl Display for SignificantBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let channels: Vec<String> = channel_names(self.color_type)
+            .iter()
+            .zip(&self.bits)
+            .map(|(name, bits)| format!("{} {}", name, bits))
+            .collect();
+
+        write!(f, "{}", channels.join(", "))
+    }
+}
+
+/// The colour to show the image against, stored in the `bKGD` chunk. What it
+/// holds depends on the colour type, so it's parsed with
+/// [`BackgroundChunk::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundChunk {
+    /// An entry of the palette.
+    Indexed(u8),
+    /// A grey level, at the image's bit depth.
+    Grayscale(u16),
+    /// A colour, at the image's bit depth.
+    Rgb([u16; 3]),
+}
+
+impl BackgroundChunk {
+    /// Takes a palette index, a grey level or red, green and blue samples,
+    /// depending on the colour type of `header`.
+    #[throws(AncillaryError)]
+    pub fn new(values: &[u16], header: &Ihdr) -> Self {
+        let color_type = header.color_type();
+        let bit_depth = match color_type {
+            ColorType::Indexed => 8,
+            _ => header.bit_depth(),
+        };
+        if let Some(&value) = values
+            .iter()
+            .find(|&&value| u32::from(value) >= 1 << bit_depth)
+        {
+            throw!(AncillaryError::InvalidBackground { value, bit_depth })
+        }
+
+        match (color_type, values) {
+            (ColorType::Indexed, &[index]) => BackgroundChunk::Indexed(index as u8),
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, &[gray]) => {
+                BackgroundChunk::Grayscale(gray)
+            }
+            (ColorType::Rgb | ColorType::Rgba, &[red, green, blue]) => {
+                BackgroundChunk::Rgb([red, green, blue])
+            }
+            _ => throw!(AncillaryError::InvalidChannelCount {
+                color_type,
+                expected: match color_type {
+                    ColorType::Rgb | ColorType::Rgba => 3,
+                    _ => 1,
+                },
+                actual: values.len()
+            }),
+        }
+    }
+
+    #[throws(AncillaryError)]
+    pub fn parse(chunk: &Chunk, color_type: ColorType) -> Self {
+        check_chunk_type(chunk, consts::bKGD)?;
+
+        let data = chunk.data();
+        match (color_type, data.len()) {
+            (ColorType::Indexed, 1) => BackgroundChunk::Indexed(data[0]),
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, 2) => {
+                BackgroundChunk::Grayscale(read_u16(data, 0))
+            }
+            (ColorType::Rgb | ColorType::Rgba, 6) => {
+                BackgroundChunk::Rgb([read_u16(data, 0), read_u16(data, 1), read_u16(data, 2)])
+            }
+            (_, length) => throw!(AncillaryError::InvalidLength {
+                chunk_type: consts::bKGD,
+                color_type,
+                length
+            }),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            BackgroundChunk::Indexed(index) => vec![*index],
+            BackgroundChunk::Grayscale(gray) => gray.to_be_bytes().to_vec(),
+            BackgroundChunk::Rgb(rgb) => {
+                rgb.iter().flat_map(|sample| sample.to_be_bytes()).collect()
+            }
+        };
+
+        Chunk::new(consts::bKGD, data)
+    }
+}
+
+impl Display for BackgroundChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackgroundChunk::Indexed(index) => write!(f, "palette entry {}", index),
+            BackgroundChunk::Grayscale(gray) => write!(f, "gray {}", gray),
+            BackgroundChunk::Rgb([red, green, blue]) => {
+                write!(f, "rgb {}, {}, {}", red, green, blue)
+            }
+        }
+    }
+}
+
+/// Approximate usage of each palette entry, stored in the `hIST` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramChunk {
+    frequencies: Vec<u16>,
+}
+
+impl HistogramChunk {
+    #[throws(AncillaryError)]
+    pub fn new(frequencies: Vec<u16>) -> Self {
+        if !(1..=MAX_ENTRIES).contains(&frequencies.len()) {
+            throw!(AncillaryError::InvalidHistogramLength(
+                frequencies.len() * 2
+            ))
+        }
+
+        HistogramChunk { frequencies }
+    }
+
+    /// Counts how often each of the `entries` palette entries is used by the
+    /// pixels of an indexed `image`. Counts are scaled to fit 16 bits, keeping
+    /// entries that are used at all above zero.
+    #[throws(AncillaryError)]
+    pub fn from_image(image: &Image, entries: usize) -> Self {
+        let header = image.header();
+        if header.color_type() != ColorType::Indexed {
+            throw!(AncillaryError::NotIndexed(header.color_type()))
+        }
+
+        let bit_depth = header.bit_depth() as usize;
+        let per_byte = 8 / bit_depth;
+        let mask = ((1u16 << bit_depth) - 1) as u8;
+        let width = header.width() as usize;
+
+        let mut counts = vec![0u64; entries];
+        for row in image.rows() {
+            for pixel in 0..width {
+                let shift = 8 - bit_depth * (pixel % per_byte + 1);
+                let index = (row[pixel / per_byte] >> shift) & mask;
+                if let Some(count) = counts.get_mut(index as usize) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let max = counts.iter().copied().max().unwrap_or(0).max(1);
+        let frequencies = counts
+            .iter()
+            .map(|&count| match count {
+                0 => 0,
+                count => (count * u64::from(u16::MAX) / max).max(1) as u16,
+            })
+            .collect();
+
+        HistogramChunk::new(frequencies)?
+    }
+
+    /// One value per palette entry, in index order.
+    pub fn frequencies(&self) -> &[u16] {
+        &self.frequencies
+    }
+
+    pub fn len(&self) -> usize {
+        self.frequencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frequencies.is_empty()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self
+            .frequencies
+            .iter()
+            .flat_map(|frequency| frequency.to_be_bytes())
+            .collect();
+
+        Chunk::new(consts::hIST, data)
+    }
+}
+
+impl TryFrom<&Chunk> for HistogramChunk {
+    type Error = AncillaryError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        check_chunk_type(chunk, consts::hIST)?;
+
+        let data = chunk.data();
+        if !data.len().is_multiple_of(2) {
+            throw!(AncillaryError::InvalidHistogramLength(data.len()))
+        }
+
+        let frequencies = (0..data.len() / 2).map(|index| read_u16(data, index));
+        HistogramChunk::new(frequencies.collect())
+            .map_err(|_| AncillaryError::InvalidHistogramLength(data.len()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, ihdr::Interlace};
+
+    fn header(bit_depth: u8, color_type: ColorType) -> Ihdr {
+        Ihdr::new(4, 4, bit_depth, color_type, Interlace::None).unwrap()
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        let rgb = header(8, ColorType::Rgb);
+        let sbit = SignificantBits::new(vec![5, 6, 5], &rgb).unwrap();
+
+        assert_eq!(sbit.to_string(), "red 5, green 6, blue 5");
+        assert_eq!(
+            SignificantBits::parse(&sbit.to_chunk(), &rgb).unwrap(),
+            sbit
+        );
+        assert!(matches!(
+            SignificantBits::new(vec![5, 6], &rgb),
+            Err(AncillaryError::InvalidChannelCount {
+                expected: 3,
+                actual: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            SignificantBits::new(vec![3], &header(2, ColorType::Grayscale)),
+            Err(AncillaryError::InvalidSignificantBits { bits: 3, max: 2 })
+        ));
+        assert!(SignificantBits::new(vec![8, 8, 8], &header(1, ColorType::Indexed)).is_ok());
+    }
+
+    #[test]
+    fn test_background_chunk_by_color_type() {
+        let chunk = Chunk::new(consts::bKGD, vec![0, 200]);
+
+        assert_eq!(
+            BackgroundChunk::parse(&chunk, ColorType::GrayscaleAlpha).unwrap(),
+            BackgroundChunk::Grayscale(200)
+        );
+        assert!(matches!(
+            BackgroundChunk::parse(&chunk, ColorType::Indexed),
+            Err(AncillaryError::InvalidLength { length: 2, .. })
+        ));
+
+        let rgb = BackgroundChunk::new(&[255, 128, 0], &header(8, ColorType::Rgba)).unwrap();
+        assert_eq!(
+            BackgroundChunk::parse(&rgb.to_chunk(), ColorType::Rgba).unwrap(),
+            rgb
+        );
+        assert!(matches!(
+            BackgroundChunk::new(&[4], &header(2, ColorType::Grayscale)),
+            Err(AncillaryError::InvalidBackground {
+                value: 4,
+                bit_depth: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_histogram_from_image() {
+        // Three pixels of index 1 for every one of index 2
+        let png = PngBuilder::new(4, 2)
+            .color_type(ColorType::Indexed)
+            .bit_depth(2)
+            .pixels(vec![0b0101_0110, 0b0110_0101])
+            .build()
+            .unwrap();
+        let image = png.decode_image().unwrap();
+
+        let histogram = HistogramChunk::from_image(&image, 4).unwrap();
+        assert_eq!(histogram.frequencies(), [0, u16::MAX, 21845, 0]);
+        assert_eq!(
+            HistogramChunk::try_from(&histogram.to_chunk()).unwrap(),
+            histogram
+        );
+
+        let chunk = Chunk::new(consts::hIST, vec![0; 3]);
+        assert!(matches!(
+            HistogramChunk::try_from(&chunk),
+            Err(AncillaryError::InvalidHistogramLength(3))
+        ));
+    }
+}
@@ -0,0 +1,537 @@
+use fehler::{throw, throws};
+use std::{fmt::Display, time::Duration};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::{consts, ChunkType},
+    ihdr::{Ihdr, IhdrError},
+    image::idat_chunks,
+    png::Png,
+};
+
+#[derive(Debug, Error)]
+pub enum ApngError {
+    #[error("expected a `{expected}` chunk but got `{actual}`")]
+    UnexpectedChunkType { expected: String, actual: String },
+
+    #[error("{chunk_type} chunk data must be {expected} bytes, got {actual}")]
+    InvalidLength {
+        chunk_type: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("png has no `acTL` chunk, so it isn't animated")]
+    NotAnimated,
+
+    #[error("unknown dispose op `{0}`")]
+    UnknownDisposeOp(u8),
+
+    #[error("unknown blend op `{0}`")]
+    UnknownBlendOp(u8),
+
+    #[error("expected sequence number {expected} but got {actual}")]
+    OutOfSequence { expected: u32, actual: u32 },
+
+    #[error("frame data found before any `fcTL` chunk")]
+    DataWithoutControl,
+
+    #[error("acTL declares {declared} frames but the png has {actual}")]
+    FrameCountMismatch { declared: u32, actual: u32 },
+
+    #[error("invalid frame header")]
+    InvalidHeader(#[from] IhdrError),
+}
+
+const ANIMATION_CONTROL_LENGTH: usize = 8;
+const FRAME_CONTROL_LENGTH: usize = 26;
+
+/// Delays with a zero denominator are in hundredths of a second.
+const DEFAULT_DELAY_DENOMINATOR: u16 = 100;
+
+#[throws(ApngError)]
+fn check_chunk(chunk: &Chunk, expected: ChunkType, length: Option<usize>) {
+    if *chunk.chunk_type() != expected {
+        throw!(ApngError::UnexpectedChunkType {
+            expected: expected.to_string(),
+            actual: chunk.chunk_type().to_string()
+        })
+    }
+
+    let actual = chunk.length();
+    match length {
+        Some(expected) if actual != expected => throw!(ApngError::InvalidLength {
+            chunk_type: chunk.chunk_type().to_string(),
+            expected,
+            actual
+        }),
+        // Frame data only needs room for its sequence number
+        None if actual < 4 => throw!(ApngError::InvalidLength {
+            chunk_type: chunk.chunk_type().to_string(),
+            expected: 4,
+            actual
+        }),
+        _ => {}
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().expect("slice of length 4"))
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes(data[at..at + 2].try_into().expect("slice of length 2"))
+}
+
+/// Number of frames and plays, stored in the `acTL` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    num_frames: u32,
+    num_plays: u32,
+}
+
+impl AnimationControl {
+    pub fn new(num_frames: u32, num_plays: u32) -> Self {
+        AnimationControl {
+            num_frames,
+            num_plays,
+        }
+    }
+
+    pub fn num_frames(&self) -> u32 {
+        self.num_frames
+    }
+
+    /// How many times the animation plays, with 0 meaning forever.
+    pub fn num_plays(&self) -> u32 {
+        self.num_plays
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [self.num_frames.to_be_bytes(), self.num_plays.to_be_bytes()].concat();
+        Chunk::new(consts::acTL, data)
+    }
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = ApngError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        check_chunk(chunk, consts::acTL, Some(ANIMATION_CONTROL_LENGTH))?;
+
+        let data = chunk.data();
+        AnimationControl::new(read_u32(data, 0), read_u32(data, 4))
+    }
+}
+
+/// What happens to a frame's region before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None,
+    /// Cleared to fully transparent black.
+    Background,
+    /// Reverted to what it was before the frame was drawn.
+    Previous,
+}
+
+impl Display for DisposeOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisposeOp::None => write!(f, "none"),
+            DisposeOp::Background => write!(f, "background"),
+            DisposeOp::Previous => write!(f, "previous"),
+        }
+    }
+}
+
+/// How a frame is drawn onto the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    /// Replaces the region, alpha included.
+    Source,
+    /// Alpha composited over the region.
+    Over,
+}
+
+impl Display for BlendOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlendOp::Source => write!(f, "source"),
+            BlendOp::Over => write!(f, "over"),
+        }
+    }
+}
+
+/// Size, position, timing and compositing of one frame, stored in an `fcTL`
+/// chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    /// How long the frame is shown for.
+    pub fn delay(&self) -> Duration {
+        let denominator = match self.delay_den {
+            0 => DEFAULT_DELAY_DENOMINATOR,
+            denominator => denominator,
+        };
+
+        Duration::from_secs_f64(self.delay_num as f64 / denominator as f64)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let dispose_op = match self.dispose_op {
+            DisposeOp::None => 0,
+            DisposeOp::Background => 1,
+            DisposeOp::Previous => 2,
+        };
+        let blend_op = match self.blend_op {
+            BlendOp::Source => 0,
+            BlendOp::Over => 1,
+        };
+
+        let data = [
+            &self.sequence_number.to_be_bytes()[..],
+            &self.width.to_be_bytes(),
+            &self.height.to_be_bytes(),
+            &self.x_offset.to_be_bytes(),
+            &self.y_offset.to_be_bytes(),
+            &self.delay_num.to_be_bytes(),
+            &self.delay_den.to_be_bytes(),
+            &[dispose_op, blend_op],
+        ]
+        .concat();
+
+        Chunk::new(consts::fcTL, data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = ApngError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        check_chunk(chunk, consts::fcTL, Some(FRAME_CONTROL_LENGTH))?;
+
+        let data = chunk.data();
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            op => throw!(ApngError::UnknownDisposeOp(op)),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            op => throw!(ApngError::UnknownBlendOp(op)),
+        };
+
+        FrameControl {
+            sequence_number: read_u32(data, 0),
+            width: read_u32(data, 4),
+            height: read_u32(data, 8),
+            x_offset: read_u32(data, 12),
+            y_offset: read_u32(data, 16),
+            delay_num: read_u16(data, 20),
+            delay_den: read_u16(data, 22),
+            dispose_op,
+            blend_op,
+        }
+    }
+}
+
+/// Compressed image data of a frame after the first, stored in an `fdAT`
+/// chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameData {
+    pub sequence_number: u32,
+    pub data: Vec<u8>,
+}
+
+impl FrameData {
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [&self.sequence_number.to_be_bytes()[..], &self.data].concat();
+        Chunk::new(consts::fdAT, data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameData {
+    type Error = ApngError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        check_chunk(chunk, consts::fdAT, None)?;
+
+        FrameData {
+            sequence_number: read_u32(chunk.data(), 0),
+            data: chunk.data()[4..].to_vec(),
+        }
+    }
+}
+
+/// One frame of an animation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub control: FrameControl,
+    /// The frame's compressed image data, concatenated across its chunks.
+    pub data: Vec<u8>,
+    /// Whether the frame's data is in `IDAT` chunks, making it the image
+    /// shown by decoders that don't support animation.
+    pub is_default_image: bool,
+}
+
+impl Frame {
+    /// The frame as a png of its own, made from the header, palette and
+    /// colour chunks of `source`, the animation it came from. Only the
+    /// frame's own region is kept: it isn't composited onto earlier frames.
+    #[throws(ApngError)]
+    pub fn to_png(&self, source: &Png) -> Png {
+        let header = source.header()?;
+        let header = Ihdr::new(
+            self.control.width,
+            self.control.height,
+            header.bit_depth(),
+            header.color_type(),
+            header.interlace(),
+        )?;
+
+        // Everything between the header and the image data describes how
+        // to draw pixels, apart from the animation's own control chunks
+        let image_start = |chunk: &Chunk| {
+            *chunk.chunk_type() == consts::IDAT || *chunk.chunk_type() == consts::fcTL
+        };
+        let shared = source
+            .chunks()
+            .iter()
+            .skip(1)
+            .take_while(|chunk| !image_start(chunk))
+            .filter(|chunk| *chunk.chunk_type() != consts::acTL)
+            .cloned();
+
+        let idats = idat_chunks(&self.data);
+
+        let chunks = std::iter::once(header.to_chunk())
+            .chain(shared)
+            .chain(idats)
+            .chain(std::iter::once(Chunk::new(consts::IEND, vec![])))
+            .collect();
+
+        Png::from_chunks(chunks)
+    }
+}
+
+/// The frames of an animated png, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+pub fn is_animated(png: &Png) -> bool {
+    png.chunk_by_type("acTL").is_some()
+}
+
+impl Animation {
+    /// Collects the frames of `png`, checking that `fcTL` and `fdAT` chunks
+    /// are numbered in sequence and that every frame `acTL` declares is there.
+    #[throws(ApngError)]
+    pub fn from_png(png: &Png) -> Self {
+        let control = png.chunk_by_type("acTL").ok_or(ApngError::NotAnimated)?;
+        let control = AnimationControl::try_from(control)?;
+
+        let mut frames: Vec<Frame> = vec![];
+        let mut next_sequence_number = 0;
+        let mut check_sequence = |actual: u32| {
+            if actual != next_sequence_number {
+                return Err(ApngError::OutOfSequence {
+                    expected: next_sequence_number,
+                    actual,
+                });
+            }
+            next_sequence_number += 1;
+            Ok(())
+        };
+
+        for chunk in png.chunks() {
+            let chunk_type = *chunk.chunk_type();
+            if chunk_type == consts::fcTL {
+                let frame_control = FrameControl::try_from(chunk)?;
+                check_sequence(frame_control.sequence_number)?;
+                frames.push(Frame {
+                    control: frame_control,
+                    data: vec![],
+                    is_default_image: false,
+                });
+            } else if chunk_type == consts::fdAT {
+                let frame_data = FrameData::try_from(chunk)?;
+                check_sequence(frame_data.sequence_number)?;
+                let frame = frames.last_mut().ok_or(ApngError::DataWithoutControl)?;
+                frame.data.extend(frame_data.data);
+            } else if chunk_type == consts::IDAT {
+                // Without an fcTL first, the default image isn't a frame
+                if let Some(frame) = frames.last_mut() {
+                    frame.data.extend(chunk.data());
+                    frame.is_default_image = true;
+                }
+            }
+        }
+
+        if frames.len() != control.num_frames() as usize {
+            throw!(ApngError::FrameCountMismatch {
+                declared: control.num_frames(),
+                actual: frames.len() as u32
+            })
+        }
+
+        Animation { control, frames }
+    }
+
+    /// Time for one play through every frame.
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.control.delay()).sum()
+    }
+}
+
+/// Whether inserting a chunk at `index` would put it inside a frame, between
+/// an `fcTL` and the data it describes or between data chunks of one frame.
+/// APNG decoders expect each frame's chunks to follow on from each other.
+pub fn splits_frame(chunks: &[Chunk], index: usize) -> bool {
+    let Some(next) = chunks.get(index) else {
+        return false;
+    };
+
+    let next = *next.chunk_type();
+    next == consts::fdAT
+        || (next == consts::IDAT
+            && chunks[..index]
+                .iter()
+                .any(|chunk| *chunk.chunk_type() == consts::fcTL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{ColorType, Interlace};
+
+    fn frame_control(sequence_number: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width: 4,
+            height: 2,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 0,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Over,
+        }
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(consts::IHDR, vec![0; 13]),
+            AnimationControl::new(2, 0).to_chunk(),
+            frame_control(0).to_chunk(),
+            Chunk::new(consts::IDAT, b"first".to_vec()),
+            frame_control(1).to_chunk(),
+            FrameData {
+                sequence_number: 2,
+                data: b"sec".to_vec(),
+            }
+            .to_chunk(),
+            FrameData {
+                sequence_number: 3,
+                data: b"ond".to_vec(),
+            }
+            .to_chunk(),
+            Chunk::new(consts::IEND, vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_frame_control_roundtrip() {
+        let control = frame_control(7);
+        assert_eq!(
+            FrameControl::try_from(&control.to_chunk()).unwrap(),
+            control
+        );
+        assert_eq!(control.delay(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_animation_from_png() {
+        let animation = Animation::from_png(&testing_png()).unwrap();
+
+        assert_eq!(animation.control.num_frames(), 2);
+        assert_eq!(animation.frames[0].data, b"first");
+        assert!(animation.frames[0].is_default_image);
+        assert_eq!(animation.frames[1].data, b"second");
+        assert_eq!(animation.duration(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_animation_checks_sequence() {
+        let mut png = testing_png();
+        png.retain_chunks(|chunk| chunk.chunk_type() != &consts::fdAT || chunk.data()[3] != 2);
+
+        assert!(matches!(
+            Animation::from_png(&png),
+            Err(ApngError::OutOfSequence {
+                expected: 2,
+                actual: 3
+            })
+        ));
+        assert!(matches!(
+            Animation::from_png(&Png::from_chunks(vec![])),
+            Err(ApngError::NotAnimated)
+        ));
+    }
+
+    #[test]
+    fn test_frame_to_png() {
+        let mut source = testing_png();
+        source.remove_chunk_at(0).unwrap();
+        source
+            .insert_chunk(
+                0,
+                Ihdr::new(8, 8, 8, ColorType::Indexed, Interlace::None)
+                    .unwrap()
+                    .to_chunk(),
+            )
+            .unwrap();
+        source.insert_chunk_ordered(Chunk::new(consts::PLTE, vec![0; 3]));
+
+        let animation = Animation::from_png(&source).unwrap();
+        let frame = animation.frames[1].to_png(&source).unwrap();
+        let types: Vec<String> = frame
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(types, ["IHDR", "PLTE", "IDAT", "IEND"]);
+        assert_eq!((frame.width().unwrap(), frame.height().unwrap()), (4, 2));
+        assert_eq!(frame.image_data(), b"second");
+    }
+
+    #[test]
+    fn test_splits_frame() {
+        let png = testing_png();
+        let splits: Vec<usize> = (0..=png.chunks().len())
+            .filter(|index| splits_frame(png.chunks(), *index))
+            .collect();
+
+        assert_eq!(splits, [3, 5, 6]);
+    }
+}
@@ -0,0 +1,229 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::consts,
+    ihdr::{ColorType, Ihdr, IhdrError, Interlace},
+    image::{self, idat_chunks, Image, ImageError},
+    png::Png,
+};
+
+#[derive(Debug, Error)]
+pub enum PngBuildError {
+    #[error("invalid image header")]
+    InvalidHeader(#[from] IhdrError),
+
+    #[error("invalid image data")]
+    InvalidImage(#[from] ImageError),
+
+    #[error("a pixel takes {expected} bytes, got {actual}")]
+    InvalidPixel { expected: usize, actual: usize },
+
+    #[error("pixel data must be {expected} bytes, got {actual}")]
+    InvalidPixelData { expected: usize, actual: usize },
+}
+
+#[derive(Debug, Clone)]
+enum Pixels {
+    Solid(Vec<u8>),
+    Noise,
+    Buffer(Vec<u8>),
+}
+
+/// Creates a minimal png from scratch: a header, the image data and `IEND`.
+///
+/// Defaults to an opaque black 8-bit RGB image.
+#[derive(Debug, Clone)]
+pub struct PngBuilder {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    pixels: Pixels,
+}
+
+impl PngBuilder {
+    pub fn new(width: u32, height: u32) -> Self {
+        PngBuilder {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            pixels: Pixels::Solid(vec![0; 3]),
+        }
+    }
+
+    pub fn color_type(mut self, color_type: ColorType) -> Self {
+        self.color_type = color_type;
+        self
+    }
+
+    pub fn bit_depth(mut self, bit_depth: u8) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    /// Fills the image with one pixel, given as it's stored: big endian
+    /// samples, or a single sample value for bit depths below 8.
+    pub fn solid(mut self, pixel: &[u8]) -> Self {
+        self.pixels = Pixels::Solid(pixel.to_vec());
+        self
+    }
+
+    /// Fills the image with random pixels, which makes a good cover for a
+    /// payload hidden in the least significant bits.
+    pub fn noise(mut self) -> Self {
+        self.pixels = Pixels::Noise;
+        self
+    }
+
+    /// Uses `pixels` as the image, row after row, each laid out like a
+    /// scanline without its filter type byte.
+    pub fn pixels(mut self, pixels: Vec<u8>) -> Self {
+        self.pixels = Pixels::Buffer(pixels);
+        self
+    }
+
+    #[throws(PngBuildError)]
+    pub fn build(self) -> Png {
+        let header = Ihdr::new(
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type,
+            Interlace::None,
+        )?;
+        // Images too large to decode again aren't built either
+        image::check_size(&header, image::MAX_PIXELS)?;
+        let bits_per_pixel = header.bits_per_pixel() as usize;
+        let stride = (self.width as usize * bits_per_pixel).div_ceil(8);
+
+        let rows: Vec<Vec<u8>> = match self.pixels {
+            Pixels::Solid(pixel) => {
+                let row = solid_row(&pixel, self.width as usize, bits_per_pixel)?;
+                vec![row; self.height as usize]
+            }
+            Pixels::Noise => {
+                let mut pixels = vec![0; stride * self.height as usize];
+                OsRng.fill_bytes(&mut pixels);
+                pixels.chunks(stride).map(<[u8]>::to_vec).collect()
+            }
+            Pixels::Buffer(pixels) => {
+                let expected = stride * self.height as usize;
+                if pixels.len() != expected {
+                    throw!(PngBuildError::InvalidPixelData {
+                        expected,
+                        actual: pixels.len()
+                    })
+                }
+                pixels.chunks(stride).map(<[u8]>::to_vec).collect()
+            }
+        };
+
+        // Unfiltered rows deflate well enough for flat or generated images
+        let filters = vec![0; rows.len()];
+        let data = Image::new(header, rows, filters).encode()?;
+
+        let chunks = std::iter::once(header.to_chunk())
+            .chain(idat_chunks(&data))
+            .chain(std::iter::once(Chunk::new(consts::IEND, vec![])))
+            .collect();
+
+        Png::from_chunks(chunks)
+    }
+}
+
+/// A row of `width` copies of `pixel`, packing samples narrower than a byte.
+#[throws(PngBuildError)]
+fn solid_row(pixel: &[u8], width: usize, bits_per_pixel: usize) -> Vec<u8> {
+    let expected = bits_per_pixel.div_ceil(8);
+    if pixel.len() != expected {
+        throw!(PngBuildError::InvalidPixel {
+            expected,
+            actual: pixel.len()
+        })
+    }
+
+    if bits_per_pixel >= 8 {
+        return pixel.repeat(width);
+    }
+
+    let sample = pixel[0] & ((1 << bits_per_pixel) - 1);
+    let byte = (0..8 / bits_per_pixel).fold(0u8, |byte, _| (byte << bits_per_pixel) | sample);
+    vec![byte; (width * bits_per_pixel).div_ceil(8)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_solid() {
+        let png = PngBuilder::new(5, 3)
+            .solid(&[0x33, 0x66, 0x99])
+            .build()
+            .unwrap();
+        let image = png.decode_image().unwrap();
+
+        assert!(png.validate().is_empty());
+        assert_eq!(image.rows().len(), 3);
+        assert_eq!(image.row(2).unwrap(), [0x33, 0x66, 0x99].repeat(5));
+    }
+
+    #[test]
+    fn test_build_pixels() {
+        let pixels: Vec<u8> = (0..4 * 4 * 2).collect();
+        let png = PngBuilder::new(4, 4)
+            .color_type(ColorType::GrayscaleAlpha)
+            .pixels(pixels.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(png.decode_image().unwrap().rows().concat(), pixels);
+    }
+
+    #[test]
+    fn test_build_noise() {
+        let png = PngBuilder::new(16, 16).noise().build().unwrap();
+        let image = png.decode_image().unwrap();
+
+        assert_eq!(image.rows().len(), 16);
+        assert_ne!(image.row(0), image.row(1));
+    }
+
+    #[test]
+    fn test_build_low_bit_depth() {
+        let png = PngBuilder::new(3, 1)
+            .color_type(ColorType::Grayscale)
+            .bit_depth(2)
+            .solid(&[0b10])
+            .build()
+            .unwrap();
+
+        assert_eq!(png.decode_image().unwrap().row(0).unwrap(), [0b1010_1010]);
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        assert!(matches!(
+            PngBuilder::new(2, 2).solid(&[0; 4]).build(),
+            Err(PngBuildError::InvalidPixel {
+                expected: 3,
+                actual: 4
+            })
+        ));
+        assert!(matches!(
+            PngBuilder::new(2, 2).pixels(vec![0; 11]).build(),
+            Err(PngBuildError::InvalidPixelData {
+                expected: 12,
+                actual: 11
+            })
+        ));
+        assert!(matches!(
+            PngBuilder::new(0, 2).build(),
+            Err(PngBuildError::InvalidHeader(_))
+        ));
+    }
+}
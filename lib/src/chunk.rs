@@ -1,10 +1,17 @@
-use crc::{Crc, CRC_32_ISO_HDLC};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
 use fehler::{throw, throws};
-use std::fmt::Display;
+#[cfg(feature = "std")]
+use std::io::Write;
 use thiserror::Error;
+use tracing::{debug, trace};
 
 use crate::chunk_type::{ChunkType, ChunkTypeParseError};
 
+/// Shared crc table, so it isn't rebuilt for every chunk.
+#[cfg(not(feature = "crc32fast"))]
+static CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
 #[derive(Debug, Error)]
 pub enum ChunkParseError {
     #[error("chunk too short")]
@@ -20,12 +27,33 @@ pub enum ChunkParseError {
     ChunkTooLong,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
     data: Vec<u8>,
     crc: u32,
+    offset: Option<u64>,
+}
+
+// Where a chunk was read from doesn't make it a different chunk
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.length == other.length
+            && self.chunk_type == other.chunk_type
+            && self.data == other.data
+            && self.crc == other.crc
+    }
+}
+
+impl Eq for Chunk {}
+
+impl core::hash::Hash for Chunk {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.chunk_type.hash(state);
+        self.data.hash(state);
+        self.crc.hash(state);
+    }
 }
 
 impl Chunk {
@@ -37,6 +65,7 @@ impl Chunk {
             chunk_type,
             data,
             crc,
+            offset: None,
         }
     }
 
@@ -56,37 +85,127 @@ impl Chunk {
         self.crc
     }
 
+    /// Bytes the chunk takes up in a file: its data, plus the length, type
+    /// and crc fields.
+    pub fn size(&self) -> usize {
+        self.length() + 12
+    }
+
+    /// Where the chunk starts in the file it was parsed from, if it was
+    /// parsed as part of a png.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    pub(crate) fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// The data as text, with invalid utf-8 replaced by `U+FFFD`.
     pub fn data_as_string(&self) -> String {
-        String::from_utf8_lossy(&self.data).to_string()
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+
+    /// The data as text, failing on invalid utf-8 instead of hiding it.
+    pub fn data_as_str(&self) -> Result<&str, core::str::Utf8Error> {
+        core::str::from_utf8(&self.data)
     }
 
-    fn calculate_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
-        let hasher = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    /// Up to `max_len` bytes of the data as printable ascii, with other
+    /// bytes shown as `.` and an ellipsis when the data goes on.
+    pub fn preview(&self, max_len: usize) -> String {
+        let mut preview: String = self
+            .data
+            .iter()
+            .take(max_len)
+            .map(|&byte| match byte {
+                b' '..=b'~' => byte as char,
+                _ => '.',
+            })
+            .collect();
+
+        if self.data.len() > max_len {
+            preview.push_str("...");
+        }
 
-        let mut digest = hasher.digest();
-        digest.update(&chunk_type.bytes());
-        digest.update(data);
+        preview
+    }
 
-        digest.finalize()
+    pub(crate) fn calculate_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        let mut hasher = CrcHasher::new(chunk_type);
+        hasher.update(data);
+        hasher.finalize()
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.length
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.data.iter())
-            .chain(self.crc.to_be_bytes().iter())
-            .copied()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.data.len() + 12);
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_type.bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes.extend_from_slice(&self.crc.to_be_bytes());
+        bytes
+    }
+
+    /// Writes the length, type, data and crc of the chunk to `writer`.
+    #[cfg(feature = "std")]
+    #[throws(std::io::Error)]
+    pub fn write_to(&self, writer: &mut (impl Write + ?Sized)) {
+        writer.write_all(&self.length.to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.crc.to_be_bytes())?;
     }
 }
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = ChunkParseError;
+/// Computes a chunk crc over data that arrives in pieces.
+pub(crate) struct CrcHasher {
+    #[cfg(feature = "crc32fast")]
+    hasher: crc32fast::Hasher,
+    #[cfg(not(feature = "crc32fast"))]
+    digest: crc::Digest<'static, u32>,
+}
 
-    #[throws(Self::Error)]
-    fn try_from(raw_chunk: &[u8]) -> Self {
+impl CrcHasher {
+    pub(crate) fn new(chunk_type: &ChunkType) -> Self {
+        #[cfg(feature = "crc32fast")]
+        let mut hasher = CrcHasher {
+            hasher: crc32fast::Hasher::new(),
+        };
+        #[cfg(not(feature = "crc32fast"))]
+        let mut hasher = CrcHasher {
+            digest: CRC.digest(),
+        };
+
+        hasher.update(&chunk_type.bytes());
+        hasher
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        #[cfg(feature = "crc32fast")]
+        self.hasher.update(data);
+        #[cfg(not(feature = "crc32fast"))]
+        self.digest.update(data);
+    }
+
+    pub(crate) fn finalize(self) -> u32 {
+        #[cfg(feature = "crc32fast")]
+        {
+            self.hasher.finalize()
+        }
+
+        #[cfg(not(feature = "crc32fast"))]
+        {
+            self.digest.finalize()
+        }
+    }
+}
+
+impl Chunk {
+    /// Parses a raw chunk, optionally keeping a stored crc that doesn't match
+    /// its contents.
+    #[throws(ChunkParseError)]
+    pub(crate) fn parse(raw_chunk: &[u8], verify_crc: bool) -> Self {
         let length: [u8; 4] = raw_chunk
             .get(..4)
             .ok_or(ChunkParseError::ChunkTooShort)?
@@ -113,14 +232,23 @@ impl TryFrom<&[u8]> for Chunk {
             .ok_or(ChunkParseError::ChunkTooShort)?
             .try_into()
             .expect("slice of length 4");
+        trace!(header = ?&raw_chunk[..8], crc = ?crc, "chunk bytes");
         let crc = u32::from_be_bytes(crc);
 
-        let calculated_crc = Chunk::calculate_crc(&chunk_type, &data);
-        if calculated_crc != crc {
-            throw!(ChunkParseError::InvalidCrc {
-                expected: crc,
-                actual: calculated_crc
-            })
+        if verify_crc {
+            let calculated_crc = Chunk::calculate_crc(&chunk_type, &data);
+            debug!(
+                %chunk_type,
+                stored = format_args!("{:#010x}", crc),
+                calculated = format_args!("{:#010x}", calculated_crc),
+                "checked crc"
+            );
+            if calculated_crc != crc {
+                throw!(ChunkParseError::InvalidCrc {
+                    expected: crc,
+                    actual: calculated_crc
+                })
+            }
         }
 
         if raw_chunk.len() > data_end_index + 4 {
@@ -132,12 +260,22 @@ impl TryFrom<&[u8]> for Chunk {
             chunk_type,
             data,
             crc,
+            offset: None,
         }
     }
 }
 
+impl TryFrom<&[u8]> for Chunk {
+    type Error = ChunkParseError;
+
+    #[throws(Self::Error)]
+    fn try_from(raw_chunk: &[u8]) -> Self {
+        Chunk::parse(raw_chunk, true)?
+    }
+}
+
 impl Display for Chunk {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "Chunk {{\n  length: {}\n  chunk_type: {}\n  data: {:?}\n  crc: {}\n}}\n",
@@ -149,6 +287,64 @@ impl Display for Chunk {
     }
 }
 
+/// Always has a matching length and crc, like chunks made with [`Chunk::new`].
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Chunk {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Chunk::new(u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("chunk_type", &self.chunk_type)?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("crc", &self.crc)?;
+        state.serialize_field("data", &STANDARD.encode(&self.data))?;
+        state.end()
+    }
+}
+
+/// The length and crc are optional, but checked against the data if given.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct RawChunk {
+            chunk_type: ChunkType,
+            length: Option<u32>,
+            crc: Option<u32>,
+            data: String,
+        }
+
+        let raw = RawChunk::deserialize(deserializer)?;
+        let data = STANDARD.decode(&raw.data).map_err(Error::custom)?;
+        let chunk = Chunk::new(raw.chunk_type, data);
+
+        if raw.length.is_some_and(|length| length != chunk.length) {
+            return Err(Error::custom("length doesn't match the data"));
+        }
+
+        if let Some(crc) = raw.crc.filter(|crc| *crc != chunk.crc) {
+            return Err(Error::custom(ChunkParseError::InvalidCrc {
+                expected: crc,
+                actual: chunk.crc,
+            }));
+        }
+
+        Ok(chunk)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -204,6 +400,19 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_chunk_str() {
+        let chunk = testing_chunk();
+        assert_eq!(
+            chunk.data_as_str(),
+            Ok("This is where your secret message will be!")
+        );
+
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), vec![0x68, 0xff]);
+        assert!(chunk.data_as_str().is_err());
+        assert_eq!(chunk.data_as_string(), "h\u{fffd}");
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
@@ -258,6 +467,28 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_binary_chunk_round_trip() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data: Vec<u8> = (0..=255).collect();
+        let chunk = Chunk::new(chunk_type, data.clone());
+
+        let parsed = Chunk::try_from(chunk.as_bytes().as_ref()).unwrap();
+
+        assert_eq!(parsed.data(), data.as_slice());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunk_write_to() {
+        let chunk = testing_chunk();
+
+        let mut bytes = vec![];
+        chunk.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, chunk.as_bytes());
+        assert_eq!(bytes.len(), chunk.length() + 12);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -278,4 +509,49 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_chunk_serialize() {
+        let chunk = testing_chunk();
+        let value = serde_json::to_value(&chunk).unwrap();
+
+        assert_eq!(value["chunk_type"], "RuSt");
+        assert_eq!(value["length"], 42);
+        assert_eq!(value["crc"], 2882656334u32);
+        assert_eq!(
+            value["data"],
+            "VGhpcyBpcyB3aGVyZSB5b3VyIHNlY3JldCBtZXNzYWdlIHdpbGwgYmUh"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_chunk_deserialize() {
+        let chunk = testing_chunk();
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert_eq!(serde_json::from_str::<Chunk>(&json).unwrap(), chunk);
+
+        let minimal = r#"{"chunk_type": "RuSt", "data": "aGk="}"#;
+        assert_eq!(
+            serde_json::from_str::<Chunk>(minimal).unwrap().data(),
+            b"hi"
+        );
+
+        let bad_crc = r#"{"chunk_type": "RuSt", "crc": 1, "data": "aGk="}"#;
+        assert!(serde_json::from_str::<Chunk>(bad_crc).is_err());
+
+        let bad_length = r#"{"chunk_type": "RuSt", "length": 3, "data": "aGk="}"#;
+        assert!(serde_json::from_str::<Chunk>(bad_length).is_err());
+    }
+
+    #[test]
+    fn test_chunk_preview() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("RuSt").unwrap(),
+            b"hi\x00there".to_vec(),
+        );
+        assert_eq!(chunk.preview(32), "hi.there");
+        assert_eq!(chunk.preview(4), "hi.t...");
+    }
 }
@@ -1,5 +1,6 @@
+use alloc::vec::Vec;
+use core::{fmt::Display, str::FromStr};
 use fehler::{throw, throws};
-use std::{fmt::Display, str::FromStr};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,15 +9,116 @@ pub enum ChunkTypeParseError {
     InvalidLength(usize),
 
     #[error("not valid utf8")]
-    InvalidUtf8(#[from] std::str::Utf8Error),
+    InvalidUtf8(#[from] core::str::Utf8Error),
 
     #[error("invalid character `{0}`, only upper and lower case alphabets allowed")]
     InvalidCharacter(char),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ChunkType([u8; 4]);
 
+/// What kind of chunk a type names, from the case of its letters.
+///
+/// Public types are reserved for the png spec, so they are standard even when
+/// [`ChunkType::is_standard`] doesn't know them yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkCategory {
+    /// Public and needed to display the image.
+    CriticalStandard,
+    /// Public and safe for decoders to skip.
+    AncillaryStandard,
+    /// Defined by an application rather than the spec.
+    Private,
+    /// The reserved bit is set, which no spec allows yet.
+    Invalid,
+}
+
+impl Display for ChunkCategory {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            ChunkCategory::CriticalStandard => "critical",
+            ChunkCategory::AncillaryStandard => "ancillary",
+            ChunkCategory::Private => "private",
+            ChunkCategory::Invalid => "invalid",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Chunk types defined by the png spec and its registered extensions.
+#[allow(non_upper_case_globals)]
+pub mod consts {
+    use super::ChunkType;
+
+    pub const IHDR: ChunkType = ChunkType(*b"IHDR");
+    pub const PLTE: ChunkType = ChunkType(*b"PLTE");
+    pub const IDAT: ChunkType = ChunkType(*b"IDAT");
+    pub const IEND: ChunkType = ChunkType(*b"IEND");
+    pub const acTL: ChunkType = ChunkType(*b"acTL");
+    pub const bKGD: ChunkType = ChunkType(*b"bKGD");
+    pub const cHRM: ChunkType = ChunkType(*b"cHRM");
+    pub const cICP: ChunkType = ChunkType(*b"cICP");
+    pub const cLLI: ChunkType = ChunkType(*b"cLLI");
+    pub const eXIf: ChunkType = ChunkType(*b"eXIf");
+    pub const fcTL: ChunkType = ChunkType(*b"fcTL");
+    pub const fdAT: ChunkType = ChunkType(*b"fdAT");
+    pub const gAMA: ChunkType = ChunkType(*b"gAMA");
+    pub const gIFg: ChunkType = ChunkType(*b"gIFg");
+    pub const gIFx: ChunkType = ChunkType(*b"gIFx");
+    pub const hIST: ChunkType = ChunkType(*b"hIST");
+    pub const iCCP: ChunkType = ChunkType(*b"iCCP");
+    pub const iTXt: ChunkType = ChunkType(*b"iTXt");
+    pub const mDCV: ChunkType = ChunkType(*b"mDCV");
+    pub const oFFs: ChunkType = ChunkType(*b"oFFs");
+    pub const pCAL: ChunkType = ChunkType(*b"pCAL");
+    pub const pHYs: ChunkType = ChunkType(*b"pHYs");
+    pub const sBIT: ChunkType = ChunkType(*b"sBIT");
+    pub const sCAL: ChunkType = ChunkType(*b"sCAL");
+    pub const sPLT: ChunkType = ChunkType(*b"sPLT");
+    pub const sRGB: ChunkType = ChunkType(*b"sRGB");
+    pub const sTER: ChunkType = ChunkType(*b"sTER");
+    pub const tEXt: ChunkType = ChunkType(*b"tEXt");
+    pub const tIME: ChunkType = ChunkType(*b"tIME");
+    pub const tRNS: ChunkType = ChunkType(*b"tRNS");
+    pub const zTXt: ChunkType = ChunkType(*b"zTXt");
+}
+
+/// Standard chunk types, with what they hold.
+const STANDARD_CHUNKS: [(ChunkType, &str); 31] = [
+    (consts::IHDR, "image header"),
+    (consts::PLTE, "palette"),
+    (consts::IDAT, "image data"),
+    (consts::IEND, "image trailer"),
+    (consts::acTL, "animation control"),
+    (consts::bKGD, "background color"),
+    (consts::cHRM, "primary chromaticities and white point"),
+    (consts::cICP, "coding-independent code points"),
+    (consts::cLLI, "content light level information"),
+    (consts::eXIf, "exif metadata"),
+    (consts::fcTL, "frame control"),
+    (consts::fdAT, "frame data"),
+    (consts::gAMA, "image gamma"),
+    (consts::gIFg, "gif graphic control extension"),
+    (consts::gIFx, "gif application extension"),
+    (consts::hIST, "palette histogram"),
+    (consts::iCCP, "embedded icc profile"),
+    (consts::iTXt, "international textual data"),
+    (consts::mDCV, "mastering display color volume"),
+    (consts::oFFs, "image offset"),
+    (consts::pCAL, "pixel value calibration"),
+    (consts::pHYs, "physical pixel dimensions"),
+    (consts::sBIT, "significant bits"),
+    (consts::sCAL, "physical scale of image subject"),
+    (consts::sPLT, "suggested palette"),
+    (consts::sRGB, "standard rgb color space"),
+    (consts::sTER, "stereo image indicator"),
+    (consts::tEXt, "textual data"),
+    (consts::tIME, "last modification time"),
+    (consts::tRNS, "transparency"),
+    (consts::zTXt, "compressed textual data"),
+];
+
 impl ChunkType {
     #[throws(ChunkTypeParseError)]
     fn validate_content(content: &[u8]) {
@@ -24,7 +126,7 @@ impl ChunkType {
             throw!(ChunkTypeParseError::InvalidLength(content.len()));
         }
 
-        match std::str::from_utf8(content) {
+        match core::str::from_utf8(content) {
             Err(error) => throw!(ChunkTypeParseError::InvalidUtf8(error)),
             Ok(content) => {
                 for c in content.chars() {
@@ -40,7 +142,46 @@ impl ChunkType {
         byte & 0b00100000 > 0
     }
 
-    pub fn bytes(&self) -> [u8; 4] {
+    /// Sets the case bits of four letters to make an ancillary, private,
+    /// safe-to-copy type, the right kind for custom data.
+    fn private_from_letters(letters: [u8; 4]) -> Self {
+        let [first, second, third, fourth] = letters;
+
+        Self([
+            first.to_ascii_lowercase(),
+            second.to_ascii_lowercase(),
+            third.to_ascii_uppercase(),
+            fourth.to_ascii_lowercase(),
+        ])
+    }
+
+    /// An ancillary, private, safe-to-copy chunk type made from the first four
+    /// letters of `label`, so "TEST" becomes "teSt".
+    #[throws(ChunkTypeParseError)]
+    pub fn new_private(label: &str) -> Self {
+        let letters: Vec<u8> = label
+            .bytes()
+            .filter(|byte| byte.is_ascii_alphabetic())
+            .take(4)
+            .collect();
+
+        let letters: [u8; 4] = letters
+            .try_into()
+            .map_err(|letters: Vec<u8>| ChunkTypeParseError::InvalidLength(letters.len()))?;
+
+        ChunkType::private_from_letters(letters)
+    }
+
+    /// A random ancillary, private, safe-to-copy chunk type.
+    #[cfg(feature = "std")]
+    pub fn random_private() -> Self {
+        let mut letters = [0; 4];
+        getrandom::getrandom(&mut letters).expect("the os random number generator failed");
+
+        ChunkType::private_from_letters(letters.map(|byte| b'a' + byte % 26))
+    }
+
+    pub const fn bytes(&self) -> [u8; 4] {
         self.0
     }
 
@@ -63,6 +204,31 @@ impl ChunkType {
     pub fn is_safe_to_copy(&self) -> bool {
         ChunkType::is_5th_bit_set(&self.0[3])
     }
+
+    pub fn category(&self) -> ChunkCategory {
+        if !self.is_reserved_bit_valid() {
+            ChunkCategory::Invalid
+        } else if !self.is_public() {
+            ChunkCategory::Private
+        } else if self.is_critical() {
+            ChunkCategory::CriticalStandard
+        } else {
+            ChunkCategory::AncillaryStandard
+        }
+    }
+
+    /// Whether the type is defined by the png spec or a registered extension.
+    pub fn is_standard(&self) -> bool {
+        self.known_description().is_some()
+    }
+
+    /// What a standard chunk of this type holds, e.g. "image header".
+    pub fn known_description(&self) -> Option<&'static str> {
+        STANDARD_CHUNKS
+            .iter()
+            .find(|(chunk_type, _)| chunk_type == self)
+            .map(|(_, description)| *description)
+    }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
@@ -90,15 +256,52 @@ impl FromStr for ChunkType {
 }
 
 impl Display for ChunkType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "{:#}",
-            std::str::from_utf8(&self.0).expect("checked in constructors for valid utf8")
+            core::str::from_utf8(&self.0).expect("checked in constructors for valid utf8")
         )
     }
 }
 
+/// Four ascii letters of any case, so types with the reserved bit set are
+/// generated too.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ChunkType {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let bytes: [u8; 4] = u.arbitrary()?;
+
+        Ok(ChunkType(bytes.map(|byte| {
+            let letter = b'a' + byte % 26;
+            if byte & 0b10000000 > 0 {
+                letter.to_ascii_uppercase()
+            } else {
+                letter
+            }
+        })))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (4, Some(4))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let chunk_type = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        ChunkType::from_str(&chunk_type).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,6 +323,36 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    pub fn test_chunk_type_new_private() {
+        let chunk = ChunkType::new_private("TEST").unwrap();
+        assert_eq!(chunk.to_string(), "teSt");
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_reserved_bit_valid());
+        assert!(chunk.is_safe_to_copy());
+
+        assert_eq!(
+            ChunkType::new_private("my 1st label").unwrap().to_string(),
+            "mySt"
+        );
+        assert!(matches!(
+            ChunkType::new_private("ab-1"),
+            Err(ChunkTypeParseError::InvalidLength(2))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn test_chunk_type_random_private() {
+        let chunk = ChunkType::random_private();
+        assert!(ChunkType::from_str(&chunk.to_string()).is_ok());
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_valid());
+        assert!(chunk.is_safe_to_copy());
+    }
+
     #[test]
     pub fn test_chunk_type_is_critical() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -183,6 +416,52 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(consts::IHDR.is_standard());
+        assert!(ChunkType::from_str("zTXt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("IDAt").unwrap().is_standard());
+    }
+
+    #[test]
+    pub fn test_chunk_type_known_description() {
+        assert_eq!(consts::tEXt.known_description(), Some("textual data"));
+        assert_eq!(
+            ChunkType::from_str("ruSt").unwrap().known_description(),
+            None
+        );
+        assert!(STANDARD_CHUNKS
+            .iter()
+            .all(|(chunk_type, _)| chunk_type.is_valid()));
+    }
+
+    #[test]
+    pub fn test_chunk_type_category() {
+        let category = |chunk_type| ChunkType::from_str(chunk_type).unwrap().category();
+        assert_eq!(category("IHDR"), ChunkCategory::CriticalStandard);
+        assert_eq!(category("tEXt"), ChunkCategory::AncillaryStandard);
+        assert_eq!(category("ruSt"), ChunkCategory::Private);
+        assert_eq!(category("RuSt"), ChunkCategory::Private);
+        assert_eq!(category("Rust"), ChunkCategory::Invalid);
+        assert_eq!(ChunkCategory::AncillaryStandard.to_string(), "ancillary");
+    }
+
+    #[test]
+    pub fn test_chunk_type_in_collections() {
+        use std::collections::{BTreeSet, HashMap};
+
+        let mut counts = HashMap::new();
+        for chunk_type in [consts::IDAT, consts::tEXt, consts::IDAT] {
+            *counts.entry(chunk_type).or_insert(0) += 1;
+        }
+        assert_eq!(counts[&consts::IDAT], 2);
+
+        let sorted: BTreeSet<ChunkType> = [consts::tEXt, consts::IHDR, consts::IDAT].into();
+        let sorted: Vec<String> = sorted.iter().map(ChunkType::to_string).collect();
+        assert_eq!(sorted, ["IDAT", "IHDR", "tEXt"]);
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -196,4 +475,19 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    pub fn test_chunk_type_serialize() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(serde_json::to_string(&chunk).unwrap(), "\"RuSt\"");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    pub fn test_chunk_type_deserialize() {
+        let chunk: ChunkType = serde_json::from_str("\"RuSt\"").unwrap();
+        assert_eq!(chunk, ChunkType::from_str("RuSt").unwrap());
+        assert!(serde_json::from_str::<ChunkType>("\"Ru1t\"").is_err());
+    }
 }
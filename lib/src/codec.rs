@@ -0,0 +1,1086 @@
+use fehler::{throw, throws};
+use std::{error::Error, fmt::Display};
+use thiserror::Error;
+
+use crate::{
+    ancillary::{channel_names, BackgroundChunk, HistogramChunk, SignificantBits},
+    apng::{AnimationControl, BlendOp, DisposeOp, FrameControl},
+    chunk::Chunk,
+    chunk_type::{consts, ChunkType},
+    color::{ChromaticitiesChunk, GammaChunk, RenderingIntent, SrgbChunk},
+    exif::{ByteOrder, ExifChunk},
+    icc::IccProfileChunk,
+    ihdr::{ColorType, Ihdr, Interlace},
+    palette::{PaletteChunk, SuggestedPaletteChunk, SuggestedPaletteEntry},
+    phys::{PhysChunk, PhysUnit},
+    text::{CompressedTextChunk, InternationalTextChunk, TextChunk},
+    time::TimeChunk,
+};
+
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("no codec for `{0}` chunks")]
+    NoCodec(ChunkType),
+
+    #[error("invalid chunk data")]
+    InvalidChunk(#[source] Box<dyn Error + Send + Sync>),
+
+    #[error("missing field `{0}`")]
+    MissingField(String),
+
+    #[error("field `{field}` must be {expected}")]
+    InvalidField {
+        field: String,
+        expected: &'static str,
+    },
+}
+
+impl CodecError {
+    /// Wraps the error of the typed chunk a codec is built on.
+    pub fn invalid(error: impl Error + Send + Sync + 'static) -> Self {
+        CodecError::InvalidChunk(Box::new(error))
+    }
+
+    fn invalid_field(field: &str, expected: &'static str) -> Self {
+        CodecError::InvalidField {
+            field: field.to_string(),
+            expected,
+        }
+    }
+}
+
+/// The decoded contents of a chunk, loosely typed so that codecs for any
+/// chunk can share it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    List(Vec<TypedValue>),
+    /// Named fields, in the order they are stored in the chunk.
+    Record(Vec<(String, TypedValue)>),
+}
+
+impl TypedValue {
+    pub fn record<const N: usize>(fields: [(&str, TypedValue); N]) -> Self {
+        TypedValue::Record(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The field called `name`, if this is a record that has one.
+    pub fn get(&self, name: &str) -> Option<&TypedValue> {
+        match self {
+            TypedValue::Record(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TypedValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            TypedValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Integers are widened, since a value like a gamma of 1 may be written
+    /// without a fraction.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TypedValue::Float(value) => Some(*value),
+            TypedValue::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            TypedValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            TypedValue::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[TypedValue]> {
+        match self {
+            TypedValue::List(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn fmt_nested(&self, f: &mut std::fmt::Formatter<'_>, nested: bool) -> std::fmt::Result {
+        match self {
+            TypedValue::Bool(value) => write!(f, "{}", value),
+            TypedValue::Integer(value) => write!(f, "{}", value),
+            TypedValue::Float(value) => write!(f, "{}", value),
+            TypedValue::Text(value) => write!(f, "{:?}", value),
+            TypedValue::Bytes(value) => write!(f, "<{} bytes>", value.len()),
+            TypedValue::List(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    value.fmt_nested(f, true)?;
+                }
+                write!(f, "]")
+            }
+            TypedValue::Record(fields) => {
+                if nested {
+                    write!(f, "{{")?;
+                }
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", name)?;
+                    value.fmt_nested(f, true)?;
+                }
+                if nested {
+                    write!(f, "}}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for TypedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_nested(f, false)
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($integer:ty),*) => {
+        $(
+            impl From<$integer> for TypedValue {
+                fn from(value: $integer) -> Self {
+                    TypedValue::Integer(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(u8, u16, u32, i64);
+
+impl From<bool> for TypedValue {
+    fn from(value: bool) -> Self {
+        TypedValue::Bool(value)
+    }
+}
+
+impl From<f64> for TypedValue {
+    fn from(value: f64) -> Self {
+        TypedValue::Float(value)
+    }
+}
+
+impl From<&str> for TypedValue {
+    fn from(value: &str) -> Self {
+        TypedValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for TypedValue {
+    fn from(value: String) -> Self {
+        TypedValue::Text(value)
+    }
+}
+
+/// Bytes are base64 encoded, like chunk data.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TypedValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use serde::ser::SerializeMap;
+
+        match self {
+            TypedValue::Bool(value) => serializer.serialize_bool(*value),
+            TypedValue::Integer(value) => serializer.serialize_i64(*value),
+            TypedValue::Float(value) => serializer.serialize_f64(*value),
+            TypedValue::Text(value) => serializer.serialize_str(value),
+            TypedValue::Bytes(value) => serializer.serialize_str(&STANDARD.encode(value)),
+            TypedValue::List(values) => serializer.collect_seq(values),
+            TypedValue::Record(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, value) in fields {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Turns chunks of the types it matches into a [`TypedValue`] and back.
+pub trait ChunkCodec: Send + Sync {
+    fn matches(&self, chunk_type: &ChunkType) -> bool;
+
+    fn decode(&self, chunk: &Chunk) -> Result<TypedValue, CodecError>;
+
+    fn encode(&self, value: &TypedValue) -> Result<Chunk, CodecError>;
+}
+
+/// The codecs to decode chunks with. Codecs registered later win over
+/// earlier ones, so the built-in codecs can be replaced.
+pub struct CodecRegistry {
+    codecs: Vec<Box<dyn ChunkCodec>>,
+}
+
+impl CodecRegistry {
+    /// A registry with codecs for the standard chunks.
+    pub fn new() -> Self {
+        let mut registry = CodecRegistry::empty();
+        for codec in builtin_codecs() {
+            registry.register(codec);
+        }
+
+        registry
+    }
+
+    pub fn empty() -> Self {
+        CodecRegistry { codecs: vec![] }
+    }
+
+    pub fn register(&mut self, codec: impl ChunkCodec + 'static) {
+        self.codecs.push(Box::new(codec));
+    }
+
+    pub fn codec(&self, chunk_type: &ChunkType) -> Option<&dyn ChunkCodec> {
+        self.codecs
+            .iter()
+            .rev()
+            .find(|codec| codec.matches(chunk_type))
+            .map(Box::as_ref)
+    }
+
+    #[throws(CodecError)]
+    pub fn decode(&self, chunk: &Chunk) -> TypedValue {
+        let chunk_type = chunk.chunk_type();
+        let codec = self
+            .codec(chunk_type)
+            .ok_or(CodecError::NoCodec(*chunk_type))?;
+
+        codec.decode(chunk)?
+    }
+
+    #[throws(CodecError)]
+    pub fn encode(&self, chunk_type: &ChunkType, value: &TypedValue) -> Chunk {
+        let codec = self
+            .codec(chunk_type)
+            .ok_or(CodecError::NoCodec(*chunk_type))?;
+
+        codec.encode(value)?
+    }
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        CodecRegistry::new()
+    }
+}
+
+type DecodeFn = fn(&Chunk) -> Result<TypedValue, CodecError>;
+type EncodeFn = fn(&TypedValue) -> Result<Chunk, CodecError>;
+
+/// A codec for a single standard chunk type, built on its typed chunk.
+struct BuiltinCodec {
+    chunk_type: ChunkType,
+    decode: DecodeFn,
+    encode: EncodeFn,
+}
+
+impl ChunkCodec for BuiltinCodec {
+    fn matches(&self, chunk_type: &ChunkType) -> bool {
+        *chunk_type == self.chunk_type
+    }
+
+    fn decode(&self, chunk: &Chunk) -> Result<TypedValue, CodecError> {
+        (self.decode)(chunk)
+    }
+
+    fn encode(&self, value: &TypedValue) -> Result<Chunk, CodecError> {
+        (self.encode)(value)
+    }
+}
+
+/// Codecs for the standard chunks that can be read on their own. `tRNS` is
+/// left out: whether it holds palette alphas or a transparent colour depends
+/// on the image's colour type, which its length doesn't always give away.
+fn builtin_codecs() -> Vec<BuiltinCodec> {
+    let codec = |chunk_type, decode, encode| BuiltinCodec {
+        chunk_type,
+        decode,
+        encode,
+    };
+
+    vec![
+        codec(consts::IHDR, decode_ihdr, encode_ihdr),
+        codec(consts::PLTE, decode_plte, encode_plte),
+        codec(consts::gAMA, decode_gama, encode_gama),
+        codec(consts::sRGB, decode_srgb, encode_srgb),
+        codec(consts::cHRM, decode_chrm, encode_chrm),
+        codec(consts::iCCP, decode_iccp, encode_iccp),
+        codec(consts::pHYs, decode_phys, encode_phys),
+        codec(consts::hIST, decode_hist, encode_hist),
+        codec(consts::bKGD, decode_bkgd, encode_bkgd),
+        codec(consts::sBIT, decode_sbit, encode_sbit),
+        codec(consts::eXIf, decode_exif, encode_exif),
+        codec(consts::sPLT, decode_splt, encode_splt),
+        codec(consts::tIME, decode_time, encode_time),
+        codec(consts::tEXt, decode_text, encode_text),
+        codec(consts::zTXt, decode_ztxt, encode_ztxt),
+        codec(consts::iTXt, decode_itxt, encode_itxt),
+        codec(consts::acTL, decode_actl, encode_actl),
+        codec(consts::fcTL, decode_fctl, encode_fctl),
+    ]
+}
+
+// Field accessors for the encoders, which should say what's wrong with a
+// value rather than just that it didn't fit.
+
+#[throws(CodecError)]
+fn field<'a>(value: &'a TypedValue, name: &str) -> &'a TypedValue {
+    value
+        .get(name)
+        .ok_or_else(|| CodecError::MissingField(name.to_string()))?
+}
+
+#[throws(CodecError)]
+fn integer<T: TryFrom<i64>>(value: &TypedValue, name: &str) -> T {
+    field(value, name)?
+        .as_integer()
+        .and_then(|integer| T::try_from(integer).ok())
+        .ok_or_else(|| CodecError::invalid_field(name, "an integer in range"))?
+}
+
+#[throws(CodecError)]
+fn float(value: &TypedValue, name: &str) -> f64 {
+    field(value, name)?
+        .as_float()
+        .ok_or_else(|| CodecError::invalid_field(name, "a number"))?
+}
+
+#[throws(CodecError)]
+fn text<'a>(value: &'a TypedValue, name: &str) -> &'a str {
+    field(value, name)?
+        .as_text()
+        .ok_or_else(|| CodecError::invalid_field(name, "text"))?
+}
+
+#[throws(CodecError)]
+fn list<'a>(value: &'a TypedValue, name: &str) -> &'a [TypedValue] {
+    field(value, name)?
+        .as_list()
+        .ok_or_else(|| CodecError::invalid_field(name, "a list"))?
+}
+
+/// Matches the text of a field against the way each variant is displayed.
+#[throws(CodecError)]
+fn variant<T: Display + Copy>(value: &TypedValue, name: &str, variants: &[T]) -> T {
+    let text = text(value, name)?;
+    *variants
+        .iter()
+        .find(|variant| variant.to_string() == text)
+        .ok_or_else(|| CodecError::invalid_field(name, "a known variant"))?
+}
+
+#[throws(CodecError)]
+fn decode_ihdr(chunk: &Chunk) -> TypedValue {
+    let header = Ihdr::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("width", header.width().into()),
+        ("height", header.height().into()),
+        ("bit_depth", header.bit_depth().into()),
+        ("color_type", header.color_type().to_string().into()),
+        ("interlace", header.interlace().to_string().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_ihdr(value: &TypedValue) -> Chunk {
+    let color_types = [
+        ColorType::Grayscale,
+        ColorType::Rgb,
+        ColorType::Indexed,
+        ColorType::GrayscaleAlpha,
+        ColorType::Rgba,
+    ];
+
+    Ihdr::new(
+        integer(value, "width")?,
+        integer(value, "height")?,
+        integer(value, "bit_depth")?,
+        variant(value, "color_type", &color_types)?,
+        variant(value, "interlace", &[Interlace::None, Interlace::Adam7])?,
+    )
+    .map_err(CodecError::invalid)?
+    .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_plte(chunk: &Chunk) -> TypedValue {
+    let palette = PaletteChunk::try_from(chunk).map_err(CodecError::invalid)?;
+    let entries = palette
+        .entries()
+        .iter()
+        .map(|[red, green, blue]| format!("#{:02x}{:02x}{:02x}", red, green, blue).into())
+        .collect();
+
+    TypedValue::record([("entries", TypedValue::List(entries))])
+}
+
+#[throws(CodecError)]
+fn encode_plte(value: &TypedValue) -> Chunk {
+    let entries = list(value, "entries")?
+        .iter()
+        .map(|entry| {
+            let hex = entry
+                .as_text()
+                .and_then(|text| text.strip_prefix('#'))
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| CodecError::invalid_field("entries", "#rrggbb colours"))?;
+            let [_, red, green, blue] = hex.to_be_bytes();
+            Ok([red, green, blue])
+        })
+        .collect::<Result<_, CodecError>>()?;
+
+    PaletteChunk::new(entries)
+        .map_err(CodecError::invalid)?
+        .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_gama(chunk: &Chunk) -> TypedValue {
+    let gamma = GammaChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([("gamma", gamma.gamma().into())])
+}
+
+#[throws(CodecError)]
+fn encode_gama(value: &TypedValue) -> Chunk {
+    GammaChunk::new(float(value, "gamma")?)
+        .map_err(CodecError::invalid)?
+        .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_srgb(chunk: &Chunk) -> TypedValue {
+    let srgb = SrgbChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([(
+        "rendering_intent",
+        srgb.rendering_intent().to_string().into(),
+    )])
+}
+
+#[throws(CodecError)]
+fn encode_srgb(value: &TypedValue) -> Chunk {
+    let intents = [
+        RenderingIntent::Perceptual,
+        RenderingIntent::RelativeColorimetric,
+        RenderingIntent::Saturation,
+        RenderingIntent::AbsoluteColorimetric,
+    ];
+
+    SrgbChunk::new(variant(value, "rendering_intent", &intents)?).to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_chrm(chunk: &Chunk) -> TypedValue {
+    let chrm = ChromaticitiesChunk::try_from(chunk).map_err(CodecError::invalid)?;
+    let (white, red, green, blue) = (chrm.white(), chrm.red(), chrm.green(), chrm.blue());
+
+    TypedValue::record([
+        ("white_x", white.0.into()),
+        ("white_y", white.1.into()),
+        ("red_x", red.0.into()),
+        ("red_y", red.1.into()),
+        ("green_x", green.0.into()),
+        ("green_y", green.1.into()),
+        ("blue_x", blue.0.into()),
+        ("blue_y", blue.1.into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_chrm(value: &TypedValue) -> Chunk {
+    let point = |name: &str| -> Result<(f64, f64), CodecError> {
+        Ok((
+            float(value, &format!("{}_x", name))?,
+            float(value, &format!("{}_y", name))?,
+        ))
+    };
+
+    ChromaticitiesChunk::new(
+        point("white")?,
+        point("red")?,
+        point("green")?,
+        point("blue")?,
+    )
+    .map_err(CodecError::invalid)?
+    .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_iccp(chunk: &Chunk) -> TypedValue {
+    let icc = IccProfileChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("name", icc.name().into()),
+        ("profile", TypedValue::Bytes(icc.profile().to_vec())),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_iccp(value: &TypedValue) -> Chunk {
+    let profile = field(value, "profile")?
+        .as_bytes()
+        .ok_or_else(|| CodecError::invalid_field("profile", "bytes"))?;
+
+    IccProfileChunk::new(text(value, "name")?, profile.to_vec())
+        .and_then(|icc| icc.to_chunk())
+        .map_err(CodecError::invalid)?
+}
+
+#[throws(CodecError)]
+fn decode_phys(chunk: &Chunk) -> TypedValue {
+    let phys = PhysChunk::try_from(chunk).map_err(CodecError::invalid)?;
+    let unit = match phys.unit() {
+        PhysUnit::Unknown => "unknown",
+        PhysUnit::Meter => "meter",
+    };
+
+    TypedValue::record([
+        ("x_pixels_per_unit", phys.x_pixels_per_unit().into()),
+        ("y_pixels_per_unit", phys.y_pixels_per_unit().into()),
+        ("unit", unit.into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_phys(value: &TypedValue) -> Chunk {
+    let unit = match text(value, "unit")? {
+        "unknown" => PhysUnit::Unknown,
+        "meter" => PhysUnit::Meter,
+        _ => throw!(CodecError::invalid_field(
+            "unit",
+            "\"unknown\" or \"meter\""
+        )),
+    };
+
+    PhysChunk::new(
+        integer(value, "x_pixels_per_unit")?,
+        integer(value, "y_pixels_per_unit")?,
+        unit,
+    )
+    .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_hist(chunk: &Chunk) -> TypedValue {
+    let histogram = HistogramChunk::try_from(chunk).map_err(CodecError::invalid)?;
+    let frequencies = histogram.frequencies().iter().map(|&f| f.into()).collect();
+
+    TypedValue::record([("frequencies", TypedValue::List(frequencies))])
+}
+
+#[throws(CodecError)]
+fn encode_hist(value: &TypedValue) -> Chunk {
+    let frequencies = list(value, "frequencies")?
+        .iter()
+        .map(|frequency| {
+            frequency
+                .as_integer()
+                .and_then(|frequency| u16::try_from(frequency).ok())
+                .ok_or_else(|| CodecError::invalid_field("frequencies", "16-bit integers"))
+        })
+        .collect::<Result<_, _>>()?;
+
+    HistogramChunk::new(frequencies)
+        .map_err(CodecError::invalid)?
+        .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_bkgd(chunk: &Chunk) -> TypedValue {
+    // Which colour type it was written for shows in the length
+    let color_type = match chunk.data().len() {
+        1 => ColorType::Indexed,
+        6 => ColorType::Rgb,
+        _ => ColorType::Grayscale,
+    };
+
+    match BackgroundChunk::parse(chunk, color_type).map_err(CodecError::invalid)? {
+        BackgroundChunk::Indexed(index) => TypedValue::record([("index", index.into())]),
+        BackgroundChunk::Grayscale(gray) => TypedValue::record([("gray", gray.into())]),
+        BackgroundChunk::Rgb([red, green, blue]) => TypedValue::record([
+            ("red", red.into()),
+            ("green", green.into()),
+            ("blue", blue.into()),
+        ]),
+    }
+}
+
+#[throws(CodecError)]
+fn encode_bkgd(value: &TypedValue) -> Chunk {
+    let background = if value.get("index").is_some() {
+        BackgroundChunk::Indexed(integer(value, "index")?)
+    } else if value.get("gray").is_some() {
+        BackgroundChunk::Grayscale(integer(value, "gray")?)
+    } else {
+        BackgroundChunk::Rgb([
+            integer(value, "red")?,
+            integer(value, "green")?,
+            integer(value, "blue")?,
+        ])
+    };
+
+    background.to_chunk()
+}
+
+/// A header for sBIT chunks read without the image's own, with the colour
+/// type their channels call for and the deepest samples it allows. Indexed
+/// images have the same channels as rgb ones.
+fn sbit_header(color_type: ColorType) -> Ihdr {
+    Ihdr::new(1, 1, 16, color_type, Interlace::None).expect("valid header")
+}
+
+#[throws(CodecError)]
+fn decode_sbit(chunk: &Chunk) -> TypedValue {
+    let color_type = match chunk.data().len() {
+        2 => ColorType::GrayscaleAlpha,
+        3 => ColorType::Rgb,
+        4 => ColorType::Rgba,
+        _ => ColorType::Grayscale,
+    };
+    let sbit =
+        SignificantBits::parse(chunk, &sbit_header(color_type)).map_err(CodecError::invalid)?;
+
+    TypedValue::Record(
+        channel_names(color_type)
+            .iter()
+            .zip(sbit.bits())
+            .map(|(name, &bits)| (name.to_string(), bits.into()))
+            .collect(),
+    )
+}
+
+#[throws(CodecError)]
+fn encode_sbit(value: &TypedValue) -> Chunk {
+    let color_type = match (value.get("red").is_some(), value.get("alpha").is_some()) {
+        (true, true) => ColorType::Rgba,
+        (true, false) => ColorType::Rgb,
+        (false, true) => ColorType::GrayscaleAlpha,
+        (false, false) => ColorType::Grayscale,
+    };
+    let bits = channel_names(color_type)
+        .iter()
+        .map(|name| integer(value, name))
+        .collect::<Result<_, _>>()?;
+
+    SignificantBits::new(bits, &sbit_header(color_type))
+        .map_err(CodecError::invalid)?
+        .to_chunk()
+}
+
+/// The entries are only there to be read, `data` is what gets encoded.
+#[throws(CodecError)]
+fn decode_exif(chunk: &Chunk) -> TypedValue {
+    let exif_chunk = ExifChunk::try_from(chunk).map_err(CodecError::invalid)?;
+    let exif = exif_chunk.parse().map_err(CodecError::invalid)?;
+
+    let byte_order = match exif.byte_order() {
+        ByteOrder::LittleEndian => "little endian",
+        ByteOrder::BigEndian => "big endian",
+    };
+    let entries = exif
+        .entries()
+        .iter()
+        .map(|entry| {
+            TypedValue::record([
+                ("ifd", entry.ifd.to_string().into()),
+                ("tag", entry.tag.into()),
+                ("name", entry.name().unwrap_or_default().into()),
+                ("value", entry.value.to_string().into()),
+            ])
+        })
+        .collect();
+
+    TypedValue::record([
+        ("byte_order", byte_order.into()),
+        ("entries", TypedValue::List(entries)),
+        ("data", TypedValue::Bytes(exif_chunk.data().to_vec())),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_exif(value: &TypedValue) -> Chunk {
+    let data = field(value, "data")?
+        .as_bytes()
+        .ok_or_else(|| CodecError::invalid_field("data", "bytes"))?;
+
+    ExifChunk::new(data)
+        .map_err(CodecError::invalid)?
+        .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_splt(chunk: &Chunk) -> TypedValue {
+    let splt = SuggestedPaletteChunk::try_from(chunk).map_err(CodecError::invalid)?;
+    let entries = splt
+        .entries()
+        .iter()
+        .map(|entry| {
+            TypedValue::record([
+                ("red", entry.red.into()),
+                ("green", entry.green.into()),
+                ("blue", entry.blue.into()),
+                ("alpha", entry.alpha.into()),
+                ("frequency", entry.frequency.into()),
+            ])
+        })
+        .collect();
+
+    TypedValue::record([
+        ("name", splt.name().into()),
+        ("sample_depth", splt.sample_depth().into()),
+        ("entries", TypedValue::List(entries)),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_splt(value: &TypedValue) -> Chunk {
+    let entries = list(value, "entries")?
+        .iter()
+        .map(|entry| {
+            Ok(SuggestedPaletteEntry {
+                red: integer(entry, "red")?,
+                green: integer(entry, "green")?,
+                blue: integer(entry, "blue")?,
+                alpha: integer(entry, "alpha")?,
+                frequency: integer(entry, "frequency")?,
+            })
+        })
+        .collect::<Result<_, CodecError>>()?;
+
+    SuggestedPaletteChunk::new(
+        text(value, "name")?,
+        integer(value, "sample_depth")?,
+        entries,
+    )
+    .and_then(|splt| splt.to_chunk())
+    .map_err(CodecError::invalid)?
+}
+
+#[throws(CodecError)]
+fn decode_time(chunk: &Chunk) -> TypedValue {
+    let time = TimeChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("year", time.year().into()),
+        ("month", time.month().into()),
+        ("day", time.day().into()),
+        ("hour", time.hour().into()),
+        ("minute", time.minute().into()),
+        ("second", time.second().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_time(value: &TypedValue) -> Chunk {
+    TimeChunk::new(
+        integer(value, "year")?,
+        integer(value, "month")?,
+        integer(value, "day")?,
+        integer(value, "hour")?,
+        integer(value, "minute")?,
+        integer(value, "second")?,
+    )
+    .map_err(CodecError::invalid)?
+    .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_text(chunk: &Chunk) -> TypedValue {
+    let text = TextChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("keyword", text.keyword().into()),
+        ("text", text.text().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_text(value: &TypedValue) -> Chunk {
+    TextChunk::new(text(value, "keyword")?, text(value, "text")?)
+        .map_err(CodecError::invalid)?
+        .to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_ztxt(chunk: &Chunk) -> TypedValue {
+    let text = CompressedTextChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("keyword", text.keyword().into()),
+        ("text", text.text().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_ztxt(value: &TypedValue) -> Chunk {
+    CompressedTextChunk::new(text(value, "keyword")?, text(value, "text")?)
+        .and_then(|text| text.to_chunk())
+        .map_err(CodecError::invalid)?
+}
+
+#[throws(CodecError)]
+fn decode_itxt(chunk: &Chunk) -> TypedValue {
+    let text = InternationalTextChunk::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("keyword", text.keyword().into()),
+        ("compressed", text.is_compressed().into()),
+        ("language_tag", text.language_tag().into()),
+        ("translated_keyword", text.translated_keyword().into()),
+        ("text", text.text().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_itxt(value: &TypedValue) -> Chunk {
+    let compressed = field(value, "compressed")?
+        .as_bool()
+        .ok_or_else(|| CodecError::invalid_field("compressed", "true or false"))?;
+
+    InternationalTextChunk::new(text(value, "keyword")?, text(value, "text")?)
+        .and_then(|itxt| {
+            itxt.with_compression(compressed).with_language(
+                text(value, "language_tag").unwrap_or_default(),
+                text(value, "translated_keyword").unwrap_or_default(),
+            )
+        })
+        .and_then(|itxt| itxt.to_chunk())
+        .map_err(CodecError::invalid)?
+}
+
+#[throws(CodecError)]
+fn decode_actl(chunk: &Chunk) -> TypedValue {
+    let control = AnimationControl::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("num_frames", control.num_frames().into()),
+        ("num_plays", control.num_plays().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_actl(value: &TypedValue) -> Chunk {
+    AnimationControl::new(integer(value, "num_frames")?, integer(value, "num_plays")?).to_chunk()
+}
+
+#[throws(CodecError)]
+fn decode_fctl(chunk: &Chunk) -> TypedValue {
+    let control = FrameControl::try_from(chunk).map_err(CodecError::invalid)?;
+
+    TypedValue::record([
+        ("sequence_number", control.sequence_number.into()),
+        ("width", control.width.into()),
+        ("height", control.height.into()),
+        ("x_offset", control.x_offset.into()),
+        ("y_offset", control.y_offset.into()),
+        ("delay_num", control.delay_num.into()),
+        ("delay_den", control.delay_den.into()),
+        ("dispose_op", control.dispose_op.to_string().into()),
+        ("blend_op", control.blend_op.to_string().into()),
+    ])
+}
+
+#[throws(CodecError)]
+fn encode_fctl(value: &TypedValue) -> Chunk {
+    let dispose_ops = [DisposeOp::None, DisposeOp::Background, DisposeOp::Previous];
+
+    FrameControl {
+        sequence_number: integer(value, "sequence_number")?,
+        width: integer(value, "width")?,
+        height: integer(value, "height")?,
+        x_offset: integer(value, "x_offset")?,
+        y_offset: integer(value, "y_offset")?,
+        delay_num: integer(value, "delay_num")?,
+        delay_den: integer(value, "delay_den")?,
+        dispose_op: variant(value, "dispose_op", &dispose_ops)?,
+        blend_op: variant(value, "blend_op", &[BlendOp::Source, BlendOp::Over])?,
+    }
+    .to_chunk()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_builtin_codecs_round_trip() {
+        let registry = CodecRegistry::new();
+        let chunks = [
+            Ihdr::new(4, 3, 8, ColorType::Rgba, Interlace::Adam7)
+                .unwrap()
+                .to_chunk(),
+            PaletteChunk::new(vec![[0x33, 0x66, 0x99]])
+                .unwrap()
+                .to_chunk(),
+            SrgbChunk::new(RenderingIntent::Saturation).to_chunk(),
+            PhysChunk::from_dpi(300).to_chunk(),
+            TextChunk::new("Title", "A png").unwrap().to_chunk(),
+            InternationalTextChunk::new("Title", "ünïcödé")
+                .unwrap()
+                .with_language("de", "Titel")
+                .unwrap()
+                .to_chunk()
+                .unwrap(),
+            AnimationControl::new(2, 0).to_chunk(),
+        ];
+
+        for chunk in chunks {
+            let value = registry.decode(&chunk).unwrap();
+            let encoded = registry.encode(chunk.chunk_type(), &value).unwrap();
+            assert_eq!(encoded.data(), chunk.data(), "{}", chunk.chunk_type());
+        }
+    }
+
+    #[test]
+    fn test_ancillary_codecs() {
+        let registry = CodecRegistry::new();
+        #[rustfmt::skip]
+        let exif = [
+            b'I', b'I', 0x2a, 0, 8, 0, 0, 0,
+            // One entry, the camera make
+            1, 0, 0x0f, 0x01, 2, 0, 4, 0, 0, 0, b'a', b'b', b'c', 0,
+            0, 0, 0, 0,
+        ];
+        let chunks = [
+            Chunk::new(consts::bKGD, vec![7]),
+            Chunk::new(consts::bKGD, vec![0, 0x80]),
+            Chunk::new(consts::bKGD, vec![0, 1, 0, 2, 0, 3]),
+            Chunk::new(consts::sBIT, vec![5]),
+            Chunk::new(consts::sBIT, vec![5, 6, 5, 8]),
+            Chunk::new(consts::eXIf, exif.to_vec()),
+        ];
+
+        for chunk in chunks {
+            let value = registry.decode(&chunk).unwrap();
+            let encoded = registry.encode(chunk.chunk_type(), &value).unwrap();
+            assert_eq!(encoded.data(), chunk.data(), "{}", chunk.chunk_type());
+        }
+
+        let value = registry
+            .decode(&Chunk::new(consts::sBIT, vec![5, 6, 5]))
+            .unwrap();
+        assert_eq!(value.to_string(), "red: 5, green: 6, blue: 5");
+
+        let value = registry
+            .decode(&Chunk::new(consts::eXIf, exif.to_vec()))
+            .unwrap();
+        let entries = value.get("entries").unwrap().as_list().unwrap();
+        assert_eq!(entries[0].get("name"), Some(&"Make".into()));
+        assert_eq!(entries[0].get("value"), Some(&"abc".into()));
+
+        assert!(registry.codec(&consts::tRNS).is_none());
+    }
+
+    #[test]
+    fn test_decoded_display() {
+        let registry = CodecRegistry::new();
+        let chunk = PaletteChunk::new(vec![[0x33, 0x66, 0x99], [0, 0, 0]])
+            .unwrap()
+            .to_chunk();
+
+        assert_eq!(
+            registry.decode(&chunk).unwrap().to_string(),
+            r##"entries: ["#336699", "#000000"]"##
+        );
+    }
+
+    #[test]
+    fn test_encode_invalid_value() {
+        let registry = CodecRegistry::new();
+        let value = TypedValue::record([("num_frames", 1u32.into())]);
+
+        assert!(matches!(
+            registry.encode(&consts::acTL, &value),
+            Err(CodecError::MissingField(field)) if field == "num_plays"
+        ));
+
+        let value = TypedValue::record([("gamma", "high".into())]);
+        assert!(matches!(
+            registry.encode(&consts::gAMA, &value),
+            Err(CodecError::InvalidField { .. })
+        ));
+    }
+
+    struct Upper;
+
+    impl ChunkCodec for Upper {
+        fn matches(&self, chunk_type: &ChunkType) -> bool {
+            chunk_type.to_string() == "ruSt" || *chunk_type == consts::tEXt
+        }
+
+        fn decode(&self, chunk: &Chunk) -> Result<TypedValue, CodecError> {
+            Ok(String::from_utf8_lossy(chunk.data()).to_uppercase().into())
+        }
+
+        fn encode(&self, value: &TypedValue) -> Result<Chunk, CodecError> {
+            let text = value.as_text().ok_or(CodecError::InvalidField {
+                field: "value".to_string(),
+                expected: "text",
+            })?;
+            let chunk_type = ChunkType::from_str("ruSt").unwrap();
+            Ok(Chunk::new(chunk_type, text.to_lowercase().into_bytes()))
+        }
+    }
+
+    #[test]
+    fn test_register_codec() {
+        let mut registry = CodecRegistry::new();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunk = Chunk::new(chunk_type, b"hidden".to_vec());
+
+        assert!(matches!(
+            registry.decode(&chunk),
+            Err(CodecError::NoCodec(_))
+        ));
+
+        registry.register(Upper);
+        assert_eq!(registry.decode(&chunk).unwrap(), "HIDDEN".into());
+
+        // Later codecs take over from the built-in ones
+        let text = TextChunk::new("a", "b").unwrap().to_chunk();
+        assert_eq!(registry.decode(&text).unwrap(), "A\0B".into());
+    }
+}
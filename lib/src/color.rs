@@ -0,0 +1,349 @@
+use fehler::{throw, throws};
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Debug, Error)]
+pub enum ColorError {
+    #[error("expected a `{expected}` chunk but got `{actual}`")]
+    UnexpectedChunkType { expected: String, actual: String },
+
+    #[error("`{0}` chunk data must be {1} bytes")]
+    InvalidLength(String, usize),
+
+    #[error("value `{0}` is out of range")]
+    OutOfRange(f64),
+
+    #[error("unknown rendering intent `{0}`")]
+    UnknownRenderingIntent(u8),
+
+    #[error("`sRGB` chunk must not be combined with a `{0}` chunk")]
+    Conflict(String),
+}
+
+const GAMMA: [u8; 4] = *b"gAMA";
+const STANDARD_RGB: [u8; 4] = *b"sRGB";
+const CHROMATICITIES: [u8; 4] = *b"cHRM";
+
+/// gAMA and cHRM store their values multiplied by this factor.
+const SCALE: f64 = 100_000.0;
+
+#[throws(ColorError)]
+fn check_chunk(chunk: &Chunk, expected: [u8; 4], length: usize) -> &[u8] {
+    if chunk.chunk_type().bytes() != expected {
+        throw!(ColorError::UnexpectedChunkType {
+            expected: String::from_utf8_lossy(&expected).into_owned(),
+            actual: chunk.chunk_type().to_string(),
+        })
+    }
+
+    if chunk.data().len() != length {
+        throw!(ColorError::InvalidLength(
+            chunk.chunk_type().to_string(),
+            length
+        ))
+    }
+
+    chunk.data()
+}
+
+#[throws(ColorError)]
+fn to_fixed_point(value: f64) -> u32 {
+    let scaled = (value * SCALE).round();
+    if !(0.0..=u32::MAX as f64).contains(&scaled) {
+        throw!(ColorError::OutOfRange(value))
+    }
+
+    scaled as u32
+}
+
+fn from_fixed_point(value: u32) -> f64 {
+    value as f64 / SCALE
+}
+
+fn read_u32(data: &[u8], index: usize) -> u32 {
+    u32::from_be_bytes(
+        data[index * 4..index * 4 + 4]
+            .try_into()
+            .expect("slice of length 4"),
+    )
+}
+
+fn new_chunk(chunk_type: [u8; 4], data: Vec<u8>) -> Chunk {
+    Chunk::new(
+        ChunkType::try_from(chunk_type).expect("valid chunk type"),
+        data,
+    )
+}
+
+/// Image gamma, stored in a `gAMA` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GammaChunk(u32);
+
+impl GammaChunk {
+    #[throws(ColorError)]
+    pub fn new(gamma: f64) -> Self {
+        match to_fixed_point(gamma)? {
+            0 => throw!(ColorError::OutOfRange(gamma)),
+            gamma => GammaChunk(gamma),
+        }
+    }
+
+    pub fn gamma(&self) -> f64 {
+        from_fixed_point(self.0)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        new_chunk(GAMMA, self.0.to_be_bytes().to_vec())
+    }
+}
+
+impl TryFrom<&Chunk> for GammaChunk {
+    type Error = ColorError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        let data = check_chunk(chunk, GAMMA, 4)?;
+        GammaChunk(read_u32(data, 0))
+    }
+}
+
+impl Display for GammaChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.5} (1/{:.2})", self.gamma(), 1.0 / self.gamma())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderingIntent::Perceptual => write!(f, "perceptual"),
+            RenderingIntent::RelativeColorimetric => write!(f, "relative colorimetric"),
+            RenderingIntent::Saturation => write!(f, "saturation"),
+            RenderingIntent::AbsoluteColorimetric => write!(f, "absolute colorimetric"),
+        }
+    }
+}
+
+/// Marks the image as sRGB with a rendering intent, stored in an `sRGB` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrgbChunk(RenderingIntent);
+
+impl SrgbChunk {
+    pub fn new(intent: RenderingIntent) -> Self {
+        SrgbChunk(intent)
+    }
+
+    pub fn rendering_intent(&self) -> RenderingIntent {
+        self.0
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let intent = match self.0 {
+            RenderingIntent::Perceptual => 0,
+            RenderingIntent::RelativeColorimetric => 1,
+            RenderingIntent::Saturation => 2,
+            RenderingIntent::AbsoluteColorimetric => 3,
+        };
+
+        new_chunk(STANDARD_RGB, vec![intent])
+    }
+}
+
+impl TryFrom<&Chunk> for SrgbChunk {
+    type Error = ColorError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        let data = check_chunk(chunk, STANDARD_RGB, 1)?;
+
+        SrgbChunk(match data[0] {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            intent => throw!(ColorError::UnknownRenderingIntent(intent)),
+        })
+    }
+}
+
+/// CIE 1931 x,y chromaticities of the white point and the three primaries,
+/// stored in a `cHRM` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChromaticitiesChunk([u32; 8]);
+
+impl ChromaticitiesChunk {
+    #[throws(ColorError)]
+    pub fn new(white: (f64, f64), red: (f64, f64), green: (f64, f64), blue: (f64, f64)) -> Self {
+        let values = [
+            white.0, white.1, red.0, red.1, green.0, green.1, blue.0, blue.1,
+        ];
+
+        let mut fixed = [0; 8];
+        for (fixed, value) in fixed.iter_mut().zip(values) {
+            *fixed = to_fixed_point(value)?;
+        }
+
+        ChromaticitiesChunk(fixed)
+    }
+
+    fn point(&self, index: usize) -> (f64, f64) {
+        (
+            from_fixed_point(self.0[index * 2]),
+            from_fixed_point(self.0[index * 2 + 1]),
+        )
+    }
+
+    pub fn white(&self) -> (f64, f64) {
+        self.point(0)
+    }
+
+    pub fn red(&self) -> (f64, f64) {
+        self.point(1)
+    }
+
+    pub fn green(&self) -> (f64, f64) {
+        self.point(2)
+    }
+
+    pub fn blue(&self) -> (f64, f64) {
+        self.point(3)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        new_chunk(
+            CHROMATICITIES,
+            self.0
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<&Chunk> for ChromaticitiesChunk {
+    type Error = ColorError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        let data = check_chunk(chunk, CHROMATICITIES, 32)?;
+
+        let mut values = [0; 8];
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = read_u32(data, index);
+        }
+
+        ChromaticitiesChunk(values)
+    }
+}
+
+impl Display for ChromaticitiesChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = ["white", "red", "green", "blue"];
+        for (index, name) in names.iter().enumerate() {
+            let (x, y) = self.point(index);
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ({:.5}, {:.5})", name, x, y)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that an `sRGB` chunk isn't combined with `gAMA` or `cHRM` chunks,
+/// which it supersedes.
+#[throws(ColorError)]
+pub fn validate_color_chunks(chunks: &[Chunk]) {
+    let has = |chunk_type: [u8; 4]| {
+        chunks
+            .iter()
+            .any(|chunk| chunk.chunk_type().bytes() == chunk_type)
+    };
+
+    if has(STANDARD_RGB) {
+        for conflicting in [GAMMA, CHROMATICITIES] {
+            if has(conflicting) {
+                throw!(ColorError::Conflict(
+                    String::from_utf8_lossy(&conflicting).into_owned()
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gamma_chunk_round_trip() {
+        let gamma = GammaChunk::new(1.0 / 2.2).unwrap();
+        let chunk = gamma.to_chunk();
+
+        assert_eq!(chunk.data(), &45455u32.to_be_bytes());
+        assert_eq!(GammaChunk::try_from(&chunk).unwrap(), gamma);
+        assert_eq!(gamma.to_string(), "0.45455 (1/2.20)");
+    }
+
+    #[test]
+    fn test_gamma_chunk_out_of_range() {
+        assert!(matches!(
+            GammaChunk::new(0.0),
+            Err(ColorError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            GammaChunk::new(-1.0),
+            Err(ColorError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_srgb_chunk_round_trip() {
+        let srgb = SrgbChunk::new(RenderingIntent::Saturation);
+        let chunk = srgb.to_chunk();
+
+        assert_eq!(chunk.data(), &[2]);
+        assert_eq!(SrgbChunk::try_from(&chunk).unwrap(), srgb);
+        assert!(matches!(
+            SrgbChunk::try_from(&new_chunk(STANDARD_RGB, vec![4])),
+            Err(ColorError::UnknownRenderingIntent(4))
+        ));
+    }
+
+    #[test]
+    fn test_chromaticities_chunk_round_trip() {
+        let chrm =
+            ChromaticitiesChunk::new((0.3127, 0.329), (0.64, 0.33), (0.3, 0.6), (0.15, 0.06))
+                .unwrap();
+        let chunk = chrm.to_chunk();
+
+        assert_eq!(chunk.length(), 32);
+        assert_eq!(&chunk.data()[..4], &31270u32.to_be_bytes());
+        assert_eq!(ChromaticitiesChunk::try_from(&chunk).unwrap(), chrm);
+        assert_eq!(chrm.green(), (0.3, 0.6));
+    }
+
+    #[test]
+    fn test_validate_color_chunks() {
+        let srgb = || SrgbChunk::new(RenderingIntent::Perceptual).to_chunk();
+        let gamma = || GammaChunk::new(0.45455).unwrap().to_chunk();
+
+        assert!(validate_color_chunks(&[gamma()]).is_ok());
+        assert!(validate_color_chunks(&[srgb()]).is_ok());
+        assert!(matches!(
+            validate_color_chunks(&[srgb(), gamma()]),
+            Err(ColorError::Conflict(chunk_type)) if chunk_type == "gAMA"
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+use fehler::{throw, throws};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+use thiserror::Error;
+
+use crate::parser::ParseOptions;
+
+/// Largest output [`inflate`] and [`decompress`] produce, so a small crafted
+/// payload can't expand until memory runs out.
+pub const MAX_DECOMPRESSED_SIZE: u64 = ParseOptions::DEFAULT_MAX_CHUNK_SIZE as u64;
+
+#[derive(Debug, Error)]
+pub enum CompressionError {
+    #[error("payload is not compressed")]
+    NotCompressed,
+
+    #[error("unknown compression method `{0}`")]
+    UnknownMethod(u8),
+
+    #[error("compressed payload too short")]
+    PayloadTooShort,
+
+    #[error("failed to compress payload")]
+    CompressionFailed(#[source] std::io::Error),
+
+    #[error("failed to decompress payload")]
+    DecompressionFailed(#[source] std::io::Error),
+
+    #[error("decompressed payload exceeds the limit of `{max}` bytes")]
+    DecompressedTooLarge { max: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMethod {
+    Zlib,
+    Zstd,
+}
+
+impl CompressionMethod {
+    fn id(&self) -> u8 {
+        match self {
+            CompressionMethod::Zlib => 0,
+            CompressionMethod::Zstd => 1,
+        }
+    }
+
+    #[throws(CompressionError)]
+    fn from_id(id: u8) -> Self {
+        match id {
+            0 => CompressionMethod::Zlib,
+            1 => CompressionMethod::Zstd,
+            _ => throw!(CompressionError::UnknownMethod(id)),
+        }
+    }
+}
+
+impl Display for CompressionMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionMethod::Zlib => write!(f, "zlib"),
+            CompressionMethod::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Compressed payload layout:
+///
+/// `MAGIC (4) | method (1) | compressed data`
+pub const MAGIC: [u8; 4] = *b"PMCZ";
+
+const HEADER_LENGTH: usize = MAGIC.len() + 1;
+
+pub fn is_compressed(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+#[throws(CompressionError)]
+pub fn compression_method(payload: &[u8]) -> CompressionMethod {
+    if !is_compressed(payload) {
+        throw!(CompressionError::NotCompressed)
+    }
+
+    let id = *payload
+        .get(MAGIC.len())
+        .ok_or(CompressionError::PayloadTooShort)?;
+
+    CompressionMethod::from_id(id)?
+}
+
+/// Compresses `data` into a bare zlib stream, as used inside png chunks.
+#[throws(CompressionError)]
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+    encoder
+        .write_all(data)
+        .map_err(CompressionError::CompressionFailed)?;
+    encoder
+        .finish()
+        .map_err(CompressionError::CompressionFailed)?
+}
+
+/// Decompresses a bare zlib stream, as used inside png chunks, up to
+/// [`MAX_DECOMPRESSED_SIZE`] bytes.
+#[throws(CompressionError)]
+pub fn inflate(compressed: &[u8]) -> Vec<u8> {
+    inflate_limited(compressed, MAX_DECOMPRESSED_SIZE)?
+}
+
+/// Like [`inflate`], failing once the output grows past `max` bytes.
+#[throws(CompressionError)]
+pub fn inflate_limited(compressed: &[u8], max: u64) -> Vec<u8> {
+    read_limited(ZlibDecoder::new(compressed), max)?
+}
+
+/// Reads at most one byte more than `max`, to tell whether there was more.
+#[throws(CompressionError)]
+fn read_limited(reader: impl Read, max: u64) -> Vec<u8> {
+    let mut data = vec![];
+    reader
+        .take(max + 1)
+        .read_to_end(&mut data)
+        .map_err(CompressionError::DecompressionFailed)?;
+
+    if data.len() as u64 > max {
+        throw!(CompressionError::DecompressedTooLarge { max })
+    }
+    data
+}
+
+#[throws(CompressionError)]
+pub fn compress(method: CompressionMethod, data: &[u8]) -> Vec<u8> {
+    let compressed = match method {
+        CompressionMethod::Zlib => deflate(data)?,
+        CompressionMethod::Zstd => {
+            zstd::encode_all(data, 0).map_err(CompressionError::CompressionFailed)?
+        }
+    };
+
+    MAGIC
+        .iter()
+        .chain([method.id()].iter())
+        .chain(compressed.iter())
+        .copied()
+        .collect()
+}
+
+/// Decompresses a payload made by [`compress`], up to
+/// [`MAX_DECOMPRESSED_SIZE`] bytes.
+#[throws(CompressionError)]
+pub fn decompress(payload: &[u8]) -> Vec<u8> {
+    decompress_limited(payload, MAX_DECOMPRESSED_SIZE)?
+}
+
+/// Like [`decompress`], failing once the output grows past `max` bytes.
+#[throws(CompressionError)]
+pub fn decompress_limited(payload: &[u8], max: u64) -> Vec<u8> {
+    let method = compression_method(payload)?;
+    let compressed = &payload[HEADER_LENGTH..];
+
+    match method {
+        CompressionMethod::Zlib => inflate_limited(compressed, max)?,
+        CompressionMethod::Zstd => {
+            let decoder =
+                zstd::Decoder::new(compressed).map_err(CompressionError::DecompressionFailed)?;
+            read_limited(decoder, max)?
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_data() -> Vec<u8> {
+        "{\"message\": \"This is where your secret message will be!\"}"
+            .repeat(20)
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_zlib_round_trip() {
+        let data = testing_data();
+        let payload = compress(CompressionMethod::Zlib, &data).unwrap();
+
+        assert!(is_compressed(&payload));
+        assert!(payload.len() < data.len());
+        assert_eq!(
+            compression_method(&payload).unwrap(),
+            CompressionMethod::Zlib
+        );
+        assert_eq!(decompress(&payload).unwrap(), data);
+    }
+
+    #[test]
+    fn test_zstd_round_trip() {
+        let data = testing_data();
+        let payload = compress(CompressionMethod::Zstd, &data).unwrap();
+
+        assert!(is_compressed(&payload));
+        assert!(payload.len() < data.len());
+        assert_eq!(
+            compression_method(&payload).unwrap(),
+            CompressionMethod::Zstd
+        );
+        assert_eq!(decompress(&payload).unwrap(), data);
+    }
+
+    #[test]
+    fn test_deflate_inflate_round_trip() {
+        let data = testing_data();
+        let compressed = deflate(&data).unwrap();
+
+        assert!(!is_compressed(&compressed));
+        assert_eq!(inflate(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decompression_bomb() {
+        // A few kilobytes that expand to 16 MiB of zeros
+        let data = vec![0; 16 * 1024 * 1024];
+        let max = 1024 * 1024;
+
+        let compressed = deflate(&data).unwrap();
+        assert!(compressed.len() < 32 * 1024);
+        assert!(matches!(
+            inflate_limited(&compressed, max),
+            Err(CompressionError::DecompressedTooLarge { max: 1048576 })
+        ));
+
+        for method in [CompressionMethod::Zlib, CompressionMethod::Zstd] {
+            let payload = compress(method, &data).unwrap();
+            assert!(matches!(
+                decompress_limited(&payload, max),
+                Err(CompressionError::DecompressedTooLarge { .. })
+            ));
+            assert_eq!(
+                decompress_limited(&payload, data.len() as u64).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_plain_payload() {
+        let data = decompress(b"not compressed");
+        assert!(matches!(data, Err(CompressionError::NotCompressed)));
+    }
+
+    #[test]
+    fn test_decompress_unknown_method() {
+        let data = decompress(b"PMCZ\x09data");
+        assert!(matches!(data, Err(CompressionError::UnknownMethod(9))));
+    }
+}
@@ -0,0 +1,440 @@
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use argon2::Argon2;
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{mac, sign};
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("failed to derive key from passphrase")]
+    KeyDerivation,
+
+    #[error("payload is not encrypted")]
+    NotEncrypted,
+
+    #[error("unsupported encryption version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("encrypted payload too short")]
+    PayloadTooShort,
+
+    #[error("encryption failed")]
+    EncryptionFailed,
+
+    #[error("decryption failed, wrong passphrase or tampered payload")]
+    DecryptionFailed,
+
+    #[cfg(feature = "age")]
+    #[error("invalid age recipient `{0}`")]
+    InvalidRecipient(String),
+
+    #[cfg(feature = "age")]
+    #[error("no age recipients given")]
+    NoRecipients,
+
+    #[cfg(feature = "age")]
+    #[error("invalid age identity file")]
+    InvalidIdentity,
+
+    #[cfg(feature = "age")]
+    #[error("age decryption failed")]
+    Age(#[from] age::DecryptError),
+}
+
+/// Encrypted payload layout:
+///
+/// `MAGIC (4) | VERSION (1) | salt (16) | nonce (12) | ciphertext + tag`
+pub const MAGIC: [u8; 4] = *b"PMEC";
+pub const VERSION: u8 = 1;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + SALT_LENGTH + NONCE_LENGTH;
+const TAG_LENGTH: usize = 16;
+
+/// Bytes [`encrypt`] adds to a message.
+pub const OVERHEAD: usize = HEADER_LENGTH + TAG_LENGTH;
+
+pub fn is_encrypted(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+#[throws(CryptoError)]
+fn derive_key(passphrase: &[u8], salt: &[u8]) -> [u8; KEY_LENGTH] {
+    let mut key = [0; KEY_LENGTH];
+
+    Argon2::default()
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|_| CryptoError::KeyDerivation)?;
+
+    key
+}
+
+#[throws(CryptoError)]
+pub fn encrypt(passphrase: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut salt = [0; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).expect("key of length 32");
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+
+    MAGIC
+        .iter()
+        .chain([VERSION].iter())
+        .chain(salt.iter())
+        .chain(nonce.iter())
+        .chain(ciphertext.iter())
+        .copied()
+        .collect()
+}
+
+#[throws(CryptoError)]
+pub fn decrypt(passphrase: &[u8], payload: &[u8]) -> Vec<u8> {
+    if !is_encrypted(payload) {
+        throw!(CryptoError::NotEncrypted)
+    }
+
+    if payload.len() < HEADER_LENGTH {
+        throw!(CryptoError::PayloadTooShort)
+    }
+
+    let version = payload[MAGIC.len()];
+    if version != VERSION {
+        throw!(CryptoError::UnsupportedVersion(version))
+    }
+
+    let salt_start = MAGIC.len() + 1;
+    let nonce_start = salt_start + SALT_LENGTH;
+
+    let salt = &payload[salt_start..nonce_start];
+    let nonce = Nonce::from_slice(&payload[nonce_start..HEADER_LENGTH]);
+    let ciphertext = &payload[HEADER_LENGTH..];
+
+    let key = derive_key(passphrase, salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).expect("key of length 32");
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|_| CryptoError::DecryptionFailed)?
+}
+
+/// How much of `payload` its decoys copy: the plain-text headers of every
+/// envelope, down to the first bytes that look random, so no prefix sets the
+/// real payload apart. Signed or tagged messages that aren't encrypted are
+/// readable anyway, and copied up to their signature or tag.
+fn decoy_header_length(payload: &[u8]) -> usize {
+    let envelope = |header: usize, trailer: usize| {
+        let inner = payload
+            .get(header..payload.len().saturating_sub(trailer))
+            .unwrap_or_default();
+        header
+            + match decoy_header_length(inner) {
+                0 => inner.len(),
+                length => length,
+            }
+    };
+
+    if sign::is_signed(payload) {
+        return envelope(sign::HEADER_LENGTH, sign::SIGNATURE_LENGTH);
+    }
+    if mac::is_tagged(payload) {
+        return envelope(mac::HEADER_LENGTH, mac::TAG_LENGTH);
+    }
+    #[cfg(feature = "age")]
+    if is_age_encrypted(payload) {
+        return age_header_length(payload);
+    }
+
+    // The salt and nonce after the version are random already
+    match is_encrypted(payload) {
+        true => (MAGIC.len() + 1).min(payload.len()),
+        false => 0,
+    }
+}
+
+/// Length of the text header of an age payload, up to and including the
+/// `--- ` line with its mac, after which the payload is random.
+#[cfg(feature = "age")]
+fn age_header_length(payload: &[u8]) -> usize {
+    let mac_line = payload.windows(5).position(|window| window == b"\n--- ");
+    mac_line
+        .and_then(|start| {
+            let end = payload[start + 1..]
+                .iter()
+                .position(|&byte| byte == b'\n')?;
+            Some(start + 1 + end + 1)
+        })
+        .unwrap_or(AGE_MAGIC.len().min(payload.len()))
+}
+
+/// A random payload that looks like `payload`, with the same headers and
+/// about the same length, but that no key will decrypt or verify.
+pub fn decoy(payload: &[u8]) -> Vec<u8> {
+    let header = &payload[..decoy_header_length(payload)];
+
+    // Only the random part varies in length, as the headers can't
+    let random = payload.len() - header.len();
+    let spread = random / 4 + 1;
+    let length =
+        header.len() + (random + OsRng.next_u32() as usize % spread).saturating_sub(spread / 2);
+
+    let mut decoy = vec![0; length];
+    decoy[..header.len()].copy_from_slice(header);
+    OsRng.fill_bytes(&mut decoy[header.len()..]);
+
+    decoy
+}
+
+/// Mixes `count` decoys in with `payload`, in random order.
+pub fn with_decoys(payload: &[u8], count: usize) -> Vec<Vec<u8>> {
+    let mut payloads: Vec<Vec<u8>> = (0..count).map(|_| decoy(payload)).collect();
+    payloads.push(payload.to_vec());
+
+    for index in (1..payloads.len()).rev() {
+        let other = OsRng.next_u32() as usize % (index + 1);
+        payloads.swap(index, other);
+    }
+
+    payloads
+}
+
+/// Start of every payload encrypted to age recipients.
+#[cfg(feature = "age")]
+pub const AGE_MAGIC: &[u8] = b"age-encryption.org/";
+
+#[cfg(feature = "age")]
+pub fn is_age_encrypted(payload: &[u8]) -> bool {
+    payload.starts_with(AGE_MAGIC)
+}
+
+/// Encrypts `plaintext` so that any of the `age1...` `recipients` can decrypt it.
+#[cfg(feature = "age")]
+#[throws(CryptoError)]
+pub fn encrypt_to_recipients(recipients: &[&str], plaintext: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let recipients = recipients
+        .iter()
+        .map(|recipient| {
+            recipient
+                .parse::<age::x25519::Recipient>()
+                .map(|recipient| Box::new(recipient) as Box<dyn age::Recipient + Send>)
+                .map_err(|_| CryptoError::InvalidRecipient(recipient.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let encryptor = age::Encryptor::with_recipients(recipients).ok_or(CryptoError::NoRecipients)?;
+
+    let mut payload = vec![];
+    let mut writer = encryptor
+        .wrap_output(&mut payload)
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+    writer
+        .write_all(plaintext)
+        .and_then(|_| writer.finish())
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+
+    payload
+}
+
+/// Decrypts an age payload with the identities in an age identity file.
+#[cfg(feature = "age")]
+#[throws(CryptoError)]
+pub fn decrypt_with_identities(identity_file: &[u8], payload: &[u8]) -> Vec<u8> {
+    use std::io::Read;
+
+    if !is_age_encrypted(payload) {
+        throw!(CryptoError::NotEncrypted)
+    }
+
+    let identities: Vec<age::x25519::Identity> = age::IdentityFile::from_buffer(identity_file)
+        .map_err(|_| CryptoError::InvalidIdentity)?
+        .into_identities()
+        .into_iter()
+        .map(|entry| match entry {
+            age::IdentityFileEntry::Native(identity) => identity,
+        })
+        .collect();
+
+    if identities.is_empty() {
+        throw!(CryptoError::InvalidIdentity)
+    }
+
+    let decryptor = match age::Decryptor::new(payload)? {
+        age::Decryptor::Recipients(decryptor) => decryptor,
+        _ => throw!(CryptoError::NotEncrypted),
+    };
+
+    let mut reader = decryptor.decrypt(
+        identities
+            .iter()
+            .map(|identity| identity as &dyn age::Identity),
+    )?;
+
+    let mut plaintext = vec![];
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+
+    plaintext
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let payload = encrypt(b"hunter2", b"This is a secret message!").unwrap();
+        assert!(is_encrypted(&payload));
+        assert_eq!(payload.len(), b"This is a secret message!".len() + OVERHEAD);
+
+        let plaintext = decrypt(b"hunter2", &payload).unwrap();
+        assert_eq!(plaintext, b"This is a secret message!");
+    }
+
+    #[test]
+    fn test_decrypt_wrong_passphrase() {
+        let payload = encrypt(b"hunter2", b"This is a secret message!").unwrap();
+        let plaintext = decrypt(b"hunter3", &payload);
+        assert!(matches!(plaintext, Err(CryptoError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_decrypt_tampered_payload() {
+        let mut payload = encrypt(b"hunter2", b"This is a secret message!").unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+
+        assert!(decrypt(b"hunter2", &payload).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_plain_payload() {
+        let plaintext = decrypt(b"hunter2", b"not encrypted");
+        assert!(matches!(plaintext, Err(CryptoError::NotEncrypted)));
+    }
+
+    #[test]
+    fn test_with_decoys() {
+        let payload = encrypt(b"hunter2", b"This is a secret message!").unwrap();
+        let payloads = with_decoys(&payload, 4);
+
+        assert_eq!(payloads.len(), 5);
+        assert_eq!(payloads.iter().filter(|p| **p == payload).count(), 1);
+
+        for decoy in payloads.iter().filter(|p| **p != payload) {
+            assert!(is_encrypted(decoy));
+            assert!(decoy.len() >= payload.len() * 7 / 8);
+            assert!(decoy.len() <= payload.len() * 9 / 8 + 1);
+            assert!(matches!(
+                decrypt(b"hunter2", decoy),
+                Err(CryptoError::DecryptionFailed)
+            ));
+        }
+    }
+
+    /// Checks every decoy shares the first `header` bytes of `payload` and
+    /// differs from it right after.
+    fn assert_decoy_headers(payload: &[u8], header: usize) {
+        for decoy in with_decoys(payload, 8)
+            .iter()
+            .filter(|decoy| decoy.as_slice() != payload)
+        {
+            assert_eq!(decoy[..header], payload[..header]);
+            assert_ne!(decoy[header..], payload[header..]);
+        }
+    }
+
+    #[test]
+    fn test_decoy_headers() {
+        use crate::chunk_type::ChunkType;
+        use std::str::FromStr;
+
+        let encrypted = encrypt(b"hunter2", b"This is a secret message!").unwrap();
+        assert_decoy_headers(&encrypted, 5);
+
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let tagged = mac::tag(b"key", &chunk_type, &encrypted);
+        assert_decoy_headers(&tagged, 10);
+
+        let (secret_key, _) = sign::generate_keypair();
+        let signed = sign::sign(&secret_key, &tagged);
+        assert_decoy_headers(&signed, 15);
+
+        // Without encryption the message can be read anyway, so only the
+        // signature sets it apart
+        let signed = sign::sign(&secret_key, b"signed in the clear");
+        assert_decoy_headers(&signed, 5 + b"signed in the clear".len());
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn test_age_decoys() {
+        use age::secrecy::ExposeSecret;
+
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        let identity_file = identity.to_string();
+
+        let payload = encrypt_to_recipients(&[&recipient], b"for your eyes only").unwrap();
+        let mac_line = payload.windows(5).position(|w| w == b"\n--- ").unwrap() + 1;
+        let header = mac_line
+            + payload[mac_line..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .unwrap()
+            + 1;
+        assert_decoy_headers(&payload, header);
+
+        for decoy in with_decoys(&payload, 4).iter().filter(|p| **p != payload) {
+            assert!(
+                decrypt_with_identities(identity_file.expose_secret().as_bytes(), decoy).is_err()
+            );
+        }
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn test_age_round_trip() {
+        use age::secrecy::ExposeSecret;
+
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        let identity_file = identity.to_string();
+
+        let payload = encrypt_to_recipients(&[&recipient], b"for your eyes only").unwrap();
+        assert!(is_age_encrypted(&payload));
+
+        let plaintext =
+            decrypt_with_identities(identity_file.expose_secret().as_bytes(), &payload).unwrap();
+        assert_eq!(plaintext, b"for your eyes only");
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn test_age_wrong_identity() {
+        use age::secrecy::ExposeSecret;
+
+        let recipient = age::x25519::Identity::generate().to_public().to_string();
+        let other = age::x25519::Identity::generate().to_string();
+
+        let payload = encrypt_to_recipients(&[&recipient], b"secret").unwrap();
+
+        assert!(decrypt_with_identities(other.expose_secret().as_bytes(), &payload).is_err());
+        assert!(matches!(
+            encrypt_to_recipients(&["age1nope"], b"secret"),
+            Err(CryptoError::InvalidRecipient(_))
+        ));
+    }
+}
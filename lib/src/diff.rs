@@ -0,0 +1,292 @@
+use sha2::{Digest, Sha256};
+
+use crate::{chunk::Chunk, png::Png};
+
+/// Largest number of chunk pairs compared when aligning two pngs. Beyond it
+/// the differing middle of the files isn't aligned, only paired up by type.
+const MAX_ALIGNMENT_CELLS: usize = 1 << 22;
+
+/// A difference between the chunks of two pngs. Indices are positions in the
+/// chunk list of the png the chunk belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkChange<'a> {
+    Removed {
+        index: usize,
+        chunk: &'a Chunk,
+    },
+    Added {
+        index: usize,
+        chunk: &'a Chunk,
+    },
+    Modified {
+        old_index: usize,
+        new_index: usize,
+        old: &'a Chunk,
+        new: &'a Chunk,
+    },
+}
+
+/// A run of bytes that differs between two chunk payloads, starting at
+/// `offset` in both. Either side is shorter when one payload ends early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteChange<'a> {
+    pub offset: usize,
+    pub old: &'a [u8],
+    pub new: &'a [u8],
+}
+
+/// SHA-256 of a chunk's data, in hex.
+pub fn data_hash(chunk: &Chunk) -> String {
+    Sha256::digest(chunk.data())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Pairs of indices of equal chunks, in order, forming a longest common
+/// subsequence of `old` and `new`.
+fn align(old: &[Chunk], new: &[Chunk]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|index| (index, index)).collect();
+
+    if old_middle.len() * new_middle.len() <= MAX_ALIGNMENT_CELLS {
+        // lengths[i][j] is the length of the longest common subsequence of
+        // old_middle[i..] and new_middle[j..]
+        let width = new_middle.len() + 1;
+        let mut lengths = vec![0u32; (old_middle.len() + 1) * width];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                lengths[i * width + j] = match old_middle[i] == new_middle[j] {
+                    true => lengths[(i + 1) * width + j + 1] + 1,
+                    false => lengths[(i + 1) * width + j].max(lengths[i * width + j + 1]),
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() && j < new_middle.len() {
+            if old_middle[i] == new_middle[j] {
+                pairs.push((prefix + i, prefix + j));
+                (i, j) = (i + 1, j + 1);
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+
+    pairs.extend(
+        (0..suffix).map(|offset| (old.len() - suffix + offset, new.len() - suffix + offset)),
+    );
+
+    pairs
+}
+
+/// Reports the chunks in a gap between aligned chunks, pairing removed and
+/// added chunks of the same type as modifications.
+fn diff_gap<'a>(
+    old: &'a [Chunk],
+    old_range: std::ops::Range<usize>,
+    new: &'a [Chunk],
+    new_range: std::ops::Range<usize>,
+    changes: &mut Vec<ChunkChange<'a>>,
+) {
+    let mut added: Vec<usize> = new_range.collect();
+
+    for old_index in old_range {
+        let chunk_type = old[old_index].chunk_type();
+        match added
+            .iter()
+            .position(|&index| new[index].chunk_type() == chunk_type)
+        {
+            Some(position) => {
+                let new_index = added.remove(position);
+                changes.push(ChunkChange::Modified {
+                    old_index,
+                    new_index,
+                    old: &old[old_index],
+                    new: &new[new_index],
+                });
+            }
+            None => changes.push(ChunkChange::Removed {
+                index: old_index,
+                chunk: &old[old_index],
+            }),
+        }
+    }
+
+    changes.extend(added.into_iter().map(|index| ChunkChange::Added {
+        index,
+        chunk: &new[index],
+    }));
+}
+
+/// Lists the chunks removed, added and modified going from `old` to `new`.
+/// Chunks kept with the same type, data and crc aren't reported, a chunk that
+/// only moved shows up as removed and added.
+pub fn diff<'a>(old: &'a Png, new: &'a Png) -> Vec<ChunkChange<'a>> {
+    let (old, new) = (old.chunks(), new.chunks());
+    let mut changes = vec![];
+    let (mut old_start, mut new_start) = (0, 0);
+
+    let end = (old.len(), new.len());
+    for (old_index, new_index) in align(old, new).into_iter().chain([end]) {
+        diff_gap(
+            old,
+            old_start..old_index,
+            new,
+            new_start..new_index,
+            &mut changes,
+        );
+        (old_start, new_start) = (old_index + 1, new_index + 1);
+    }
+
+    changes
+}
+
+/// Lists the runs of bytes that differ between `old` and `new`, comparing
+/// them at the same offsets.
+pub fn byte_changes<'a>(old: &'a [u8], new: &'a [u8]) -> Vec<ByteChange<'a>> {
+    let mut changes = vec![];
+    let common = old.len().min(new.len());
+    let mut offset = 0;
+
+    while offset < common {
+        if old[offset] == new[offset] {
+            offset += 1;
+            continue;
+        }
+
+        let start = offset;
+        while offset < common && old[offset] != new[offset] {
+            offset += 1;
+        }
+        changes.push(ByteChange {
+            offset: start,
+            old: &old[start..offset],
+            new: &new[start..offset],
+        });
+    }
+
+    if old.len() != new.len() {
+        changes.push(ByteChange {
+            offset: common,
+            old: &old[common..],
+            new: &new[common..],
+        });
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), data.to_vec())
+    }
+
+    fn png(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(chunk_type, data)| chunk(chunk_type, data))
+                .collect(),
+        )
+    }
+
+    fn summary(changes: &[ChunkChange]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|change| match change {
+                ChunkChange::Removed { index, chunk } => {
+                    format!("-{}{}", chunk.chunk_type(), index)
+                }
+                ChunkChange::Added { index, chunk } => format!("+{}{}", chunk.chunk_type(), index),
+                ChunkChange::Modified {
+                    old_index,
+                    new_index,
+                    old,
+                    ..
+                } => format!("~{}{}{}", old.chunk_type(), old_index, new_index),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let a = png(&[("IHDR", b"h"), ("IDAT", b"d"), ("IEND", b"")]);
+        let b = png(&[("IHDR", b"h"), ("IDAT", b"d"), ("IEND", b"")]);
+
+        assert!(diff(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let a = png(&[
+            ("IHDR", b"h"),
+            ("tEXt", b"a"),
+            ("gAMA", b"g"),
+            ("IDAT", b"d"),
+            ("IEND", b""),
+        ]);
+        let b = png(&[
+            ("IHDR", b"h"),
+            ("tEXt", b"b"),
+            ("IDAT", b"d"),
+            ("ruSt", b"r"),
+            ("IEND", b""),
+        ]);
+
+        assert_eq!(summary(&diff(&a, &b)), vec!["~tEXt11", "-gAMA2", "+ruSt3"]);
+    }
+
+    #[test]
+    fn test_diff_moved_chunk() {
+        let a = png(&[("IHDR", b"h"), ("tEXt", b"t"), ("IDAT", b"d")]);
+        let b = png(&[("IHDR", b"h"), ("IDAT", b"d"), ("tEXt", b"t")]);
+
+        assert_eq!(summary(&diff(&a, &b)), vec!["-tEXt1", "+tEXt2"]);
+    }
+
+    #[test]
+    fn test_data_hash() {
+        assert_eq!(
+            data_hash(&chunk("IEND", b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_byte_changes() {
+        assert_eq!(
+            byte_changes(b"hello world", b"jello wor"),
+            vec![
+                ByteChange {
+                    offset: 0,
+                    old: b"h",
+                    new: b"j"
+                },
+                ByteChange {
+                    offset: 9,
+                    old: b"ld",
+                    new: b""
+                },
+            ]
+        );
+        assert!(byte_changes(b"same", b"same").is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use fehler::{throw, throws};
+use std::io::Read;
+
+use crate::{
+    chunk::{ChunkParseError, CrcHasher},
+    chunk_type::ChunkType,
+    png::{read_exact_or_eof, Png, PngParseError},
+};
+
+/// Chunk data is handed out in pieces of at most this many bytes.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// One step of parsing a png stream with [`parse_events`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngEvent<'a> {
+    /// The png signature was read and is valid.
+    Signature,
+    /// The length and type of the next chunk, and where it starts.
+    ChunkHeader {
+        offset: u64,
+        length: u32,
+        chunk_type: ChunkType,
+    },
+    /// A piece of the current chunk's data. Long chunks arrive in several
+    /// pieces, and the slice is only valid until the next event.
+    ChunkData(&'a [u8]),
+    /// The stored crc of the current chunk, after all of its data. A crc
+    /// that doesn't match fails the parse here, after the data was seen.
+    ChunkEnd { crc: u32 },
+}
+
+/// What the parser should do after an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    Continue,
+    /// Reads past the rest of the current chunk's data without handing it
+    /// out. `ChunkEnd` still follows.
+    SkipChunk,
+    Stop,
+}
+
+/// Receives the events of a png stream, one at a time.
+pub trait PngVisitor {
+    /// Parse errors are converted to this, so visitors that write somewhere
+    /// can fail with their own errors too.
+    type Error: From<PngParseError>;
+
+    fn visit(&mut self, event: PngEvent<'_>) -> Result<Visit, Self::Error>;
+}
+
+/// Parses a png stream into events without holding more than a small buffer
+/// in memory, however large the chunks are.
+pub struct EventParser<R> {
+    reader: R,
+    verify_crc: bool,
+}
+
+impl<R: Read> EventParser<R> {
+    pub fn new(reader: R) -> Self {
+        EventParser {
+            reader,
+            verify_crc: true,
+        }
+    }
+
+    /// Sets whether chunk crcs are checked. Defaults to true.
+    pub fn verify_crc(mut self, verify_crc: bool) -> Self {
+        self.verify_crc = verify_crc;
+        self
+    }
+
+    /// Feeds every event to `visitor`, until the stream ends or the visitor
+    /// stops.
+    #[throws(V::Error)]
+    pub fn run<V: PngVisitor>(mut self, visitor: &mut V) {
+        let mut signature = [0; 8];
+        if !read_exact_or_eof(&mut self.reader, &mut signature)?
+            || signature != Png::STANDARD_HEADER
+        {
+            throw!(PngParseError::InvalidPngHeader)
+        }
+        if visitor.visit(PngEvent::Signature)? == Visit::Stop {
+            return;
+        }
+
+        let mut offset = signature.len() as u64;
+        let mut buffer = vec![0; BUFFER_SIZE];
+
+        loop {
+            let mut header = [0; 8];
+            if !read_exact_or_eof(&mut self.reader, &mut header)? {
+                break;
+            }
+
+            let length = u32::from_be_bytes(header[..4].try_into().expect("slice of length 4"));
+            let chunk_type: [u8; 4] = header[4..].try_into().expect("slice of length 4");
+            let chunk_type = ChunkType::try_from(chunk_type)
+                .map_err(|err| PngParseError::from(ChunkParseError::from(err)))?;
+
+            let event = PngEvent::ChunkHeader {
+                offset,
+                length,
+                chunk_type,
+            };
+            let mut skip = match visitor.visit(event)? {
+                Visit::Continue => false,
+                Visit::SkipChunk => true,
+                Visit::Stop => return,
+            };
+
+            // The crc is computed even for skipped data, so a skipped chunk
+            // can't hide a corrupt file
+            let mut hasher = CrcHasher::new(&chunk_type);
+            let mut remaining = length as usize;
+            while remaining > 0 {
+                let piece = &mut buffer[..remaining.min(BUFFER_SIZE)];
+                read_exact(&mut self.reader, piece)?;
+                hasher.update(piece);
+                remaining -= piece.len();
+
+                if !skip {
+                    match visitor.visit(PngEvent::ChunkData(piece))? {
+                        Visit::Continue => {}
+                        Visit::SkipChunk => skip = true,
+                        Visit::Stop => return,
+                    }
+                }
+            }
+
+            let mut crc = [0; 4];
+            read_exact(&mut self.reader, &mut crc)?;
+            let crc = u32::from_be_bytes(crc);
+            let calculated_crc = hasher.finalize();
+            if self.verify_crc && calculated_crc != crc {
+                throw!(PngParseError::from(ChunkParseError::InvalidCrc {
+                    expected: crc,
+                    actual: calculated_crc
+                }))
+            }
+
+            if visitor.visit(PngEvent::ChunkEnd { crc })? == Visit::Stop {
+                return;
+            }
+            offset += length as u64 + 12;
+        }
+    }
+}
+
+/// Parses a png stream into events for `visitor`, checking chunk crcs. See
+/// [`EventParser`] for the options.
+#[throws(V::Error)]
+pub fn parse_events<V: PngVisitor>(reader: impl Read, visitor: &mut V) {
+    EventParser::new(reader).run(visitor)?
+}
+
+#[throws(PngParseError)]
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) {
+    if !read_exact_or_eof(reader, buf)? {
+        throw!(PngParseError::PngTooShort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, chunk::Chunk, png::ChunkReader};
+
+    /// Rebuilds the chunks it is shown, skipping those of one type.
+    struct Collector {
+        skip: Option<ChunkType>,
+        pieces: usize,
+        current: Option<(ChunkType, Vec<u8>)>,
+        chunks: Vec<Chunk>,
+    }
+
+    impl Collector {
+        fn new(skip: Option<ChunkType>) -> Self {
+            Collector {
+                skip,
+                pieces: 0,
+                current: None,
+                chunks: vec![],
+            }
+        }
+    }
+
+    impl PngVisitor for Collector {
+        type Error = PngParseError;
+
+        fn visit(&mut self, event: PngEvent<'_>) -> Result<Visit, Self::Error> {
+            match event {
+                PngEvent::ChunkHeader { chunk_type, .. } if Some(chunk_type) == self.skip => {
+                    return Ok(Visit::SkipChunk)
+                }
+                PngEvent::ChunkHeader { chunk_type, .. } => {
+                    self.current = Some((chunk_type, vec![]));
+                }
+                PngEvent::ChunkData(data) => {
+                    self.pieces += 1;
+                    self.current.as_mut().unwrap().1.extend(data);
+                }
+                PngEvent::ChunkEnd { .. } => {
+                    if let Some((chunk_type, data)) = self.current.take() {
+                        self.chunks.push(Chunk::new(chunk_type, data));
+                    }
+                }
+                PngEvent::Signature => {}
+            }
+
+            Ok(Visit::Continue)
+        }
+    }
+
+    fn noise_png() -> Vec<u8> {
+        // Large enough for the image data to come in several pieces
+        PngBuilder::new(128, 128)
+            .noise()
+            .build()
+            .unwrap()
+            .as_bytes()
+    }
+
+    #[test]
+    fn test_events_match_chunks() {
+        let bytes = noise_png();
+        let mut collector = Collector::new(None);
+        parse_events(bytes.as_slice(), &mut collector).unwrap();
+
+        let chunks: Vec<Chunk> = ChunkReader::new(bytes.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(collector.chunks, chunks);
+        assert!(collector.pieces > chunks.len());
+    }
+
+    #[test]
+    fn test_skip_and_stop() {
+        let bytes = noise_png();
+        let mut collector = Collector::new(Some(ChunkType::try_from(*b"IDAT").unwrap()));
+        parse_events(bytes.as_slice(), &mut collector).unwrap();
+
+        let types: Vec<String> = collector
+            .chunks
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IEND"]);
+
+        struct FirstHeader(Option<u64>);
+        impl PngVisitor for FirstHeader {
+            type Error = PngParseError;
+
+            fn visit(&mut self, event: PngEvent<'_>) -> Result<Visit, Self::Error> {
+                if let PngEvent::ChunkHeader { offset, .. } = event {
+                    self.0 = Some(offset);
+                    return Ok(Visit::Stop);
+                }
+                Ok(Visit::Continue)
+            }
+        }
+
+        // Stopping early never reads the rest, truncated or not
+        let mut first = FirstHeader(None);
+        parse_events(&bytes[..20], &mut first).unwrap();
+        assert_eq!(first.0, Some(8));
+    }
+
+    #[test]
+    fn test_invalid_stream() {
+        let mut bytes = noise_png();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(matches!(
+            parse_events(bytes.as_slice(), &mut Collector::new(None)),
+            Err(PngParseError::ChunkParsingError(
+                ChunkParseError::InvalidCrc { .. }
+            ))
+        ));
+        assert!(EventParser::new(bytes.as_slice())
+            .verify_crc(false)
+            .run(&mut Collector::new(None))
+            .is_ok());
+
+        assert!(matches!(
+            parse_events(&bytes[..bytes.len() - 2], &mut Collector::new(None)),
+            Err(PngParseError::PngTooShort)
+        ));
+        assert!(matches!(
+            parse_events(&bytes[1..], &mut Collector::new(None)),
+            Err(PngParseError::InvalidPngHeader)
+        ));
+    }
+}
@@ -0,0 +1,499 @@
+use fehler::{throw, throws};
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Debug, Error)]
+pub enum ExifError {
+    #[error("expected an `eXIf` chunk but got `{0}`")]
+    UnexpectedChunkType(String),
+
+    #[error("invalid tiff header")]
+    InvalidHeader,
+
+    #[error("exif data truncated at offset `{0}`")]
+    Truncated(usize),
+
+    #[error("ifd offsets loop back on themselves")]
+    OffsetLoop,
+}
+
+const EXIF: [u8; 4] = *b"eXIf";
+
+/// Prefix used by the jpeg APP1 segment, which isn't allowed in png.
+const JPEG_EXIF_PREFIX: &[u8] = b"Exif\0\0";
+
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const GPS_IFD_POINTER: u16 = 0x8825;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ifd {
+    Primary,
+    Exif,
+    Gps,
+}
+
+impl Display for Ifd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ifd::Primary => write!(f, "IFD0"),
+            Ifd::Exif => write!(f, "Exif"),
+            Ifd::Gps => write!(f, "GPS"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SignedByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SignedShort(Vec<i16>),
+    SignedLong(Vec<i32>),
+    SignedRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    Unknown { field_type: u16, count: u32 },
+}
+
+fn join<T: Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for ExifValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExifValue::Ascii(value) => write!(f, "{}", value),
+            ExifValue::Byte(values) => write!(f, "{}", join(values)),
+            ExifValue::Short(values) => write!(f, "{}", join(values)),
+            ExifValue::Long(values) => write!(f, "{}", join(values)),
+            ExifValue::SignedByte(values) => write!(f, "{}", join(values)),
+            ExifValue::SignedShort(values) => write!(f, "{}", join(values)),
+            ExifValue::SignedLong(values) => write!(f, "{}", join(values)),
+            ExifValue::Float(values) => write!(f, "{}", join(values)),
+            ExifValue::Double(values) => write!(f, "{}", join(values)),
+            ExifValue::Rational(values) => {
+                let values: Vec<String> = values.iter().map(|(n, d)| format!("{n}/{d}")).collect();
+                write!(f, "{}", values.join(", "))
+            }
+            ExifValue::SignedRational(values) => {
+                let values: Vec<String> = values.iter().map(|(n, d)| format!("{n}/{d}")).collect();
+                write!(f, "{}", values.join(", "))
+            }
+            ExifValue::Undefined(bytes) => {
+                if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                    write!(f, "{}", String::from_utf8_lossy(bytes))
+                } else {
+                    write!(f, "<{} bytes>", bytes.len())
+                }
+            }
+            ExifValue::Unknown { field_type, count } => {
+                write!(f, "<{} values of unknown type {}>", count, field_type)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExifEntry {
+    pub ifd: Ifd,
+    pub tag: u16,
+    pub value: ExifValue,
+}
+
+impl ExifEntry {
+    pub fn name(&self) -> Option<&'static str> {
+        tag_name(self.ifd, self.tag)
+    }
+}
+
+pub fn tag_name(ifd: Ifd, tag: u16) -> Option<&'static str> {
+    let name = match (ifd, tag) {
+        (Ifd::Gps, 0x0000) => "GPSVersionID",
+        (Ifd::Gps, 0x0001) => "GPSLatitudeRef",
+        (Ifd::Gps, 0x0002) => "GPSLatitude",
+        (Ifd::Gps, 0x0003) => "GPSLongitudeRef",
+        (Ifd::Gps, 0x0004) => "GPSLongitude",
+        (Ifd::Gps, 0x0005) => "GPSAltitudeRef",
+        (Ifd::Gps, 0x0006) => "GPSAltitude",
+        (Ifd::Gps, 0x0007) => "GPSTimeStamp",
+        (Ifd::Gps, 0x001d) => "GPSDateStamp",
+        (Ifd::Gps, _) => return None,
+        (_, 0x0100) => "ImageWidth",
+        (_, 0x0101) => "ImageLength",
+        (_, 0x010e) => "ImageDescription",
+        (_, 0x010f) => "Make",
+        (_, 0x0110) => "Model",
+        (_, 0x0112) => "Orientation",
+        (_, 0x011a) => "XResolution",
+        (_, 0x011b) => "YResolution",
+        (_, 0x0128) => "ResolutionUnit",
+        (_, 0x0131) => "Software",
+        (_, 0x0132) => "DateTime",
+        (_, 0x013b) => "Artist",
+        (_, 0x8298) => "Copyright",
+        (_, 0x829a) => "ExposureTime",
+        (_, 0x829d) => "FNumber",
+        (_, 0x8769) => "ExifIFDPointer",
+        (_, 0x8825) => "GPSInfoIFDPointer",
+        (_, 0x8827) => "ISOSpeedRatings",
+        (_, 0x9000) => "ExifVersion",
+        (_, 0x9003) => "DateTimeOriginal",
+        (_, 0x9004) => "DateTimeDigitized",
+        (_, 0x9201) => "ShutterSpeedValue",
+        (_, 0x9202) => "ApertureValue",
+        (_, 0x9209) => "Flash",
+        (_, 0x920a) => "FocalLength",
+        (_, 0x9286) => "UserComment",
+        (_, 0xa001) => "ColorSpace",
+        (_, 0xa002) => "PixelXDimension",
+        (_, 0xa003) => "PixelYDimension",
+        (_, 0xa433) => "LensMake",
+        (_, 0xa434) => "LensModel",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Parsed contents of a tiff structured exif block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exif {
+    byte_order: ByteOrder,
+    entries: Vec<ExifEntry>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    byte_order: ByteOrder,
+}
+
+impl<'a> Reader<'a> {
+    #[throws(ExifError)]
+    fn bytes(&self, offset: usize, length: usize) -> &'a [u8] {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(ExifError::Truncated(offset))?
+    }
+
+    #[throws(ExifError)]
+    fn u16(&self, offset: usize) -> u16 {
+        let bytes = self
+            .bytes(offset, 2)?
+            .try_into()
+            .expect("slice of length 2");
+        match self.byte_order {
+            ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+        }
+    }
+
+    #[throws(ExifError)]
+    fn u32(&self, offset: usize) -> u32 {
+        let bytes = self
+            .bytes(offset, 4)?
+            .try_into()
+            .expect("slice of length 4");
+        match self.byte_order {
+            ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+        }
+    }
+
+    #[throws(ExifError)]
+    fn u64(&self, offset: usize) -> u64 {
+        let bytes = self
+            .bytes(offset, 8)?
+            .try_into()
+            .expect("slice of length 8");
+        match self.byte_order {
+            ByteOrder::LittleEndian => u64::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u64::from_be_bytes(bytes),
+        }
+    }
+
+    #[throws(ExifError)]
+    fn values<T>(
+        &self,
+        offset: usize,
+        count: usize,
+        size: usize,
+        read: impl Fn(&Self, usize) -> Result<T, ExifError>,
+    ) -> Vec<T> {
+        (0..count)
+            .map(|i| read(self, offset + i * size))
+            .collect::<Result<_, _>>()?
+    }
+
+    #[throws(ExifError)]
+    fn entry(&self, ifd: Ifd, offset: usize) -> ExifEntry {
+        let tag = self.u16(offset)?;
+        let field_type = self.u16(offset + 2)?;
+        let count = self.u32(offset + 4)?;
+
+        let size = match field_type {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => {
+                return ExifEntry {
+                    ifd,
+                    tag,
+                    value: ExifValue::Unknown { field_type, count },
+                }
+            }
+        };
+
+        let count = count as usize;
+        let length = count
+            .checked_mul(size)
+            .ok_or(ExifError::Truncated(offset))?;
+
+        // Values that fit into 4 bytes are stored inline instead of an offset
+        let at = if length <= 4 {
+            offset + 8
+        } else {
+            self.u32(offset + 8)? as usize
+        };
+        self.bytes(at, length)?;
+
+        let value = match field_type {
+            1 => ExifValue::Byte(self.bytes(at, count)?.to_vec()),
+            2 => {
+                let text = self.bytes(at, count)?;
+                let text = text.split(|&b| b == 0).next().unwrap_or_default();
+                ExifValue::Ascii(String::from_utf8_lossy(text).to_string())
+            }
+            3 => ExifValue::Short(self.values(at, count, size, Self::u16)?),
+            4 => ExifValue::Long(self.values(at, count, size, Self::u32)?),
+            5 => ExifValue::Rational(
+                self.values(at, count, size, |r, o| Ok((r.u32(o)?, r.u32(o + 4)?)))?,
+            ),
+            6 => ExifValue::SignedByte(self.bytes(at, count)?.iter().map(|&b| b as i8).collect()),
+            7 => ExifValue::Undefined(self.bytes(at, count)?.to_vec()),
+            8 => ExifValue::SignedShort(self.values(at, count, size, |r, o| Ok(r.u16(o)? as i16))?),
+            9 => {
+                ExifValue::SignedLong(self.values(at, count, size, |r, o| Ok(r.u32(o)? as i32))?)
+            }
+            10 => ExifValue::SignedRational(self.values(at, count, size, |r, o| {
+                Ok((r.u32(o)? as i32, r.u32(o + 4)? as i32))
+            })?),
+            11 => ExifValue::Float(
+                self.values(at, count, size, |r, o| Ok(f32::from_bits(r.u32(o)?)))?,
+            ),
+            _ => ExifValue::Double(
+                self.values(at, count, size, |r, o| Ok(f64::from_bits(r.u64(o)?)))?,
+            ),
+        };
+
+        ExifEntry { ifd, tag, value }
+    }
+
+    #[throws(ExifError)]
+    fn ifd(&self, ifd: Ifd, offset: usize, visited: &mut Vec<usize>, entries: &mut Vec<ExifEntry>) {
+        if visited.contains(&offset) {
+            throw!(ExifError::OffsetLoop)
+        }
+        visited.push(offset);
+
+        let count = self.u16(offset)? as usize;
+
+        for i in 0..count {
+            let entry = self.entry(ifd, offset + 2 + i * 12)?;
+
+            let sub_ifd = match (&entry.tag, &entry.value) {
+                (&EXIF_IFD_POINTER, ExifValue::Long(values)) => {
+                    values.first().map(|&offset| (Ifd::Exif, offset))
+                }
+                (&GPS_IFD_POINTER, ExifValue::Long(values)) => {
+                    values.first().map(|&offset| (Ifd::Gps, offset))
+                }
+                _ => None,
+            };
+
+            entries.push(entry);
+
+            if let Some((sub_ifd, sub_offset)) = sub_ifd {
+                self.ifd(sub_ifd, sub_offset as usize, visited, entries)?;
+            }
+        }
+    }
+}
+
+impl Exif {
+    #[throws(ExifError)]
+    pub fn parse(data: &[u8]) -> Self {
+        let byte_order = match data.get(..4) {
+            Some([b'I', b'I', 42, 0]) => ByteOrder::LittleEndian,
+            Some([b'M', b'M', 0, 42]) => ByteOrder::BigEndian,
+            _ => throw!(ExifError::InvalidHeader),
+        };
+
+        let reader = Reader { data, byte_order };
+        let offset = reader.u32(4)? as usize;
+
+        let mut entries = vec![];
+        reader.ifd(Ifd::Primary, offset, &mut vec![], &mut entries)?;
+
+        Exif {
+            byte_order,
+            entries,
+        }
+    }
+
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    pub fn entries(&self) -> &[ExifEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, ifd: Ifd, tag: u16) -> Option<&ExifEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.ifd == ifd && entry.tag == tag)
+    }
+}
+
+/// Raw tiff structured exif data stored in an `eXIf` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExifChunk {
+    data: Vec<u8>,
+}
+
+impl ExifChunk {
+    /// Wraps raw exif data, stripping the `Exif\0\0` prefix found in jpeg files.
+    #[throws(ExifError)]
+    pub fn new(data: &[u8]) -> Self {
+        let data = data.strip_prefix(JPEG_EXIF_PREFIX).unwrap_or(data);
+        Exif::parse(data)?;
+
+        ExifChunk {
+            data: data.to_vec(),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[throws(ExifError)]
+    pub fn parse(&self) -> Exif {
+        Exif::parse(&self.data)?
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let chunk_type = ChunkType::try_from(EXIF).expect("valid chunk type");
+        Chunk::new(chunk_type, self.data.clone())
+    }
+}
+
+impl TryFrom<&Chunk> for ExifChunk {
+    type Error = ExifError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        if chunk.chunk_type().bytes() != EXIF {
+            throw!(ExifError::UnexpectedChunkType(
+                chunk.chunk_type().to_string()
+            ))
+        }
+
+        ExifChunk {
+            data: chunk.data().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    fn testing_exif() -> Vec<u8> {
+        vec![
+            b'I', b'I', 42, 0, 8, 0, 0, 0,          // header, ifd0 at 8
+            3, 0,                                   // 3 entries
+            0x0f, 0x01, 2, 0, 6, 0, 0, 0, 50, 0, 0, 0,  // Make, ascii[6] at 50
+            0x12, 0x01, 3, 0, 1, 0, 0, 0, 1, 0, 0, 0,   // Orientation, short = 1
+            0x69, 0x87, 4, 0, 1, 0, 0, 0, 56, 0, 0, 0,  // ExifIFDPointer = 56
+            0, 0, 0, 0,                             // no next ifd
+            b'C', b'a', b'n', b'o', b'n', 0,        // "Canon" at 50
+            1, 0,                                   // exif ifd, 1 entry
+            0x9d, 0x82, 5, 0, 1, 0, 0, 0, 74, 0, 0, 0,  // FNumber, rational at 74
+            0, 0, 0, 0,                             // no next ifd
+            28, 0, 0, 0, 10, 0, 0, 0,               // 28/10
+        ]
+    }
+
+    #[test]
+    fn test_parse_exif() {
+        let exif = Exif::parse(&testing_exif()).unwrap();
+
+        assert_eq!(exif.byte_order(), ByteOrder::LittleEndian);
+        assert_eq!(exif.entries().len(), 4);
+
+        let make = exif.entry(Ifd::Primary, 0x010f).unwrap();
+        assert_eq!(make.name(), Some("Make"));
+        assert_eq!(make.value, ExifValue::Ascii("Canon".to_string()));
+
+        let orientation = exif.entry(Ifd::Primary, 0x0112).unwrap();
+        assert_eq!(orientation.value, ExifValue::Short(vec![1]));
+
+        let f_number = exif.entry(Ifd::Exif, 0x829d).unwrap();
+        assert_eq!(f_number.value.to_string(), "28/10");
+    }
+
+    #[test]
+    fn test_parse_invalid_header() {
+        assert!(matches!(
+            Exif::parse(b"JUNKJUNK"),
+            Err(ExifError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let exif = testing_exif();
+        assert!(matches!(
+            Exif::parse(&exif[..40]),
+            Err(ExifError::Truncated(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_offset_loop() {
+        let mut exif = testing_exif();
+        exif[42] = 8;
+        assert!(matches!(Exif::parse(&exif), Err(ExifError::OffsetLoop)));
+    }
+
+    #[test]
+    fn test_exif_chunk_strips_jpeg_prefix() {
+        let data = [JPEG_EXIF_PREFIX, &testing_exif()].concat();
+        let exif = ExifChunk::new(&data).unwrap();
+        assert_eq!(exif.data(), testing_exif().as_slice());
+
+        let chunk = exif.to_chunk();
+        assert_eq!(chunk.chunk_type().to_string(), "eXIf");
+        assert_eq!(ExifChunk::try_from(&chunk).unwrap(), exif);
+    }
+}
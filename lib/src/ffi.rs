@@ -0,0 +1,342 @@
+//! C interface, declared in `include/pngme.h`. The shared library is built
+//! on demand with `cargo rustc --release --features ffi --crate-type cdylib`,
+//! so other builds of the crate, `no_std` ones included, stay plain rlibs.
+//!
+//! Every function returns one of the `PNGME_*` status codes. Memory handed
+//! out through an out parameter belongs to the caller, who must release it
+//! with the matching `pngme_*_free` function. Inputs are only borrowed for
+//! the duration of the call.
+
+use std::{
+    ffi::{c_char, CStr},
+    panic::{catch_unwind, UnwindSafe},
+    ptr, slice,
+    str::FromStr,
+};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, compression, png::Png};
+
+pub const PNGME_OK: i32 = 0;
+pub const PNGME_ERROR_NULL_POINTER: i32 = 1;
+pub const PNGME_ERROR_INVALID_PNG: i32 = 2;
+pub const PNGME_ERROR_INVALID_CHUNK_TYPE: i32 = 3;
+pub const PNGME_ERROR_CHUNK_NOT_FOUND: i32 = 4;
+pub const PNGME_ERROR_DECOMPRESSION: i32 = 5;
+pub const PNGME_ERROR_PANIC: i32 = 6;
+
+/// Bytes owned by the caller, released with [`pngme_buffer_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct PngmeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl PngmeBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len();
+
+        PngmeBuffer {
+            data: Box::into_raw(bytes) as *mut u8,
+            len,
+        }
+    }
+}
+
+/// Summary of a chunk, in the array filled by [`pngme_list_chunks`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PngmeChunk {
+    pub chunk_type: [u8; 4],
+    pub length: u32,
+    pub crc: u32,
+}
+
+/// Runs `body`, turning errors and panics into status codes.
+fn guard(body: impl FnOnce() -> Result<(), i32> + UnwindSafe) -> i32 {
+    match catch_unwind(body) {
+        Ok(Ok(())) => PNGME_OK,
+        Ok(Err(code)) => code,
+        Err(_) => PNGME_ERROR_PANIC,
+    }
+}
+
+/// Borrows `len` bytes at `data`, which may be null if `len` is 0.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], i32> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(PNGME_ERROR_NULL_POINTER),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+unsafe fn parse_png(data: *const u8, len: usize) -> Result<Png, i32> {
+    Png::try_from(bytes(data, len)?).map_err(|_| PNGME_ERROR_INVALID_PNG)
+}
+
+unsafe fn parse_chunk_type(chunk_type: *const c_char) -> Result<ChunkType, i32> {
+    if chunk_type.is_null() {
+        return Err(PNGME_ERROR_NULL_POINTER);
+    }
+
+    CStr::from_ptr(chunk_type)
+        .to_str()
+        .ok()
+        .and_then(|chunk_type| ChunkType::from_str(chunk_type).ok())
+        .ok_or(PNGME_ERROR_INVALID_CHUNK_TYPE)
+}
+
+/// Stores `message` in a new chunk of type `chunk_type` and writes the
+/// resulting png to `out`.
+///
+/// # Safety
+///
+/// `png` and `message` must point to `png_len` and `message_len` readable
+/// bytes, `chunk_type` to a null terminated string and `out` to a writable
+/// [`PngmeBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn pngme_encode(
+    png: *const u8,
+    png_len: usize,
+    chunk_type: *const c_char,
+    message: *const u8,
+    message_len: usize,
+    out: *mut PngmeBuffer,
+) -> i32 {
+    guard(|| {
+        if out.is_null() {
+            return Err(PNGME_ERROR_NULL_POINTER);
+        }
+
+        let mut png = parse_png(png, png_len)?;
+        let chunk_type = parse_chunk_type(chunk_type)?;
+        let message = bytes(message, message_len)?;
+
+        png.insert_chunk_ordered(Chunk::new(chunk_type, message.to_vec()));
+        out.write(PngmeBuffer::new(png.as_bytes()));
+
+        Ok(())
+    })
+}
+
+/// Writes the message in the first chunk of type `chunk_type` to `out`,
+/// decompressed if needed.
+///
+/// # Safety
+///
+/// `png` must point to `png_len` readable bytes, `chunk_type` to a null
+/// terminated string and `out` to a writable [`PngmeBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn pngme_decode(
+    png: *const u8,
+    png_len: usize,
+    chunk_type: *const c_char,
+    out: *mut PngmeBuffer,
+) -> i32 {
+    guard(|| {
+        if out.is_null() {
+            return Err(PNGME_ERROR_NULL_POINTER);
+        }
+
+        let png = parse_png(png, png_len)?;
+        let chunk_type = parse_chunk_type(chunk_type)?;
+        let chunk = png
+            .chunks()
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == chunk_type)
+            .ok_or(PNGME_ERROR_CHUNK_NOT_FOUND)?;
+
+        let message = match compression::is_compressed(chunk.data()) {
+            true => compression::decompress(chunk.data()).map_err(|_| PNGME_ERROR_DECOMPRESSION)?,
+            false => chunk.data().to_vec(),
+        };
+        out.write(PngmeBuffer::new(message));
+
+        Ok(())
+    })
+}
+
+/// Writes an array with a [`PngmeChunk`] for every chunk of the png to
+/// `out` and its length to `count`.
+///
+/// # Safety
+///
+/// `png` must point to `png_len` readable bytes, `out` and `count` must be
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_list_chunks(
+    png: *const u8,
+    png_len: usize,
+    out: *mut *mut PngmeChunk,
+    count: *mut usize,
+) -> i32 {
+    guard(|| {
+        if out.is_null() || count.is_null() {
+            return Err(PNGME_ERROR_NULL_POINTER);
+        }
+
+        let png = parse_png(png, png_len)?;
+        let chunks: Box<[PngmeChunk]> = png
+            .chunks()
+            .iter()
+            .map(|chunk| PngmeChunk {
+                chunk_type: chunk.chunk_type().bytes(),
+                length: chunk.length() as u32,
+                crc: chunk.crc(),
+            })
+            .collect();
+
+        count.write(chunks.len());
+        out.write(Box::into_raw(chunks) as *mut PngmeChunk);
+
+        Ok(())
+    })
+}
+
+/// Releases a buffer filled by this library. Freeing an empty buffer is a
+/// no-op.
+///
+/// # Safety
+///
+/// `buffer` must come from this library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_buffer_free(buffer: PngmeBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Releases an array filled by [`pngme_list_chunks`].
+///
+/// # Safety
+///
+/// `chunks` and `count` must come from the same [`pngme_list_chunks`] call
+/// and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_chunks_free(chunks: *mut PngmeChunk, count: usize) {
+    if !chunks.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(chunks, count)));
+    }
+}
+
+/// A static, null terminated description of a status code.
+#[no_mangle]
+pub extern "C" fn pngme_error_message(code: i32) -> *const c_char {
+    let message: &'static CStr = match code {
+        PNGME_OK => c"success",
+        PNGME_ERROR_NULL_POINTER => c"unexpected null pointer",
+        PNGME_ERROR_INVALID_PNG => c"invalid png",
+        PNGME_ERROR_INVALID_CHUNK_TYPE => c"invalid chunk type",
+        PNGME_ERROR_CHUNK_NOT_FOUND => c"chunk not found",
+        PNGME_ERROR_DECOMPRESSION => c"failed to decompress message",
+        PNGME_ERROR_PANIC => c"internal error",
+        _ => c"unknown error",
+    };
+
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_bytes() -> Vec<u8> {
+        Png::from_chunks(
+            [
+                ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0][..]),
+                ("IEND", &[]),
+            ]
+            .iter()
+            .map(|(chunk_type, data)| Chunk::new(chunk_type.parse().unwrap(), data.to_vec()))
+            .collect(),
+        )
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let mut encoded = PngmeBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let mut decoded = PngmeBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+
+        let png = png_bytes();
+
+        unsafe {
+            let message = b"hello from c";
+            let code = pngme_encode(
+                png.as_ptr(),
+                png.len(),
+                c"ruSt".as_ptr(),
+                message.as_ptr(),
+                message.len(),
+                &mut encoded,
+            );
+            assert_eq!(code, PNGME_OK);
+
+            let code = pngme_decode(encoded.data, encoded.len, c"ruSt".as_ptr(), &mut decoded);
+            assert_eq!(code, PNGME_OK);
+            assert_eq!(slice::from_raw_parts(decoded.data, decoded.len), message);
+
+            pngme_buffer_free(encoded);
+            pngme_buffer_free(decoded);
+        }
+    }
+
+    #[test]
+    fn test_list_chunks() {
+        let mut chunks = ptr::null_mut();
+        let mut count = 0;
+        let png = png_bytes();
+
+        unsafe {
+            let code = pngme_list_chunks(png.as_ptr(), png.len(), &mut chunks, &mut count);
+            assert_eq!(code, PNGME_OK);
+
+            let listed = slice::from_raw_parts(chunks, count);
+            assert_eq!(listed.len(), 2);
+            assert_eq!(&listed[0].chunk_type, b"IHDR");
+
+            pngme_chunks_free(chunks, count);
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let mut out = PngmeBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let png = png_bytes();
+
+        unsafe {
+            assert_eq!(
+                pngme_decode(b"nope".as_ptr(), 4, c"ruSt".as_ptr(), &mut out),
+                PNGME_ERROR_INVALID_PNG
+            );
+            assert_eq!(
+                pngme_decode(png.as_ptr(), png.len(), c"ru".as_ptr(), &mut out),
+                PNGME_ERROR_INVALID_CHUNK_TYPE
+            );
+            assert_eq!(
+                pngme_decode(png.as_ptr(), png.len(), c"ruSt".as_ptr(), &mut out),
+                PNGME_ERROR_CHUNK_NOT_FOUND
+            );
+            assert_eq!(
+                pngme_decode(ptr::null(), 4, c"ruSt".as_ptr(), &mut out),
+                PNGME_ERROR_NULL_POINTER
+            );
+
+            let message = CStr::from_ptr(pngme_error_message(PNGME_ERROR_CHUNK_NOT_FOUND));
+            assert_eq!(message, c"chunk not found");
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::chunk::Chunk;
+
+#[derive(Debug, Error)]
+pub enum FragmentError {
+    #[error("payload is not a fragment")]
+    NotFragment,
+
+    #[error("unsupported fragment version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("fragment too short")]
+    FragmentTooShort,
+
+    #[error("fragment size must be at least 1 byte")]
+    InvalidFragmentSize,
+
+    #[error("message `{id:08x}` is missing fragment {index} of {count}")]
+    MissingFragment { id: u32, index: u32, count: u32 },
+
+    #[error("fragments of message `{id:08x}` don't add up to the original payload")]
+    Corrupted { id: u32 },
+}
+
+/// Fragment layout:
+///
+/// `MAGIC (4) | VERSION (1) | id (4) | index (4) | count (4) | data`
+///
+/// The id is the crc of the whole payload, which also checks the result of
+/// reassembling it.
+pub const MAGIC: [u8; 4] = *b"PMFR";
+pub const VERSION: u8 = 1;
+
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 12;
+
+pub fn is_fragment(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+/// A single fragment of a payload split across chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragment<'a> {
+    pub id: u32,
+    pub index: u32,
+    pub count: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    #[throws(FragmentError)]
+    pub fn parse(payload: &'a [u8]) -> Self {
+        if !is_fragment(payload) {
+            throw!(FragmentError::NotFragment)
+        }
+
+        if payload.len() < HEADER_LENGTH {
+            throw!(FragmentError::FragmentTooShort)
+        }
+
+        let version = payload[MAGIC.len()];
+        if version != VERSION {
+            throw!(FragmentError::UnsupportedVersion(version))
+        }
+
+        let field = |start: usize| {
+            u32::from_be_bytes(
+                payload[start..start + 4]
+                    .try_into()
+                    .expect("slice of length 4"),
+            )
+        };
+
+        Fragment {
+            id: field(5),
+            index: field(9),
+            count: field(13),
+            data: &payload[HEADER_LENGTH..],
+        }
+    }
+}
+
+static CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+fn payload_id(payload: &[u8]) -> u32 {
+    CRC.checksum(payload)
+}
+
+/// Wraps each part of a payload in a fragment envelope.
+fn envelop<'a>(payload: &[u8], parts: impl ExactSizeIterator<Item = &'a [u8]>) -> Vec<Vec<u8>> {
+    let id = payload_id(payload);
+    let count = parts.len() as u32;
+
+    parts
+        .enumerate()
+        .map(|(index, data)| {
+            MAGIC
+                .iter()
+                .chain([VERSION].iter())
+                .chain(id.to_be_bytes().iter())
+                .chain((index as u32).to_be_bytes().iter())
+                .chain(count.to_be_bytes().iter())
+                .chain(data.iter())
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+/// Splits `payload` into fragments carrying at most `max_fragment_size` bytes
+/// of it each. A payload that already fits is returned as is.
+#[throws(FragmentError)]
+pub fn split(payload: &[u8], max_fragment_size: usize) -> Vec<Vec<u8>> {
+    if max_fragment_size == 0 {
+        throw!(FragmentError::InvalidFragmentSize)
+    }
+
+    if payload.len() <= max_fragment_size {
+        return vec![payload.to_vec()];
+    }
+
+    envelop(payload, payload.chunks(max_fragment_size))
+}
+
+/// Splits `payload` into exactly `count` fragments of about the same size,
+/// e.g. to spread it over several files. Fragments may be empty if the
+/// payload is shorter than `count`.
+pub fn split_into(payload: &[u8], count: usize) -> Vec<Vec<u8>> {
+    let count = count.max(1);
+    let bound = |index: usize| index * payload.len() / count;
+
+    envelop(
+        payload,
+        (0..count).map(|index| &payload[bound(index)..bound(index + 1)]),
+    )
+}
+
+/// Joins the fragments of chunks into whole payloads, keeping other chunks'
+/// data as is. Each payload is paired with the chunk it starts in.
+#[throws(FragmentError)]
+pub fn reassemble(chunks: &[Chunk]) -> Vec<(&Chunk, Vec<u8>)> {
+    let mut payloads = vec![];
+    let mut done: Vec<u32> = vec![];
+
+    for chunk in chunks {
+        if !is_fragment(chunk.data()) {
+            payloads.push((chunk, chunk.data().to_vec()));
+            continue;
+        }
+
+        let first = Fragment::parse(chunk.data())?;
+        if done.contains(&first.id) {
+            continue;
+        }
+
+        let mut fragments: Vec<Fragment> = chunks
+            .iter()
+            .filter(|chunk| is_fragment(chunk.data()))
+            .map(|chunk| Fragment::parse(chunk.data()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|fragment| fragment.id == first.id)
+            .collect();
+        fragments.sort_by_key(|fragment| fragment.index);
+        fragments.dedup_by_key(|fragment| fragment.index);
+
+        for index in 0..first.count {
+            if fragments.get(index as usize).map(|fragment| fragment.index) != Some(index) {
+                throw!(FragmentError::MissingFragment {
+                    id: first.id,
+                    index,
+                    count: first.count
+                })
+            }
+        }
+
+        let payload: Vec<u8> = fragments
+            .iter()
+            .take(first.count as usize)
+            .flat_map(|fragment| fragment.data)
+            .copied()
+            .collect();
+        if payload_id(&payload) != first.id {
+            throw!(FragmentError::Corrupted { id: first.id })
+        }
+
+        done.push(first.id);
+        payloads.push((chunk, payload));
+    }
+
+    payloads
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunks(payloads: Vec<Vec<u8>>) -> Vec<Chunk> {
+        payloads
+            .into_iter()
+            .map(|payload| Chunk::new(ChunkType::from_str("puLa").unwrap(), payload))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_small_payload() {
+        assert_eq!(split(b"short", 16).unwrap(), vec![b"short".to_vec()]);
+        assert!(matches!(
+            split(b"short", 0),
+            Err(FragmentError::InvalidFragmentSize)
+        ));
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let payload: Vec<u8> = (0..=255).collect();
+        let fragments = split(&payload, 100).unwrap();
+        assert_eq!(fragments.len(), 3);
+
+        let fragment = Fragment::parse(&fragments[2]).unwrap();
+        assert_eq!((fragment.index, fragment.count), (2, 3));
+        assert_eq!(fragment.data.len(), 56);
+
+        let mut fragments = fragments;
+        fragments.swap(0, 2);
+        fragments.insert(1, b"plain".to_vec());
+        let chunks = chunks(fragments);
+
+        let payloads: Vec<Vec<u8>> = reassemble(&chunks)
+            .unwrap()
+            .into_iter()
+            .map(|(_, payload)| payload)
+            .collect();
+        assert_eq!(payloads, vec![payload, b"plain".to_vec()]);
+    }
+
+    #[test]
+    fn test_split_into() {
+        let fragments = split_into(b"0123456789", 3);
+        let sizes: Vec<usize> = fragments
+            .iter()
+            .map(|fragment| Fragment::parse(fragment).unwrap().data.len())
+            .collect();
+        assert_eq!(sizes, vec![3, 3, 4]);
+
+        let mut fragments = split_into(b"ab", 3);
+        fragments.reverse();
+        let (_, payload) = reassemble(&chunks(fragments)).unwrap().remove(0);
+        assert_eq!(payload, b"ab");
+    }
+
+    #[test]
+    fn test_reassemble_missing_fragment() {
+        let mut fragments = split(&[7; 30], 10).unwrap();
+        fragments.remove(1);
+
+        assert!(matches!(
+            reassemble(&chunks(fragments)),
+            Err(FragmentError::MissingFragment { index: 1, .. })
+        ));
+    }
+}
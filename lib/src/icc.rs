@@ -0,0 +1,177 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::{self, CompressionError},
+    text::{self, TextChunkError},
+};
+
+#[derive(Debug, Error)]
+pub enum IccError {
+    #[error("expected an `iCCP` chunk but got `{0}`")]
+    UnexpectedChunkType(String),
+
+    #[error("invalid profile name")]
+    InvalidName(#[from] TextChunkError),
+
+    #[error("missing null separator after profile name")]
+    MissingSeparator,
+
+    #[error("unknown compression method `{0}`")]
+    UnknownCompressionMethod(u8),
+
+    #[error("not a valid icc profile")]
+    InvalidProfile,
+
+    #[error("failed to (de)compress profile")]
+    Compression(#[from] CompressionError),
+}
+
+const ICC_PROFILE: [u8; 4] = *b"iCCP";
+
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+const PROFILE_HEADER_LENGTH: usize = 128;
+const PROFILE_SIGNATURE: &[u8; 4] = b"acsp";
+
+/// Checks the parts of the icc profile header that identify it as one.
+#[throws(IccError)]
+fn validate_profile(profile: &[u8]) {
+    if profile.len() < PROFILE_HEADER_LENGTH || &profile[36..40] != PROFILE_SIGNATURE {
+        throw!(IccError::InvalidProfile)
+    }
+
+    let size = u32::from_be_bytes(profile[..4].try_into().expect("slice of length 4"));
+    if size as usize != profile.len() {
+        throw!(IccError::InvalidProfile)
+    }
+}
+
+/// An embedded icc colour profile stored in an `iCCP` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfileChunk {
+    name: String,
+    profile: Vec<u8>,
+}
+
+impl IccProfileChunk {
+    #[throws(IccError)]
+    pub fn new(name: &str, profile: Vec<u8>) -> Self {
+        text::validate_keyword(name)?;
+        validate_profile(&profile)?;
+
+        IccProfileChunk {
+            name: name.to_string(),
+            profile,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
+    }
+
+    #[throws(IccError)]
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [
+            text::encode_latin1(&self.name).expect("checked in constructor"),
+            vec![0, COMPRESSION_METHOD_DEFLATE],
+            compression::deflate(&self.profile)?,
+        ]
+        .concat();
+
+        let chunk_type = ChunkType::try_from(ICC_PROFILE).expect("valid chunk type");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for IccProfileChunk {
+    type Error = IccError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        if chunk.chunk_type().bytes() != ICC_PROFILE {
+            throw!(IccError::UnexpectedChunkType(
+                chunk.chunk_type().to_string()
+            ))
+        }
+
+        let data = chunk.data();
+        let separator = data
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(IccError::MissingSeparator)?;
+
+        let method = *data.get(separator + 1).ok_or(IccError::MissingSeparator)?;
+        if method != COMPRESSION_METHOD_DEFLATE {
+            throw!(IccError::UnknownCompressionMethod(method))
+        }
+
+        IccProfileChunk {
+            name: text::decode_latin1(&data[..separator]),
+            profile: compression::inflate(&data[separator + 2..])?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_profile() -> Vec<u8> {
+        let mut profile = vec![0; 200];
+        profile[..4].copy_from_slice(&200u32.to_be_bytes());
+        profile[36..40].copy_from_slice(PROFILE_SIGNATURE);
+        profile
+    }
+
+    #[test]
+    fn test_icc_profile_round_trip() {
+        let icc = IccProfileChunk::new("Display P3", testing_profile()).unwrap();
+        let chunk = icc.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "iCCP");
+        assert!(chunk.data().starts_with(b"Display P3\0\0"));
+        assert!(chunk.length() < 200);
+        assert_eq!(IccProfileChunk::try_from(&chunk).unwrap(), icc);
+    }
+
+    #[test]
+    fn test_icc_profile_rejects_invalid_profile() {
+        assert!(matches!(
+            IccProfileChunk::new("Display P3", vec![0; 200]),
+            Err(IccError::InvalidProfile)
+        ));
+
+        let mut profile = testing_profile();
+        profile.push(0);
+        assert!(matches!(
+            IccProfileChunk::new("Display P3", profile),
+            Err(IccError::InvalidProfile)
+        ));
+    }
+
+    #[test]
+    fn test_icc_profile_rejects_invalid_name() {
+        assert!(matches!(
+            IccProfileChunk::new("", testing_profile()),
+            Err(IccError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn test_icc_profile_unknown_compression() {
+        let chunk_type = ChunkType::try_from(ICC_PROFILE).unwrap();
+        let chunk = Chunk::new(chunk_type, b"name\0\x01data".to_vec());
+
+        assert!(matches!(
+            IccProfileChunk::try_from(&chunk),
+            Err(IccError::UnknownCompressionMethod(1))
+        ));
+    }
+}
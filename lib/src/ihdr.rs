@@ -0,0 +1,287 @@
+use fehler::{throw, throws};
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Debug, Error)]
+pub enum IhdrError {
+    #[error("png has no chunks")]
+    MissingHeader,
+
+    #[error("expected an `IHDR` chunk but got `{0}`")]
+    UnexpectedChunkType(String),
+
+    #[error("IHDR chunk data must be 13 bytes, got {0}")]
+    InvalidLength(usize),
+
+    #[error("image dimensions must be between 1 and 2^31 - 1")]
+    InvalidDimensions,
+
+    #[error("unknown color type `{0}`")]
+    UnknownColorType(u8),
+
+    #[error("bit depth {bit_depth} is not allowed for color type {color_type}")]
+    InvalidBitDepth {
+        bit_depth: u8,
+        color_type: ColorType,
+    },
+
+    #[error("unknown compression method `{0}`")]
+    UnknownCompressionMethod(u8),
+
+    #[error("unknown filter method `{0}`")]
+    UnknownFilterMethod(u8),
+
+    #[error("unknown interlace method `{0}`")]
+    UnknownInterlaceMethod(u8),
+}
+
+const IMAGE_HEADER: [u8; 4] = *b"IHDR";
+
+const DATA_LENGTH: usize = 13;
+
+const MAX_DIMENSION: u32 = i32::MAX as u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn id(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    #[throws(IhdrError)]
+    fn from_id(id: u8) -> Self {
+        match id {
+            0 => ColorType::Grayscale,
+            2 => ColorType::Rgb,
+            3 => ColorType::Indexed,
+            4 => ColorType::GrayscaleAlpha,
+            6 => ColorType::Rgba,
+            _ => throw!(IhdrError::UnknownColorType(id)),
+        }
+    }
+
+    /// Number of samples per pixel.
+    pub fn channels(&self) -> u8 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorType::Grayscale => write!(f, "grayscale"),
+            ColorType::Rgb => write!(f, "RGB"),
+            ColorType::Indexed => write!(f, "indexed"),
+            ColorType::GrayscaleAlpha => write!(f, "grayscale + alpha"),
+            ColorType::Rgba => write!(f, "RGBA"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interlace {
+    None,
+    Adam7,
+}
+
+impl Display for Interlace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interlace::None => write!(f, "none"),
+            Interlace::Adam7 => write!(f, "Adam7"),
+        }
+    }
+}
+
+/// The image header, stored in the `IHDR` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    interlace: Interlace,
+}
+
+impl Ihdr {
+    #[throws(IhdrError)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+        interlace: Interlace,
+    ) -> Self {
+        if !(1..=MAX_DIMENSION).contains(&width) || !(1..=MAX_DIMENSION).contains(&height) {
+            throw!(IhdrError::InvalidDimensions)
+        }
+
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            throw!(IhdrError::InvalidBitDepth {
+                bit_depth,
+                color_type
+            })
+        }
+
+        Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlace,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    pub fn interlace(&self) -> Interlace {
+        self.interlace
+    }
+
+    pub fn bits_per_pixel(&self) -> u8 {
+        self.bit_depth * self.color_type.channels()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let interlace = match self.interlace {
+            Interlace::None => 0,
+            Interlace::Adam7 => 1,
+        };
+
+        let data = [
+            &self.width.to_be_bytes()[..],
+            &self.height.to_be_bytes(),
+            &[self.bit_depth, self.color_type.id(), 0, 0, interlace],
+        ]
+        .concat();
+
+        let chunk_type = ChunkType::try_from(IMAGE_HEADER).expect("valid chunk type");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = IhdrError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        if chunk.chunk_type().bytes() != IMAGE_HEADER {
+            throw!(IhdrError::UnexpectedChunkType(
+                chunk.chunk_type().to_string()
+            ))
+        }
+
+        let data = chunk.data();
+        if data.len() != DATA_LENGTH {
+            throw!(IhdrError::InvalidLength(data.len()))
+        }
+
+        if data[10] != 0 {
+            throw!(IhdrError::UnknownCompressionMethod(data[10]))
+        }
+
+        if data[11] != 0 {
+            throw!(IhdrError::UnknownFilterMethod(data[11]))
+        }
+
+        let interlace = match data[12] {
+            0 => Interlace::None,
+            1 => Interlace::Adam7,
+            method => throw!(IhdrError::UnknownInterlaceMethod(method)),
+        };
+
+        Ihdr::new(
+            u32::from_be_bytes(data[..4].try_into().expect("slice of length 4")),
+            u32::from_be_bytes(data[4..8].try_into().expect("slice of length 4")),
+            data[8],
+            ColorType::from_id(data[9])?,
+            interlace,
+        )?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = Ihdr::new(640, 480, 8, ColorType::Rgba, Interlace::Adam7).unwrap();
+        let chunk = ihdr.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "IHDR");
+        assert_eq!(chunk.data(), &[0, 0, 2, 128, 0, 0, 1, 224, 8, 6, 0, 0, 1]);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+        assert_eq!(ihdr.bits_per_pixel(), 32);
+    }
+
+    #[test]
+    fn test_ihdr_invalid_bit_depth() {
+        assert!(matches!(
+            Ihdr::new(1, 1, 4, ColorType::Rgb, Interlace::None),
+            Err(IhdrError::InvalidBitDepth { bit_depth: 4, .. })
+        ));
+        assert!(Ihdr::new(1, 1, 4, ColorType::Indexed, Interlace::None).is_ok());
+    }
+
+    #[test]
+    fn test_ihdr_invalid_dimensions() {
+        assert!(matches!(
+            Ihdr::new(0, 1, 8, ColorType::Rgb, Interlace::None),
+            Err(IhdrError::InvalidDimensions)
+        ));
+    }
+
+    #[test]
+    fn test_ihdr_unknown_color_type() {
+        let chunk_type = ChunkType::try_from(IMAGE_HEADER).unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 5, 0, 0, 0]);
+
+        assert!(matches!(
+            Ihdr::try_from(&chunk),
+            Err(IhdrError::UnknownColorType(5))
+        ));
+    }
+}
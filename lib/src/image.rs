@@ -0,0 +1,607 @@
+use fehler::{throw, throws};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::consts,
+    ihdr::{Ihdr, IhdrError, Interlace},
+};
+
+#[derive(Debug, Error)]
+pub enum ImageError {
+    #[error("invalid image header")]
+    InvalidHeader(#[from] IhdrError),
+
+    #[error("png has no image data")]
+    NoImageData,
+
+    #[error("failed to inflate image data")]
+    Inflate(#[source] std::io::Error),
+
+    #[error("failed to deflate image data")]
+    Deflate(#[source] std::io::Error),
+
+    #[error("image data is {actual} bytes, expected {expected}")]
+    UnexpectedDataLength { expected: usize, actual: usize },
+
+    #[error("unknown scanline filter type `{0}`")]
+    UnknownFilter(u8),
+
+    #[error("image is {width}x{height} pixels, more than the limit of `{max}`")]
+    TooManyPixels { width: u32, height: u32, max: u64 },
+
+    #[error("image of {width}x{height} pixels is too large to hold in memory")]
+    SizeOverflow { width: u32, height: u32 },
+}
+
+/// Most pixels an image may have to be decoded, unless another limit is
+/// given, so a crafted header can't make decoding allocate without bound.
+pub const MAX_PIXELS: u64 = 64 * 1024 * 1024;
+
+/// Largest IDAT chunk written when storing encoded image data.
+const MAX_IDAT_LENGTH: usize = 1 << 16;
+
+/// Origin and spacing, as `(x, y, dx, dy)`, of the seven Adam7 passes.
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Decoded pixel data of a png.
+///
+/// Rows are unfiltered and, for interlaced images, reassembled into their
+/// final position, so every row is laid out like a non-interlaced scanline
+/// without its filter type byte. Samples keep the bit depth from the header.
+#[derive(Debug)]
+pub struct Image {
+    header: Ihdr,
+    rows: Vec<Vec<u8>>,
+    filters: Vec<u8>,
+}
+
+impl Image {
+    pub(crate) fn new(header: Ihdr, rows: Vec<Vec<u8>>, filters: Vec<u8>) -> Self {
+        Image {
+            header,
+            rows,
+            filters,
+        }
+    }
+
+    /// Inflates and unfilters the concatenated data of the IDAT chunks,
+    /// failing for images of more than `max_pixels` pixels before anything
+    /// is allocated.
+    #[throws(ImageError)]
+    pub(crate) fn decode(header: Ihdr, data: &[u8], max_pixels: u64) -> Self {
+        let (width, height) = (header.width() as usize, header.height() as usize);
+        let passes = passes(&header);
+        let sizes = pass_sizes(&header);
+        // The size check in `inflate` also keeps every buffer size below from
+        // overflowing
+        let inflated = inflate(&header, data, max_pixels)?;
+
+        let bits_per_pixel = header.bits_per_pixel() as usize;
+        let mut rows = vec![vec![0; stride(&header, width)]; height];
+        let mut filters = vec![];
+        let mut remaining = &inflated[..];
+
+        for (&(x, y, dx, dy), &(pass_width, pass_height)) in passes.iter().zip(&sizes) {
+            let pass_stride = stride(&header, pass_width);
+            let (pass, rest) = remaining.split_at(pass_height * (pass_stride + 1));
+            remaining = rest;
+
+            let (pass_rows, pass_filters) = unfilter(&header, pass, pass_stride)?;
+            filters.extend(pass_filters);
+
+            if header.interlace() == Interlace::None {
+                rows = pass_rows;
+                continue;
+            }
+
+            for (pass_row, source) in pass_rows.iter().enumerate() {
+                let target = &mut rows[y + pass_row * dy];
+                for pixel in 0..pass_width {
+                    copy_pixel(source, pixel, target, x + pixel * dx, bits_per_pixel);
+                }
+            }
+        }
+
+        Image {
+            header,
+            rows,
+            filters,
+        }
+    }
+
+    /// Filters every scanline again with its original filter type and
+    /// deflates the result. Interlaced images are split back into their
+    /// passes first.
+    #[throws(ImageError)]
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let bpp = self.bytes_per_pixel();
+        let mut filters = self.filters.iter();
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        for pass in self.passes() {
+            let mut previous: Cow<[u8]> =
+                Cow::Owned(vec![0; pass.first().map_or(0, |row| row.len())]);
+
+            for row in pass {
+                let filter = *filters.next().expect("a filter type for every scanline");
+
+                let mut line = Vec::with_capacity(row.len() + 1);
+                line.push(filter);
+                for index in 0..row.len() {
+                    let (a, c) = neighbours(&row, &previous, index, bpp);
+                    line.push(row[index].wrapping_sub(predict(filter, a, previous[index], c)?));
+                }
+
+                encoder.write_all(&line).map_err(ImageError::Deflate)?;
+                previous = row;
+            }
+        }
+
+        encoder.finish().map_err(ImageError::Deflate)?
+    }
+
+    /// Scanlines of each pass as stored in the file, so just the rows for
+    /// images that aren't interlaced.
+    fn passes(&self) -> Vec<Vec<Cow<'_, [u8]>>> {
+        if self.header.interlace() == Interlace::None {
+            return vec![self
+                .rows
+                .iter()
+                .map(|row| Cow::Borrowed(&row[..]))
+                .collect()];
+        }
+
+        let bits_per_pixel = self.header.bits_per_pixel() as usize;
+        passes(&self.header)
+            .into_iter()
+            .zip(pass_sizes(&self.header))
+            .map(|((x, y, dx, dy), (pass_width, pass_height))| {
+                (0..pass_height)
+                    .map(|pass_row| {
+                        let source = &self.rows[y + pass_row * dy];
+                        let mut row = vec![0; stride(&self.header, pass_width)];
+                        for pixel in 0..pass_width {
+                            copy_pixel(source, x + pixel * dx, &mut row, pixel, bits_per_pixel);
+                        }
+                        Cow::Owned(row)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn header(&self) -> &Ihdr {
+        &self.header
+    }
+
+    pub fn rows(&self) -> &[Vec<u8>] {
+        &self.rows
+    }
+
+    pub(crate) fn rows_mut(&mut self) -> &mut [Vec<u8>] {
+        &mut self.rows
+    }
+
+    pub fn row(&self, index: usize) -> Option<&[u8]> {
+        self.rows.get(index).map(Vec::as_slice)
+    }
+
+    /// Filter type of every stored scanline, in file order. Interlaced
+    /// images list the scanlines of each pass in turn.
+    pub fn filters(&self) -> &[u8] {
+        &self.filters
+    }
+
+    /// Length of a row in bytes.
+    pub fn stride(&self) -> usize {
+        stride(&self.header, self.header.width() as usize)
+    }
+
+    /// Distance in bytes between corresponding bytes of neighbouring pixels,
+    /// as used by the filters.
+    pub fn bytes_per_pixel(&self) -> usize {
+        bytes_per_pixel(&self.header)
+    }
+}
+
+/// Splits encoded image data into IDAT chunks of a reasonable size.
+pub(crate) fn idat_chunks(data: &[u8]) -> impl Iterator<Item = Chunk> + '_ {
+    data.chunks(MAX_IDAT_LENGTH)
+        .map(|data| Chunk::new(consts::IDAT, data.to_vec()))
+}
+
+/// Inflates the image data and deflates it again at `level`, from 0 to 9,
+/// keeping the scanlines and their filters as they are.
+#[throws(ImageError)]
+pub(crate) fn recompress(header: &Ihdr, data: &[u8], level: u32) -> Vec<u8> {
+    let inflated = inflate(header, data, MAX_PIXELS)?;
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::new(level));
+    encoder.write_all(&inflated).map_err(ImageError::Deflate)?;
+    encoder.finish().map_err(ImageError::Deflate)?
+}
+
+fn passes(header: &Ihdr) -> Vec<(usize, usize, usize, usize)> {
+    match header.interlace() {
+        Interlace::None => vec![(0, 0, 1, 1)],
+        Interlace::Adam7 => ADAM7_PASSES.to_vec(),
+    }
+}
+
+/// Sizes of the reduced images stored for each pass, empty passes have no
+/// scanlines at all.
+fn pass_sizes(header: &Ihdr) -> Vec<(usize, usize)> {
+    let (width, height) = (header.width() as usize, header.height() as usize);
+
+    passes(header)
+        .iter()
+        .map(|&(x, y, dx, dy)| {
+            let pass_width = (width + dx - 1 - x) / dx;
+            let pass_height = (height + dy - 1 - y) / dy;
+            match pass_width == 0 || pass_height == 0 {
+                true => (0, 0),
+                false => (pass_width, pass_height),
+            }
+        })
+        .collect()
+}
+
+/// Checks the image `header` describes has at most `max_pixels` pixels,
+/// returning the length of its filtered scanlines, which bounds the size of
+/// every buffer the image needs.
+#[throws(ImageError)]
+pub(crate) fn check_size(header: &Ihdr, max_pixels: u64) -> usize {
+    let (width, height) = (header.width(), header.height());
+    if width as u64 * height as u64 > max_pixels {
+        throw!(ImageError::TooManyPixels {
+            width,
+            height,
+            max: max_pixels
+        })
+    }
+
+    let size = pass_sizes(header)
+        .iter()
+        .try_fold(0usize, |total, &(pass_width, pass_height)| {
+            let line = checked_stride(header, pass_width)?.checked_add(1)?;
+            total.checked_add(line.checked_mul(pass_height)?)
+        });
+    size.ok_or(ImageError::SizeOverflow { width, height })?
+}
+
+/// Inflates the image data, checking it holds exactly the filtered
+/// scanlines `header` calls for.
+#[throws(ImageError)]
+fn inflate(header: &Ihdr, data: &[u8], max_pixels: u64) -> Vec<u8> {
+    let expected = check_size(header, max_pixels)?;
+
+    // Inflate at most one byte more than expected, so a crafted stream
+    // can't exhaust memory
+    let mut inflated = vec![];
+    ZlibDecoder::new(data)
+        .take(expected as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(ImageError::Inflate)?;
+
+    if inflated.len() != expected {
+        throw!(ImageError::UnexpectedDataLength {
+            expected,
+            actual: inflated.len()
+        })
+    }
+
+    inflated
+}
+
+/// Length of a row of `width` pixels in bytes, if it fits in a `usize`.
+fn checked_stride(header: &Ihdr, width: usize) -> Option<usize> {
+    Some(
+        width
+            .checked_mul(header.bits_per_pixel() as usize)?
+            .div_ceil(8),
+    )
+}
+
+fn stride(header: &Ihdr, width: usize) -> usize {
+    checked_stride(header, width).expect("image size is checked before use")
+}
+
+fn bytes_per_pixel(header: &Ihdr) -> usize {
+    (header.bits_per_pixel() as usize).div_ceil(8)
+}
+
+/// Left (`a`) and upper left (`c`) neighbours of the byte at `index`.
+fn neighbours(row: &[u8], previous: &[u8], index: usize, bpp: usize) -> (u8, u8) {
+    match index.checked_sub(bpp) {
+        Some(index) => (row[index], previous[index]),
+        None => (0, 0),
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Prediction of a byte from its left (`a`), up (`b`) and upper left (`c`)
+/// neighbours for scanline filter `filter`.
+#[throws(ImageError)]
+fn predict(filter: u8, a: u8, b: u8, c: u8) -> u8 {
+    match filter {
+        0 => 0,
+        1 => a,
+        2 => b,
+        3 => ((a as u16 + b as u16) / 2) as u8,
+        4 => paeth(a, b, c),
+        _ => throw!(ImageError::UnknownFilter(filter)),
+    }
+}
+
+/// Splits `data` into scanlines of `stride` bytes and reverses their
+/// filters, returning the rows and their filter types.
+#[throws(ImageError)]
+fn unfilter(header: &Ihdr, data: &[u8], stride: usize) -> (Vec<Vec<u8>>, Vec<u8>) {
+    let bpp = bytes_per_pixel(header);
+    let zero_row = vec![0; stride];
+    let mut rows: Vec<Vec<u8>> = vec![];
+    let mut filters = vec![];
+
+    for line in data.chunks(stride + 1) {
+        let filter = line[0];
+        let previous = rows.last().unwrap_or(&zero_row);
+        let mut row = line[1..].to_vec();
+
+        for index in 0..stride {
+            let (a, c) = neighbours(&row, previous, index, bpp);
+            row[index] = row[index].wrapping_add(predict(filter, a, previous[index], c)?);
+        }
+
+        filters.push(filter);
+        rows.push(row);
+    }
+
+    (rows, filters)
+}
+
+/// Copies pixel `from` of `source` to pixel `to` of `target`, both packed at
+/// `bits_per_pixel`.
+fn copy_pixel(source: &[u8], from: usize, target: &mut [u8], to: usize, bits_per_pixel: usize) {
+    if bits_per_pixel >= 8 {
+        let bytes = bits_per_pixel / 8;
+        target[to * bytes..(to + 1) * bytes]
+            .copy_from_slice(&source[from * bytes..(from + 1) * bytes]);
+        return;
+    }
+
+    // Pixels below 8 bits are packed from the most significant bit down
+    let mask = (1u8 << bits_per_pixel) - 1;
+    let shift = |pixel: usize| 8 - bits_per_pixel - (pixel * bits_per_pixel) % 8;
+    let value = (source[from * bits_per_pixel / 8] >> shift(from)) & mask;
+
+    let byte = &mut target[to * bits_per_pixel / 8];
+    *byte = (*byte & !(mask << shift(to))) | (value << shift(to));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    fn testing_rows(width: usize, height: usize, channels: usize) -> Vec<Vec<u8>> {
+        (0..height)
+            .map(|row| {
+                (0..width * channels)
+                    .map(|index| (row * 31 + index * 7 + index * index) as u8)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Interlaces and deflates `rows` of an 8-bit image without filtering.
+    fn interlace(header: &Ihdr, rows: &[Vec<u8>]) -> Vec<u8> {
+        let bpp = bytes_per_pixel(header);
+        let (width, height) = (header.width() as usize, header.height() as usize);
+        let mut raw = vec![];
+
+        for (x, y, dx, dy) in ADAM7_PASSES {
+            if x >= width || y >= height {
+                continue;
+            }
+            for row in rows.iter().skip(y).step_by(dy) {
+                raw.push(0);
+                for pixel in (x..width).step_by(dx) {
+                    raw.extend(&row[pixel * bpp..(pixel + 1) * bpp]);
+                }
+            }
+        }
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&raw).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let header = Ihdr::new(9, 7, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let image = Image {
+            header,
+            rows: testing_rows(9, 7, 3),
+            filters: vec![0, 1, 2, 3, 4, 0, 1],
+        };
+
+        let decoded = Image::decode(header, &image.encode().unwrap(), MAX_PIXELS).unwrap();
+
+        assert_eq!(decoded.rows(), image.rows());
+        assert_eq!(decoded.filters(), image.filters());
+        assert_eq!(decoded.stride(), 27);
+        assert_eq!(decoded.bytes_per_pixel(), 3);
+    }
+
+    #[test]
+    fn test_decode_interlaced() {
+        let header = Ihdr::new(11, 10, 8, ColorType::GrayscaleAlpha, Interlace::Adam7).unwrap();
+        let rows = testing_rows(11, 10, 2);
+
+        let decoded = Image::decode(header, &interlace(&header, &rows), MAX_PIXELS).unwrap();
+
+        assert_eq!(decoded.rows(), rows);
+    }
+
+    #[test]
+    fn test_encode_decode_interlaced() {
+        let header = Ihdr::new(13, 9, 8, ColorType::Rgba, Interlace::Adam7).unwrap();
+        let decoded = Image::decode(
+            header,
+            &interlace(&header, &testing_rows(13, 9, 4)),
+            MAX_PIXELS,
+        )
+        .unwrap();
+
+        // Use every filter type across the passes
+        let filters = (0..decoded.filters().len() as u8)
+            .map(|index| index % 5)
+            .collect();
+        let image = Image::new(header, decoded.rows().to_vec(), filters);
+        let redecoded = Image::decode(header, &image.encode().unwrap(), MAX_PIXELS).unwrap();
+
+        assert_eq!(redecoded.rows(), image.rows());
+        assert_eq!(redecoded.filters(), image.filters());
+    }
+
+    #[test]
+    fn test_encode_interlaced_packed_pixels() {
+        let header = Ihdr::new(10, 5, 2, ColorType::Grayscale, Interlace::Adam7).unwrap();
+        let rows: Vec<Vec<u8>> = (0..5u8)
+            .map(|row| vec![row * 37, 0b1110_0100, row])
+            .collect();
+        let filters = vec![0; pass_sizes(&header).iter().map(|size| size.1).sum()];
+        let image = Image::new(header, rows, filters);
+
+        let decoded = Image::decode(header, &image.encode().unwrap(), MAX_PIXELS).unwrap();
+
+        // Padding bits past the last pixel aren't stored
+        let padding = 0b1111_0000;
+        let stored: Vec<Vec<u8>> = image
+            .rows()
+            .iter()
+            .map(|row| vec![row[0], row[1], row[2] & padding])
+            .collect();
+        assert_eq!(decoded.rows(), stored);
+    }
+
+    #[test]
+    fn test_decode_interlaced_packed_pixels() {
+        // A 1-bit checkerboard
+        let header = Ihdr::new(10, 3, 1, ColorType::Grayscale, Interlace::Adam7).unwrap();
+        let rows = vec![vec![0b1010_1010, 0b1000_0000]; 3];
+        let mut raw = vec![];
+        for (x, y, dx, dy) in ADAM7_PASSES {
+            if x >= 10 || y >= 3 {
+                continue;
+            }
+            let pixels: Vec<u8> = (x..10)
+                .step_by(dx)
+                .map(|pixel| (pixel % 2 == 0) as u8)
+                .collect();
+            for _ in (y..3).step_by(dy) {
+                raw.push(0);
+                raw.extend(pixels.chunks(8).map(|bits| {
+                    bits.iter()
+                        .enumerate()
+                        .fold(0, |byte, (index, bit)| byte | bit << (7 - index))
+                }));
+            }
+        }
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&raw).unwrap();
+
+        let decoded = Image::decode(header, &encoder.finish().unwrap(), MAX_PIXELS).unwrap();
+
+        assert_eq!(decoded.rows(), rows);
+    }
+
+    #[test]
+    fn test_decode_wrong_length() {
+        let header = Ihdr::new(4, 4, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let image = Image {
+            header,
+            rows: testing_rows(4, 3, 3),
+            filters: vec![0; 3],
+        };
+
+        assert!(matches!(
+            Image::decode(header, &image.encode().unwrap(), MAX_PIXELS),
+            Err(ImageError::UnexpectedDataLength {
+                expected: 52,
+                actual: 39
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_unknown_filter() {
+        let header = Ihdr::new(1, 1, 8, ColorType::Grayscale, Interlace::None).unwrap();
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[5, 0]).unwrap();
+
+        assert!(matches!(
+            Image::decode(header, &encoder.finish().unwrap(), MAX_PIXELS),
+            Err(ImageError::UnknownFilter(5))
+        ));
+    }
+
+    #[test]
+    fn test_decode_too_many_pixels() {
+        let header = Ihdr::new(1 << 30, 1 << 30, 8, ColorType::Rgb, Interlace::None).unwrap();
+        assert!(matches!(
+            Image::decode(header, &[], MAX_PIXELS),
+            Err(ImageError::TooManyPixels {
+                max: MAX_PIXELS,
+                ..
+            })
+        ));
+
+        let header = Ihdr::new(4, 4, 8, ColorType::Rgb, Interlace::None).unwrap();
+        assert!(matches!(
+            Image::decode(header, &[], 15),
+            Err(ImageError::TooManyPixels { max: 15, .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_size_overflow() {
+        let max = (1 << 31) - 1;
+        let header = Ihdr::new(max, max, 16, ColorType::Rgba, Interlace::Adam7).unwrap();
+
+        assert!(matches!(
+            Image::decode(header, &[], u64::MAX),
+            Err(ImageError::SizeOverflow { .. })
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+#[derive(Debug, Error)]
+pub enum KvError {
+    #[error("payload is not a key-value store")]
+    NotStore,
+
+    #[error("unsupported key-value store version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("key-value store is truncated")]
+    Truncated,
+
+    #[error("key-value store contains invalid utf-8")]
+    InvalidUtf8,
+}
+
+/// Store layout:
+///
+/// `MAGIC (4) | VERSION (1) | (key length (4) | key | value length (4) | value)*`
+///
+/// Lengths are big endian, and entries are sorted by key.
+pub const MAGIC: [u8; 4] = *b"PMKV";
+pub const VERSION: u8 = 1;
+
+/// Ancillary, private and safe to copy, so other tools keep it around.
+pub const CHUNK_TYPE: [u8; 4] = *b"pmKv";
+
+pub fn chunk_type() -> ChunkType {
+    ChunkType::try_from(CHUNK_TYPE).expect("valid chunk type")
+}
+
+pub fn is_store(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+/// Small text settings kept together in a single chunk.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KvStore(BTreeMap<String, String>);
+
+impl KvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The store in the first chunk of [`CHUNK_TYPE`], or an empty one.
+    #[throws(KvError)]
+    pub fn from_png(png: &Png) -> Self {
+        let chunk_type = chunk_type();
+        match png
+            .chunks()
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == chunk_type)
+        {
+            Some(chunk) => Self::parse(chunk.data())?,
+            None => Self::new(),
+        }
+    }
+
+    #[throws(KvError)]
+    pub fn parse(payload: &[u8]) -> Self {
+        if !is_store(payload) {
+            throw!(KvError::NotStore)
+        }
+
+        let version = *payload.get(MAGIC.len()).ok_or(KvError::Truncated)?;
+        if version != VERSION {
+            throw!(KvError::UnsupportedVersion(version))
+        }
+
+        let mut rest = &payload[MAGIC.len() + 1..];
+        let mut entries = BTreeMap::new();
+        while !rest.is_empty() {
+            let key = take_string(&mut rest)?;
+            let value = take_string(&mut rest)?;
+            entries.insert(key, value);
+        }
+
+        Self(entries)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+
+        for (key, value) in &self.0 {
+            for field in [key, value] {
+                bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(field.as_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(chunk_type(), self.to_bytes())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Sets `key` to `value`, returning the value it replaced.
+    pub fn set(&mut self, key: &str, value: &str) -> Option<String> {
+        self.0.insert(key.to_string(), value.to_string())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(key)
+    }
+
+    /// Entries sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[throws(KvError)]
+fn take_string(rest: &mut &[u8]) -> String {
+    if rest.len() < 4 {
+        throw!(KvError::Truncated)
+    }
+
+    let (length, tail) = rest.split_at(4);
+    let length = u32::from_be_bytes(length.try_into().expect("4 bytes")) as usize;
+    if tail.len() < length {
+        throw!(KvError::Truncated)
+    }
+
+    let (field, tail) = tail.split_at(length);
+    *rest = tail;
+
+    String::from_utf8(field.to_vec()).map_err(|_| KvError::InvalidUtf8)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut store = KvStore::new();
+        store.set("theme", "dark");
+        store.set("owner", "ferris");
+        assert_eq!(store.set("theme", "light"), Some("dark".to_string()));
+
+        let parsed = KvStore::parse(&store.to_bytes()).unwrap();
+        assert_eq!(parsed, store);
+        assert_eq!(
+            parsed.iter().collect::<Vec<_>>(),
+            [("owner", "ferris"), ("theme", "light")]
+        );
+    }
+
+    #[test]
+    fn test_empty_store() {
+        let store = KvStore::parse(&KvStore::new().to_bytes()).unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut store = KvStore::new();
+        store.set("key", "value");
+        assert_eq!(store.remove("key"), Some("value".to_string()));
+        assert_eq!(store.remove("key"), None);
+        assert_eq!(store.get("key"), None);
+    }
+
+    #[test]
+    fn test_invalid_store() {
+        assert!(matches!(KvStore::parse(b"text"), Err(KvError::NotStore)));
+        assert!(matches!(
+            KvStore::parse(b"PMKV\x02"),
+            Err(KvError::UnsupportedVersion(2))
+        ));
+
+        let mut store = KvStore::new();
+        store.set("key", "value");
+        let bytes = store.to_bytes();
+        assert!(matches!(
+            KvStore::parse(&bytes[..bytes.len() - 1]),
+            Err(KvError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_chunk_type() {
+        let chunk_type = chunk_type();
+        assert!(!chunk_type.is_critical());
+        assert!(!chunk_type.is_public());
+        assert!(chunk_type.is_safe_to_copy());
+    }
+}
@@ -0,0 +1,113 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LabelError {
+    #[error("payload has no label")]
+    NotLabelled,
+
+    #[error("unsupported label version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("labelled payload too short")]
+    PayloadTooShort,
+
+    #[error("labels must be 1 to 255 bytes of utf-8")]
+    InvalidLabel,
+}
+
+/// Labelled payload layout:
+///
+/// `MAGIC (4) | VERSION (1) | label length (1) | label | payload`
+///
+/// Labels let several messages share a chunk type, each one found by name.
+pub const MAGIC: [u8; 4] = *b"PMLB";
+pub const VERSION: u8 = 1;
+
+const HEADER_LENGTH: usize = MAGIC.len() + 2;
+
+pub fn is_labelled(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+pub fn is_valid_label(label: &str) -> bool {
+    (1..=u8::MAX as usize).contains(&label.len())
+}
+
+/// Wraps `payload` in an envelope named `label`.
+#[throws(LabelError)]
+pub fn label(label: &str, payload: &[u8]) -> Vec<u8> {
+    if !is_valid_label(label) {
+        throw!(LabelError::InvalidLabel)
+    }
+
+    MAGIC
+        .iter()
+        .chain([VERSION, label.len() as u8].iter())
+        .chain(label.as_bytes())
+        .chain(payload)
+        .copied()
+        .collect()
+}
+
+/// Splits a labelled payload into its label and the payload inside.
+#[throws(LabelError)]
+pub fn split(payload: &[u8]) -> (&str, &[u8]) {
+    if !is_labelled(payload) {
+        throw!(LabelError::NotLabelled)
+    }
+
+    if payload.len() < HEADER_LENGTH {
+        throw!(LabelError::PayloadTooShort)
+    }
+
+    let version = payload[MAGIC.len()];
+    if version != VERSION {
+        throw!(LabelError::UnsupportedVersion(version))
+    }
+
+    let length = payload[MAGIC.len() + 1] as usize;
+    let rest = &payload[HEADER_LENGTH..];
+    if rest.len() < length {
+        throw!(LabelError::PayloadTooShort)
+    }
+
+    let (label, payload) = rest.split_at(length);
+    let label = std::str::from_utf8(label).map_err(|_| LabelError::InvalidLabel)?;
+
+    (label, payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_roundtrip() {
+        let labelled = label("build-info", b"v1.2.3").unwrap();
+        assert!(is_labelled(&labelled));
+        assert_eq!(split(&labelled).unwrap(), ("build-info", &b"v1.2.3"[..]));
+    }
+
+    #[test]
+    fn test_invalid_labels() {
+        assert!(matches!(label("", b"data"), Err(LabelError::InvalidLabel)));
+        assert!(matches!(
+            label(&"x".repeat(256), b"data"),
+            Err(LabelError::InvalidLabel)
+        ));
+    }
+
+    #[test]
+    fn test_split_unlabelled() {
+        assert!(matches!(split(b"message"), Err(LabelError::NotLabelled)));
+        assert!(matches!(
+            split(b"PMLB\x01\x05abc"),
+            Err(LabelError::PayloadTooShort)
+        ));
+        assert!(matches!(
+            split(b"PMLB\x02\x01a"),
+            Err(LabelError::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -1,3 +1,82 @@
+//! Reading and writing png chunks.
+//!
+//! Without the default `std` feature only [`chunk_type`], [`chunk`] and the
+//! in-memory [`parser`] are built, needing nothing more than `alloc`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod chunk;
 pub mod chunk_type;
+pub mod parser;
+
+#[cfg(feature = "std")]
+pub mod ancillary;
+#[cfg(feature = "std")]
+pub mod apng;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod color;
+#[cfg(feature = "std")]
+pub mod compression;
+#[cfg(feature = "std")]
+pub mod crypto;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod exif;
+#[cfg(feature = "std")]
+pub mod fragment;
+#[cfg(feature = "std")]
+pub mod icc;
+#[cfg(feature = "std")]
+pub mod ihdr;
+#[cfg(feature = "std")]
+pub mod image;
+#[cfg(feature = "std")]
+pub mod kv;
+#[cfg(feature = "std")]
+pub mod label;
+#[cfg(feature = "std")]
+pub mod mac;
+#[cfg(feature = "std")]
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod phys;
+#[cfg(feature = "std")]
 pub mod png;
+#[cfg(feature = "std")]
+pub mod repair;
+#[cfg(feature = "std")]
+pub mod scan;
+#[cfg(feature = "std")]
+pub mod shamir;
+#[cfg(feature = "std")]
+pub mod sign;
+#[cfg(feature = "std")]
+pub mod sniff;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stego;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod test_utils;
+#[cfg(feature = "std")]
+pub mod text;
+#[cfg(feature = "std")]
+pub mod time;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod xmp;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,148 @@
+use fehler::{throw, throws};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::chunk_type::ChunkType;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Error)]
+pub enum MacError {
+    #[error("payload has no integrity tag")]
+    NotTagged,
+
+    #[error("unsupported integrity tag version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("tagged payload too short")]
+    PayloadTooShort,
+
+    #[error("integrity tag does not match, the chunk was modified or the key is wrong")]
+    InvalidTag,
+}
+
+/// Tagged payload layout:
+///
+/// `MAGIC (4) | VERSION (1) | payload | tag (32)`
+///
+/// The tag is an HMAC-SHA256 over the chunk type followed by everything
+/// before the tag, so moving the payload to another chunk type is detected.
+pub const MAGIC: [u8; 4] = *b"PMMC";
+pub const VERSION: u8 = 1;
+
+pub(crate) const HEADER_LENGTH: usize = MAGIC.len() + 1;
+pub(crate) const TAG_LENGTH: usize = 32;
+
+pub fn is_tagged(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+fn hmac(key: &[u8], chunk_type: &ChunkType, tagged: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(&chunk_type.bytes());
+    mac.update(tagged);
+    mac
+}
+
+/// Wraps `payload` in an envelope with an integrity tag for `chunk_type`.
+pub fn tag(key: &[u8], chunk_type: &ChunkType, payload: &[u8]) -> Vec<u8> {
+    let mut envelope: Vec<u8> = MAGIC
+        .iter()
+        .chain([VERSION].iter())
+        .chain(payload.iter())
+        .copied()
+        .collect();
+    let tag = hmac(key, chunk_type, &envelope).finalize().into_bytes();
+    envelope.extend_from_slice(&tag);
+
+    envelope
+}
+
+/// Splits a tagged envelope into the tagged bytes and the tag.
+#[throws(MacError)]
+fn split(envelope: &[u8]) -> (&[u8], &[u8]) {
+    if !is_tagged(envelope) {
+        throw!(MacError::NotTagged)
+    }
+
+    if envelope.len() < HEADER_LENGTH + TAG_LENGTH {
+        throw!(MacError::PayloadTooShort)
+    }
+
+    let version = envelope[MAGIC.len()];
+    if version != VERSION {
+        throw!(MacError::UnsupportedVersion(version))
+    }
+
+    envelope.split_at(envelope.len() - TAG_LENGTH)
+}
+
+/// Checks the integrity tag of an envelope, returning the tagged payload.
+#[throws(MacError)]
+pub fn verify(key: &[u8], chunk_type: &ChunkType, envelope: &[u8]) -> Vec<u8> {
+    let (tagged, tag) = split(envelope)?;
+
+    hmac(key, chunk_type, tagged)
+        .verify_slice(tag)
+        .map_err(|_| MacError::InvalidTag)?;
+
+    tagged[HEADER_LENGTH..].to_vec()
+}
+
+/// Returns the payload of an envelope without checking its tag.
+#[throws(MacError)]
+pub fn strip_tag(envelope: &[u8]) -> Vec<u8> {
+    let (tagged, _) = split(envelope)?;
+
+    tagged[HEADER_LENGTH..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_tag_verify_round_trip() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let envelope = tag(b"key", &chunk_type, b"This is a tagged message!");
+        assert!(is_tagged(&envelope));
+
+        let payload = verify(b"key", &chunk_type, &envelope).unwrap();
+        assert_eq!(payload, b"This is a tagged message!");
+        assert_eq!(strip_tag(&envelope).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_verify_detects_modification() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let mut envelope = tag(b"key", &chunk_type, b"message");
+        envelope[HEADER_LENGTH] ^= 1;
+
+        assert!(matches!(
+            verify(b"key", &chunk_type, &envelope),
+            Err(MacError::InvalidTag)
+        ));
+    }
+
+    #[test]
+    fn test_verify_wrong_key_or_chunk_type() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let envelope = tag(b"key", &chunk_type, b"message");
+
+        assert!(matches!(
+            verify(b"other key", &chunk_type, &envelope),
+            Err(MacError::InvalidTag)
+        ));
+        assert!(matches!(
+            verify(b"key", &ChunkType::from_str("ruSs").unwrap(), &envelope),
+            Err(MacError::InvalidTag)
+        ));
+        assert!(matches!(
+            verify(b"key", &chunk_type, b"message"),
+            Err(MacError::NotTagged)
+        ));
+    }
+}
@@ -0,0 +1,415 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::{consts, ChunkType},
+    ihdr::ColorType,
+    text::{self, TextChunkError},
+};
+
+#[derive(Debug, Error)]
+pub enum PaletteError {
+    #[error("expected a `{expected}` chunk but got `{actual}`")]
+    UnexpectedChunkType { expected: String, actual: String },
+
+    #[error("PLTE chunk data must be 1 to 256 entries of 3 bytes, got {0} bytes")]
+    InvalidPaletteLength(usize),
+
+    #[error("tRNS chunk for {color_type} images can't be {length} bytes")]
+    InvalidTransparencyLength {
+        color_type: ColorType,
+        length: usize,
+    },
+
+    #[error("{0} images have an alpha channel and can't have a tRNS chunk")]
+    TransparencyNotAllowed(ColorType),
+
+    #[error("invalid suggested palette name")]
+    InvalidName(#[from] TextChunkError),
+
+    #[error("missing null separator after the sPLT name")]
+    MissingSeparator,
+
+    #[error("sPLT sample depth must be 8 or 16, got {0}")]
+    InvalidSampleDepth(u8),
+
+    #[error("sPLT entries with sample depth {sample_depth} don't fit in {length} bytes")]
+    InvalidSuggestedPaletteLength { sample_depth: u8, length: usize },
+
+    #[error("sample `{value}` doesn't fit in sample depth {sample_depth}")]
+    SampleOutOfRange { value: u16, sample_depth: u8 },
+}
+
+const MAX_ENTRIES: usize = 256;
+
+#[throws(PaletteError)]
+fn check_chunk_type(chunk: &Chunk, expected: ChunkType) {
+    if *chunk.chunk_type() != expected {
+        throw!(PaletteError::UnexpectedChunkType {
+            expected: expected.to_string(),
+            actual: chunk.chunk_type().to_string()
+        })
+    }
+}
+
+/// The colours indexed images draw from, stored in the `PLTE` chunk. Other
+/// colour types may carry one as a suggestion for viewers with few colours.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteChunk {
+    entries: Vec<[u8; 3]>,
+}
+
+impl PaletteChunk {
+    #[throws(PaletteError)]
+    pub fn new(entries: Vec<[u8; 3]>) -> Self {
+        if !(1..=MAX_ENTRIES).contains(&entries.len()) {
+            throw!(PaletteError::InvalidPaletteLength(entries.len() * 3))
+        }
+
+        PaletteChunk { entries }
+    }
+
+    /// Red, green and blue of each entry, in index order.
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(consts::PLTE, self.entries.concat())
+    }
+}
+
+impl TryFrom<&Chunk> for PaletteChunk {
+    type Error = PaletteError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        check_chunk_type(chunk, consts::PLTE)?;
+
+        let data = chunk.data();
+        if !data.len().is_multiple_of(3) {
+            throw!(PaletteError::InvalidPaletteLength(data.len()))
+        }
+
+        let entries = data
+            .chunks(3)
+            .map(|rgb| rgb.try_into().expect("slice of length 3"))
+            .collect();
+
+        PaletteChunk::new(entries).map_err(|_| PaletteError::InvalidPaletteLength(data.len()))?
+    }
+}
+
+/// Simple transparency, stored in the `tRNS` chunk. What it holds depends on
+/// the colour type, so it's parsed with [`TransparencyChunk::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransparencyChunk {
+    /// Alpha of the first palette entries, the rest are opaque.
+    Indexed(Vec<u8>),
+    /// The one grey level that is fully transparent.
+    Grayscale(u16),
+    /// The one colour that is fully transparent.
+    Rgb([u16; 3]),
+}
+
+impl TransparencyChunk {
+    #[throws(PaletteError)]
+    pub fn parse(chunk: &Chunk, color_type: ColorType) -> Self {
+        check_chunk_type(chunk, consts::tRNS)?;
+
+        let data = chunk.data();
+        let sample = |index: usize| u16::from_be_bytes([data[index * 2], data[index * 2 + 1]]);
+        let invalid_length = || PaletteError::InvalidTransparencyLength {
+            color_type,
+            length: data.len(),
+        };
+
+        match color_type {
+            ColorType::Indexed if data.len() <= MAX_ENTRIES => {
+                TransparencyChunk::Indexed(data.to_vec())
+            }
+            ColorType::Grayscale if data.len() == 2 => TransparencyChunk::Grayscale(sample(0)),
+            ColorType::Rgb if data.len() == 6 => {
+                TransparencyChunk::Rgb([sample(0), sample(1), sample(2)])
+            }
+            ColorType::Indexed | ColorType::Grayscale | ColorType::Rgb => throw!(invalid_length()),
+            ColorType::GrayscaleAlpha | ColorType::Rgba => {
+                throw!(PaletteError::TransparencyNotAllowed(color_type))
+            }
+        }
+    }
+
+    /// Alpha of palette entry `index`, opaque when the chunk doesn't cover it
+    /// or isn't for an indexed image.
+    pub fn alpha(&self, index: usize) -> u8 {
+        match self {
+            TransparencyChunk::Indexed(alphas) => alphas.get(index).copied().unwrap_or(u8::MAX),
+            _ => u8::MAX,
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            TransparencyChunk::Indexed(alphas) => alphas.clone(),
+            TransparencyChunk::Grayscale(gray) => gray.to_be_bytes().to_vec(),
+            TransparencyChunk::Rgb(rgb) => {
+                rgb.iter().flat_map(|sample| sample.to_be_bytes()).collect()
+            }
+        };
+
+        Chunk::new(consts::tRNS, data)
+    }
+}
+
+/// One colour of a suggested palette, with samples at the palette's sample
+/// depth. Alpha isn't premultiplied, and `frequency` is only relative to the
+/// other entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+impl SuggestedPaletteEntry {
+    fn samples(&self) -> [u16; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+    }
+}
+
+/// A named palette suggested for viewers that can show few colours, stored
+/// in an `sPLT` chunk. A png may have several, each with its own name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedPaletteChunk {
+    name: String,
+    sample_depth: u8,
+    entries: Vec<SuggestedPaletteEntry>,
+}
+
+impl SuggestedPaletteChunk {
+    #[throws(PaletteError)]
+    pub fn new(name: &str, sample_depth: u8, entries: Vec<SuggestedPaletteEntry>) -> Self {
+        text::validate_keyword(name)?;
+
+        if sample_depth != 8 && sample_depth != 16 {
+            throw!(PaletteError::InvalidSampleDepth(sample_depth))
+        }
+
+        if sample_depth == 8 {
+            let mut samples = entries.iter().flat_map(SuggestedPaletteEntry::samples);
+            if let Some(value) = samples.find(|&value| value > u8::MAX.into()) {
+                throw!(PaletteError::SampleOutOfRange {
+                    value,
+                    sample_depth
+                })
+            }
+        }
+
+        SuggestedPaletteChunk {
+            name: name.to_string(),
+            sample_depth,
+            entries,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sample_depth(&self) -> u8 {
+        self.sample_depth
+    }
+
+    pub fn entries(&self) -> &[SuggestedPaletteEntry] {
+        &self.entries
+    }
+
+    #[throws(PaletteError)]
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = text::encode_latin1(&self.name)?;
+        data.push(0);
+        data.push(self.sample_depth);
+
+        for entry in &self.entries {
+            for sample in entry.samples() {
+                match self.sample_depth {
+                    8 => data.push(sample as u8),
+                    _ => data.extend(sample.to_be_bytes()),
+                }
+            }
+            data.extend(entry.frequency.to_be_bytes());
+        }
+
+        Chunk::new(consts::sPLT, data)
+    }
+}
+
+impl TryFrom<&Chunk> for SuggestedPaletteChunk {
+    type Error = PaletteError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        check_chunk_type(chunk, consts::sPLT)?;
+
+        let data = chunk.data();
+        let separator = data
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(PaletteError::MissingSeparator)?;
+        let name = text::decode_latin1(&data[..separator]);
+
+        let (&sample_depth, rest) = data[separator + 1..]
+            .split_first()
+            .ok_or(PaletteError::InvalidSampleDepth(0))?;
+        let entry_length = match sample_depth {
+            8 => 6,
+            16 => 10,
+            _ => throw!(PaletteError::InvalidSampleDepth(sample_depth)),
+        };
+        if !rest.len().is_multiple_of(entry_length) {
+            throw!(PaletteError::InvalidSuggestedPaletteLength {
+                sample_depth,
+                length: rest.len()
+            })
+        }
+
+        let entries = rest
+            .chunks(entry_length)
+            .map(|entry| {
+                let sample = |index: usize| match sample_depth {
+                    8 => entry[index].into(),
+                    _ => u16::from_be_bytes([entry[index * 2], entry[index * 2 + 1]]),
+                };
+                SuggestedPaletteEntry {
+                    red: sample(0),
+                    green: sample(1),
+                    blue: sample(2),
+                    alpha: sample(3),
+                    frequency: u16::from_be_bytes([
+                        entry[entry_length - 2],
+                        entry[entry_length - 1],
+                    ]),
+                }
+            })
+            .collect();
+
+        SuggestedPaletteChunk::new(&name, sample_depth, entries)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_chunk_round_trip() {
+        let palette = PaletteChunk::new(vec![[0x33, 0x66, 0x99], [0, 0, 0]]).unwrap();
+        let chunk = palette.to_chunk();
+
+        assert_eq!(chunk.data(), [0x33, 0x66, 0x99, 0, 0, 0]);
+        assert_eq!(PaletteChunk::try_from(&chunk).unwrap(), palette);
+    }
+
+    #[test]
+    fn test_palette_chunk_invalid_length() {
+        let chunk = Chunk::new(consts::PLTE, vec![0; 4]);
+        assert!(matches!(
+            PaletteChunk::try_from(&chunk),
+            Err(PaletteError::InvalidPaletteLength(4))
+        ));
+
+        let chunk = Chunk::new(consts::PLTE, vec![0; 257 * 3]);
+        assert!(PaletteChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_transparency_chunk_by_color_type() {
+        let chunk = Chunk::new(consts::tRNS, vec![0, 128]);
+
+        let indexed = TransparencyChunk::parse(&chunk, ColorType::Indexed).unwrap();
+        assert_eq!((indexed.alpha(1), indexed.alpha(5)), (128, 255));
+        assert_eq!(
+            TransparencyChunk::parse(&chunk, ColorType::Grayscale).unwrap(),
+            TransparencyChunk::Grayscale(128)
+        );
+        assert!(matches!(
+            TransparencyChunk::parse(&chunk, ColorType::Rgb),
+            Err(PaletteError::InvalidTransparencyLength { length: 2, .. })
+        ));
+        assert!(matches!(
+            TransparencyChunk::parse(&chunk, ColorType::Rgba),
+            Err(PaletteError::TransparencyNotAllowed(_))
+        ));
+
+        let rgb = TransparencyChunk::Rgb([1, 2, 3]);
+        assert_eq!(
+            TransparencyChunk::parse(&rgb.to_chunk(), ColorType::Rgb).unwrap(),
+            rgb
+        );
+    }
+
+    #[test]
+    fn test_suggested_palette_round_trip() {
+        let entry = |red, frequency| SuggestedPaletteEntry {
+            red,
+            green: 0x20,
+            blue: 0x30,
+            alpha: 0xff,
+            frequency,
+        };
+
+        let splt =
+            SuggestedPaletteChunk::new("web safe", 8, vec![entry(0x10, 7), entry(0, 0)]).unwrap();
+        let chunk = splt.to_chunk().unwrap();
+
+        assert_eq!(&chunk.data()[..10], b"web safe\0\x08");
+        assert_eq!(chunk.data().len(), 10 + 2 * 6);
+        assert_eq!(SuggestedPaletteChunk::try_from(&chunk).unwrap(), splt);
+
+        let deep = SuggestedPaletteChunk::new("deep", 16, vec![entry(0x1234, 1)]).unwrap();
+        let chunk = deep.to_chunk().unwrap();
+        assert_eq!(chunk.data().len(), 6 + 10);
+        assert_eq!(SuggestedPaletteChunk::try_from(&chunk).unwrap(), deep);
+    }
+
+    #[test]
+    fn test_suggested_palette_invalid() {
+        let entry = SuggestedPaletteEntry {
+            red: 256,
+            green: 0,
+            blue: 0,
+            alpha: 0,
+            frequency: 0,
+        };
+        assert!(matches!(
+            SuggestedPaletteChunk::new("palette", 8, vec![entry]),
+            Err(PaletteError::SampleOutOfRange { value: 256, .. })
+        ));
+        assert!(matches!(
+            SuggestedPaletteChunk::new("", 8, vec![]),
+            Err(PaletteError::InvalidName(_))
+        ));
+
+        let chunk = Chunk::new(consts::sPLT, b"palette\0\x04".to_vec());
+        assert!(matches!(
+            SuggestedPaletteChunk::try_from(&chunk),
+            Err(PaletteError::InvalidSampleDepth(4))
+        ));
+        let chunk = Chunk::new(consts::sPLT, b"palette\0\x08\0\0\0".to_vec());
+        assert!(matches!(
+            SuggestedPaletteChunk::try_from(&chunk),
+            Err(PaletteError::InvalidSuggestedPaletteLength { length: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+use fehler::{throw, throws};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::chunk::{Chunk, ChunkParseError};
+
+/// The eight bytes every png starts with.
+pub const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+#[derive(Debug, Error)]
+pub enum PngParseError {
+    #[error("error parsing chunk")]
+    ChunkParsingError(#[from] ChunkParseError),
+
+    #[error("invalid png header")]
+    InvalidPngHeader,
+
+    #[error("input too short")]
+    PngTooShort,
+
+    #[cfg(feature = "std")]
+    #[error("failed to read png")]
+    Io(#[from] std::io::Error),
+
+    #[error("chunk length `{length}` exceeds the limit of `{max}` bytes")]
+    ChunkTooLarge { length: u32, max: u32 },
+
+    #[error("png has more than `{max}` chunks")]
+    TooManyChunks { max: usize },
+}
+
+/// What to do with chunks whose stored crc doesn't match their contents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum CrcMode {
+    /// Fail to parse the png.
+    #[default]
+    Strict,
+    /// Skip the chunk and report a [`ParseWarning`].
+    Warn,
+    /// Keep the chunk, along with its stored crc.
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct ParseOptions {
+    pub crc_mode: CrcMode,
+    /// Whether to compute chunk crcs at all. When off, every chunk keeps its
+    /// stored crc and `crc_mode` has no effect.
+    pub verify_crc: bool,
+    /// Largest chunk data length accepted, in bytes.
+    pub max_chunk_size: u32,
+    /// Largest number of chunks accepted, including skipped ones.
+    pub max_chunk_count: usize,
+}
+
+impl ParseOptions {
+    pub const DEFAULT_MAX_CHUNK_SIZE: u32 = 256 * 1024 * 1024;
+    pub const DEFAULT_MAX_CHUNK_COUNT: usize = 1_000_000;
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            crc_mode: CrcMode::default(),
+            verify_crc: true,
+            max_chunk_size: Self::DEFAULT_MAX_CHUNK_SIZE,
+            max_chunk_count: Self::DEFAULT_MAX_CHUNK_COUNT,
+        }
+    }
+}
+
+/// A corrupt chunk skipped while parsing with [`CrcMode::Warn`].
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// Byte offset of the chunk in the file.
+    pub offset: usize,
+    pub error: ChunkParseError,
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "skipped chunk at byte {}: {}", self.offset, self.error)
+    }
+}
+
+/// Splits a whole png held in memory into its chunks, checking the signature,
+/// chunk lengths and crcs. This is all of parsing that doesn't need `std`,
+/// and what `Png::parse` is built on.
+#[throws(PngParseError)]
+pub fn parse_chunks(value: &[u8], options: ParseOptions) -> (Vec<Chunk>, Vec<ParseWarning>) {
+    let header = value.get(..8).ok_or(PngParseError::PngTooShort)?;
+
+    if header != SIGNATURE {
+        throw!(PngParseError::InvalidPngHeader)
+    }
+
+    let mut index = 8;
+    let mut chunks = vec![];
+    let mut warnings = vec![];
+    let mut count = 0;
+
+    while index < value.len() {
+        let length: [u8; 4] = value
+            .get(index..index + 4)
+            .ok_or(PngParseError::PngTooShort)?
+            .try_into()
+            .expect("slice of length 4");
+        let length = u32::from_be_bytes(length);
+        debug!(offset = index, length, "reading chunk");
+        if length > options.max_chunk_size {
+            throw!(PngParseError::ChunkTooLarge {
+                length,
+                max: options.max_chunk_size
+            })
+        }
+        let length = length as usize + 12;
+
+        count += 1;
+        if count > options.max_chunk_count {
+            throw!(PngParseError::TooManyChunks {
+                max: options.max_chunk_count
+            })
+        }
+
+        let chunk = value
+            .get(index..index + length)
+            .ok_or(PngParseError::PngTooShort)?;
+
+        let verify_crc = options.verify_crc && options.crc_mode != CrcMode::Ignore;
+        match Chunk::parse(chunk, verify_crc) {
+            Ok(chunk) => chunks.push(chunk.with_offset(index as u64)),
+            Err(error @ ChunkParseError::InvalidCrc { .. })
+                if options.crc_mode == CrcMode::Warn =>
+            {
+                debug!(offset = index, "skipping chunk with a bad crc");
+                warnings.push(ParseWarning {
+                    offset: index,
+                    error,
+                })
+            }
+            Err(error) => throw!(error),
+        }
+
+        index += length;
+    }
+
+    (chunks, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunk_bytes(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        Chunk::new(ChunkType::try_from(*chunk_type).unwrap(), data.to_vec()).as_bytes()
+    }
+
+    #[test]
+    fn test_parse_chunks() {
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(chunk_bytes(b"tEXt", b"Title\0parsed"));
+        let corrupt = bytes.len();
+        bytes.extend(chunk_bytes(b"ruSt", b"corrupt"));
+        bytes.extend(chunk_bytes(b"IEND", b""));
+        // Last byte of the ruSt chunk's crc
+        bytes[corrupt + 12 + 6] ^= 0xff;
+
+        assert!(matches!(
+            parse_chunks(&bytes, ParseOptions::default()),
+            Err(PngParseError::ChunkParsingError(
+                ChunkParseError::InvalidCrc { .. }
+            ))
+        ));
+
+        let options = ParseOptions {
+            crc_mode: CrcMode::Warn,
+            ..ParseOptions::default()
+        };
+        let (chunks, warnings) = parse_chunks(&bytes, options).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].offset, corrupt);
+
+        assert!(matches!(
+            parse_chunks(&bytes[..bytes.len() - 1], options),
+            Err(PngParseError::PngTooShort)
+        ));
+        assert!(matches!(
+            parse_chunks(&bytes[1..], options),
+            Err(PngParseError::InvalidPngHeader)
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Debug, Error)]
+pub enum PhysError {
+    #[error("expected a `pHYs` chunk but got `{0}`")]
+    UnexpectedChunkType(String),
+
+    #[error("pHYs chunk data must be 9 bytes, got {0}")]
+    InvalidLength(usize),
+
+    #[error("unknown unit specifier `{0}`")]
+    UnknownUnit(u8),
+}
+
+const PHYSICAL_DIMENSIONS: [u8; 4] = *b"pHYs";
+
+const DATA_LENGTH: usize = 9;
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysUnit {
+    /// Only the aspect ratio is known.
+    Unknown,
+    Meter,
+}
+
+/// Intended pixel size or aspect ratio, stored in a `pHYs` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysChunk {
+    x_pixels_per_unit: u32,
+    y_pixels_per_unit: u32,
+    unit: PhysUnit,
+}
+
+impl PhysChunk {
+    pub fn new(x_pixels_per_unit: u32, y_pixels_per_unit: u32, unit: PhysUnit) -> Self {
+        PhysChunk {
+            x_pixels_per_unit,
+            y_pixels_per_unit,
+            unit,
+        }
+    }
+
+    /// Same resolution on both axes, converted to pixels per meter.
+    pub fn from_dpi(dpi: u32) -> Self {
+        let pixels_per_meter = (dpi as f64 / METERS_PER_INCH).round() as u32;
+        PhysChunk::new(pixels_per_meter, pixels_per_meter, PhysUnit::Meter)
+    }
+
+    pub fn x_pixels_per_unit(&self) -> u32 {
+        self.x_pixels_per_unit
+    }
+
+    pub fn y_pixels_per_unit(&self) -> u32 {
+        self.y_pixels_per_unit
+    }
+
+    pub fn unit(&self) -> PhysUnit {
+        self.unit
+    }
+
+    /// The x and y resolution in dots per inch, if the unit is known.
+    pub fn dpi(&self) -> Option<(u32, u32)> {
+        let to_dpi = |pixels_per_meter: u32| (pixels_per_meter as f64 * METERS_PER_INCH).round();
+
+        match self.unit {
+            PhysUnit::Unknown => None,
+            PhysUnit::Meter => Some((
+                to_dpi(self.x_pixels_per_unit) as u32,
+                to_dpi(self.y_pixels_per_unit) as u32,
+            )),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let unit = match self.unit {
+            PhysUnit::Unknown => 0,
+            PhysUnit::Meter => 1,
+        };
+
+        let data = [
+            &self.x_pixels_per_unit.to_be_bytes()[..],
+            &self.y_pixels_per_unit.to_be_bytes(),
+            &[unit],
+        ]
+        .concat();
+
+        let chunk_type = ChunkType::try_from(PHYSICAL_DIMENSIONS).expect("valid chunk type");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for PhysChunk {
+    type Error = PhysError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        if chunk.chunk_type().bytes() != PHYSICAL_DIMENSIONS {
+            throw!(PhysError::UnexpectedChunkType(
+                chunk.chunk_type().to_string()
+            ))
+        }
+
+        let data = chunk.data();
+        if data.len() != DATA_LENGTH {
+            throw!(PhysError::InvalidLength(data.len()))
+        }
+
+        let unit = match data[8] {
+            0 => PhysUnit::Unknown,
+            1 => PhysUnit::Meter,
+            unit => throw!(PhysError::UnknownUnit(unit)),
+        };
+
+        PhysChunk::new(
+            u32::from_be_bytes(data[..4].try_into().expect("slice of length 4")),
+            u32::from_be_bytes(data[4..8].try_into().expect("slice of length 4")),
+            unit,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phys_chunk_round_trip() {
+        let phys = PhysChunk::new(3780, 3780, PhysUnit::Meter);
+        let chunk = phys.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "pHYs");
+        assert_eq!(chunk.data(), &[0, 0, 14, 196, 0, 0, 14, 196, 1]);
+        assert_eq!(PhysChunk::try_from(&chunk).unwrap(), phys);
+    }
+
+    #[test]
+    fn test_phys_chunk_dpi_conversion() {
+        let phys = PhysChunk::from_dpi(300);
+
+        assert_eq!(phys.x_pixels_per_unit(), 11811);
+        assert_eq!(phys.dpi(), Some((300, 300)));
+        assert_eq!(PhysChunk::from_dpi(72).dpi(), Some((72, 72)));
+    }
+
+    #[test]
+    fn test_phys_chunk_unknown_unit_has_no_dpi() {
+        let phys = PhysChunk::new(1, 2, PhysUnit::Unknown);
+        assert_eq!(phys.dpi(), None);
+    }
+
+    #[test]
+    fn test_phys_chunk_invalid_unit() {
+        let chunk_type = ChunkType::try_from(PHYSICAL_DIMENSIONS).unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0, 0, 0, 1, 0, 0, 0, 1, 2]);
+
+        assert!(matches!(
+            PhysChunk::try_from(&chunk),
+            Err(PhysError::UnknownUnit(2))
+        ));
+    }
+}
@@ -1,25 +1,26 @@
 use crate::{
-    chunk::{Chunk, ChunkParseError},
-    chunk_type::{ChunkType, ChunkTypeParseError},
+    apng,
+    chunk::Chunk,
+    chunk_type::{consts, ChunkType, ChunkTypeParseError},
+    ihdr::{ColorType, Ihdr, IhdrError, Interlace},
+    image::{self, Image, ImageError},
+    parser,
+    validate::Diagnostic,
 };
+
+pub use crate::parser::{CrcMode, ParseOptions, ParseWarning, PngParseError};
 use fehler::{throw, throws};
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{ErrorKind, Read, Write},
+    str::FromStr,
+};
 use thiserror::Error;
+use tracing::debug;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Png(Vec<Chunk>);
 
-#[derive(Debug, Error)]
-pub enum PngParseError {
-    #[error("error parsing chunk")]
-    ChunkParsingError(#[from] ChunkParseError),
-
-    #[error("invalid png header")]
-    InvalidPngHeader,
-
-    #[error("input too short")]
-    PngTooShort,
-}
-
 #[derive(Debug, Error)]
 pub enum PngChunkRemoveError {
     #[error("error parsing chunk type")]
@@ -29,17 +30,217 @@ pub enum PngChunkRemoveError {
     ChunkNotFound,
 }
 
+/// Colour space chunks, which must come before `PLTE`.
+const BEFORE_PLTE: [[u8; 4]; 8] = [
+    consts::cHRM.bytes(),
+    consts::gAMA.bytes(),
+    consts::iCCP.bytes(),
+    consts::sBIT.bytes(),
+    consts::sRGB.bytes(),
+    consts::cICP.bytes(),
+    consts::mDCV.bytes(),
+    consts::cLLI.bytes(),
+];
+
+/// Palette and image related chunks, which must come before `IDAT`.
+const BEFORE_IDAT: [[u8; 4]; 9] = [
+    consts::PLTE.bytes(),
+    consts::bKGD.bytes(),
+    consts::hIST.bytes(),
+    consts::tRNS.bytes(),
+    consts::pHYs.bytes(),
+    consts::sPLT.bytes(),
+    consts::oFFs.bytes(),
+    consts::pCAL.bytes(),
+    consts::sCAL.bytes(),
+];
+
+/// Chunk types that must come after a chunk of type `chunk_type`.
+pub(crate) fn must_precede(chunk_type: &[u8; 4]) -> &'static [[u8; 4]] {
+    const PLTE_IDAT_IEND: [[u8; 4]; 3] = [
+        consts::PLTE.bytes(),
+        consts::IDAT.bytes(),
+        consts::IEND.bytes(),
+    ];
+    const IDAT_IEND: [[u8; 4]; 2] = [consts::IDAT.bytes(), consts::IEND.bytes()];
+    const IEND: [[u8; 4]; 1] = [consts::IEND.bytes()];
+
+    if BEFORE_PLTE.contains(chunk_type) {
+        &PLTE_IDAT_IEND
+    } else if BEFORE_IDAT.contains(chunk_type) {
+        &IDAT_IEND
+    } else if *chunk_type == consts::IEND.bytes() {
+        &[]
+    } else {
+        &IEND
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PngChunkInsertError {
+    #[error("index `{index}` is out of range for {len} chunks")]
+    IndexOutOfRange { index: usize, len: usize },
+}
+
+#[derive(Debug, Error)]
+pub enum PngChunkCopyError {
+    #[error("`{chunk_type}` is a critical chunk and can't be copied")]
+    CriticalChunk { chunk_type: String },
+}
+
+#[derive(Debug, Error)]
+pub enum PngDuplicateChunkError {
+    #[error("`{chunk_type}` chunk may only appear once")]
+    DuplicateChunk { chunk_type: String },
+}
+
+/// Chunks that may appear at most once.
+pub(crate) const UNIQUE_CHUNKS: [[u8; 4]; 20] = [
+    consts::IHDR.bytes(),
+    consts::PLTE.bytes(),
+    consts::IEND.bytes(),
+    consts::cHRM.bytes(),
+    consts::gAMA.bytes(),
+    consts::iCCP.bytes(),
+    consts::sBIT.bytes(),
+    consts::sRGB.bytes(),
+    consts::cICP.bytes(),
+    consts::mDCV.bytes(),
+    consts::cLLI.bytes(),
+    consts::bKGD.bytes(),
+    consts::hIST.bytes(),
+    consts::tRNS.bytes(),
+    consts::pHYs.bytes(),
+    consts::tIME.bytes(),
+    consts::eXIf.bytes(),
+    consts::oFFs.bytes(),
+    consts::pCAL.bytes(),
+    consts::sCAL.bytes(),
+];
+
+/// What to do with chunks that may appear at most once but appear more often.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail with [`PngDuplicateChunkError`].
+    #[default]
+    Reject,
+    /// Leave the chunks as they are.
+    Warn,
+    /// Keep the first chunk of each type and remove the rest.
+    Deduplicate,
+}
+
 impl Png {
-    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = parser::SIGNATURE;
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
         Self(chunks)
     }
 
+    /// Appends `chunk` after every other chunk, even `IEND`.
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.0.push(chunk);
     }
 
+    /// Inserts `chunk` at `index` in [`Png::chunks`], shifting later chunks.
+    #[throws(PngChunkInsertError)]
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) {
+        if index > self.0.len() {
+            throw!(PngChunkInsertError::IndexOutOfRange {
+                index,
+                len: self.0.len()
+            })
+        }
+
+        self.0.insert(index, chunk);
+    }
+
+    /// Inserts `chunk` where the png spec allows it: colour space chunks
+    /// before `PLTE`, other palette and image related chunks before `IDAT`,
+    /// and everything else before `IEND`. Falls back to the end of the file
+    /// when the expected chunks are missing. Never lands inside an APNG
+    /// frame, moving in front of the frame's `fcTL` instead.
+    pub fn insert_chunk_ordered(&mut self, chunk: Chunk) {
+        let before = must_precede(&chunk.chunk_type().bytes());
+
+        let mut position = self
+            .0
+            .iter()
+            .position(|chunk| before.contains(&chunk.chunk_type().bytes()))
+            .unwrap_or(self.0.len());
+        while position > 0 && apng::splits_frame(&self.0, position) {
+            position -= 1;
+        }
+
+        self.0.insert(position, chunk);
+    }
+
+    /// Swaps the first chunk with the same type as `chunk` in place, returning
+    /// the old one. Inserts `chunk` with [`Png::insert_chunk_ordered`] when
+    /// there is no such chunk.
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+        match self
+            .0
+            .iter_mut()
+            .find(|existing| existing.chunk_type() == chunk.chunk_type())
+        {
+            Some(existing) => Some(std::mem::replace(existing, chunk)),
+            None => {
+                self.insert_chunk_ordered(chunk);
+                None
+            }
+        }
+    }
+
+    /// Copies the chunks of `source` picked by `select`, placing them with
+    /// [`Png::insert_chunk_ordered`] and replacing chunks that may appear only
+    /// once. Chunks that aren't safe to copy depend on the image data, so
+    /// they are skipped unless both pngs have the same critical chunks. The
+    /// types of skipped chunks are returned.
+    #[throws(PngChunkCopyError)]
+    pub fn copy_chunks_from(
+        &mut self,
+        source: &Png,
+        mut select: impl FnMut(&Chunk) -> bool,
+    ) -> Vec<String> {
+        let selected: Vec<&Chunk> = source.0.iter().filter(|chunk| select(chunk)).collect();
+
+        if let Some(chunk) = selected
+            .iter()
+            .find(|chunk| chunk.chunk_type().is_critical())
+        {
+            throw!(PngChunkCopyError::CriticalChunk {
+                chunk_type: chunk.chunk_type().to_string()
+            })
+        }
+
+        let same_image = self.critical_chunks().eq(source.critical_chunks());
+
+        let mut skipped = vec![];
+        for chunk in selected {
+            let chunk_type = chunk.chunk_type();
+            if !chunk_type.is_safe_to_copy() && !same_image {
+                skipped.push(chunk_type.to_string());
+                continue;
+            }
+
+            let copy = Chunk::new(*chunk_type, chunk.data().to_vec());
+            if UNIQUE_CHUNKS.contains(&chunk_type.bytes()) {
+                self.replace_chunk(copy);
+            } else {
+                self.insert_chunk_ordered(copy);
+            }
+        }
+
+        skipped
+    }
+
+    fn critical_chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.0
+            .iter()
+            .filter(|chunk| chunk.chunk_type().is_critical())
+    }
+
     #[throws(PngChunkRemoveError)]
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Chunk {
         let chunk_type = match ChunkType::from_str(chunk_type) {
@@ -56,36 +257,375 @@ impl Png {
         self.0.remove(chunk_position)
     }
 
-    pub fn header(&self) -> &[u8; 8] {
+    /// Removes every chunk of type `chunk_type`, returning them in file order.
+    #[throws(PngChunkRemoveError)]
+    pub fn remove_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        let chunk_type = ChunkType::from_str(chunk_type)?;
+
+        let (removed, kept) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition(|chunk| *chunk.chunk_type() == chunk_type);
+        self.0 = kept;
+
+        removed
+    }
+
+    /// Removes the chunk at `index` in [`Png::chunks`].
+    #[throws(PngChunkRemoveError)]
+    pub fn remove_chunk_at(&mut self, index: usize) -> Chunk {
+        if index >= self.0.len() {
+            throw!(PngChunkRemoveError::ChunkNotFound)
+        }
+
+        self.0.remove(index)
+    }
+
+    /// Keeps only the chunks for which `keep` returns true.
+    pub fn retain_chunks(&mut self, keep: impl FnMut(&Chunk) -> bool) {
+        self.0.retain(keep);
+    }
+
+    pub fn signature(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
     }
 
+    /// Parses the `IHDR` chunk, which must be the first chunk.
+    #[throws(IhdrError)]
+    pub fn header(&self) -> Ihdr {
+        let chunk = self.0.first().ok_or(IhdrError::MissingHeader)?;
+        Ihdr::try_from(chunk)?
+    }
+
+    // The properties below parse the header when asked for, so a png that's
+    // only having chunks moved around never pays for it.
+
+    #[throws(IhdrError)]
+    pub fn width(&self) -> u32 {
+        self.header()?.width()
+    }
+
+    #[throws(IhdrError)]
+    pub fn height(&self) -> u32 {
+        self.header()?.height()
+    }
+
+    #[throws(IhdrError)]
+    pub fn bit_depth(&self) -> u8 {
+        self.header()?.bit_depth()
+    }
+
+    #[throws(IhdrError)]
+    pub fn color_type(&self) -> ColorType {
+        self.header()?.color_type()
+    }
+
+    #[throws(IhdrError)]
+    pub fn is_interlaced(&self) -> bool {
+        self.header()?.interlace() == Interlace::Adam7
+    }
+
+    /// Concatenated data of every `IDAT` chunk, still compressed.
+    pub fn image_data(&self) -> Vec<u8> {
+        self.0
+            .iter()
+            .filter(|chunk| *chunk.chunk_type() == consts::IDAT)
+            .flat_map(|chunk| chunk.data())
+            .copied()
+            .collect()
+    }
+
+    /// Inflates the image data and reverses its scanline filters, returning
+    /// the raw pixel rows. Images of more than [`image::MAX_PIXELS`] pixels
+    /// are refused.
+    #[throws(ImageError)]
+    pub fn decode_image(&self) -> Image {
+        self.decode_image_limited(image::MAX_PIXELS)?
+    }
+
+    /// Like [`Png::decode_image`], refusing images of more than `max_pixels`
+    /// pixels instead.
+    #[throws(ImageError)]
+    pub fn decode_image_limited(&self, max_pixels: u64) -> Image {
+        let data = self.image_data();
+        if data.is_empty() {
+            throw!(ImageError::NoImageData)
+        }
+
+        Image::decode(self.header()?, &data, max_pixels)?
+    }
+
+    /// Deflates the image data again at `level`, from 0 to 9, storing it in
+    /// as few `IDAT` chunks as will hold it where the old ones were. Other
+    /// chunks, `fdAT` frames included, are left alone.
+    #[throws(ImageError)]
+    pub fn recompress_image_data(&mut self, level: u32) {
+        let data = self.image_data();
+        if data.is_empty() {
+            throw!(ImageError::NoImageData)
+        }
+        let data = image::recompress(&self.header()?, &data, level)?;
+
+        let is_image_data = |chunk: &Chunk| *chunk.chunk_type() == consts::IDAT;
+        let position = self
+            .0
+            .iter()
+            .position(is_image_data)
+            .expect("image data comes from IDAT chunks");
+        self.0.retain(|chunk| !is_image_data(chunk));
+        self.0.splice(position..position, image::idat_chunks(&data));
+    }
+
     pub fn chunks(&self) -> &[Chunk] {
         &self.0
     }
 
+    pub fn into_chunks(self) -> Vec<Chunk> {
+        self.0
+    }
+
+    /// Checks chunk ordering, duplicates, required chunks, reserved bits and
+    /// crcs, returning every violation found.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        crate::validate::validate_chunks(&self.0)
+    }
+
+    /// Types of chunks that may appear at most once but appear more often.
+    pub fn duplicate_chunks(&self) -> Vec<String> {
+        let mut seen: Vec<[u8; 4]> = vec![];
+        let mut duplicates = vec![];
+
+        for chunk in &self.0 {
+            let chunk_type = chunk.chunk_type().bytes();
+            if !UNIQUE_CHUNKS.contains(&chunk_type) {
+                continue;
+            }
+
+            let name = chunk.chunk_type().to_string();
+            if seen.contains(&chunk_type) && !duplicates.contains(&name) {
+                duplicates.push(name);
+            }
+            seen.push(chunk_type);
+        }
+
+        duplicates
+    }
+
+    /// Applies `policy` to duplicated chunks, returning the duplicated types.
+    #[throws(PngDuplicateChunkError)]
+    pub fn enforce_unique_chunks(&mut self, policy: DuplicatePolicy) -> Vec<String> {
+        let duplicates = self.duplicate_chunks();
+
+        match policy {
+            DuplicatePolicy::Reject => {
+                if let Some(chunk_type) = duplicates.first() {
+                    throw!(PngDuplicateChunkError::DuplicateChunk {
+                        chunk_type: chunk_type.clone()
+                    })
+                }
+            }
+            DuplicatePolicy::Warn => {}
+            DuplicatePolicy::Deduplicate => {
+                let mut seen: Vec<[u8; 4]> = vec![];
+                self.0.retain(|chunk| {
+                    let chunk_type = chunk.chunk_type().bytes();
+                    if !UNIQUE_CHUNKS.contains(&chunk_type) {
+                        return true;
+                    }
+
+                    let first = !seen.contains(&chunk_type);
+                    seen.push(chunk_type);
+                    first
+                });
+            }
+        }
+
+        duplicates
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        let chunk_type = match ChunkType::from_str(chunk_type) {
-            Err(_) => return None,
-            Ok(chunk) => chunk,
-        };
+        self.chunks_by_type(chunk_type).next()
+    }
+
+    /// Every chunk of type `chunk_type`, in file order. Empty if `chunk_type`
+    /// is not a valid chunk type.
+    pub fn chunks_by_type(&self, chunk_type: &str) -> impl Iterator<Item = &Chunk> {
+        let chunk_type = ChunkType::from_str(chunk_type).ok();
 
         self.0
             .iter()
-            .find(|&chunk| chunk.chunk_type() == &chunk_type)
+            .filter(move |&chunk| Some(chunk.chunk_type()) == chunk_type.as_ref())
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        Self::STANDARD_HEADER
-            .iter()
-            .copied()
-            .chain(
-                self.0
-                    .iter()
-                    .flat_map(|chunk| chunk.as_bytes())
-                    .collect::<Vec<u8>>(),
-            )
-            .collect()
+        let mut bytes = vec![];
+        self.write_to(&mut bytes)
+            .expect("writing to a vec never fails");
+        bytes
+    }
+
+    /// Writes the signature and every chunk to `writer`, without building the
+    /// whole file in memory first.
+    #[throws(std::io::Error)]
+    pub fn write_to(&self, writer: &mut (impl Write + ?Sized)) {
+        writer.write_all(&Self::STANDARD_HEADER)?;
+
+        for chunk in &self.0 {
+            chunk.write_to(writer)?;
+        }
+    }
+}
+
+impl Png {
+    /// Parses a png, returning it along with any warnings for skipped chunks.
+    #[throws(PngParseError)]
+    pub fn parse(value: &[u8], options: ParseOptions) -> (Self, Vec<ParseWarning>) {
+        let (chunks, warnings) = parser::parse_chunks(value, options)?;
+        (Self(chunks), warnings)
+    }
+}
+
+/// Fills `buf`, returning false if the stream ended before any byte.
+#[throws(PngParseError)]
+pub(crate) fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> bool {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return false,
+            Ok(0) => throw!(PngParseError::PngTooShort),
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => throw!(err),
+        }
+    }
+
+    true
+}
+
+/// Reads chunks one at a time from a png stream, so large files can be
+/// scanned without holding them in memory. Stops after the first error.
+pub struct ChunkReader<R> {
+    reader: R,
+    verify_crc: bool,
+    max_chunk_size: u32,
+    max_chunk_count: usize,
+    count: usize,
+    offset: u64,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R) -> Self {
+        ChunkReader {
+            reader,
+            verify_crc: true,
+            max_chunk_size: ParseOptions::DEFAULT_MAX_CHUNK_SIZE,
+            max_chunk_count: ParseOptions::DEFAULT_MAX_CHUNK_COUNT,
+            count: 0,
+            offset: 0,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Sets whether chunk crcs are checked. Defaults to true.
+    pub fn verify_crc(mut self, verify_crc: bool) -> Self {
+        self.verify_crc = verify_crc;
+        self
+    }
+
+    /// Sets the largest chunk data length accepted, in bytes.
+    pub fn max_chunk_size(mut self, max_chunk_size: u32) -> Self {
+        self.max_chunk_size = max_chunk_size;
+        self
+    }
+
+    /// Sets the largest number of chunks accepted.
+    pub fn max_chunk_count(mut self, max_chunk_count: usize) -> Self {
+        self.max_chunk_count = max_chunk_count;
+        self
+    }
+
+    #[throws(PngParseError)]
+    fn read_chunk(&mut self) -> Option<Chunk> {
+        if !self.started {
+            self.started = true;
+
+            let mut header = [0; 8];
+            if !read_exact_or_eof(&mut self.reader, &mut header)? || header != Png::STANDARD_HEADER
+            {
+                throw!(PngParseError::InvalidPngHeader)
+            }
+            self.offset = header.len() as u64;
+        }
+
+        let mut raw_chunk = vec![0; 8];
+        if !read_exact_or_eof(&mut self.reader, &mut raw_chunk)? {
+            return None;
+        }
+
+        let length = u32::from_be_bytes(raw_chunk[..4].try_into().expect("slice of length 4"));
+        debug!(offset = self.offset, length, "reading chunk");
+        if length > self.max_chunk_size {
+            throw!(PngParseError::ChunkTooLarge {
+                length,
+                max: self.max_chunk_size
+            })
+        }
+
+        self.count += 1;
+        if self.count > self.max_chunk_count {
+            throw!(PngParseError::TooManyChunks {
+                max: self.max_chunk_count
+            })
+        }
+        let rest = length as u64 + 4;
+
+        // Read through `take` so a bogus length can't cause a huge allocation
+        let read = (&mut self.reader).take(rest).read_to_end(&mut raw_chunk)?;
+        if (read as u64) < rest {
+            throw!(PngParseError::PngTooShort)
+        }
+        let offset = self.offset;
+        self.offset += raw_chunk.len() as u64;
+
+        Some(Chunk::parse(&raw_chunk, self.verify_crc)?.with_offset(offset))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk, PngParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let chunk = self.read_chunk().transpose();
+        if !matches!(chunk, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        chunk
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Png {
+    /// Parses a png straight from a memory-mapped file, instead of reading the
+    /// whole file into memory first.
+    ///
+    /// The file must not be modified by another process while it is parsed.
+    #[throws(PngParseError)]
+    pub fn from_file_mmap(path: impl AsRef<std::path::Path>) -> Self {
+        let file = std::fs::File::open(path)?;
+
+        // Safety: the mapping is only read while parsing and dropped before
+        // returning; concurrent modification is ruled out by the caller.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+
+        Png::try_from(&mmap[..])?
     }
 }
 
@@ -94,39 +634,142 @@ impl TryFrom<&[u8]> for Png {
 
     #[throws(Self::Error)]
     fn try_from(value: &[u8]) -> Self {
-        let header = value.get(..8).ok_or(PngParseError::PngTooShort)?;
+        Png::parse(value, ParseOptions::default())?.0
+    }
+}
 
-        if header != Png::STANDARD_HEADER {
-            throw!(PngParseError::InvalidPngHeader)
+/// A table of the chunks, one per line. The alternate form `{:#}` adds a
+/// preview of each chunk's data.
+impl Display for Png {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", ChunkRow::header(f.alternate()))?;
+        for (index, chunk) in self.0.iter().enumerate() {
+            match f.alternate() {
+                true => writeln!(f, "{:#}", ChunkRow::new(index, chunk))?,
+                false => writeln!(f, "{}", ChunkRow::new(index, chunk))?,
+            }
         }
+        Ok(())
+    }
+}
+
+/// One line of the table a [`Png`] displays as, for printing chunks one at
+/// a time as they're read. `LENGTH` is the chunk's data length and `SIZE`
+/// what it takes up on disk, with its length, type and crc.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkRow<'a> {
+    index: usize,
+    chunk: &'a Chunk,
+}
 
-        let mut index = 8;
-        let mut chunks = vec![];
+impl<'a> ChunkRow<'a> {
+    /// Bytes of data shown by the alternate form.
+    pub const PREVIEW_LENGTH: usize = 32;
 
-        while index < value.len() {
-            let length: [u8; 4] = value
-                .get(index..index + 4)
-                .ok_or(PngParseError::PngTooShort)?
-                .try_into()
-                .expect("slice of length 4");
-            let length = u32::from_be_bytes(length) as usize + 12;
+    /// `chunk`, the `index`th in its png.
+    pub fn new(index: usize, chunk: &'a Chunk) -> Self {
+        ChunkRow { index, chunk }
+    }
 
-            let chunk = value
-                .get(index..index + length)
-                .ok_or(PngParseError::PngTooShort)?;
-            let chunk = Chunk::try_from(chunk)?;
+    /// Column titles, matching rows displayed with the same form.
+    pub fn header(preview: bool) -> String {
+        let header = format!(
+            "{:>5}  {:>10}  {:4}  {:>10}  {:>10}  {:8}  {:24}",
+            "#", "OFFSET", "TYPE", "LENGTH", "SIZE", "CRC", "FLAGS"
+        );
 
-            chunks.push(chunk);
-            index += length;
+        match preview {
+            true => format!("{}  DATA", header),
+            false => header.trim_end().to_string(),
         }
+    }
 
-        Self(chunks)
+    fn flags(&self) -> String {
+        let chunk_type = self.chunk.chunk_type();
+        let mut flags = [
+            if chunk_type.is_critical() {
+                "critical"
+            } else {
+                "ancillary"
+            },
+            if chunk_type.is_public() {
+                "public"
+            } else {
+                "private"
+            },
+            if chunk_type.is_safe_to_copy() {
+                "safe"
+            } else {
+                "unsafe"
+            },
+        ]
+        .join(" ");
+
+        if !chunk_type.is_reserved_bit_valid() {
+            flags.push_str(" reserved");
+        }
+
+        flags
     }
 }
 
-impl Display for Png {
+impl Display for ChunkRow<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Png({:?})", self.0)
+        let offset = self
+            .chunk
+            .offset()
+            .map_or_else(|| "-".to_string(), |offset| offset.to_string());
+
+        let row = format!(
+            "{:>5}  {:>10}  {}  {:>10}  {:>10}  {:08x}  {:24}",
+            self.index,
+            offset,
+            self.chunk.chunk_type(),
+            self.chunk.length(),
+            self.chunk.size(),
+            self.chunk.crc(),
+            self.flags()
+        );
+
+        let row = match f.alternate() {
+            true => format!("{}  {}", row, self.chunk.preview(Self::PREVIEW_LENGTH)),
+            false => row,
+        };
+
+        write!(f, "{}", row.trim_end())
+    }
+}
+
+/// Any chunks in any order, not necessarily a valid png.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Png {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Png(u.arbitrary()?))
+    }
+}
+
+/// Serialized as `{"chunks": [...]}`, without the signature.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Png {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Png", 1)?;
+        state.serialize_field("chunks", &self.0)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Png {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct RawPng {
+            chunks: Vec<Chunk>,
+        }
+
+        Ok(Png(RawPng::deserialize(deserializer)?.chunks))
     }
 }
 
@@ -139,6 +782,7 @@ mod tests {
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
@@ -230,6 +874,278 @@ mod tests {
         assert!(png.is_err());
     }
 
+    fn corrupt_png_bytes() -> Vec<u8> {
+        let mut chunks = testing_chunks();
+        let mut bad_chunk = chunks.remove(1).as_bytes();
+        *bad_chunk.last_mut().unwrap() ^= 0xFF;
+
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks[0].as_bytes())
+            .chain(bad_chunk)
+            .chain(chunks[1].as_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_strict_rejects_bad_crc() {
+        let bytes = corrupt_png_bytes();
+
+        assert!(matches!(
+            Png::try_from(bytes.as_ref()),
+            Err(PngParseError::ChunkParsingError(
+                ChunkParseError::InvalidCrc { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_parse_warn_skips_bad_crc() {
+        let bytes = corrupt_png_bytes();
+        let options = ParseOptions {
+            crc_mode: CrcMode::Warn,
+            ..Default::default()
+        };
+        let (png, warnings) = Png::parse(&bytes, options).unwrap();
+
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.chunk_by_type("miDl").is_none());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].offset, 8 + 12 + 20);
+    }
+
+    #[test]
+    fn test_parse_ignore_keeps_bad_crc() {
+        let bytes = corrupt_png_bytes();
+        let options = ParseOptions {
+            crc_mode: CrcMode::Ignore,
+            ..Default::default()
+        };
+        let (png, warnings) = Png::parse(&bytes, options).unwrap();
+
+        assert_eq!(png.chunks().len(), 3);
+        assert!(warnings.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_parse_without_verify_crc() {
+        let bytes = corrupt_png_bytes();
+        let options = ParseOptions {
+            verify_crc: false,
+            ..Default::default()
+        };
+        let (png, warnings) = Png::parse(&bytes, options).unwrap();
+
+        assert_eq!(png.chunks().len(), 3);
+        assert!(warnings.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_chunk_reader_without_verify_crc() {
+        let bytes = corrupt_png_bytes();
+        let chunks: Vec<Chunk> = ChunkReader::new(bytes.as_slice())
+            .verify_crc(false)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_rejects_oversized_chunk() {
+        let options = ParseOptions {
+            max_chunk_size: 10,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Png::parse(&PNG_FILE, options),
+            Err(PngParseError::ChunkTooLarge { max: 10, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_rejects_too_many_chunks() {
+        let options = ParseOptions {
+            max_chunk_count: 2,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Png::parse(&PNG_FILE, options),
+            Err(PngParseError::TooManyChunks { max: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_reader_limits() {
+        let mut reader = ChunkReader::new(&PNG_FILE[..]).max_chunk_count(1);
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(PngParseError::TooManyChunks { max: 1 }))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    fn png_with_duplicates() -> Png {
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+        };
+
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("tIME", &[1; 7]),
+            chunk("tEXt", b"a"),
+            chunk("tEXt", b"b"),
+            chunk("tIME", &[2; 7]),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_duplicate_chunks() {
+        assert_eq!(png_with_duplicates().duplicate_chunks(), vec!["tIME"]);
+        assert!(testing_png().duplicate_chunks().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_hdr_metadata_chunks() {
+        let mut png = png_with_duplicates();
+        for chunk_type in [consts::mDCV, consts::cLLI] {
+            png.insert_chunk_ordered(Chunk::new(chunk_type, vec![]));
+            png.insert_chunk_ordered(Chunk::new(chunk_type, vec![]));
+        }
+
+        assert_eq!(png.duplicate_chunks(), vec!["tIME", "mDCV", "cLLI"]);
+        png.enforce_unique_chunks(DuplicatePolicy::Deduplicate)
+            .unwrap();
+        assert_eq!(png.chunks_by_type("mDCV").count(), 1);
+        assert_eq!(png.chunks_by_type("cLLI").count(), 1);
+    }
+
+    #[test]
+    fn test_enforce_unique_chunks_reject() {
+        let mut png = png_with_duplicates();
+
+        assert!(matches!(
+            png.enforce_unique_chunks(DuplicatePolicy::Reject),
+            Err(PngDuplicateChunkError::DuplicateChunk { chunk_type }) if chunk_type == "tIME"
+        ));
+        assert_eq!(png.chunks().len(), 6);
+    }
+
+    #[test]
+    fn test_enforce_unique_chunks_warn() {
+        let mut png = png_with_duplicates();
+        let duplicates = png.enforce_unique_chunks(DuplicatePolicy::Warn).unwrap();
+
+        assert_eq!(duplicates, vec!["tIME"]);
+        assert_eq!(png.chunks().len(), 6);
+    }
+
+    #[test]
+    fn test_enforce_unique_chunks_deduplicate() {
+        let mut png = png_with_duplicates();
+        png.enforce_unique_chunks(DuplicatePolicy::Deduplicate)
+            .unwrap();
+
+        assert_eq!(png.chunks().len(), 5);
+        assert_eq!(png.chunks_by_type("tIME").count(), 1);
+        assert_eq!(png.chunk_by_type("tIME").unwrap().data(), &[1; 7]);
+        assert_eq!(png.chunks_by_type("tEXt").count(), 2);
+    }
+
+    #[test]
+    fn test_chunk_reader() {
+        let chunks: Vec<Chunk> = ChunkReader::new(&PNG_FILE[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        assert_eq!(chunks, png.chunks());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_png_serde_roundtrip() {
+        let png = testing_png();
+        let json = serde_json::to_value(&png).unwrap();
+
+        assert_eq!(json["chunks"][0]["chunk_type"], "FrSt");
+        assert_eq!(serde_json::from_value::<Png>(json).unwrap(), png);
+    }
+
+    #[test]
+    fn test_clone_is_independent() {
+        let png = testing_png();
+        let mut edited = png.clone();
+        edited.remove_chunk("FrSt").unwrap();
+
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(edited.chunks().len(), 2);
+        assert_ne!(png, edited);
+        assert_eq!(png, png.clone());
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offsets: Vec<Option<u64>> = png.chunks().iter().map(Chunk::offset).collect();
+
+        let mut expected = vec![];
+        let mut offset = 8;
+        for chunk in png.chunks() {
+            expected.push(Some(offset));
+            offset += chunk.size() as u64;
+        }
+        assert_eq!(offsets, expected);
+        assert_eq!(offset as usize, PNG_FILE.len());
+
+        let streamed: Vec<Option<u64>> = ChunkReader::new(&PNG_FILE[..])
+            .map(|chunk| chunk.unwrap().offset())
+            .collect();
+        assert_eq!(streamed, expected);
+
+        assert_eq!(testing_chunks()[0].offset(), None);
+    }
+
+    #[test]
+    fn test_chunk_reader_stops_on_error() {
+        let bytes = corrupt_png_bytes();
+        let mut reader = ChunkReader::new(bytes.as_slice());
+
+        assert_eq!(
+            &reader.next().unwrap().unwrap().chunk_type().to_string(),
+            "FrSt"
+        );
+        assert!(matches!(
+            reader.next(),
+            Some(Err(PngParseError::ChunkParsingError(
+                ChunkParseError::InvalidCrc { .. }
+            )))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_chunk_reader_truncated() {
+        let mut reader = ChunkReader::new(&PNG_FILE[..100]);
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader
+            .by_ref()
+            .any(|chunk| matches!(chunk, Err(PngParseError::PngTooShort))));
+        assert!(matches!(
+            ChunkReader::new(&b"not a png file"[..]).next(),
+            Some(Err(PngParseError::InvalidPngHeader))
+        ));
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -245,6 +1161,19 @@ mod tests {
         assert_eq!(&chunk.data_as_string(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "Another one").unwrap());
+
+        let messages: Vec<String> = png
+            .chunks_by_type("miDl")
+            .map(|chunk| chunk.data_as_string())
+            .collect();
+        assert_eq!(messages, ["I am another chunk", "Another one"]);
+        assert_eq!(png.chunks_by_type("1234").count(), 0);
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -254,6 +1183,177 @@ mod tests {
         assert_eq!(&chunk.data_as_string(), "Message");
     }
 
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        png.insert_chunk(4, chunk_from_strings("TeSt", "Last").unwrap())
+            .unwrap();
+
+        assert_eq!(&png.chunks()[1].data_as_string(), "Message");
+        assert_eq!(&png.chunks()[4].data_as_string(), "Last");
+        assert!(matches!(
+            png.insert_chunk(6, chunk_from_strings("TeSt", "Message").unwrap()),
+            Err(PngChunkInsertError::IndexOutOfRange { index: 6, len: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_insert_chunk_ordered() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("PLTE", "palette").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.insert_chunk_ordered(chunk_from_strings("TeSt", "Message").unwrap());
+        png.insert_chunk_ordered(chunk_from_strings("pHYs", "dimensions").unwrap());
+        png.insert_chunk_ordered(chunk_from_strings("gAMA", "gamma").unwrap());
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            ["IHDR", "gAMA", "PLTE", "pHYs", "IDAT", "IDAT", "TeSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_insert_chunk_ordered_hdr_metadata() {
+        let mut png = Png::from_chunks(
+            ["IHDR", "PLTE", "IDAT", "IEND"]
+                .iter()
+                .map(|chunk_type| chunk_from_strings(chunk_type, "").unwrap())
+                .collect(),
+        );
+        png.insert_chunk_ordered(Chunk::new(consts::mDCV, vec![0; 24]));
+        png.insert_chunk_ordered(Chunk::new(consts::cLLI, vec![0; 8]));
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "mDCV", "cLLI", "PLTE", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_ordered_apng() {
+        let mut png = Png::from_chunks(
+            ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "IEND"]
+                .iter()
+                .map(|chunk_type| chunk_from_strings(chunk_type, "").unwrap())
+                .collect(),
+        );
+        png.insert_chunk_ordered(chunk_from_strings("pHYs", "dimensions").unwrap());
+        png.insert_chunk_ordered(chunk_from_strings("TeSt", "Message").unwrap());
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            ["IHDR", "acTL", "pHYs", "fcTL", "IDAT", "fcTL", "fdAT", "TeSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_insert_chunk_ordered_without_iend() {
+        let mut png = testing_png();
+        png.insert_chunk_ordered(chunk_from_strings("TeSt", "Message").unwrap());
+
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "TeSt"
+        );
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let old = png.replace_chunk(chunk_from_strings("miDl", "Replaced").unwrap());
+
+        assert_eq!(old.unwrap().data_as_string(), "I am another chunk");
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.chunks()[1].data_as_string(), "Replaced");
+
+        assert!(png
+            .replace_chunk(chunk_from_strings("TeSt", "Message").unwrap())
+            .is_none());
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_copy_chunks_from() {
+        let source = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "other image").unwrap(),
+            chunk_from_strings("tEXt", "first").unwrap(),
+            chunk_from_strings("pHYs", "new dpi").unwrap(),
+            chunk_from_strings("tEXt", "second").unwrap(),
+            chunk_from_strings("tIME", "unsafe").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "image").unwrap(),
+            chunk_from_strings("pHYs", "old dpi").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let skipped = png
+            .copy_chunks_from(&source, |chunk| !chunk.chunk_type().is_critical())
+            .unwrap();
+
+        assert_eq!(skipped, vec!["tIME".to_string()]);
+        let chunks: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| format!("{}:{}", chunk.chunk_type(), chunk.data_as_string()))
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![
+                "IHDR:image",
+                "pHYs:new dpi",
+                "IDAT:data",
+                "tEXt:first",
+                "tEXt:second",
+                "IEND:",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copy_chunks_from_same_image() {
+        let source = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "image").unwrap(),
+            chunk_from_strings("cpYT", "unsafe").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "image").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        assert!(png
+            .copy_chunks_from(&source, |chunk| chunk.chunk_type().bytes() == *b"cpYT")
+            .unwrap()
+            .is_empty());
+        assert!(png.chunk_by_type("cpYT").is_some());
+
+        assert!(matches!(
+            png.copy_chunks_from(&source, |_| true),
+            Err(PngChunkCopyError::CriticalChunk { chunk_type }) if chunk_type == "IHDR"
+        ));
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -263,6 +1363,40 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "Another one").unwrap());
+
+        let removed = png.remove_chunks("miDl").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].data_as_string(), "Another one");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_chunks("miDl").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+
+        let removed = png.remove_chunk_at(1).unwrap();
+        assert_eq!(&removed.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(matches!(
+            png.remove_chunk_at(2),
+            Err(PngChunkRemoveError::ChunkNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_retain_chunks() {
+        let mut png = testing_png();
+        png.retain_chunks(|chunk| chunk.chunk_type().is_critical());
+
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.chunk_by_type("miDl").is_none());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -270,6 +1404,87 @@ mod tests {
     }
 
     #[test]
+    fn test_header() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let header = png.header().unwrap();
+
+        assert_eq!((header.width(), header.height()), (50, 50));
+        assert!(matches!(
+            testing_png().header(),
+            Err(IhdrError::UnexpectedChunkType(_))
+        ));
+    }
+
+    #[test]
+    fn test_image_properties() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        assert_eq!((png.width().unwrap(), png.height().unwrap()), (50, 50));
+        assert_eq!(png.bit_depth().unwrap(), 8);
+        assert_eq!(png.color_type().unwrap(), ColorType::Rgba);
+        assert!(!png.is_interlaced().unwrap());
+        assert!(testing_png().width().is_err());
+    }
+
+    #[test]
+    fn test_recompress_image_data() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image = png.decode_image().unwrap();
+
+        // Spread the image data over many tiny chunks around a text chunk
+        let mut chunks: Vec<Chunk> = png.chunks().to_vec();
+        let first_idat = chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .unwrap();
+        chunks.retain(|chunk| chunk.chunk_type().to_string() != "IDAT");
+        let fragments: Vec<Chunk> = png
+            .image_data()
+            .chunks(100)
+            .map(|data| Chunk::new(consts::IDAT, data.to_vec()))
+            .collect();
+        chunks.splice(first_idat..first_idat, fragments);
+        let mut fragmented = Png::from_chunks(chunks);
+
+        fragmented.recompress_image_data(9).unwrap();
+
+        assert_eq!(fragmented.chunks_by_type("IDAT").count(), 1);
+        assert_eq!(fragmented.decode_image().unwrap().rows(), image.rows());
+        assert_eq!(fragmented.chunks().len(), png.chunks().len());
+        assert!(matches!(
+            testing_png().recompress_image_data(9),
+            Err(ImageError::NoImageData)
+        ));
+    }
+
+    #[test]
+    fn test_decode_image() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image = png.decode_image().unwrap();
+
+        assert_eq!(image.rows().len(), 50);
+        assert_eq!(image.filters().len(), 50);
+        assert!(image.rows().iter().all(|row| row.len() == image.stride()));
+        assert!(matches!(
+            testing_png().decode_image(),
+            Err(ImageError::NoImageData)
+        ));
+    }
+
+    #[test]
+    fn test_decode_image_limited() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let pixels = png.header().unwrap().width() as u64 * 50;
+
+        assert!(png.decode_image_limited(pixels).is_ok());
+        assert!(matches!(
+            png.decode_image_limited(pixels - 1),
+            Err(ImageError::TooManyPixels { .. })
+        ));
+    }
+
+    #[test]
+    #[allow(clippy::iter_cloned_collect)]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
@@ -277,6 +1492,40 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        let mut bytes = vec![];
+        png.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, PNG_FILE);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_from_file_mmap() {
+        let path = std::env::temp_dir().join(format!("pngme-mmap-{}.png", std::process::id()));
+        std::fs::write(&path, PNG_FILE).unwrap();
+
+        let png = Png::from_file_mmap(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(png.unwrap().as_bytes(), PNG_FILE);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary_png_round_trip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut u = Unstructured::new(&PNG_FILE);
+        let png = Png::arbitrary(&mut u).unwrap();
+        assert!(!png.chunks().is_empty());
+
+        let parsed = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed, png);
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -295,6 +1544,54 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_png_display_table() {
+        let png = testing_png();
+        let table = png.to_string();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("    #      OFFSET  TYPE"));
+        assert!(lines[1].contains("FrSt"));
+        assert!(lines[2].ends_with("ancillary private safe"));
+        assert!(lines[3].ends_with("critical public safe"));
+        assert!(!table.contains("first chunk"));
+
+        let detailed = format!("{:#}", png);
+        assert!(detailed.lines().next().unwrap().ends_with("DATA"));
+        assert!(detailed.contains("I am the first chunk"));
+    }
+
+    #[test]
+    fn test_chunk_row() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let row = ChunkRow::new(0, &png.chunks()[0]).to_string();
+
+        assert_eq!(
+            row,
+            format!(
+                "    0           8  IHDR          13          25  {:08x}  critical public unsafe",
+                png.chunks()[0].crc()
+            )
+        );
+        assert_eq!(
+            ChunkRow::header(false),
+            "    #      OFFSET  TYPE      LENGTH        SIZE  CRC       FLAGS"
+        );
+    }
+
+    #[test]
+    fn test_png_types_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Png>();
+        assert_send_sync::<Chunk>();
+        assert_send_sync::<ChunkType>();
+        assert_send_sync::<PngParseError>();
+        assert_send_sync::<PngChunkRemoveError>();
+        assert_send_sync::<PngChunkInsertError>();
+    }
+
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
         6, 0, 0, 0, 30, 63, 136, 177, 0, 0, 0, 1, 115, 82, 71, 66, 0, 174, 206, 28, 233, 0, 0, 0,
@@ -0,0 +1,248 @@
+use fehler::{throw, throws};
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{self, Png},
+    validate::MAX_CHUNK_LENGTH,
+};
+
+#[derive(Debug, Error)]
+pub enum RepairError {
+    #[error("invalid png signature")]
+    InvalidSignature,
+}
+
+/// A change made while repairing a png. Offsets refer to the original file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    RecomputedCrc {
+        offset: usize,
+        chunk_type: String,
+    },
+    DroppedInvalidChunk {
+        offset: usize,
+    },
+    DroppedTruncatedChunk {
+        offset: usize,
+    },
+    DroppedTrailingData {
+        offset: usize,
+        length: usize,
+    },
+    MovedChunk {
+        offset: usize,
+        chunk_type: String,
+        before: String,
+    },
+    AddedIend,
+}
+
+impl Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fix::RecomputedCrc { offset, chunk_type } => {
+                write!(f, "byte {}: recomputed crc of `{}`", offset, chunk_type)
+            }
+            Fix::DroppedInvalidChunk { offset } => {
+                write!(f, "byte {}: dropped chunk with invalid type", offset)
+            }
+            Fix::DroppedTruncatedChunk { offset } => {
+                write!(f, "byte {}: dropped truncated chunk", offset)
+            }
+            Fix::DroppedTrailingData { offset, length } => {
+                write!(f, "byte {}: dropped {} bytes after `IEND`", offset, length)
+            }
+            Fix::MovedChunk {
+                offset,
+                chunk_type,
+                before,
+            } => write!(
+                f,
+                "byte {}: moved `{}` before `{}`",
+                offset, chunk_type, before
+            ),
+            Fix::AddedIend => write!(f, "added missing `IEND`"),
+        }
+    }
+}
+
+/// Rebuilds a png from possibly broken bytes, returning it along with the
+/// list of fixes applied. Only structural problems are fixed; chunk contents
+/// are kept as is.
+#[throws(RepairError)]
+pub fn repair(bytes: &[u8]) -> (Png, Vec<Fix>) {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        throw!(RepairError::InvalidSignature)
+    }
+
+    let mut png = Png::from_chunks(vec![]);
+    let mut fixes = vec![];
+    let mut index = Png::STANDARD_HEADER.len();
+
+    while index < bytes.len() {
+        if png.chunk_by_type("IEND").is_some() {
+            fixes.push(Fix::DroppedTrailingData {
+                offset: index,
+                length: bytes.len() - index,
+            });
+            break;
+        }
+
+        let raw_chunk = bytes
+            .get(index..index + 4)
+            .map(|length| u32::from_be_bytes(length.try_into().expect("slice of length 4")))
+            .filter(|&length| length <= MAX_CHUNK_LENGTH)
+            .and_then(|length| bytes.get(index..index + length as usize + 12));
+
+        let Some(raw_chunk) = raw_chunk else {
+            fixes.push(Fix::DroppedTruncatedChunk { offset: index });
+            break;
+        };
+
+        let offset = index;
+        index += raw_chunk.len();
+
+        let Ok(chunk) = Chunk::parse(raw_chunk, false) else {
+            fixes.push(Fix::DroppedInvalidChunk { offset });
+            continue;
+        };
+
+        let chunk_type = chunk.chunk_type().to_string();
+        let calculated = Chunk::calculate_crc(chunk.chunk_type(), chunk.data());
+        let chunk = if chunk.crc() != calculated {
+            fixes.push(Fix::RecomputedCrc {
+                offset,
+                chunk_type: chunk_type.clone(),
+            });
+            Chunk::new(*chunk.chunk_type(), chunk.data().to_vec())
+        } else {
+            chunk
+        };
+
+        let before = png::must_precede(&chunk.chunk_type().bytes());
+        let misplaced = png.chunks().iter().find(|placed| {
+            placed.chunk_type() != chunk.chunk_type()
+                && before.contains(&placed.chunk_type().bytes())
+        });
+
+        match misplaced {
+            Some(placed) if !chunk.chunk_type().is_critical() => {
+                fixes.push(Fix::MovedChunk {
+                    offset,
+                    chunk_type,
+                    before: placed.chunk_type().to_string(),
+                });
+                png.insert_chunk_ordered(chunk);
+            }
+            _ => png.append_chunk(chunk),
+        }
+    }
+
+    if png.chunk_by_type("IEND").is_none() {
+        let iend = ChunkType::try_from(*b"IEND").expect("valid chunk type");
+        png.append_chunk(Chunk::new(iend, vec![]));
+        fixes.push(Fix::AddedIend);
+    }
+
+    (png, fixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(chunks: &[Chunk]) -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|chunk| chunk.as_bytes()))
+            .collect()
+    }
+
+    fn valid_chunks() -> Vec<Chunk> {
+        vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("IDAT", b"data"),
+            chunk("IEND", b""),
+        ]
+    }
+
+    #[test]
+    fn test_repair_valid_png() {
+        let bytes = png_bytes(&valid_chunks());
+        let (png, fixes) = repair(&bytes).unwrap();
+
+        assert!(fixes.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_repair_crc_and_trailing_data() {
+        let mut bytes = png_bytes(&valid_chunks());
+        let idat_offset = 8 + 12 + 13;
+        bytes[idat_offset + 12 + 4 - 1] ^= 0xFF;
+        bytes.extend_from_slice(b"garbage");
+
+        let (png, fixes) = repair(&bytes).unwrap();
+        assert_eq!(
+            fixes,
+            vec![
+                Fix::RecomputedCrc {
+                    offset: idat_offset,
+                    chunk_type: "IDAT".to_string()
+                },
+                Fix::DroppedTrailingData {
+                    offset: bytes.len() - 7,
+                    length: 7
+                },
+            ]
+        );
+        assert_eq!(validate(&png.as_bytes()), vec![]);
+    }
+
+    #[test]
+    fn test_repair_moves_misplaced_chunks() {
+        let mut chunks = valid_chunks();
+        chunks.insert(2, chunk("gAMA", &[0, 0, 177, 143]));
+        chunks.insert(3, chunk("tEXt", b"a\0b"));
+
+        let (png, fixes) = repair(&png_bytes(&chunks)).unwrap();
+        assert_eq!(fixes.len(), 1);
+        assert!(matches!(&fixes[0], Fix::MovedChunk { chunk_type, .. } if chunk_type == "gAMA"));
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), "gAMA");
+        assert_eq!(validate(&png.as_bytes()), vec![]);
+    }
+
+    #[test]
+    fn test_repair_truncated_file() {
+        let bytes = png_bytes(&valid_chunks()[..2]);
+        let (png, fixes) = repair(&bytes[..bytes.len() - 2]).unwrap();
+
+        assert_eq!(
+            fixes,
+            vec![
+                Fix::DroppedTruncatedChunk {
+                    offset: 8 + 12 + 13
+                },
+                Fix::AddedIend
+            ]
+        );
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_repair_invalid_signature() {
+        assert!(matches!(
+            repair(b"not a png"),
+            Err(RepairError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt::Display;
+
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, png::Png, text::TextualChunk, validate::MAX_CHUNK_LENGTH,
+};
+
+/// Ancillary chunks that hold compressed data, and so are expected to look
+/// random.
+const COMPRESSED_CHUNKS: [&[u8; 4]; 4] = [b"fdAT", b"iCCP", b"iTXt", b"zTXt"];
+
+/// Entropy, in bits per byte, above which data is likely compressed or
+/// encrypted.
+pub const ENTROPY_THRESHOLD: f64 = 7.5;
+
+/// Shorter data can't reach the entropy threshold, since `n` bytes have at
+/// most `log2(n)` bits of entropy per byte.
+const MIN_ENTROPY_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FindingKind {
+    PrivateChunk { chunk_type: String },
+    UnregisteredChunk { chunk_type: String },
+    HighEntropy { chunk_type: String, entropy: f64 },
+    BinaryText { chunk_type: String },
+    DataAfterIend { length: usize },
+}
+
+impl Display for FindingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FindingKind::PrivateChunk { chunk_type } => {
+                write!(f, "private chunk `{}`", chunk_type)
+            }
+            FindingKind::UnregisteredChunk { chunk_type } => {
+                write!(f, "unregistered public chunk `{}`", chunk_type)
+            }
+            FindingKind::HighEntropy {
+                chunk_type,
+                entropy,
+            } => write!(
+                f,
+                "`{}` looks compressed or encrypted ({:.2} bits per byte)",
+                chunk_type, entropy
+            ),
+            FindingKind::BinaryText { chunk_type } => {
+                write!(f, "`{}` holds binary data instead of text", chunk_type)
+            }
+            FindingKind::DataAfterIend { length } => {
+                write!(f, "{} bytes of data after `IEND`", length)
+            }
+        }
+    }
+}
+
+/// Something that may hide a payload, found at `offset` bytes into the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub offset: usize,
+    pub kind: FindingKind,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.kind)
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte, from 0 to 8.
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / data.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn is_binary_text(chunk: &Chunk) -> bool {
+    match TextualChunk::try_from(chunk) {
+        Ok(text) => text
+            .text()
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')),
+        Err(_) => true,
+    }
+}
+
+fn scan_chunk(offset: usize, chunk: &Chunk, findings: &mut Vec<Finding>) {
+    let chunk_type = chunk.chunk_type();
+    let bytes = chunk_type.bytes();
+    let mut report = |kind| findings.push(Finding { offset, kind });
+
+    if !chunk_type.is_public() {
+        report(FindingKind::PrivateChunk {
+            chunk_type: chunk_type.to_string(),
+        });
+    } else if !chunk_type.is_standard() {
+        report(FindingKind::UnregisteredChunk {
+            chunk_type: chunk_type.to_string(),
+        });
+    }
+
+    if TextualChunk::is_textual(chunk) && is_binary_text(chunk) {
+        report(FindingKind::BinaryText {
+            chunk_type: chunk_type.to_string(),
+        });
+    }
+
+    if !chunk_type.is_critical()
+        && !COMPRESSED_CHUNKS.contains(&&bytes)
+        && chunk.data().len() >= MIN_ENTROPY_LENGTH
+    {
+        let entropy = entropy(chunk.data());
+        if entropy > ENTROPY_THRESHOLD {
+            report(FindingKind::HighEntropy {
+                chunk_type: chunk_type.to_string(),
+                entropy,
+            });
+        }
+    }
+}
+
+/// Looks for places a png could hide a payload in: private or unregistered
+/// chunks, ancillary chunks with random looking data, text chunks holding
+/// binary data and data after `IEND`.
+///
+/// Structural problems are left to [`crate::validate::validate`], scanning
+/// stops at the first one.
+pub fn scan(bytes: &[u8]) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        return findings;
+    }
+
+    let mut index = Png::STANDARD_HEADER.len();
+
+    while let Some(header) = bytes.get(index..index + 8) {
+        let length = u32::from_be_bytes(header[..4].try_into().expect("slice of length 4"));
+        let chunk_type: [u8; 4] = header[4..].try_into().expect("slice of length 4");
+
+        if length > MAX_CHUNK_LENGTH || ChunkType::try_from(chunk_type).is_err() {
+            break;
+        }
+
+        let Some(raw_chunk) = bytes.get(index..index + length as usize + 12) else {
+            break;
+        };
+
+        let chunk = Chunk::parse(raw_chunk, false).expect("length and type are valid");
+        scan_chunk(index, &chunk, &mut findings);
+        index += raw_chunk.len();
+
+        if &chunk_type == b"IEND" {
+            if index < bytes.len() {
+                findings.push(Finding {
+                    offset: index,
+                    kind: FindingKind::DataAfterIend {
+                        length: bytes.len() - index,
+                    },
+                });
+            }
+            break;
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextChunk;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(chunks: Vec<Chunk>) -> Vec<u8> {
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    fn clean_chunks() -> Vec<Chunk> {
+        vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("IDAT", &(0..=255).collect::<Vec<u8>>()),
+            TextChunk::new("Title", "hello\nworld").unwrap().to_chunk(),
+            chunk("IEND", b""),
+        ]
+    }
+
+    fn kinds(bytes: &[u8]) -> Vec<FindingKind> {
+        scan(bytes)
+            .into_iter()
+            .map(|finding| finding.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        assert_eq!(entropy(&[0, 1, 0, 1]), 1.0);
+        assert_eq!(entropy(&(0..=255).collect::<Vec<u8>>()), 8.0);
+    }
+
+    #[test]
+    fn test_scan_clean_png() {
+        assert_eq!(scan(&png_bytes(clean_chunks())), vec![]);
+    }
+
+    #[test]
+    fn test_scan_chunk_types() {
+        let mut chunks = clean_chunks();
+        chunks.insert(1, chunk("ruSt", b"message"));
+        chunks.insert(1, chunk("ABCD", b""));
+
+        assert_eq!(
+            kinds(&png_bytes(chunks)),
+            vec![
+                FindingKind::UnregisteredChunk {
+                    chunk_type: "ABCD".to_string()
+                },
+                FindingKind::PrivateChunk {
+                    chunk_type: "ruSt".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_high_entropy() {
+        let random: Vec<u8> = (0..1024u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let mut chunks = clean_chunks();
+        chunks.insert(1, chunk("tIME", &random));
+        chunks.insert(1, chunk("zTXt", &random));
+
+        let findings = scan(&png_bytes(chunks));
+
+        assert!(findings.iter().any(|finding| matches!(
+            &finding.kind,
+            FindingKind::HighEntropy { chunk_type, .. } if chunk_type == "tIME"
+        )));
+        assert!(!findings.iter().any(|finding| matches!(
+            &finding.kind,
+            FindingKind::HighEntropy { chunk_type, .. } if chunk_type == "zTXt"
+        )));
+    }
+
+    #[test]
+    fn test_scan_binary_text() {
+        let mut chunks = clean_chunks();
+        chunks.insert(1, chunk("tEXt", b"Comment\0\x01\x02\x03"));
+        chunks.insert(1, chunk("tEXt", b"no separator"));
+
+        assert_eq!(
+            kinds(&png_bytes(chunks)),
+            vec![
+                FindingKind::BinaryText {
+                    chunk_type: "tEXt".to_string()
+                };
+                2
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_data_after_iend() {
+        let mut bytes = png_bytes(clean_chunks());
+        let iend_end = bytes.len();
+        bytes.extend(b"hidden");
+
+        assert_eq!(
+            scan(&bytes),
+            vec![Finding {
+                offset: iend_end,
+                kind: FindingKind::DataAfterIend { length: 6 }
+            }]
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Debug, Error)]
+pub enum ShamirError {
+    #[error("expected a `shAr` chunk but got `{0}`")]
+    UnexpectedChunkType(String),
+
+    #[error("share data too short")]
+    ShareTooShort,
+
+    #[error("unsupported share version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("threshold must be between 1 and {shares} shares, got {threshold}")]
+    InvalidThreshold { threshold: u8, shares: u8 },
+
+    #[error("need {needed} shares to recover the secret, got {got}")]
+    NotEnoughShares { needed: u8, got: usize },
+
+    #[error("shares belong to different secrets")]
+    MismatchedShares,
+
+    #[error("share {0} was given more than once")]
+    DuplicateShare(u8),
+}
+
+/// Chunk type shares are stored under.
+pub const SHARE: [u8; 4] = *b"shAr";
+
+/// Share chunk layout:
+///
+/// `VERSION (1) | id (4) | threshold (1) | x (1) | y`
+///
+/// The id is random per secret, so shares of different secrets aren't mixed.
+pub const VERSION: u8 = 1;
+
+const HEADER_LENGTH: usize = 7;
+
+/// Multiplies in GF(2^8) with the AES polynomial.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    product
+}
+
+/// Multiplicative inverse in GF(2^8), as `a^254`.
+fn inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exponent = 254;
+
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+
+    result
+}
+
+/// One share of a secret split with [`split`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    id: u32,
+    threshold: u8,
+    x: u8,
+    y: Vec<u8>,
+}
+
+impl Share {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Number of shares needed to recover the secret.
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// Index of the share, from 1.
+    pub fn index(&self) -> u8 {
+        self.x
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [
+            &[VERSION][..],
+            &self.id.to_be_bytes(),
+            &[self.threshold, self.x],
+            &self.y,
+        ]
+        .concat();
+
+        let chunk_type = ChunkType::try_from(SHARE).expect("valid chunk type");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Share {
+    type Error = ShamirError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        if chunk.chunk_type().bytes() != SHARE {
+            throw!(ShamirError::UnexpectedChunkType(
+                chunk.chunk_type().to_string()
+            ))
+        }
+
+        let data = chunk.data();
+        if data.len() < HEADER_LENGTH {
+            throw!(ShamirError::ShareTooShort)
+        }
+
+        if data[0] != VERSION {
+            throw!(ShamirError::UnsupportedVersion(data[0]))
+        }
+
+        Share {
+            id: u32::from_be_bytes(data[1..5].try_into().expect("slice of length 4")),
+            threshold: data[5],
+            x: data[6],
+            y: data[HEADER_LENGTH..].to_vec(),
+        }
+    }
+}
+
+/// Splits `secret` into `shares` shares, any `threshold` of which recover it.
+#[throws(ShamirError)]
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> Vec<Share> {
+    if threshold == 0 || threshold > shares {
+        throw!(ShamirError::InvalidThreshold { threshold, shares })
+    }
+
+    let id = OsRng.next_u32();
+    let mut split: Vec<Share> = (1..=shares)
+        .map(|x| Share {
+            id,
+            threshold,
+            x,
+            y: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    let mut coefficients = vec![0; threshold as usize];
+    for byte in secret {
+        coefficients[0] = *byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+
+        for share in &mut split {
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0, |y, coefficient| mul(y, share.x) ^ coefficient);
+            share.y.push(y);
+        }
+    }
+
+    split
+}
+
+/// Recovers a secret from at least `threshold` of its shares.
+#[throws(ShamirError)]
+pub fn combine(shares: &[Share]) -> Vec<u8> {
+    let first = shares
+        .first()
+        .ok_or(ShamirError::NotEnoughShares { needed: 1, got: 0 })?;
+
+    if shares.iter().any(|share| {
+        share.id != first.id || share.threshold != first.threshold || share.y.len() != first.y.len()
+    }) {
+        throw!(ShamirError::MismatchedShares)
+    }
+
+    for (index, share) in shares.iter().enumerate() {
+        if shares[..index].iter().any(|other| other.x == share.x) {
+            throw!(ShamirError::DuplicateShare(share.x))
+        }
+    }
+
+    if shares.len() < first.threshold as usize {
+        throw!(ShamirError::NotEnoughShares {
+            needed: first.threshold,
+            got: shares.len()
+        })
+    }
+
+    let shares = &shares[..first.threshold as usize];
+
+    // Lagrange basis polynomials evaluated at 0
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.x != share.x)
+                .fold(1, |basis, other| {
+                    mul(basis, mul(other.x, inv(other.x ^ share.x)))
+                })
+        })
+        .collect();
+
+    (0..first.y.len())
+        .map(|index| {
+            shares.iter().zip(&basis).fold(0, |secret, (share, basis)| {
+                secret ^ mul(share.y[index], *basis)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_inverse() {
+        for a in 1..=255 {
+            assert_eq!(mul(a, inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_split_combine_any_threshold_subset() {
+        let secret = b"This is a shared secret!";
+        let shares = split(secret, 2, 3).unwrap();
+        assert_eq!(shares.len(), 3);
+
+        for pair in [[0, 1], [0, 2], [2, 1]] {
+            let subset: Vec<Share> = pair.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine(&subset).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn test_combine_not_enough_shares() {
+        let shares = split(b"secret", 3, 5).unwrap();
+
+        assert!(matches!(
+            combine(&shares[..2]),
+            Err(ShamirError::NotEnoughShares { needed: 3, got: 2 })
+        ));
+        assert!(matches!(
+            combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]),
+            Err(ShamirError::DuplicateShare(1))
+        ));
+    }
+
+    #[test]
+    fn test_combine_mismatched_shares() {
+        let a = split(b"secret", 2, 2).unwrap();
+        let b = split(b"secret", 2, 2).unwrap();
+
+        assert!(matches!(
+            combine(&[a[0].clone(), b[1].clone()]),
+            Err(ShamirError::MismatchedShares)
+        ));
+        assert!(matches!(
+            split(b"secret", 4, 3),
+            Err(ShamirError::InvalidThreshold { .. })
+        ));
+    }
+
+    #[test]
+    fn test_share_chunk_round_trip() {
+        let share = split(b"secret", 2, 3).unwrap().remove(1);
+        let chunk = share.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "shAr");
+        assert_eq!(Share::try_from(&chunk).unwrap(), share);
+    }
+}
@@ -0,0 +1,166 @@
+use aes_gcm::aead::OsRng;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fehler::{throw, throws};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SignError {
+    #[error("payload is not signed")]
+    NotSigned,
+
+    #[error("unsupported signature version `{0}`")]
+    UnsupportedVersion(u8),
+
+    #[error("signed payload too short")]
+    PayloadTooShort,
+
+    #[error("invalid public key")]
+    InvalidPublicKey,
+
+    #[error("signature does not match, the payload was tampered with or signed by another key")]
+    InvalidSignature,
+}
+
+/// Signed payload layout:
+///
+/// `MAGIC (4) | VERSION (1) | payload | signature (64)`
+///
+/// The signature covers everything before it, header included.
+pub const MAGIC: [u8; 4] = *b"PMSG";
+pub const VERSION: u8 = 1;
+
+pub const SECRET_KEY_LENGTH: usize = ed25519_dalek::SECRET_KEY_LENGTH;
+pub const PUBLIC_KEY_LENGTH: usize = ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+pub(crate) const HEADER_LENGTH: usize = MAGIC.len() + 1;
+pub(crate) const SIGNATURE_LENGTH: usize = ed25519_dalek::SIGNATURE_LENGTH;
+
+pub fn is_signed(payload: &[u8]) -> bool {
+    payload.starts_with(&MAGIC)
+}
+
+/// Generates a new secret key along with its public key.
+pub fn generate_keypair() -> ([u8; SECRET_KEY_LENGTH], [u8; PUBLIC_KEY_LENGTH]) {
+    let signing_key = SigningKey::generate(&mut OsRng);
+
+    (
+        signing_key.to_bytes(),
+        signing_key.verifying_key().to_bytes(),
+    )
+}
+
+/// Wraps `payload` in an envelope signed with `secret_key`.
+pub fn sign(secret_key: &[u8; SECRET_KEY_LENGTH], payload: &[u8]) -> Vec<u8> {
+    let signing_key = SigningKey::from_bytes(secret_key);
+
+    let mut envelope: Vec<u8> = MAGIC
+        .iter()
+        .chain([VERSION].iter())
+        .chain(payload.iter())
+        .copied()
+        .collect();
+    let signature = signing_key.sign(&envelope);
+    envelope.extend_from_slice(&signature.to_bytes());
+
+    envelope
+}
+
+/// Splits a signed envelope into its payload and signature.
+#[throws(SignError)]
+fn split(envelope: &[u8]) -> (&[u8], [u8; SIGNATURE_LENGTH]) {
+    if !is_signed(envelope) {
+        throw!(SignError::NotSigned)
+    }
+
+    if envelope.len() < HEADER_LENGTH + SIGNATURE_LENGTH {
+        throw!(SignError::PayloadTooShort)
+    }
+
+    let version = envelope[MAGIC.len()];
+    if version != VERSION {
+        throw!(SignError::UnsupportedVersion(version))
+    }
+
+    let (signed, signature) = envelope.split_at(envelope.len() - SIGNATURE_LENGTH);
+    (
+        signed,
+        signature.try_into().expect("slice of signature length"),
+    )
+}
+
+/// Checks the signature of an envelope against `public_key`, returning the
+/// signed payload.
+#[throws(SignError)]
+pub fn verify(public_key: &[u8; PUBLIC_KEY_LENGTH], envelope: &[u8]) -> Vec<u8> {
+    let verifying_key =
+        VerifyingKey::from_bytes(public_key).map_err(|_| SignError::InvalidPublicKey)?;
+    let (signed, signature) = split(envelope)?;
+
+    verifying_key
+        .verify(signed, &Signature::from_bytes(&signature))
+        .map_err(|_| SignError::InvalidSignature)?;
+
+    signed[HEADER_LENGTH..].to_vec()
+}
+
+/// Returns the payload of an envelope without checking its signature.
+#[throws(SignError)]
+pub fn strip_signature(envelope: &[u8]) -> Vec<u8> {
+    let (signed, _) = split(envelope)?;
+
+    signed[HEADER_LENGTH..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_verify_round_trip() {
+        let (secret_key, public_key) = generate_keypair();
+        let envelope = sign(&secret_key, b"This is a signed message!");
+        assert!(is_signed(&envelope));
+
+        let payload = verify(&public_key, &envelope).unwrap();
+        assert_eq!(payload, b"This is a signed message!");
+        assert_eq!(strip_signature(&envelope).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_verify_tampered_payload() {
+        let (secret_key, public_key) = generate_keypair();
+        let mut envelope = sign(&secret_key, b"This is a signed message!");
+        envelope[HEADER_LENGTH] ^= 1;
+
+        assert!(matches!(
+            verify(&public_key, &envelope),
+            Err(SignError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_verify_wrong_key() {
+        let (secret_key, _) = generate_keypair();
+        let (_, other_public_key) = generate_keypair();
+        let envelope = sign(&secret_key, b"message");
+
+        assert!(matches!(
+            verify(&other_public_key, &envelope),
+            Err(SignError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_verify_unsigned_payload() {
+        let (_, public_key) = generate_keypair();
+
+        assert!(matches!(
+            verify(&public_key, b"plain message"),
+            Err(SignError::NotSigned)
+        ));
+        assert!(matches!(
+            strip_signature(b"PMSG\x01short"),
+            Err(SignError::PayloadTooShort)
+        ));
+    }
+}
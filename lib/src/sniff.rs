@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+/// What a payload looks like, judged from its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentType {
+    Text,
+    Json,
+    Png,
+    Jpeg,
+    Gif,
+    Pdf,
+    Gzip,
+    Zstd,
+    Zip,
+    Binary,
+}
+
+const SIGNATURES: [(&[u8], ContentType); 9] = [
+    (b"\x89PNG\r\n\x1a\n", ContentType::Png),
+    (b"\xff\xd8\xff", ContentType::Jpeg),
+    (b"GIF87a", ContentType::Gif),
+    (b"GIF89a", ContentType::Gif),
+    (b"%PDF-", ContentType::Pdf),
+    (b"\x1f\x8b", ContentType::Gzip),
+    (b"\x28\xb5\x2f\xfd", ContentType::Zstd),
+    (b"PK\x03\x04", ContentType::Zip),
+    (b"PK\x05\x06", ContentType::Zip),
+];
+
+impl ContentType {
+    pub fn is_text(&self) -> bool {
+        matches!(self, ContentType::Text | ContentType::Json)
+    }
+}
+
+impl Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ContentType::Text => "text",
+            ContentType::Json => "json",
+            ContentType::Png => "png",
+            ContentType::Jpeg => "jpeg",
+            ContentType::Gif => "gif",
+            ContentType::Pdf => "pdf",
+            ContentType::Gzip => "gzip",
+            ContentType::Zstd => "zstd",
+            ContentType::Zip => "zip",
+            ContentType::Binary => "binary",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Guesses the type of `payload` from well known magic bytes, falling back to
+/// text for printable utf-8 and binary for anything else.
+///
+/// Json is only recognized by its surrounding brackets, so a payload reported
+/// as json may still fail to parse.
+pub fn sniff(payload: &[u8]) -> ContentType {
+    if let Some((_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| payload.starts_with(signature))
+    {
+        return *content_type;
+    }
+
+    let Ok(text) = std::str::from_utf8(payload) else {
+        return ContentType::Binary;
+    };
+
+    if text
+        .chars()
+        .any(|char| char.is_control() && !matches!(char, '\t' | '\n' | '\r'))
+    {
+        return ContentType::Binary;
+    }
+
+    let trimmed = text.trim();
+    let is_json = (trimmed.starts_with('{') && trimmed.ends_with('}'))
+        || (trimmed.starts_with('[') && trimmed.ends_with(']'));
+
+    if is_json {
+        ContentType::Json
+    } else {
+        ContentType::Text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), ContentType::Png);
+        assert_eq!(sniff(b"\x1f\x8b\x08\0"), ContentType::Gzip);
+        assert_eq!(sniff(b"GIF89a\x01\0"), ContentType::Gif);
+        assert_eq!(sniff(b"PK\x03\x04rest"), ContentType::Zip);
+    }
+
+    #[test]
+    fn test_sniff_text() {
+        assert_eq!(sniff("héllo\n".as_bytes()), ContentType::Text);
+        assert_eq!(sniff(b""), ContentType::Text);
+        assert_eq!(sniff(b" {\"a\": [1]}\n"), ContentType::Json);
+        assert_eq!(sniff(b"[1, 2]"), ContentType::Json);
+    }
+
+    #[test]
+    fn test_sniff_binary() {
+        assert_eq!(sniff(b"\xff\xfe\x00"), ContentType::Binary);
+        assert_eq!(sniff(b"text\0with nul"), ContentType::Binary);
+        assert!(!ContentType::Binary.is_text());
+        assert!(ContentType::Json.is_text());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{chunk::Chunk, chunk_type::consts, png::Png};
+
+/// Chunks of one type in a png.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeStats {
+    pub chunk_type: String,
+    /// What chunks of this type hold, for standard types.
+    pub description: Option<&'static str>,
+    pub count: usize,
+    /// On-disk size of these chunks, headers and crcs included.
+    pub bytes: usize,
+}
+
+/// A breakdown of where the bytes of a png go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Size of the whole file, signature included.
+    pub total_bytes: usize,
+    pub critical_bytes: usize,
+    pub ancillary_bytes: usize,
+    /// Compressed image data in `IDAT` chunks, without their headers.
+    pub idat_bytes: usize,
+    /// Per chunk type, in order of first appearance.
+    pub types: Vec<TypeStats>,
+    /// Indices of the chunks, largest first.
+    pub largest: Vec<usize>,
+}
+
+impl Stats {
+    pub fn of(png: &Png) -> Self {
+        let chunks = png.chunks();
+        let mut stats = Stats {
+            total_bytes: Png::STANDARD_HEADER.len(),
+            critical_bytes: 0,
+            ancillary_bytes: 0,
+            idat_bytes: 0,
+            types: vec![],
+            largest: (0..chunks.len()).collect(),
+        };
+
+        for chunk in chunks {
+            let chunk_type = chunk.chunk_type().to_string();
+            let size = chunk.size();
+            stats.total_bytes += size;
+
+            if chunk.chunk_type().is_critical() {
+                stats.critical_bytes += size;
+            } else {
+                stats.ancillary_bytes += size;
+            }
+
+            if *chunk.chunk_type() == consts::IDAT {
+                stats.idat_bytes += chunk.length();
+            }
+
+            match stats
+                .types
+                .iter_mut()
+                .find(|types| types.chunk_type == chunk_type)
+            {
+                Some(types) => {
+                    types.count += 1;
+                    types.bytes += size;
+                }
+                None => stats.types.push(TypeStats {
+                    chunk_type,
+                    description: chunk.chunk_type().known_description(),
+                    count: 1,
+                    bytes: size,
+                }),
+            }
+        }
+
+        // Stable, so equally large chunks stay in file order
+        stats
+            .largest
+            .sort_by_key(|index| std::cmp::Reverse(chunks[*index].size()));
+
+        stats
+    }
+
+    /// Share of the file taken up by ancillary chunks, in percent.
+    pub fn metadata_overhead(&self) -> f64 {
+        self.ancillary_bytes as f64 * 100.0 / self.total_bytes as f64
+    }
+
+    /// The `count` largest chunks of `png`, which must be the png these
+    /// stats were taken of.
+    pub fn largest_chunks<'a>(
+        &'a self,
+        png: &'a Png,
+        count: usize,
+    ) -> impl Iterator<Item = (usize, &'a Chunk)> + 'a {
+        self.largest
+            .iter()
+            .take(count)
+            .map(|index| (*index, &png.chunks()[*index]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunk(chunk_type: &str, length: usize) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; length])
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", 13),
+            chunk("tEXt", 20),
+            chunk("IDAT", 100),
+            chunk("IDAT", 50),
+            chunk("IEND", 0),
+        ])
+    }
+
+    #[test]
+    fn test_stats_bytes() {
+        let stats = Stats::of(&testing_png());
+
+        assert_eq!(stats.total_bytes, 8 + 13 + 20 + 100 + 50 + 5 * 12);
+        assert_eq!(stats.ancillary_bytes, 32);
+        assert_eq!(stats.critical_bytes, 13 + 100 + 50 + 4 * 12);
+        assert_eq!(stats.idat_bytes, 150);
+        assert!((stats.metadata_overhead() - 3200.0 / 251.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stats_types() {
+        let stats = Stats::of(&testing_png());
+        let types: Vec<(&str, usize, usize)> = stats
+            .types
+            .iter()
+            .map(|types| (types.chunk_type.as_str(), types.count, types.bytes))
+            .collect();
+
+        assert_eq!(
+            types,
+            [
+                ("IHDR", 1, 25),
+                ("tEXt", 1, 32),
+                ("IDAT", 2, 174),
+                ("IEND", 1, 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_largest() {
+        let png = testing_png();
+        let stats = Stats::of(&png);
+        let largest: Vec<usize> = stats
+            .largest_chunks(&png, 3)
+            .map(|(index, _)| index)
+            .collect();
+
+        assert_eq!(largest, [2, 3, 1]);
+    }
+}
@@ -0,0 +1,268 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{
+    ihdr::{ColorType, Ihdr, IhdrError},
+    image::{idat_chunks, ImageError},
+    png::Png,
+};
+
+#[derive(Debug, Error)]
+pub enum StegoError {
+    #[error("invalid image header")]
+    InvalidHeader(#[from] IhdrError),
+
+    #[error("invalid image data")]
+    InvalidImage(#[from] ImageError),
+
+    #[error("lsb embedding doesn't support {0}")]
+    Unsupported(&'static str),
+
+    #[error("payload needs {needed} bytes but the image can hide {available}")]
+    InsufficientCapacity { needed: usize, available: usize },
+
+    #[error("image has no hidden payload")]
+    NoPayload,
+}
+
+/// Payload layout, spread one bit per carrier byte, most significant first:
+///
+/// `MAGIC (4) | length (4) | payload`
+pub const MAGIC: [u8; 4] = *b"PMLS";
+
+const HEADER_LENGTH: usize = MAGIC.len() + 4;
+
+const IMAGE_DATA: [u8; 4] = *b"IDAT";
+
+/// Positions of the bytes whose lowest bit carries the payload: the least
+/// significant byte of every colour sample. Alpha is left alone, so fully
+/// transparent pixels don't give anything away.
+fn carriers(header: &Ihdr) -> impl Iterator<Item = (usize, usize)> {
+    let bytes_per_sample = header.bit_depth() as usize / 8;
+    let channels = header.color_type().channels() as usize;
+    let color_channels = match header.color_type() {
+        ColorType::GrayscaleAlpha | ColorType::Rgba => channels - 1,
+        _ => channels,
+    };
+    let (width, height) = (header.width() as usize, header.height() as usize);
+
+    (0..height).flat_map(move |row| {
+        (0..width).flat_map(move |pixel| {
+            (0..color_channels).map(move |channel| {
+                let sample = pixel * channels + channel;
+                (row, sample * bytes_per_sample + bytes_per_sample - 1)
+            })
+        })
+    })
+}
+
+#[throws(StegoError)]
+fn check_supported(header: &Ihdr) {
+    if header.color_type() == ColorType::Indexed {
+        throw!(StegoError::Unsupported("indexed colour images"))
+    }
+
+    if header.bit_depth() < 8 {
+        throw!(StegoError::Unsupported("bit depths below 8"))
+    }
+}
+
+/// Number of payload bytes that can be hidden in the pixels of `png`.
+#[throws(StegoError)]
+pub fn capacity(png: &Png) -> usize {
+    let header = png.header()?;
+    check_supported(&header)?;
+
+    (carriers(&header).count() / 8).saturating_sub(HEADER_LENGTH)
+}
+
+/// Number of pixels an 8-bit RGB or RGBA image needs to hide `length` bytes.
+pub fn pixels_needed(length: usize) -> usize {
+    ((length + HEADER_LENGTH) * 8).div_ceil(3)
+}
+
+/// Hides `payload` in the least significant bits of the pixels of `png`,
+/// replacing its image data.
+#[throws(StegoError)]
+pub fn embed(png: &mut Png, payload: &[u8]) {
+    let header = png.header()?;
+    check_supported(&header)?;
+
+    let available = capacity(png)?;
+    if payload.len() > available {
+        throw!(StegoError::InsufficientCapacity {
+            needed: payload.len(),
+            available
+        })
+    }
+
+    let mut image = png.decode_image()?;
+
+    let framed = MAGIC
+        .into_iter()
+        .chain((payload.len() as u32).to_be_bytes())
+        .chain(payload.iter().copied());
+    let bits = framed.flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+
+    for ((row, index), bit) in carriers(&header).zip(bits) {
+        let byte = &mut image.rows_mut()[row][index];
+        *byte = (*byte & !1) | bit;
+    }
+
+    let data = image.encode()?;
+    let mut idats = idat_chunks(&data);
+
+    let mut chunks = vec![];
+    for chunk in std::mem::replace(png, Png::from_chunks(vec![])).into_chunks() {
+        if chunk.chunk_type().bytes() != IMAGE_DATA {
+            chunks.push(chunk);
+        } else {
+            chunks.extend(&mut idats);
+        }
+    }
+
+    *png = Png::from_chunks(chunks);
+}
+
+/// Reads a payload hidden with [`embed`].
+#[throws(StegoError)]
+pub fn extract(png: &Png) -> Vec<u8> {
+    let header = png.header()?;
+    check_supported(&header)?;
+
+    let image = png.decode_image()?;
+    let mut bits = carriers(&header).map(|(row, index)| image.rows()[row][index] & 1);
+    let mut read_byte = || (0..8).try_fold(0u8, |byte, _| bits.next().map(|bit| (byte << 1) | bit));
+
+    let mut frame_header = [0; HEADER_LENGTH];
+    for byte in &mut frame_header {
+        *byte = read_byte().ok_or(StegoError::NoPayload)?;
+    }
+
+    if frame_header[..MAGIC.len()] != MAGIC {
+        throw!(StegoError::NoPayload)
+    }
+
+    let length = u32::from_be_bytes(frame_header[MAGIC.len()..].try_into().expect("4 bytes"));
+
+    (0..length)
+        .map(|_| read_byte().ok_or(StegoError::NoPayload))
+        .collect::<Result<Vec<u8>, _>>()?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType, ihdr::Interlace, image::Image};
+
+    /// An RGBA image using every filter type, with noisy pixel data.
+    fn testing_png(width: u32, height: u32) -> Png {
+        let header = Ihdr::new(width, height, 8, ColorType::Rgba, Interlace::None).unwrap();
+        let stride = width as usize * 4;
+
+        let rows: Vec<Vec<u8>> = (0..height as usize)
+            .map(|row| {
+                (0..stride)
+                    .map(|index| (row * 31 + index * 7 + index * index) as u8)
+                    .collect()
+            })
+            .collect();
+        let filters = (0..height as u8).map(|row| row % 5).collect();
+        let image = Image::new(header, rows, filters);
+
+        let idat =
+            |data: &[u8]| Chunk::new(ChunkType::try_from(IMAGE_DATA).unwrap(), data.to_vec());
+        let data = image.encode().unwrap();
+        let (first, second) = data.split_at(data.len() / 2);
+
+        Png::from_chunks(vec![
+            header.to_chunk(),
+            idat(first),
+            idat(second),
+            Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_capacity() {
+        // 3 colour samples per pixel, one bit each, minus the frame header
+        assert_eq!(capacity(&testing_png(16, 16)).unwrap(), 16 * 16 * 3 / 8 - 8);
+        assert!(pixels_needed(capacity(&testing_png(16, 16)).unwrap()) <= 16 * 16);
+        assert!(pixels_needed(capacity(&testing_png(16, 16)).unwrap() + 1) > 16 * 16);
+    }
+
+    #[test]
+    fn test_embed_extract_round_trip() {
+        let original = testing_png(16, 16);
+        let mut png = testing_png(16, 16);
+        embed(&mut png, b"This is a hidden message!").unwrap();
+
+        assert_eq!(extract(&png).unwrap(), b"This is a hidden message!");
+        assert_eq!(png.chunks().len(), 3);
+
+        let before = original.decode_image().unwrap();
+        let after = png.decode_image().unwrap();
+        assert_eq!(before.filters(), after.filters());
+
+        for (before, after) in before.rows().iter().zip(after.rows()) {
+            for (index, (before, after)) in before.iter().zip(after).enumerate() {
+                assert!(before.abs_diff(*after) <= 1);
+                if index % 4 == 3 {
+                    assert_eq!(before, after, "alpha must not change");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_embed_extract_interlaced() {
+        let rows = testing_png(16, 16).decode_image().unwrap().rows().to_vec();
+        let header = Ihdr::new(16, 16, 8, ColorType::Rgba, Interlace::Adam7).unwrap();
+        // A 16x16 image has 30 scanlines over the seven passes
+        let filters = (0..30).map(|index| index % 5).collect();
+        let data = Image::new(header, rows.clone(), filters).encode().unwrap();
+        let mut png = Png::from_chunks(vec![
+            header.to_chunk(),
+            Chunk::new(ChunkType::try_from(IMAGE_DATA).unwrap(), data),
+        ]);
+
+        embed(&mut png, b"interlaced").unwrap();
+
+        assert_eq!(extract(&png).unwrap(), b"interlaced");
+        assert!(png.is_interlaced().unwrap());
+        let after = png.decode_image().unwrap();
+        assert_eq!(after.filters().len(), 30);
+        for (before, after) in rows.iter().zip(after.rows()) {
+            assert!(before.iter().zip(after).all(|(a, b)| a.abs_diff(*b) <= 1));
+        }
+    }
+
+    #[test]
+    fn test_embed_insufficient_capacity() {
+        let mut png = testing_png(4, 4);
+
+        assert!(matches!(
+            embed(&mut png, &[0; 64]),
+            Err(StegoError::InsufficientCapacity {
+                needed: 64,
+                available: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_extract_without_payload() {
+        assert!(matches!(
+            extract(&testing_png(16, 16)),
+            Err(StegoError::NoPayload)
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_images() {
+        let header = Ihdr::new(4, 4, 8, ColorType::Indexed, Interlace::None).unwrap();
+        let png = Png::from_chunks(vec![header.to_chunk()]);
+
+        assert!(matches!(capacity(&png), Err(StegoError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,172 @@
+//! Proptest strategies for chunk types, chunks and whole pngs, valid and
+//! slightly broken, for property testing code built on this crate.
+
+use proptest::{collection::vec, prelude::*, sample::select};
+use std::str::FromStr;
+
+use crate::{builder::PngBuilder, chunk::Chunk, chunk_type::ChunkType, ihdr::ColorType, png::Png};
+
+/// Chunk data is kept short so cases stay fast.
+const MAX_DATA_LENGTH: usize = 256;
+
+/// Colour types the builder can make on its own, with their bit depths.
+const COLOR_FORMATS: [(ColorType, u8); 11] = [
+    (ColorType::Grayscale, 1),
+    (ColorType::Grayscale, 2),
+    (ColorType::Grayscale, 4),
+    (ColorType::Grayscale, 8),
+    (ColorType::Grayscale, 16),
+    (ColorType::Rgb, 8),
+    (ColorType::Rgb, 16),
+    (ColorType::GrayscaleAlpha, 8),
+    (ColorType::GrayscaleAlpha, 16),
+    (ColorType::Rgba, 8),
+    (ColorType::Rgba, 16),
+];
+
+/// Any valid chunk type: critical or ancillary, public or private.
+pub fn chunk_type() -> impl Strategy<Value = ChunkType> {
+    "[a-zA-Z]{2}[A-Z][a-zA-Z]".prop_map(|letters| ChunkType::from_str(&letters).unwrap())
+}
+
+/// Ancillary, private, safe-to-copy chunk types, which can go anywhere in a
+/// png without changing what it means.
+pub fn private_chunk_type() -> impl Strategy<Value = ChunkType> {
+    "[a-zA-Z]{4}".prop_map(|letters| ChunkType::new_private(&letters).unwrap())
+}
+
+/// Chunk types that parse but have the reserved bit set, so
+/// [`ChunkType::is_valid`] is false.
+pub fn reserved_chunk_type() -> impl Strategy<Value = ChunkType> {
+    "[a-zA-Z]{2}[a-z][a-zA-Z]".prop_map(|letters| ChunkType::from_str(&letters).unwrap())
+}
+
+/// Chunks with a valid type and up to 256 bytes of data.
+pub fn chunk() -> impl Strategy<Value = Chunk> {
+    (chunk_type(), vec(any::<u8>(), 0..=MAX_DATA_LENGTH))
+        .prop_map(|(chunk_type, data)| Chunk::new(chunk_type, data))
+}
+
+/// Chunks of private data, like the ones `pngme encode` writes.
+pub fn private_chunk() -> impl Strategy<Value = Chunk> {
+    (private_chunk_type(), vec(any::<u8>(), 0..=MAX_DATA_LENGTH))
+        .prop_map(|(chunk_type, data)| Chunk::new(chunk_type, data))
+}
+
+/// The bytes of a chunk whose stored crc doesn't match its contents.
+pub fn chunk_bytes_with_bad_crc() -> impl Strategy<Value = Vec<u8>> {
+    (chunk(), 1..=u32::MAX).prop_map(|(chunk, flip)| corrupt_crc(chunk.as_bytes(), flip))
+}
+
+/// Small pngs that decode: a header, image data for every supported colour
+/// type and bit depth except indexed, `IEND`, and a few private chunks.
+pub fn png() -> impl Strategy<Value = Png> {
+    (select(&COLOR_FORMATS[..]), 1..=16u32, 1..=16u32)
+        .prop_flat_map(|((color_type, bit_depth), width, height)| {
+            let bits_per_pixel = color_type.channels() as usize * bit_depth as usize;
+            let stride = (width as usize * bits_per_pixel).div_ceil(8);
+            let builder = PngBuilder::new(width, height)
+                .color_type(color_type)
+                .bit_depth(bit_depth);
+
+            (
+                Just(builder),
+                vec(any::<u8>(), stride * height as usize),
+                vec(private_chunk(), 0..4),
+            )
+        })
+        .prop_map(|(builder, pixels, chunks)| {
+            let mut png = builder.pixels(pixels).build().unwrap();
+            for chunk in chunks {
+                png.insert_chunk_ordered(chunk);
+            }
+            png
+        })
+}
+
+/// The bytes of a png from [`png`] with one chunk's crc broken, along with
+/// the offset of that chunk.
+pub fn png_bytes_with_bad_crc() -> impl Strategy<Value = (Vec<u8>, usize)> {
+    png()
+        .prop_flat_map(|png| {
+            let count = png.chunks().len();
+            (Just(png), 0..count, 1..=u32::MAX)
+        })
+        .prop_map(|(png, index, flip)| {
+            let offset = Png::STANDARD_HEADER.len()
+                + png.chunks()[..index]
+                    .iter()
+                    .map(|chunk| chunk.length() + 12)
+                    .sum::<usize>();
+            let end = offset + png.chunks()[index].length() + 12;
+
+            let mut bytes = png.as_bytes();
+            let chunk = corrupt_crc(bytes[offset..end].to_vec(), flip);
+            bytes[offset..end].copy_from_slice(&chunk);
+            (bytes, offset)
+        })
+}
+
+fn corrupt_crc(mut chunk: Vec<u8>, flip: u32) -> Vec<u8> {
+    let crc_start = chunk.len() - 4;
+    let crc = u32::from_be_bytes(chunk[crc_start..].try_into().expect("slice of length 4"));
+    chunk[crc_start..].copy_from_slice(&(crc ^ flip).to_be_bytes());
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkParseError,
+        png::{CrcMode, ParseOptions, PngParseError},
+    };
+
+    proptest! {
+        #[test]
+        fn test_chunk_types(valid in chunk_type(), reserved in reserved_chunk_type()) {
+            prop_assert!(valid.is_valid());
+            prop_assert_eq!(ChunkType::from_str(&valid.to_string()).unwrap(), valid);
+            prop_assert!(!reserved.is_valid());
+        }
+
+        #[test]
+        fn test_chunk_round_trip(chunk in chunk()) {
+            let bytes = chunk.as_bytes();
+            prop_assert_eq!(Chunk::try_from(bytes.as_slice()).unwrap(), chunk);
+        }
+
+        #[test]
+        fn test_bad_crc_chunk(bytes in chunk_bytes_with_bad_crc()) {
+            let bad_crc = matches!(
+                Chunk::try_from(bytes.as_slice()),
+                Err(ChunkParseError::InvalidCrc { .. })
+            );
+            prop_assert!(bad_crc);
+        }
+
+        #[test]
+        fn test_png_round_trip(png in png()) {
+            prop_assert!(png.validate().is_empty());
+            prop_assert!(png.decode_image().is_ok());
+            prop_assert_eq!(Png::try_from(png.as_bytes().as_slice()).unwrap(), png);
+        }
+
+        #[test]
+        fn test_bad_crc_png((bytes, offset) in png_bytes_with_bad_crc()) {
+            let bad_crc = matches!(
+                Png::try_from(bytes.as_slice()),
+                Err(PngParseError::ChunkParsingError(ChunkParseError::InvalidCrc { .. }))
+            );
+            prop_assert!(bad_crc);
+
+            let options = ParseOptions {
+                crc_mode: CrcMode::Warn,
+                ..ParseOptions::default()
+            };
+            let (_, warnings) = Png::parse(&bytes, options).unwrap();
+            prop_assert_eq!(warnings.len(), 1);
+            prop_assert_eq!(warnings[0].offset, offset);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::{self, CompressionError},
+};
+
+#[derive(Debug, Error)]
+pub enum TextChunkError {
+    #[error("expected a `{expected}` chunk but got `{actual}`")]
+    UnexpectedChunkType { expected: String, actual: String },
+
+    #[error("invalid keyword `{0}`, expected 1 to 79 printable latin-1 characters")]
+    InvalidKeyword(String),
+
+    #[error("character `{0}` can't be represented in latin-1")]
+    NotLatin1(char),
+
+    #[error("invalid language tag `{0}`")]
+    InvalidLanguageTag(String),
+
+    #[error("missing null separator")]
+    MissingSeparator,
+
+    #[error("invalid compression flag `{0}`")]
+    InvalidCompressionFlag(u8),
+
+    #[error("unknown compression method `{0}`")]
+    UnknownCompressionMethod(u8),
+
+    #[error("text is not valid utf8")]
+    InvalidUtf8(#[from] std::string::FromUtf8Error),
+
+    #[error("failed to (de)compress text")]
+    Compression(#[from] CompressionError),
+}
+
+const TEXT: [u8; 4] = *b"tEXt";
+const COMPRESSED_TEXT: [u8; 4] = *b"zTXt";
+const INTERNATIONAL_TEXT: [u8; 4] = *b"iTXt";
+
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+fn chunk_type(bytes: [u8; 4]) -> ChunkType {
+    ChunkType::try_from(bytes).expect("valid chunk type")
+}
+
+#[throws(TextChunkError)]
+fn expect_chunk_type(chunk: &Chunk, expected: [u8; 4]) {
+    if chunk.chunk_type().bytes() != expected {
+        throw!(TextChunkError::UnexpectedChunkType {
+            expected: chunk_type(expected).to_string(),
+            actual: chunk.chunk_type().to_string(),
+        })
+    }
+}
+
+#[throws(TextChunkError)]
+pub(crate) fn validate_keyword(keyword: &str) {
+    let is_valid = (1..=79).contains(&keyword.chars().count())
+        && !keyword.starts_with(' ')
+        && !keyword.ends_with(' ')
+        && !keyword.contains("  ")
+        && keyword
+            .chars()
+            .all(|c| (' '..='~').contains(&c) || ('\u{a1}'..='\u{ff}').contains(&c));
+
+    if !is_valid {
+        throw!(TextChunkError::InvalidKeyword(keyword.to_string()))
+    }
+}
+
+#[throws(TextChunkError)]
+pub(crate) fn encode_latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).map_err(|_| TextChunkError::NotLatin1(c)))
+        .collect::<Result<_, _>>()?
+}
+
+pub(crate) fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Splits `data` at the first null byte, dropping the separator.
+#[throws(TextChunkError)]
+fn split_null(data: &[u8]) -> (&[u8], &[u8]) {
+    let position = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(TextChunkError::MissingSeparator)?;
+
+    (&data[..position], &data[position + 1..])
+}
+
+/// Uncompressed latin-1 text stored in a `tEXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    keyword: String,
+    text: String,
+}
+
+impl TextChunk {
+    #[throws(TextChunkError)]
+    pub fn new(keyword: &str, text: &str) -> Self {
+        validate_keyword(keyword)?;
+        encode_latin1(text)?;
+
+        TextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [
+            encode_latin1(&self.keyword).expect("checked in constructor"),
+            vec![0],
+            encode_latin1(&self.text).expect("checked in constructor"),
+        ]
+        .concat();
+
+        Chunk::new(chunk_type(TEXT), data)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = TextChunkError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        expect_chunk_type(chunk, TEXT)?;
+
+        let (keyword, text) = split_null(chunk.data())?;
+
+        TextChunk {
+            keyword: decode_latin1(keyword),
+            text: decode_latin1(text),
+        }
+    }
+}
+
+/// Zlib compressed latin-1 text stored in a `zTXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    keyword: String,
+    text: String,
+}
+
+impl CompressedTextChunk {
+    #[throws(TextChunkError)]
+    pub fn new(keyword: &str, text: &str) -> Self {
+        validate_keyword(keyword)?;
+        encode_latin1(text)?;
+
+        CompressedTextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[throws(TextChunkError)]
+    pub fn to_chunk(&self) -> Chunk {
+        let text = encode_latin1(&self.text).expect("checked in constructor");
+
+        let data = [
+            encode_latin1(&self.keyword).expect("checked in constructor"),
+            vec![0, COMPRESSION_METHOD_DEFLATE],
+            compression::deflate(&text)?,
+        ]
+        .concat();
+
+        Chunk::new(chunk_type(COMPRESSED_TEXT), data)
+    }
+}
+
+impl TryFrom<&Chunk> for CompressedTextChunk {
+    type Error = TextChunkError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        expect_chunk_type(chunk, COMPRESSED_TEXT)?;
+
+        let (keyword, rest) = split_null(chunk.data())?;
+        let (&method, compressed) = rest.split_first().ok_or(TextChunkError::MissingSeparator)?;
+
+        if method != COMPRESSION_METHOD_DEFLATE {
+            throw!(TextChunkError::UnknownCompressionMethod(method))
+        }
+
+        CompressedTextChunk {
+            keyword: decode_latin1(keyword),
+            text: decode_latin1(&compression::inflate(compressed)?),
+        }
+    }
+}
+
+/// Utf8 text with optional language information stored in an `iTXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    keyword: String,
+    compressed: bool,
+    language_tag: String,
+    translated_keyword: String,
+    text: String,
+}
+
+impl InternationalTextChunk {
+    #[throws(TextChunkError)]
+    pub fn new(keyword: &str, text: &str) -> Self {
+        validate_keyword(keyword)?;
+
+        InternationalTextChunk {
+            keyword: keyword.to_string(),
+            compressed: false,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    #[throws(TextChunkError)]
+    pub fn with_language(mut self, language_tag: &str, translated_keyword: &str) -> Self {
+        let is_valid = language_tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+        if !is_valid {
+            throw!(TextChunkError::InvalidLanguageTag(language_tag.to_string()))
+        }
+
+        self.language_tag = language_tag.to_string();
+        self.translated_keyword = translated_keyword.to_string();
+        self
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[throws(TextChunkError)]
+    pub fn to_chunk(&self) -> Chunk {
+        let text = if self.compressed {
+            compression::deflate(self.text.as_bytes())?
+        } else {
+            self.text.as_bytes().to_vec()
+        };
+
+        let data = [
+            encode_latin1(&self.keyword).expect("checked in constructor"),
+            vec![0, self.compressed as u8, COMPRESSION_METHOD_DEFLATE],
+            self.language_tag.as_bytes().to_vec(),
+            vec![0],
+            self.translated_keyword.as_bytes().to_vec(),
+            vec![0],
+            text,
+        ]
+        .concat();
+
+        Chunk::new(chunk_type(INTERNATIONAL_TEXT), data)
+    }
+}
+
+impl TryFrom<&Chunk> for InternationalTextChunk {
+    type Error = TextChunkError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        expect_chunk_type(chunk, INTERNATIONAL_TEXT)?;
+
+        let (keyword, rest) = split_null(chunk.data())?;
+
+        let (flag, method, rest) = match rest {
+            [flag, method, rest @ ..] => (*flag, *method, rest),
+            _ => throw!(TextChunkError::MissingSeparator),
+        };
+
+        let compressed = match flag {
+            0 => false,
+            1 => true,
+            _ => throw!(TextChunkError::InvalidCompressionFlag(flag)),
+        };
+
+        if compressed && method != COMPRESSION_METHOD_DEFLATE {
+            throw!(TextChunkError::UnknownCompressionMethod(method))
+        }
+
+        let (language_tag, rest) = split_null(rest)?;
+        let (translated_keyword, text) = split_null(rest)?;
+
+        let text = if compressed {
+            compression::inflate(text)?
+        } else {
+            text.to_vec()
+        };
+
+        InternationalTextChunk {
+            keyword: decode_latin1(keyword),
+            compressed,
+            language_tag: decode_latin1(language_tag),
+            translated_keyword: String::from_utf8(translated_keyword.to_vec())?,
+            text: String::from_utf8(text)?,
+        }
+    }
+}
+
+/// Any of the three textual chunk kinds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextualChunk {
+    Text(TextChunk),
+    Compressed(CompressedTextChunk),
+    International(InternationalTextChunk),
+}
+
+impl TextualChunk {
+    pub fn is_textual(chunk: &Chunk) -> bool {
+        [TEXT, COMPRESSED_TEXT, INTERNATIONAL_TEXT].contains(&chunk.chunk_type().bytes())
+    }
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextualChunk::Text(chunk) => chunk.keyword(),
+            TextualChunk::Compressed(chunk) => chunk.keyword(),
+            TextualChunk::International(chunk) => chunk.keyword(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TextualChunk::Text(chunk) => chunk.text(),
+            TextualChunk::Compressed(chunk) => chunk.text(),
+            TextualChunk::International(chunk) => chunk.text(),
+        }
+    }
+
+    #[throws(TextChunkError)]
+    pub fn to_chunk(&self) -> Chunk {
+        match self {
+            TextualChunk::Text(chunk) => chunk.to_chunk(),
+            TextualChunk::Compressed(chunk) => chunk.to_chunk()?,
+            TextualChunk::International(chunk) => chunk.to_chunk()?,
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for TextualChunk {
+    type Error = TextChunkError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        match chunk.chunk_type().bytes() {
+            COMPRESSED_TEXT => TextualChunk::Compressed(chunk.try_into()?),
+            INTERNATIONAL_TEXT => TextualChunk::International(chunk.try_into()?),
+            _ => TextualChunk::Text(chunk.try_into()?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunk_round_trip() {
+        let text = TextChunk::new("Title", "Café at dawn").unwrap();
+        let chunk = text.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Title\0Caf\xe9 at dawn");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_text_chunk_rejects_non_latin1() {
+        assert!(matches!(
+            TextChunk::new("Title", "日本"),
+            Err(TextChunkError::NotLatin1('日'))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        assert!(TextChunk::new("", "text").is_err());
+        assert!(TextChunk::new(" Title", "text").is_err());
+        assert!(TextChunk::new("Two  spaces", "text").is_err());
+        assert!(TextChunk::new(&"k".repeat(80), "text").is_err());
+    }
+
+    #[test]
+    fn test_compressed_text_chunk_round_trip() {
+        let text = CompressedTextChunk::new("Comment", &"lorem ipsum ".repeat(20)).unwrap();
+        let chunk = text.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+        assert!(chunk.length() < 240);
+        assert_eq!(CompressedTextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_text_chunk_round_trip() {
+        let text = InternationalTextChunk::new("Title", "夜明けのカフェ")
+            .unwrap()
+            .with_language("ja", "タイトル")
+            .unwrap();
+        let chunk = text.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+        assert_eq!(InternationalTextChunk::try_from(&chunk).unwrap(), text);
+
+        let compressed = text.with_compression(true);
+        let chunk = compressed.to_chunk().unwrap();
+        assert_eq!(
+            InternationalTextChunk::try_from(&chunk).unwrap(),
+            compressed
+        );
+    }
+
+    #[test]
+    fn test_textual_chunk_from_chunk() {
+        let chunk = CompressedTextChunk::new("Comment", "hello")
+            .unwrap()
+            .to_chunk()
+            .unwrap();
+
+        assert!(TextualChunk::is_textual(&chunk));
+
+        let text = TextualChunk::try_from(&chunk).unwrap();
+        assert_eq!(text.keyword(), "Comment");
+        assert_eq!(text.text(), "hello");
+    }
+
+    #[test]
+    fn test_wrong_chunk_type() {
+        let chunk = Chunk::new(chunk_type(*b"ruSt"), b"Title\0text".to_vec());
+
+        assert!(matches!(
+            TextChunk::try_from(&chunk),
+            Err(TextChunkError::UnexpectedChunkType { .. })
+        ));
+    }
+}
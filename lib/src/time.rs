@@ -0,0 +1,221 @@
+use fehler::{throw, throws};
+use std::fmt::Display;
+use thiserror::Error;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Debug, Error)]
+pub enum TimeError {
+    #[error("expected a `tIME` chunk but got `{0}`")]
+    UnexpectedChunkType(String),
+
+    #[error("tIME chunk data must be 7 bytes, got {0}")]
+    InvalidLength(usize),
+
+    #[error("invalid modification time")]
+    InvalidTime,
+}
+
+const LAST_MODIFICATION_TIME: [u8; 4] = *b"tIME";
+
+const DATA_LENGTH: usize = 7;
+
+/// The last modification time of the image, stored in a `tIME` chunk as UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeChunk {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl TimeChunk {
+    /// `second` may be 60 to allow for leap seconds, as the png spec does.
+    #[throws(TimeError)]
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            throw!(TimeError::InvalidTime)
+        }
+
+        TimeChunk {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [
+            &self.year.to_be_bytes()[..],
+            &[self.month, self.day, self.hour, self.minute, self.second],
+        ]
+        .concat();
+
+        let chunk_type = ChunkType::try_from(LAST_MODIFICATION_TIME).expect("valid chunk type");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for TimeChunk {
+    type Error = TimeError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        if chunk.chunk_type().bytes() != LAST_MODIFICATION_TIME {
+            throw!(TimeError::UnexpectedChunkType(
+                chunk.chunk_type().to_string()
+            ))
+        }
+
+        let data = chunk.data();
+        if data.len() != DATA_LENGTH {
+            throw!(TimeError::InvalidLength(data.len()))
+        }
+
+        TimeChunk::new(
+            u16::from_be_bytes([data[0], data[1]]),
+            data[2],
+            data[3],
+            data[4],
+            data[5],
+            data[6],
+        )?
+    }
+}
+
+impl Display for TimeChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<::time::OffsetDateTime> for TimeChunk {
+    fn from(datetime: ::time::OffsetDateTime) -> Self {
+        let datetime = datetime.to_offset(::time::UtcOffset::UTC);
+
+        TimeChunk {
+            year: datetime.year().clamp(0, u16::MAX.into()) as u16,
+            month: datetime.month().into(),
+            day: datetime.day(),
+            hour: datetime.hour(),
+            minute: datetime.minute(),
+            second: datetime.second(),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<TimeChunk> for ::time::OffsetDateTime {
+    type Error = TimeError;
+
+    /// Fails for leap seconds and dates that don't exist, which `tIME` can hold.
+    #[throws(Self::Error)]
+    fn try_from(time: TimeChunk) -> Self {
+        let month = ::time::Month::try_from(time.month).map_err(|_| TimeError::InvalidTime)?;
+        let date = ::time::Date::from_calendar_date(time.year.into(), month, time.day)
+            .map_err(|_| TimeError::InvalidTime)?;
+        let clock = ::time::Time::from_hms(time.hour, time.minute, time.second)
+            .map_err(|_| TimeError::InvalidTime)?;
+
+        ::time::PrimitiveDateTime::new(date, clock).assume_utc()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_chunk_round_trip() {
+        let time = TimeChunk::new(2022, 9, 4, 13, 37, 5).unwrap();
+        let chunk = time.to_chunk();
+
+        assert_eq!(chunk.chunk_type().to_string(), "tIME");
+        assert_eq!(chunk.data(), &[0x07, 0xE6, 9, 4, 13, 37, 5]);
+        assert_eq!(TimeChunk::try_from(&chunk).unwrap(), time);
+    }
+
+    #[test]
+    fn test_time_chunk_display() {
+        let time = TimeChunk::new(2022, 9, 4, 13, 37, 5).unwrap();
+        assert_eq!(time.to_string(), "2022-09-04T13:37:05Z");
+    }
+
+    #[test]
+    fn test_time_chunk_rejects_invalid_time() {
+        assert!(matches!(
+            TimeChunk::new(2022, 13, 4, 13, 37, 5),
+            Err(TimeError::InvalidTime)
+        ));
+        assert!(matches!(
+            TimeChunk::new(2022, 9, 4, 24, 37, 5),
+            Err(TimeError::InvalidTime)
+        ));
+    }
+
+    #[test]
+    fn test_time_chunk_invalid_length() {
+        let chunk_type = ChunkType::try_from(LAST_MODIFICATION_TIME).unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0; 6]);
+
+        assert!(matches!(
+            TimeChunk::try_from(&chunk),
+            Err(TimeError::InvalidLength(6))
+        ));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_time_chunk_offset_date_time_conversion() {
+        use ::time::{macros::datetime, OffsetDateTime};
+
+        let time = TimeChunk::from(datetime!(2022-09-04 15:37:05 +2));
+        assert_eq!(time, TimeChunk::new(2022, 9, 4, 13, 37, 5).unwrap());
+        assert_eq!(
+            OffsetDateTime::try_from(time).unwrap(),
+            datetime!(2022-09-04 13:37:05 UTC)
+        );
+
+        let leap_second = TimeChunk::new(2016, 12, 31, 23, 59, 60).unwrap();
+        assert!(OffsetDateTime::try_from(leap_second).is_err());
+    }
+}
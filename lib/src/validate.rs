@@ -0,0 +1,460 @@
+use std::fmt::Display;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::{ChunkCategory, ChunkType},
+    png::{self, Png},
+};
+
+/// Largest chunk length allowed by the png spec.
+pub const MAX_CHUNK_LENGTH: u32 = i32::MAX as u32;
+
+/// Chunks whose data always has the same length.
+const FIXED_LENGTHS: [(&[u8; 4], usize); 7] = [
+    (b"IHDR", 13),
+    (b"IEND", 0),
+    (b"gAMA", 4),
+    (b"cHRM", 32),
+    (b"sRGB", 1),
+    (b"pHYs", 9),
+    (b"tIME", 7),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    InvalidSignature,
+    Truncated,
+    LengthTooLarge {
+        length: u32,
+    },
+    InvalidChunkType {
+        chunk_type: [u8; 4],
+    },
+    ReservedBitSet {
+        chunk_type: String,
+    },
+    InvalidCrc {
+        chunk_type: String,
+        stored: u32,
+        calculated: u32,
+    },
+    UnexpectedLength {
+        chunk_type: String,
+        length: usize,
+        expected: usize,
+    },
+    FirstChunkNotIhdr {
+        chunk_type: String,
+    },
+    MissingChunk {
+        chunk_type: String,
+    },
+    Duplicate {
+        chunk_type: String,
+    },
+    Misplaced {
+        chunk_type: String,
+        after: String,
+    },
+    NonConsecutiveIdat,
+    DataAfterIend {
+        length: usize,
+    },
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticKind::InvalidSignature => write!(f, "invalid png signature"),
+            DiagnosticKind::Truncated => write!(f, "file ends in the middle of a chunk"),
+            DiagnosticKind::LengthTooLarge { length } => {
+                write!(f, "chunk length {} exceeds 2^31 - 1", length)
+            }
+            DiagnosticKind::InvalidChunkType { chunk_type } => {
+                write!(f, "invalid chunk type {:?}", chunk_type)
+            }
+            DiagnosticKind::ReservedBitSet { chunk_type } => {
+                write!(f, "`{}` has the reserved bit set", chunk_type)
+            }
+            DiagnosticKind::InvalidCrc {
+                chunk_type,
+                stored,
+                calculated,
+            } => write!(
+                f,
+                "`{}` has crc {:#010x}, expected {:#010x}",
+                chunk_type, stored, calculated
+            ),
+            DiagnosticKind::UnexpectedLength {
+                chunk_type,
+                length,
+                expected,
+            } => write!(
+                f,
+                "`{}` is {} bytes, expected {}",
+                chunk_type, length, expected
+            ),
+            DiagnosticKind::FirstChunkNotIhdr { chunk_type } => {
+                write!(f, "first chunk is `{}`, expected `IHDR`", chunk_type)
+            }
+            DiagnosticKind::MissingChunk { chunk_type } => {
+                write!(f, "missing `{}` chunk", chunk_type)
+            }
+            DiagnosticKind::Duplicate { chunk_type } => {
+                write!(f, "`{}` must not appear more than once", chunk_type)
+            }
+            DiagnosticKind::Misplaced { chunk_type, after } => {
+                write!(f, "`{}` must come before `{}`", chunk_type, after)
+            }
+            DiagnosticKind::NonConsecutiveIdat => write!(f, "`IDAT` chunks are not consecutive"),
+            DiagnosticKind::DataAfterIend { length } => {
+                write!(f, "{} bytes of data after `IEND`", length)
+            }
+        }
+    }
+}
+
+/// A single violation of the png spec, found at `offset` bytes into the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub offset: usize,
+    pub kind: DiagnosticKind,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.kind)
+    }
+}
+
+fn type_name(chunk_type: &[u8; 4]) -> String {
+    String::from_utf8_lossy(chunk_type).into_owned()
+}
+
+/// Checks shared by the byte and chunk validators, applied to each chunk in
+/// file order.
+#[derive(Default)]
+struct Checker {
+    seen: Vec<[u8; 4]>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn report(&mut self, offset: usize, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic { offset, kind });
+    }
+
+    /// Checks the reserved bit and crc of a chunk.
+    fn check_chunk(&mut self, offset: usize, chunk: &Chunk) {
+        let chunk_type = chunk.chunk_type();
+
+        if chunk_type.category() == ChunkCategory::Invalid {
+            self.report(
+                offset,
+                DiagnosticKind::ReservedBitSet {
+                    chunk_type: chunk_type.to_string(),
+                },
+            );
+        }
+
+        let calculated = Chunk::calculate_crc(chunk_type, chunk.data());
+        if chunk.crc() != calculated {
+            self.report(
+                offset,
+                DiagnosticKind::InvalidCrc {
+                    chunk_type: chunk_type.to_string(),
+                    stored: chunk.crc(),
+                    calculated,
+                },
+            );
+        }
+    }
+
+    /// Checks the length and placement of a chunk against the chunks before it.
+    fn check_layout(&mut self, offset: usize, chunk_type: [u8; 4], length: usize) {
+        if self.seen.is_empty() && &chunk_type != b"IHDR" {
+            self.report(
+                offset,
+                DiagnosticKind::FirstChunkNotIhdr {
+                    chunk_type: type_name(&chunk_type),
+                },
+            );
+        }
+
+        if let Some((_, expected)) = FIXED_LENGTHS
+            .iter()
+            .find(|(fixed, _)| **fixed == chunk_type)
+        {
+            if length != *expected {
+                self.report(
+                    offset,
+                    DiagnosticKind::UnexpectedLength {
+                        chunk_type: type_name(&chunk_type),
+                        length,
+                        expected: *expected,
+                    },
+                );
+            }
+        }
+
+        if png::UNIQUE_CHUNKS.contains(&chunk_type) && self.seen.contains(&chunk_type) {
+            self.report(
+                offset,
+                DiagnosticKind::Duplicate {
+                    chunk_type: type_name(&chunk_type),
+                },
+            );
+        }
+
+        if let Some(after) = self
+            .seen
+            .iter()
+            .find(|seen| **seen != chunk_type && png::must_precede(&chunk_type).contains(seen))
+        {
+            let after = type_name(after);
+            self.report(
+                offset,
+                DiagnosticKind::Misplaced {
+                    chunk_type: type_name(&chunk_type),
+                    after,
+                },
+            );
+        }
+
+        if &chunk_type == b"IDAT"
+            && self.seen.contains(b"IDAT")
+            && self.seen.last() != Some(b"IDAT")
+        {
+            self.report(offset, DiagnosticKind::NonConsecutiveIdat);
+        }
+
+        self.seen.push(chunk_type);
+    }
+
+    /// Checks for required chunks that never appeared.
+    fn finish(mut self, offset: usize) -> Vec<Diagnostic> {
+        for required in [b"IHDR", b"IDAT", b"IEND"] {
+            if !self.seen.contains(required) {
+                self.report(
+                    offset,
+                    DiagnosticKind::MissingChunk {
+                        chunk_type: type_name(required),
+                    },
+                );
+            }
+        }
+
+        self.diagnostics
+    }
+}
+
+/// Checks the structure of a png file, returning every violation found.
+/// An empty list means the file is structurally valid.
+pub fn validate(bytes: &[u8]) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        checker.report(0, DiagnosticKind::InvalidSignature);
+        return checker.diagnostics;
+    }
+
+    let mut index = Png::STANDARD_HEADER.len();
+
+    while index < bytes.len() {
+        if checker.seen.last() == Some(b"IEND") {
+            checker.report(
+                index,
+                DiagnosticKind::DataAfterIend {
+                    length: bytes.len() - index,
+                },
+            );
+            break;
+        }
+
+        let Some(header) = bytes.get(index..index + 8) else {
+            checker.report(index, DiagnosticKind::Truncated);
+            break;
+        };
+
+        let length = u32::from_be_bytes(header[..4].try_into().expect("slice of length 4"));
+        let chunk_type: [u8; 4] = header[4..].try_into().expect("slice of length 4");
+
+        if length > MAX_CHUNK_LENGTH {
+            checker.report(index, DiagnosticKind::LengthTooLarge { length });
+            break;
+        }
+
+        let Some(raw_chunk) = bytes.get(index..index + length as usize + 12) else {
+            checker.report(index, DiagnosticKind::Truncated);
+            break;
+        };
+
+        match ChunkType::try_from(chunk_type) {
+            Err(_) => checker.report(index, DiagnosticKind::InvalidChunkType { chunk_type }),
+            Ok(_) => {
+                let chunk = Chunk::parse(raw_chunk, false).expect("length and type are valid");
+                checker.check_chunk(index, &chunk);
+            }
+        }
+
+        checker.check_layout(index, chunk_type, length as usize);
+        index += raw_chunk.len();
+    }
+
+    checker.finish(bytes.len())
+}
+
+/// Checks the chunks of an already parsed png. Offsets are those the chunks
+/// would have once the png is written out.
+pub fn validate_chunks(chunks: &[Chunk]) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+    let mut index = Png::STANDARD_HEADER.len();
+
+    for chunk in chunks {
+        checker.check_chunk(index, chunk);
+        checker.check_layout(index, chunk.chunk_type().bytes(), chunk.data().len());
+        index += chunk.data().len() + 12;
+    }
+
+    checker.finish(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(chunks: &[Chunk]) -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|chunk| chunk.as_bytes()))
+            .collect()
+    }
+
+    fn valid_chunks() -> Vec<Chunk> {
+        vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IDAT", b"data"),
+            chunk("IDAT", b"data"),
+            chunk("tEXt", b"Title\0hello"),
+            chunk("IEND", b""),
+        ]
+    }
+
+    fn kinds(bytes: &[u8]) -> Vec<DiagnosticKind> {
+        validate(bytes).into_iter().map(|diag| diag.kind).collect()
+    }
+
+    #[test]
+    fn test_validate_valid_png() {
+        assert_eq!(validate(&png_bytes(&valid_chunks())), vec![]);
+    }
+
+    #[test]
+    fn test_png_validate() {
+        assert!(Png::from_chunks(valid_chunks()).validate().is_empty());
+
+        let mut chunks = valid_chunks();
+        chunks.swap(0, 1);
+        chunks.insert(2, chunk("tIME", &[0; 7]));
+        chunks.insert(2, chunk("tIME", &[0; 7]));
+        chunks.insert(2, chunk("abcd", b""));
+        let png = Png::from_chunks(chunks);
+
+        let kinds: Vec<DiagnosticKind> = png.validate().into_iter().map(|d| d.kind).collect();
+        assert_eq!(kinds, self::kinds(&png.as_bytes()));
+        assert!(kinds.contains(&DiagnosticKind::FirstChunkNotIhdr {
+            chunk_type: "gAMA".to_string()
+        }));
+        assert!(kinds.contains(&DiagnosticKind::Duplicate {
+            chunk_type: "tIME".to_string()
+        }));
+        assert!(kinds.contains(&DiagnosticKind::ReservedBitSet {
+            chunk_type: "abcd".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_validate_invalid_signature() {
+        assert_eq!(kinds(b"not a png"), vec![DiagnosticKind::InvalidSignature]);
+    }
+
+    #[test]
+    fn test_validate_bad_crc_offset() {
+        let mut bytes = png_bytes(&valid_chunks());
+        let gama_offset = 8 + 12 + 13;
+        bytes[gama_offset + 12 + 4 - 1] ^= 0xFF;
+
+        let diagnostics = validate(&bytes);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].offset, gama_offset);
+        assert!(matches!(
+            diagnostics[0].kind,
+            DiagnosticKind::InvalidCrc { .. }
+        ));
+    }
+
+    #[test]
+    fn test_validate_ordering() {
+        let mut chunks = valid_chunks();
+        let gama = chunks.remove(1);
+        chunks.insert(2, gama);
+        chunks.insert(0, chunk("tEXt", b"a\0b"));
+
+        let kinds = kinds(&png_bytes(&chunks));
+        assert!(kinds.contains(&DiagnosticKind::FirstChunkNotIhdr {
+            chunk_type: "tEXt".to_string()
+        }));
+        assert!(kinds.contains(&DiagnosticKind::Misplaced {
+            chunk_type: "gAMA".to_string(),
+            after: "IDAT".to_string()
+        }));
+        assert!(kinds.contains(&DiagnosticKind::NonConsecutiveIdat));
+    }
+
+    #[test]
+    fn test_validate_duplicates_and_lengths() {
+        let mut chunks = valid_chunks();
+        chunks.insert(1, chunk("gAMA", &[0, 0, 1]));
+
+        let kinds = kinds(&png_bytes(&chunks));
+        assert!(kinds.contains(&DiagnosticKind::UnexpectedLength {
+            chunk_type: "gAMA".to_string(),
+            length: 3,
+            expected: 4
+        }));
+        assert!(kinds.contains(&DiagnosticKind::Duplicate {
+            chunk_type: "gAMA".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_validate_trailing_data_and_truncation() {
+        let mut bytes = png_bytes(&valid_chunks());
+        bytes.extend_from_slice(b"garbage");
+        assert_eq!(
+            kinds(&bytes),
+            vec![DiagnosticKind::DataAfterIend { length: 7 }]
+        );
+
+        let bytes = png_bytes(&valid_chunks()[..3]);
+        let truncated = &bytes[..bytes.len() - 2];
+        assert_eq!(
+            kinds(truncated),
+            vec![
+                DiagnosticKind::Truncated,
+                DiagnosticKind::MissingChunk {
+                    chunk_type: "IDAT".to_string()
+                },
+                DiagnosticKind::MissingChunk {
+                    chunk_type: "IEND".to_string()
+                },
+            ]
+        );
+    }
+}
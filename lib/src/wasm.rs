@@ -0,0 +1,100 @@
+//! JavaScript bindings, built with `cargo rustc --target wasm32-unknown-unknown
+//! --features wasm --crate-type cdylib`. The zstd dependency compiles C code,
+//! so clang has to be installed.
+//!
+//! Pngs are passed in and out as `Uint8Array`s and errors are thrown as
+//! JavaScript `Error`s.
+
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, compression, png::Png};
+
+/// Image header and chunk count of a png, as returned by [`parse`].
+#[wasm_bindgen]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    #[wasm_bindgen(js_name = bitDepth)]
+    pub bit_depth: u8,
+    #[wasm_bindgen(getter_with_clone, js_name = colorType)]
+    pub color_type: String,
+    #[wasm_bindgen(getter_with_clone)]
+    pub interlace: String,
+    pub chunks: usize,
+}
+
+/// A single chunk, as returned by [`list_chunks`].
+#[wasm_bindgen]
+pub struct ChunkInfo {
+    #[wasm_bindgen(getter_with_clone, js_name = chunkType)]
+    pub chunk_type: String,
+    pub length: usize,
+    pub crc: u32,
+    #[wasm_bindgen(getter_with_clone)]
+    pub data: Vec<u8>,
+}
+
+/// Parses `png`, returning its image header.
+#[wasm_bindgen]
+pub fn parse(png: &[u8]) -> Result<ImageInfo, JsError> {
+    let png = Png::try_from(png)?;
+    let header = png.header()?;
+
+    Ok(ImageInfo {
+        width: header.width(),
+        height: header.height(),
+        bit_depth: header.bit_depth(),
+        color_type: header.color_type().to_string(),
+        interlace: header.interlace().to_string(),
+        chunks: png.chunks().len(),
+    })
+}
+
+/// Returns `png` with `message` stored in a new chunk of type `chunk_type`.
+#[wasm_bindgen(js_name = encodeMessage)]
+pub fn encode_message(png: &[u8], chunk_type: &str, message: &str) -> Result<Vec<u8>, JsError> {
+    let mut png = Png::try_from(png)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+
+    png.insert_chunk_ordered(Chunk::new(chunk_type, message.as_bytes().to_vec()));
+
+    Ok(png.as_bytes())
+}
+
+/// Returns the message in the first chunk of type `chunk_type`, decompressed
+/// if needed, or `undefined` if there is no such chunk.
+#[wasm_bindgen(js_name = decodeMessage)]
+pub fn decode_message(png: &[u8], chunk_type: &str) -> Result<Option<String>, JsError> {
+    let png = Png::try_from(png)?;
+    ChunkType::from_str(chunk_type)?;
+
+    let Some(chunk) = png.chunk_by_type(chunk_type) else {
+        return Ok(None);
+    };
+
+    let data = match compression::is_compressed(chunk.data()) {
+        true => compression::decompress(chunk.data())?,
+        false => chunk.data().to_vec(),
+    };
+
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+/// Returns every chunk of `png`, in file order.
+#[wasm_bindgen(js_name = listChunks)]
+pub fn list_chunks(png: &[u8]) -> Result<Vec<ChunkInfo>, JsError> {
+    let png = Png::try_from(png)?;
+
+    Ok(png
+        .chunks()
+        .iter()
+        .map(|chunk| ChunkInfo {
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            data: chunk.data().to_vec(),
+        })
+        .collect())
+}
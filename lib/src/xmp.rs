@@ -0,0 +1,165 @@
+use fehler::{throw, throws};
+use thiserror::Error;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::consts,
+    text::{InternationalTextChunk, TextChunkError},
+};
+
+#[derive(Debug, Error)]
+pub enum XmpError {
+    #[error("invalid iTXt chunk")]
+    InvalidText(#[from] TextChunkError),
+
+    #[error("iTXt chunk keyword is `{0}`, not `{XMP_KEYWORD}`")]
+    NotXmp(String),
+
+    #[error("xmp packets must be stored uncompressed")]
+    Compressed,
+
+    #[error("xmp packets must have an empty language tag and translated keyword")]
+    HasLanguage,
+
+    #[error("xmp packet is not xml")]
+    InvalidPacket,
+}
+
+/// Keyword of the iTXt chunk holding the xmp packet, per the xmp specification.
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+/// An xmp packet stored in an uncompressed `iTXt` chunk with an empty
+/// language tag, as required by part 3 of the xmp specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmpChunk {
+    packet: String,
+}
+
+impl XmpChunk {
+    #[throws(XmpError)]
+    pub fn new(packet: &str) -> Self {
+        if !packet
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .starts_with('<')
+        {
+            throw!(XmpError::InvalidPacket)
+        }
+
+        XmpChunk {
+            packet: packet.to_string(),
+        }
+    }
+
+    /// Whether `chunk` is an iTXt chunk with the xmp keyword.
+    pub fn is_xmp(chunk: &Chunk) -> bool {
+        *chunk.chunk_type() == consts::iTXt
+            && chunk
+                .data()
+                .strip_prefix(XMP_KEYWORD.as_bytes())
+                .is_some_and(|rest| rest.first() == Some(&0))
+    }
+
+    pub fn packet(&self) -> &str {
+        &self.packet
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        InternationalTextChunk::new(XMP_KEYWORD, &self.packet)
+            .expect("valid keyword")
+            .to_chunk()
+            .expect("uncompressed text never fails")
+    }
+}
+
+impl TryFrom<&Chunk> for XmpChunk {
+    type Error = XmpError;
+
+    #[throws(Self::Error)]
+    fn try_from(chunk: &Chunk) -> Self {
+        let text = InternationalTextChunk::try_from(chunk)?;
+
+        if text.keyword() != XMP_KEYWORD {
+            throw!(XmpError::NotXmp(text.keyword().to_string()))
+        }
+
+        if text.is_compressed() {
+            throw!(XmpError::Compressed)
+        }
+
+        if !text.language_tag().is_empty() || !text.translated_keyword().is_empty() {
+            throw!(XmpError::HasLanguage)
+        }
+
+        XmpChunk::new(text.text())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF/></x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn test_xmp_chunk_round_trip() {
+        let xmp = XmpChunk::new(PACKET).unwrap();
+        let chunk = xmp.to_chunk();
+
+        assert!(XmpChunk::is_xmp(&chunk));
+        assert_eq!(XmpChunk::try_from(&chunk).unwrap(), xmp);
+    }
+
+    #[test]
+    fn test_xmp_rejects_non_xml() {
+        assert!(matches!(
+            XmpChunk::new("hello"),
+            Err(XmpError::InvalidPacket)
+        ));
+    }
+
+    #[test]
+    fn test_xmp_rejects_compressed() {
+        let chunk = InternationalTextChunk::new(XMP_KEYWORD, PACKET)
+            .unwrap()
+            .with_compression(true)
+            .to_chunk()
+            .unwrap();
+
+        assert!(matches!(
+            XmpChunk::try_from(&chunk),
+            Err(XmpError::Compressed)
+        ));
+    }
+
+    #[test]
+    fn test_xmp_rejects_language() {
+        let chunk = InternationalTextChunk::new(XMP_KEYWORD, PACKET)
+            .unwrap()
+            .with_language("en", "")
+            .unwrap()
+            .to_chunk()
+            .unwrap();
+
+        assert!(matches!(
+            XmpChunk::try_from(&chunk),
+            Err(XmpError::HasLanguage)
+        ));
+    }
+
+    #[test]
+    fn test_xmp_rejects_other_keyword() {
+        let chunk = InternationalTextChunk::new("Title", PACKET)
+            .unwrap()
+            .to_chunk()
+            .unwrap();
+
+        assert!(!XmpChunk::is_xmp(&chunk));
+        assert!(matches!(
+            XmpChunk::try_from(&chunk),
+            Err(XmpError::NotXmp(_))
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Context;
+use fehler::{throw, throws};
+use pngme_lib::{
+    ancillary::{BackgroundChunk, HistogramChunk, SignificantBits},
+    palette::PaletteChunk,
+};
+use tracing::warn;
+
+use crate::{
+    cli::{Ancillary, AncillarySet, AncillaryShow},
+    error::{invalid_arguments, not_found},
+    output::{outln, Snapshot},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, resolve_output_path, save_png_or_preview,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Ancillary) {
+    match command {
+        Ancillary::Show(args) => show(args),
+        Ancillary::Set(args) => set(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn show(args: AncillaryShow) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let header = png.header().context("invalid image header")?;
+
+    let mut found = false;
+
+    if let Some(chunk) = png.chunk_by_type("sBIT") {
+        let sbit = SignificantBits::parse(chunk, &header).context("invalid sBIT chunk")?;
+        outln!("sBIT: {}", sbit);
+        found = true;
+    }
+
+    if let Some(chunk) = png.chunk_by_type("bKGD") {
+        let background =
+            BackgroundChunk::parse(chunk, header.color_type()).context("invalid bKGD chunk")?;
+        outln!("bKGD: {}", background);
+        found = true;
+    }
+
+    if let Some(chunk) = png.chunk_by_type("hIST") {
+        let histogram = HistogramChunk::try_from(chunk).context("invalid hIST chunk")?;
+        outln!("hIST: {} entries", histogram.len());
+        for (index, frequency) in histogram.frequencies().iter().enumerate() {
+            outln!("  [{:>3}] {}", index, frequency);
+        }
+        found = true;
+
+        let palette = png.chunk_by_type("PLTE").map(PaletteChunk::try_from);
+        match palette {
+            Some(Ok(palette)) if palette.len() != histogram.len() => warn!(
+                "hIST has {} entries but the palette has {}",
+                histogram.len(),
+                palette.len()
+            ),
+            None => warn!("hIST chunk without a palette"),
+            _ => {}
+        }
+    }
+
+    if !found {
+        outln!("No sBIT, bKGD or hIST chunks found");
+    }
+}
+
+#[throws(anyhow::Error)]
+fn set(args: AncillarySet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let header = png.header().context("invalid image header")?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let palette = png
+        .chunk_by_type("PLTE")
+        .map(PaletteChunk::try_from)
+        .transpose()
+        .context("invalid PLTE chunk")?;
+
+    let mut chunks = vec![];
+
+    if let Some(bits) = args.significant_bits {
+        let sbit = SignificantBits::new(bits, &header).context("invalid significant bits")?;
+        chunks.push(sbit.to_chunk());
+    }
+
+    if let Some(values) = args.background {
+        let background = BackgroundChunk::new(&values, &header).context("invalid background")?;
+        if let (BackgroundChunk::Indexed(index), Some(palette)) = (background, &palette) {
+            if usize::from(index) >= palette.len() {
+                throw!(invalid_arguments(format!(
+                    "background index {} is past the end of the {} entry palette",
+                    index,
+                    palette.len()
+                )))
+            }
+        }
+        chunks.push(background.to_chunk());
+    }
+
+    if args.histogram {
+        let palette = palette.ok_or_else(|| not_found("png has no palette"))?;
+        let image = png.decode_image().context("failed to decode image data")?;
+        let histogram = HistogramChunk::from_image(&image, palette.len())?;
+        chunks.push(histogram.to_chunk());
+    }
+
+    png.retain_chunks(|chunk| {
+        !chunks
+            .iter()
+            .any(|new| new.chunk_type() == chunk.chunk_type())
+    });
+
+    for chunk in chunks {
+        png.insert_chunk_ordered(chunk);
+    }
+
+    if !is_stdio(&output_path) {
+        outln!("Updated ancillary chunks");
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
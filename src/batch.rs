@@ -0,0 +1,343 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use fehler::{throw, throws};
+use rayon::prelude::*;
+
+use crate::{
+    error::{self, not_found},
+    output::{self, outln},
+    progress,
+    util::{is_stdio, is_url},
+};
+
+#[derive(Args, Clone, Debug)]
+pub struct BatchOptions {
+    /// Number of files to process concurrently in batch mode
+    #[clap(short, long, value_parser, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+}
+
+/// Arguments of a subcommand that can be applied to many pngs at once.
+pub trait BatchArgs: Sized + Send + Sync {
+    fn png_path(&self) -> &Path;
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self;
+
+    fn batch_options(&self) -> &BatchOptions;
+
+    #[throws(anyhow::Error)]
+    fn validate_batch(&self) {}
+}
+
+pub fn is_batch_path(path: &Path) -> bool {
+    if is_stdio(path) || is_url(path) || path.is_file() {
+        return false;
+    }
+
+    path.is_dir()
+        || path
+            .to_str()
+            .is_some_and(|path| path.contains(['*', '?', '[']))
+}
+
+#[throws(anyhow::Error)]
+pub fn expand_batch_path(path: &Path) -> Vec<PathBuf> {
+    let pattern = if path.is_dir() {
+        path.join("**").join("*.png")
+    } else {
+        path.to_path_buf()
+    };
+    let pattern = pattern.to_str().context("batch path is not valid utf8")?;
+
+    let mut paths: Vec<PathBuf> = glob::glob(pattern)
+        .context("invalid glob pattern")?
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    if paths.is_empty() {
+        throw!(not_found(format!("no files matched `{}`", pattern)))
+    }
+
+    paths
+}
+
+/// Collects per-file results from batch workers, which may run on any thread.
+#[derive(Debug, Default)]
+struct Reporter {
+    failed: Mutex<Vec<PathBuf>>,
+    /// Set once stdout turns out to be a closed pipe, which ends the batch.
+    broken_pipe: Mutex<Option<anyhow::Error>>,
+}
+
+impl Reporter {
+    fn report(&self, path: &Path, result: anyhow::Result<()>) {
+        if let Err(error) = result {
+            if error::is_broken_pipe(&error) {
+                self.broken_pipe
+                    .lock()
+                    .expect("reporter lock poisoned")
+                    .get_or_insert(error);
+                return;
+            }
+
+            eprintln!("error: {}: {:#}", path.display(), error);
+            self.failed
+                .lock()
+                .expect("reporter lock poisoned")
+                .push(path.to_path_buf());
+        }
+    }
+
+    /// Prints what a file's run buffered under its header, in one piece so
+    /// runs finishing at the same time don't interleave, then reports it.
+    fn report_buffered(&self, path: &Path, output: &[u8], result: anyhow::Result<()>) {
+        let mut stdout = std::io::stdout().lock();
+        let printed = writeln!(stdout, "==> {} <==", path.display())
+            .and_then(|()| stdout.write_all(output))
+            .context("failed to write stdout");
+        drop(stdout);
+
+        self.report(path, result.and(printed));
+    }
+
+    /// Whether the rest of the batch should be skipped.
+    fn is_stopped(&self) -> bool {
+        self.broken_pipe
+            .lock()
+            .expect("reporter lock poisoned")
+            .is_some()
+    }
+
+    /// Fails with the broken pipe error if there was one, which `main` exits
+    /// quietly on, otherwise prints a summary and fails if any file did.
+    #[throws(anyhow::Error)]
+    fn finish(self, total: usize) {
+        if let Some(error) = self
+            .broken_pipe
+            .into_inner()
+            .expect("reporter lock poisoned")
+        {
+            throw!(error)
+        }
+
+        let failed = self
+            .failed
+            .into_inner()
+            .expect("reporter lock poisoned")
+            .len();
+
+        eprintln!(
+            "{} files processed: {} succeeded, {} failed",
+            total,
+            total - failed,
+            failed
+        );
+
+        if failed > 0 {
+            bail!("{} of {} files failed", failed, total)
+        }
+    }
+}
+
+/// Runs `operation` directly, or once per matched file if the png path is a
+/// directory or glob. Failures are reported per file and don't stop the batch,
+/// unless stdout is a closed pipe.
+#[throws(anyhow::Error)]
+pub fn dispatch<A: BatchArgs>(args: A, operation: impl Fn(A) -> anyhow::Result<()> + Sync) {
+    if !is_batch_path(args.png_path()) {
+        return operation(args)?;
+    }
+
+    args.validate_batch()?;
+
+    let paths = expand_batch_path(args.png_path())?;
+    let jobs = args.batch_options().jobs;
+    let reporter = Reporter::default();
+    let bar = progress::files(paths.len());
+
+    if jobs <= 1 {
+        for path in &paths {
+            if reporter.is_stopped() {
+                break;
+            }
+
+            bar.suspend(|| {
+                let result =
+                    print_header(path).and_then(|()| operation(args.with_png_path(path.clone())));
+                reporter.report(path, result);
+            });
+            bar.inc(1);
+        }
+    } else {
+        // Each file's output is held back until it is done, so output of
+        // files processed at the same time doesn't interleave
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .context("failed to start worker threads")?;
+
+        pool.install(|| {
+            paths.par_iter().for_each(|path| {
+                if reporter.is_stopped() {
+                    return;
+                }
+
+                let (result, output) =
+                    output::buffered(|| operation(args.with_png_path(path.clone())));
+                bar.suspend(|| reporter.report_buffered(path, &output, result));
+                bar.inc(1);
+            })
+        });
+    }
+
+    bar.finish_and_clear();
+
+    reporter.finish(paths.len())?
+}
+
+#[throws(anyhow::Error)]
+fn print_header(path: &Path) {
+    outln!("==> {} <==", path.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct TestArgs {
+        png_path: PathBuf,
+        batch: BatchOptions,
+    }
+
+    impl BatchArgs for TestArgs {
+        fn png_path(&self) -> &Path {
+            &self.png_path
+        }
+
+        fn with_png_path(&self, png_path: PathBuf) -> Self {
+            TestArgs {
+                png_path,
+                batch: self.batch.clone(),
+            }
+        }
+
+        fn batch_options(&self) -> &BatchOptions {
+            &self.batch
+        }
+    }
+
+    #[test]
+    fn test_dispatch_stops_on_broken_pipe() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.png", "b.png", "c.png"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        for jobs in [1, 2] {
+            let runs = AtomicUsize::new(0);
+            let args = TestArgs {
+                png_path: dir.path().to_path_buf(),
+                batch: BatchOptions { jobs },
+            };
+            let result = dispatch(args, |_| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                let error = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+                Err(anyhow::Error::new(error).context("failed to write stdout"))
+            });
+
+            assert!(error::is_broken_pipe(&result.unwrap_err()));
+            // Workers already running may finish their file, no new ones start
+            assert!(runs.load(Ordering::SeqCst) <= jobs, "{} jobs", jobs);
+        }
+    }
+
+    #[test]
+    fn test_dispatch_reports_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.png", "b.png"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let runs = AtomicUsize::new(0);
+        let args = TestArgs {
+            png_path: dir.path().to_path_buf(),
+            batch: BatchOptions { jobs: 1 },
+        };
+        let (result, output) = output::buffered(|| {
+            dispatch(args, |_| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                bail!("failed")
+            })
+        });
+
+        assert!(!error::is_broken_pipe(&result.unwrap_err()));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_expand_batch_path() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        for name in ["b.png", "a.png", "notes.txt", "nested/c.png"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        assert!(is_batch_path(dir.path()));
+        assert!(!is_batch_path(&dir.path().join("a.png")));
+        assert!(!is_batch_path(Path::new("-")));
+
+        let names = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(dir.path()).unwrap().display().to_string())
+                .collect()
+        };
+        assert_eq!(
+            names(expand_batch_path(dir.path()).unwrap()),
+            ["a.png", "b.png", "nested/c.png"]
+        );
+
+        let pattern = dir.path().join("[ab].png");
+        assert!(is_batch_path(&pattern));
+        assert_eq!(
+            names(expand_batch_path(&pattern).unwrap()),
+            ["a.png", "b.png"]
+        );
+
+        let error = expand_batch_path(&dir.path().join("*.jpg")).unwrap_err();
+        assert_eq!(
+            crate::error::Failure::classify(&error),
+            crate::error::Failure::NotFound
+        );
+    }
+
+    #[test]
+    fn test_dispatch_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        std::fs::write(&path, b"").unwrap();
+
+        let args = TestArgs {
+            png_path: path.clone(),
+            batch: BatchOptions { jobs: 1 },
+        };
+        let result = dispatch(args, |args| {
+            assert_eq!(args.png_path, path);
+            bail!("failed")
+        });
+
+        // A single file's error is passed on as is, without a summary
+        assert_eq!(result.unwrap_err().to_string(), "failed");
+    }
+}
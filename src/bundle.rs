@@ -0,0 +1,66 @@
+//! Directories packed into a single message with `encode --input-dir`, and
+//! unpacked with `decode --extract-dir`. A bundle is a plain tar archive,
+//! compressed like any other message.
+
+use std::path::Path;
+
+use anyhow::Context;
+use fehler::{throw, throws};
+use tar::{Archive, Builder, EntryType, HeaderMode};
+
+use crate::error::invalid_arguments;
+
+/// Where the magic of a tar header starts, and what it starts with.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Packs everything under `dir` into a tar archive. Timestamps and owners are
+/// left out, so the same files always make the same bundle.
+#[throws(anyhow::Error)]
+pub fn pack(dir: &Path) -> Vec<u8> {
+    if !dir.is_dir() {
+        throw!(invalid_arguments(format!(
+            "{} is not a directory",
+            dir.display()
+        )))
+    }
+
+    let mut builder = Builder::new(vec![]);
+    builder.mode(HeaderMode::Deterministic);
+    builder.follow_symlinks(false);
+    builder
+        .append_dir_all(".", dir)
+        .with_context(|| format!("failed to pack {}", dir.display()))?;
+    builder.into_inner().context("failed to pack directory")?
+}
+
+pub fn is_bundle(data: &[u8]) -> bool {
+    data.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC)
+}
+
+/// Unpacks a bundle into `dir`, creating it if needed, and returns the number
+/// of files written. Entries that would land outside `dir` are skipped.
+#[throws(anyhow::Error)]
+pub fn unpack(data: &[u8], dir: &Path) -> usize {
+    if !is_bundle(data) {
+        throw!(invalid_arguments(
+            "the message is not a directory, it wasn't encoded with --input-dir"
+        ))
+    }
+
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut files = 0;
+    let mut archive = Archive::new(data);
+    for entry in archive.entries().context("failed to read bundle")? {
+        let mut entry = entry.context("failed to read bundle")?;
+        let is_file = entry.header().entry_type() == EntryType::Regular;
+        let unpacked = entry
+            .unpack_in(dir)
+            .with_context(|| format!("failed to extract into {}", dir.display()))?;
+        if unpacked && is_file {
+            files += 1;
+        }
+    }
+    files
+}
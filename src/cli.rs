@@ -1,50 +1,1983 @@
-use clap::{Args, Parser};
-use std::path::PathBuf;
+use ::time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use fehler::{throw, throws};
+use pngme_lib::{
+    chunk_type::ChunkType,
+    label,
+    png::{CrcMode, ParseOptions},
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    batch::{BatchArgs, BatchOptions},
+    error::{invalid_arguments, EXIT_CODES},
+    util::{is_stdio, STDIO_PATH},
+};
 
 #[derive(Debug, Parser)]
-#[clap(author, version, about, long_about = None)]
-pub enum Cli {
-    Encode(Encode),
+#[clap(author, version, about, long_about = None, after_help = EXIT_CODES)]
+pub struct Cli {
+    /// Log parse progress, -vv for byte level details
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    Encode(Box<Encode>),
     Decode(Decode),
     Remove(Remove),
     Print(Print),
+    Info(Info),
+    Stats(Stats),
+    Check(Check),
+    Scan(Scan),
+    Grep(Grep),
+    Frames(Frames),
+    Palette(Palette),
+    Capacity(Capacity),
+    Generate(Generate),
+    Diff(Diff),
+    Copy(CopyChunks),
+    Watch(Watch),
+    Apply(Apply),
+    Serve(Serve),
+    Repair(Repair),
+    Optimize(Optimize),
+    Extract(Extract),
+    Edit(Edit),
+    Keygen(Keygen),
+    GenType(GenType),
+    SplitSecret(SplitSecret),
+    JoinSecret(JoinSecret),
+    Completions(Completions),
+    #[clap(name = "complete-chunk-types", hide = true)]
+    ChunkTypes(ChunkTypes),
+    #[clap(subcommand)]
+    Text(Text),
+    #[clap(subcommand)]
+    Exif(Exif),
+    #[clap(subcommand)]
+    Xmp(Xmp),
+    #[clap(subcommand)]
+    Icc(Icc),
+    #[clap(subcommand)]
+    Time(Time),
+    #[clap(subcommand)]
+    Dpi(Dpi),
+    #[clap(subcommand)]
+    Color(Color),
+    #[clap(subcommand)]
+    Ancillary(Ancillary),
+    #[clap(subcommand)]
+    Splt(Splt),
+    #[clap(subcommand)]
+    Kv(Kv),
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Clone, Debug)]
 pub struct Encode {
     #[clap(value_parser, value_name = "PNG_PATH")]
     pub png_path: PathBuf,
 
-    #[clap(value_parser, value_name = "CHUNK_TYPE")]
-    pub chunk_type: String,
+    #[clap(
+        value_parser,
+        value_name = "CHUNK_TYPE",
+        required_unless_present = "set"
+    )]
+    pub chunk_type: Option<String>,
 
-    #[clap(value_parser, value_name = "MESSAGE")]
-    pub message: String,
+    /// The message to embed, - to read it from stdin
+    #[clap(
+        value_parser,
+        value_name = "MESSAGE",
+        required_unless_present_any = &[
+            "input-file",
+            "input-dir",
+            "message-file",
+            "message-from-clipboard",
+            "set",
+        ],
+        conflicts_with_all = &[
+            "input-file",
+            "input-dir",
+            "message-file",
+            "message-from-clipboard",
+        ]
+    )]
+    pub message: Option<String>,
 
     #[clap(value_parser, value_name = "OUTPUT_PNG_PATH")]
     pub output_png_path: Option<PathBuf>,
+
+    /// Write the output png to this path instead
+    #[clap(
+        short,
+        long,
+        value_parser,
+        value_name = "OUTPUT_PNG_PATH",
+        conflicts_with = "output-png-path"
+    )]
+    pub output: Option<PathBuf>,
+
+    /// Embed the contents of a file instead of a message
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub input_file: Option<PathBuf>,
+
+    /// Embed a directory and everything in it, packed into a tar archive and
+    /// compressed with zstd unless --compress says otherwise
+    #[clap(
+        long,
+        value_parser,
+        value_name = "DIR",
+        conflicts_with_all = &["input-file", "message-file", "message-from-clipboard"]
+    )]
+    pub input_dir: Option<PathBuf>,
+
+    /// Embed the text in a file instead of a message, - for stdin
+    #[clap(
+        long,
+        value_parser,
+        value_name = "PATH",
+        conflicts_with_all = &["input-file", "message-from-clipboard"]
+    )]
+    pub message_file: Option<PathBuf>,
+
+    /// Embed the text on the clipboard instead of a message
+    #[clap(long, conflicts_with = "input-file")]
+    pub message_from_clipboard: bool,
+
+    /// Add a chunk holding MESSAGE, or the contents of the file with TYPE=@PATH,
+    /// instead of a single message. Can be repeated to add several chunks in
+    /// one go
+    #[clap(
+        long,
+        value_parser = parse_chunk_message,
+        value_name = "TYPE=MESSAGE",
+        multiple_occurrences = true,
+        conflicts_with_all = &[
+            "chunk-type",
+            "message",
+            "input-file",
+            "input-dir",
+            "message-file",
+            "message-from-clipboard",
+            "split-across",
+        ]
+    )]
+    pub set: Vec<ChunkMessage>,
+
+    /// Encrypt the message with a passphrase
+    #[clap(long)]
+    pub encrypt: bool,
+
+    /// Read the passphrase from a file instead of prompting
+    #[clap(long, value_parser, value_name = "PATH", requires = "encrypt")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Encrypt the message to an age public key instead, can be repeated
+    #[clap(
+        long,
+        value_parser,
+        value_name = "RECIPIENT",
+        multiple_occurrences = true,
+        conflicts_with = "encrypt"
+    )]
+    pub recipient: Vec<String>,
+
+    /// Sign the message with an Ed25519 secret key file, see `pngme keygen`
+    #[clap(long, value_parser, value_name = "KEY_PATH")]
+    pub sign: Option<PathBuf>,
+
+    /// Add an HMAC-SHA256 integrity tag to the message using this key
+    #[clap(long, value_parser, value_name = "KEY")]
+    pub hmac: Option<String>,
+
+    /// Name the message, so others can be stored under the same chunk type
+    #[clap(long, value_parser = parse_label, value_name = "LABEL")]
+    pub label: Option<String>,
+
+    /// Split messages larger than this across several chunks, e.g. 64K or 1M
+    #[clap(long, value_parser = parse_size, value_name = "SIZE")]
+    pub max_fragment_size: Option<usize>,
+
+    /// Hide the message among N random chunks of the same type, needs an
+    /// encrypted, tagged or signed message to tell them apart
+    #[clap(
+        long,
+        value_parser,
+        value_name = "N",
+        conflicts_with_all = &["max-fragment-size", "split-across"]
+    )]
+    pub decoys: Option<usize>,
+
+    /// Spread the message over these pngs as well, modifying them in place
+    #[clap(
+        long,
+        value_parser,
+        value_name = "PNG_PATH",
+        multiple_values = true,
+        conflicts_with = "max-fragment-size"
+    )]
+    pub split_across: Vec<PathBuf>,
+
+    /// Compress the message before embedding it
+    #[clap(long, value_enum, value_name = "METHOD")]
+    pub compress: Option<Compression>,
+
+    /// Where to hide the message, lsb hides it in the pixel data where chunk
+    /// listings can't see it
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODE",
+        default_value_t = Mode::Chunk,
+        conflicts_with_all = &[
+            "decoys",
+            "max-fragment-size",
+            "split-across",
+            "append-raw",
+            "replace",
+            "position",
+        ]
+    )]
+    pub mode: Mode,
+
+    /// Append the chunk at the very end of the file, even after IEND
+    #[clap(long)]
+    pub append_raw: bool,
+
+    /// Replace existing chunks of the same type instead of adding another
+    #[clap(long, conflicts_with = "append-raw")]
+    pub replace: bool,
+
+    /// Where to insert the chunk: an index, before:TYPE or after:TYPE
+    #[clap(
+        long,
+        value_parser = parse_position,
+        value_name = "POSITION",
+        conflicts_with_all = &["append-raw", "replace"]
+    )]
+    pub position: Option<Position>,
+
+    /// What to do if the png ends up with duplicates of a chunk that may only appear once
+    #[clap(long, value_enum, value_name = "POLICY", default_value_t = Duplicates::Warn)]
+    pub duplicates: Duplicates,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub backup: BackupOptions,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
 }
 
-#[derive(Args, Debug)]
+#[derive(Clone, Copy, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Zlib,
+    Zstd,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    Chunk,
+    Lsb,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Largest first
+    Size,
+    Type,
+    Offset,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Critical, ancillary and private chunks
+    Category,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// The bytes as they are, for piping into a file or another program
+    Raw,
+    Base64,
+    Hex,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Duplicates {
+    Reject,
+    Warn,
+    Dedupe,
+}
+
+#[derive(Args, Clone, Debug)]
 pub struct Decode {
     #[clap(value_parser, value_name = "PNG_PATH")]
     pub png_path: PathBuf,
 
     #[clap(value_parser, value_name = "CHUNK_TYPE")]
     pub chunk_type: String,
+
+    /// Decrypt a message encoded with --encrypt
+    #[clap(long)]
+    pub decrypt: bool,
+
+    /// Read the passphrase from a file instead of prompting
+    #[clap(long, value_parser, value_name = "PATH", requires = "decrypt")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Decrypt a message encoded with --recipient using an age identity file
+    #[clap(long, value_parser, value_name = "PATH", conflicts_with = "decrypt")]
+    pub identity: Option<PathBuf>,
+
+    /// Refuse to print messages not signed by this Ed25519 public key file
+    #[clap(long, value_parser, value_name = "PUBLIC_KEY_PATH")]
+    pub verify: Option<PathBuf>,
+
+    /// Refuse to print messages whose integrity tag doesn't match this key
+    #[clap(long, value_parser, value_name = "KEY")]
+    pub hmac: Option<String>,
+
+    /// Only read the message stored with this label
+    #[clap(long, value_parser = parse_label, value_name = "LABEL")]
+    pub label: Option<String>,
+
+    /// Also read message fragments from these pngs, in any order
+    #[clap(long, value_parser, value_name = "PNG_PATH", multiple_values = true)]
+    pub join: Vec<PathBuf>,
+
+    /// Where the message was hidden with `encode --mode`
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODE",
+        default_value_t = Mode::Chunk,
+        conflicts_with = "join"
+    )]
+    pub mode: Mode,
+
+    /// Write the raw message bytes to a file instead of printing them
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub output_file: Option<PathBuf>,
+
+    /// Unpack a directory encoded with --input-dir into this one
+    #[clap(
+        long,
+        value_parser,
+        value_name = "DIR",
+        conflicts_with_all = &["output-file", "to-clipboard", "all", "encoding", "auto"]
+    )]
+    pub extract_dir: Option<PathBuf>,
+
+    /// Copy the message to the clipboard instead of printing it
+    #[clap(long, conflicts_with_all = &["output-file", "all"])]
+    pub to_clipboard: bool,
+
+    /// Print every message stored under this chunk type
+    #[clap(long, conflicts_with = "output-file")]
+    pub all: bool,
+
+    /// Fail on messages that aren't valid utf-8 instead of printing
+    /// replacement characters in their place
+    #[clap(long, conflicts_with = "encoding")]
+    pub strict_utf8: bool,
+
+    /// Print only the message bytes, encoded like this
+    #[clap(
+        long,
+        value_enum,
+        value_name = "ENCODING",
+        conflicts_with_all = &["output-file", "to-clipboard", "format"]
+    )]
+    pub encoding: Option<Encoding>,
+
+    /// Print the message according to what it looks like: json
+    /// pretty-printed, text as is and binary data raw
+    #[clap(
+        long,
+        conflicts_with_all = &["output-file", "to-clipboard", "format", "encoding"]
+    )]
+    pub auto: bool,
+
+    /// Print binary data with --auto even when stdout is a terminal
+    #[clap(long, requires = "auto")]
+    pub force: bool,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Clone, Debug)]
 pub struct Remove {
     #[clap(value_parser, value_name = "PNG_PATH")]
     pub png_path: PathBuf,
 
     #[clap(value_parser, value_name = "CHUNK_TYPE")]
     pub chunk_type: String,
+
+    /// Remove every chunk of this type
+    #[clap(long, conflicts_with = "index")]
+    pub all: bool,
+
+    /// Remove the Nth chunk of this type, counting from 0
+    #[clap(long, value_parser, value_name = "N")]
+    pub index: Option<usize>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub backup: BackupOptions,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Clone, Debug)]
 pub struct Print {
     #[clap(value_parser, value_name = "PNG_PATH")]
     pub png_path: PathBuf,
+
+    /// Only print chunks of these types, e.g. tEXt,zTXt
+    #[clap(
+        long = "type",
+        value_parser,
+        value_name = "CHUNK_TYPE",
+        use_value_delimiter = true
+    )]
+    pub types: Vec<String>,
+
+    /// Only print critical chunks
+    #[clap(long, conflicts_with = "ancillary")]
+    pub critical: bool,
+
+    /// Only print ancillary chunks
+    #[clap(long)]
+    pub ancillary: bool,
+
+    /// Only print chunks not registered in the png spec or its extensions
+    #[clap(long)]
+    pub unknown_only: bool,
+
+    /// Print the chunks in this order instead of as they appear in the file
+    #[clap(long, value_enum, value_name = "KEY")]
+    pub sort: Option<SortKey>,
+
+    /// Print the chunks in groups
+    #[clap(long, value_enum, value_name = "GROUP")]
+    pub group_by: Option<GroupBy>,
+
+    /// Also print the decoded contents of chunks with a known layout
+    #[clap(long)]
+    pub decoded: bool,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+}
+
+/// Print the image header
+#[derive(Args, Clone, Debug)]
+pub struct Info {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+}
+
+/// Summarize where the bytes of a png go, by chunk type and category
+#[derive(Args, Clone, Debug)]
+pub struct Stats {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Number of largest chunks to list
+    #[clap(long, value_parser, value_name = "N", default_value_t = 5)]
+    pub top: usize,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+}
+
+/// Validate the structure of a png file
+#[derive(Args, Clone, Debug)]
+pub struct Check {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+}
+
+/// Flag content that may hide a payload, such as private chunks, random
+/// looking data and data after IEND
+#[derive(Args, Clone, Debug)]
+pub struct Scan {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+}
+
+/// Search the decoded data of ancillary chunks for a regular expression
+#[derive(Args, Debug)]
+pub struct Grep {
+    #[clap(value_parser, value_name = "PATTERN")]
+    pub pattern: String,
+
+    /// Pngs, directories or glob patterns to search
+    #[clap(value_parser, value_name = "PNG_PATH", required = true)]
+    pub png_paths: Vec<PathBuf>,
+
+    /// Match without regard to case
+    #[clap(short, long)]
+    pub ignore_case: bool,
+
+    /// Only search chunks of this type
+    #[clap(long, value_parser, value_name = "CHUNK_TYPE")]
+    pub chunk_type: Option<String>,
+}
+
+/// List the frames of an animated png, with their timing and sizes
+#[derive(Args, Debug)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Frames {
+    #[clap(subcommand)]
+    pub command: Option<FramesCommand>,
+
+    #[clap(value_parser, value_name = "PNG_PATH", required = true)]
+    pub png_path: Option<PathBuf>,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+}
+
+/// List the palette of a png with each entry's alpha
+#[derive(Args, Debug)]
+pub struct Palette {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum FramesCommand {
+    Extract(FramesExtract),
+}
+
+/// Write each frame of an animated png to a png of its own. Frames are
+/// written as stored, without compositing them onto earlier frames
+#[derive(Args, Debug)]
+pub struct FramesExtract {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Directory for frame-000.png, frame-001.png and so on, created if
+    /// missing
+    #[clap(long, value_parser, value_name = "DIR")]
+    pub out_dir: PathBuf,
+
+    /// Overwrite frame files that already exist
+    #[clap(long)]
+    pub force: bool,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Print how many bytes can be embedded in a png, headers added by
+/// compression, encryption or signing included
+#[derive(Args, Clone, Debug)]
+pub struct Capacity {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Where the message would be hidden, see `encode --mode`
+    #[clap(long, value_enum, value_name = "MODE", default_value_t = Mode::Chunk)]
+    pub mode: Mode,
+
+    /// Largest chunk a message may take, e.g. 64K or 1M. Defaults to the
+    /// largest chunk pngme reads back
+    #[clap(long, value_parser = parse_size, value_name = "SIZE")]
+    pub max_chunk_size: Option<usize>,
+
+    /// Largest the png may grow to with the message in a chunk
+    #[clap(long, value_parser = parse_size, value_name = "SIZE")]
+    pub max_file_size: Option<usize>,
+
+    /// Leave room for encrypting the message with a passphrase
+    #[clap(long)]
+    pub encrypt: bool,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+
+    #[clap(flatten)]
+    pub batch: BatchOptions,
+}
+
+/// Create a cover image to hide messages in
+#[derive(Args, Debug)]
+pub struct Generate {
+    /// Image size, as WIDTHxHEIGHT
+    #[clap(
+        long,
+        value_parser = parse_dimensions,
+        value_name = "SIZE",
+        default_value = "512x512",
+        conflicts_with = "capacity"
+    )]
+    pub size: (u32, u32),
+
+    /// Pick the smallest square image that can hide N bytes with
+    /// `encode --mode lsb` instead of giving a size
+    #[clap(long, value_parser = parse_size, value_name = "N")]
+    pub capacity: Option<usize>,
+
+    /// "noise" for random pixels, or "solid:#RRGGBB" for a single colour
+    #[clap(long, value_parser = parse_style, value_name = "STYLE", default_value = "noise")]
+    pub style: Style,
+
+    /// Where to write the image, or "-" for stdout
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub out: PathBuf,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// List the chunks added, removed or modified between two pngs
+#[derive(Args, Debug)]
+pub struct Diff {
+    #[clap(value_parser, value_name = "OLD_PNG_PATH")]
+    pub old_png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "NEW_PNG_PATH")]
+    pub new_png_path: PathBuf,
+
+    /// Also show which bytes of modified chunks changed
+    #[clap(long)]
+    pub bytes: bool,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[clap(flatten)]
+    pub parse: ParseArgs,
+}
+
+/// Copy ancillary chunks from one png to another
+#[derive(Args, Debug)]
+pub struct CopyChunks {
+    #[clap(long, value_parser, value_name = "PNG_PATH")]
+    pub from: PathBuf,
+
+    #[clap(long, value_parser, value_name = "PNG_PATH")]
+    pub to: PathBuf,
+
+    /// Copy chunks of this type, can be repeated
+    #[clap(
+        long = "type",
+        value_parser,
+        value_name = "CHUNK_TYPE",
+        multiple_occurrences = true,
+        required_unless_present = "all-ancillary"
+    )]
+    pub chunk_types: Vec<String>,
+
+    /// Copy every ancillary chunk
+    #[clap(long, conflicts_with = "chunk-types")]
+    pub all_ancillary: bool,
+
+    /// Write the output png to this path instead of modifying the target
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub backup: BackupOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Keep a message embedded in the pngs of a directory as they are rewritten
+#[derive(Args, Debug)]
+pub struct Watch {
+    #[clap(value_parser, value_name = "DIR")]
+    pub dir: PathBuf,
+
+    /// Chunk type to store the message in
+    #[clap(long, value_parser, value_name = "CHUNK_TYPE")]
+    pub chunk: String,
+
+    /// File holding the message, changes to it are embedded too
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub message_file: PathBuf,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Run the encode, remove, strip and text-set operations listed in a manifest
+#[derive(Args, Debug)]
+pub struct Apply {
+    #[clap(value_parser, value_name = "MANIFEST_PATH")]
+    pub manifest_path: PathBuf,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Serve encode, decode and chunk listing over HTTP
+#[derive(Args, Debug)]
+pub struct Serve {
+    /// Address to listen on
+    #[clap(long, value_parser, value_name = "HOST", default_value = "127.0.0.1")]
+    pub host: String,
+
+    #[clap(long, value_parser, value_name = "PORT", default_value_t = 8080)]
+    pub port: u16,
+
+    /// Number of requests to handle concurrently
+    #[clap(long, value_parser, value_name = "N", default_value_t = 4)]
+    pub threads: usize,
+}
+
+/// Fix structural problems in a png file
+#[derive(Args, Debug)]
+pub struct Repair {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Recompress the image data of a png, merging its IDAT chunks and leaving
+/// every other chunk intact
+#[derive(Args, Debug)]
+pub struct Optimize {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Deflate level, from 0 for none to 9 for the smallest output
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u32).range(0..=9),
+        value_name = "LEVEL",
+        default_value_t = 9
+    )]
+    pub level: u32,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Write a chunk's raw data, byte for byte
+#[derive(Args, Debug)]
+pub struct Extract {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+
+    /// Where to write the data, or "-" for stdout
+    #[clap(long, value_parser, value_name = "PATH", default_value = STDIO_PATH)]
+    pub out: PathBuf,
+}
+
+/// Edit a chunk's data in $VISUAL or $EDITOR
+#[derive(Args, Debug)]
+pub struct Edit {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Chunk type to edit, a new chunk is added if there is none
+    #[clap(value_parser, value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+
+    #[clap(flatten)]
+    pub backup: BackupOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Generate an Ed25519 key pair for signing messages
+#[derive(Args, Debug)]
+pub struct Keygen {
+    /// Where to write the secret key, the public key goes to KEY_PATH.pub
+    #[clap(value_parser, value_name = "KEY_PATH")]
+    pub key_path: PathBuf,
+
+    /// Overwrite existing key files
+    #[clap(long)]
+    pub force: bool,
+}
+
+/// Print a chunk type that is safe to use for custom data
+#[derive(Args, Debug)]
+pub struct GenType {
+    /// Letters to build the type from, a random type is made if not given
+    #[clap(value_parser, value_name = "LABEL")]
+    pub label: Option<String>,
+}
+
+/// Split a secret into shares embedded in several pngs
+#[derive(Args, Debug)]
+pub struct SplitSecret {
+    /// Number of shares needed to recover the secret
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..), value_name = "N")]
+    pub threshold: u8,
+
+    /// Number of shares to create, one per png
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..), value_name = "N")]
+    pub shares: u8,
+
+    #[clap(value_parser, value_name = "MESSAGE")]
+    pub message: String,
+
+    #[clap(value_parser, value_name = "PNG_PATH", required = true)]
+    pub png_paths: Vec<PathBuf>,
+
+    /// Write the pngs to this directory instead of modifying them in place
+    #[clap(long, value_parser, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Recover a secret from the shares embedded in several pngs
+#[derive(Args, Debug)]
+pub struct JoinSecret {
+    #[clap(value_parser, value_name = "PNG_PATH", required = true)]
+    pub png_paths: Vec<PathBuf>,
+
+    /// Write the raw secret bytes to a file instead of printing them
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub output_file: Option<PathBuf>,
+}
+
+/// Print a shell completion script
+#[derive(Args, Debug)]
+pub struct Completions {
+    #[clap(value_enum, value_name = "SHELL")]
+    pub shell: Shell,
+}
+
+/// Print the chunk types in a png, used by the completion scripts
+#[derive(Args, Debug)]
+pub struct ChunkTypes {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+/// Manage tEXt, zTXt and iTXt keyword/value chunks
+#[derive(Debug, Subcommand)]
+pub enum Text {
+    Set(TextSet),
+    Get(TextGet),
+    List(TextList),
+    Remove(TextRemove),
+}
+
+#[derive(Args, Debug)]
+pub struct TextSet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "KEYWORD")]
+    pub keyword: String,
+
+    #[clap(value_parser, value_name = "TEXT")]
+    pub text: String,
+
+    /// Store the text zlib compressed (zTXt, or compressed iTXt)
+    #[clap(long)]
+    pub compressed: bool,
+
+    /// Store the text as utf8 in an iTXt chunk
+    #[clap(long)]
+    pub international: bool,
+
+    /// Language tag of the text, e.g. "en-GB" (implies --international)
+    #[clap(long, value_parser, value_name = "TAG")]
+    pub language: Option<String>,
+
+    /// Keyword translated into the language of the text (implies --international)
+    #[clap(long, value_parser, value_name = "KEYWORD")]
+    pub translated_keyword: Option<String>,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct TextGet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "KEYWORD")]
+    pub keyword: String,
+}
+
+#[derive(Args, Debug)]
+pub struct TextList {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct TextRemove {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "KEYWORD")]
+    pub keyword: String,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Read and write eXIf metadata
+#[derive(Debug, Subcommand)]
+pub enum Exif {
+    Dump(ExifDump),
+    Import(ExifImport),
+}
+
+#[derive(Args, Debug)]
+pub struct ExifDump {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ExifImport {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Raw tiff structured exif data, optionally with a jpeg "Exif" prefix
+    #[clap(value_parser, value_name = "EXIF_PATH")]
+    pub exif_path: PathBuf,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Manage the XMP metadata packet
+#[derive(Debug, Subcommand)]
+pub enum Xmp {
+    Get(XmpGet),
+    Set(XmpSet),
+    Remove(XmpRemove),
+}
+
+#[derive(Args, Debug)]
+pub struct XmpGet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct XmpSet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// File containing the xmp packet, or "-" for stdin
+    #[clap(value_parser, value_name = "XMP_PATH")]
+    pub xmp_path: PathBuf,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct XmpRemove {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Extract or embed an iCCP colour profile
+#[derive(Debug, Subcommand)]
+pub enum Icc {
+    Extract(IccExtract),
+    Embed(IccEmbed),
+}
+
+#[derive(Args, Debug)]
+pub struct IccExtract {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Where to write the profile, or "-" for stdout
+    #[clap(value_parser, value_name = "ICC_PATH")]
+    pub icc_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct IccEmbed {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "ICC_PATH")]
+    pub icc_path: PathBuf,
+
+    /// Profile name stored in the chunk, defaults to the file name
+    #[clap(long, value_parser, value_name = "NAME")]
+    pub name: Option<String>,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Read or write the tIME last modification time
+#[derive(Debug, Subcommand)]
+pub enum Time {
+    Get(TimeGet),
+    Set(TimeSet),
+}
+
+#[derive(Args, Debug)]
+pub struct TimeGet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct TimeSet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Use the current time
+    #[clap(long, required_unless_present = "at", conflicts_with = "at")]
+    pub now: bool,
+
+    /// Use an RFC 3339 timestamp, e.g. 2022-09-04T13:37:05Z
+    #[clap(long, value_parser = parse_rfc3339, value_name = "TIMESTAMP")]
+    pub at: Option<OffsetDateTime>,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Read or write the pHYs print resolution
+#[derive(Debug, Subcommand)]
+pub enum Dpi {
+    Get(DpiGet),
+    Set(DpiSet),
+}
+
+#[derive(Args, Debug)]
+pub struct DpiGet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct DpiSet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser = clap::value_parser!(u32).range(1..), value_name = "DPI")]
+    pub dpi: u32,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Inspect or set the gAMA, sRGB and cHRM colour chunks
+#[derive(Debug, Subcommand)]
+pub enum Color {
+    Show(ColorShow),
+    Set(ColorSet),
+}
+
+#[derive(Args, Debug)]
+pub struct ColorShow {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("color").required(true).multiple(true).args(&["gamma", "srgb", "chromaticities"])))]
+pub struct ColorSet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Write a gAMA chunk, e.g. 0.45455
+    #[clap(long, value_parser, value_name = "GAMMA")]
+    pub gamma: Option<f64>,
+
+    /// Write an sRGB chunk with this rendering intent
+    #[clap(
+        long,
+        value_enum,
+        value_name = "INTENT",
+        conflicts_with_all = &["gamma", "chromaticities"]
+    )]
+    pub srgb: Option<RenderingIntent>,
+
+    /// Write a cHRM chunk from the white point and red, green and blue primaries
+    #[clap(long, value_parser = parse_chromaticities, value_name = "WX,WY,RX,RY,GX,GY,BX,BY")]
+    pub chromaticities: Option<[f64; 8]>,
+
+    /// Remove chunks that conflict with the ones being written
+    #[clap(long)]
+    pub replace: bool,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Inspect or set the sBIT, bKGD and hIST chunks
+#[derive(Debug, Subcommand)]
+pub enum Ancillary {
+    Show(AncillaryShow),
+    Set(AncillarySet),
+}
+
+#[derive(Args, Debug)]
+pub struct AncillaryShow {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("ancillary").required(true).multiple(true).args(&["significant-bits", "background", "histogram"])))]
+pub struct AncillarySet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Write an sBIT chunk with the significant bits of each channel, e.g. 5,6,5
+    #[clap(long, value_parser, value_name = "BITS", use_value_delimiter = true)]
+    pub significant_bits: Option<Vec<u8>>,
+
+    /// Write a bKGD chunk with a palette index, a grey level or red, green and
+    /// blue samples, depending on the colour type
+    #[clap(long, value_parser, value_name = "VALUES", use_value_delimiter = true)]
+    pub background: Option<Vec<u16>>,
+
+    /// Write an hIST chunk counted from the pixels of an indexed image
+    #[clap(long)]
+    pub histogram: bool,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// List, export or import sPLT suggested palettes
+#[derive(Debug, Subcommand)]
+pub enum Splt {
+    List(SpltList),
+    Export(SpltExport),
+    Import(SpltImport),
+}
+
+#[derive(Args, Debug)]
+pub struct SpltList {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct SpltExport {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Where to write the palette as json, or "-" for stdout
+    #[clap(value_parser, value_name = "JSON_PATH", default_value = "-")]
+    pub json_path: PathBuf,
+
+    /// Palette to export, needed when the png has more than one
+    #[clap(long, value_parser, value_name = "NAME")]
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SpltImport {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    /// Palette written by `splt export`, or "-" for stdin
+    #[clap(value_parser, value_name = "JSON_PATH")]
+    pub json_path: PathBuf,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+/// Keep small key-value settings together in a single pmKv chunk
+#[derive(Debug, Subcommand)]
+pub enum Kv {
+    Set(KvSet),
+    Get(KvGet),
+    List(KvList),
+    Del(KvDel),
+}
+
+#[derive(Args, Debug)]
+pub struct KvSet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "KEY")]
+    pub key: String,
+
+    #[clap(value_parser, value_name = "VALUE")]
+    pub value: String,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct KvGet {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "KEY")]
+    pub key: String,
+}
+
+#[derive(Args, Debug)]
+pub struct KvList {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct KvDel {
+    #[clap(value_parser, value_name = "PNG_PATH")]
+    pub png_path: PathBuf,
+
+    #[clap(value_parser, value_name = "KEY")]
+    pub key: String,
+
+    #[clap(short, long, value_parser, value_name = "OUTPUT_PNG_PATH")]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub guard: GuardOptions,
+
+    #[clap(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RenderingIntent {
+    Perceptual,
+    Relative,
+    Saturation,
+    Absolute,
+}
+
+fn parse_chromaticities(value: &str) -> Result<[f64; 8], String> {
+    value
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().map_err(|err| err.to_string()))
+        .collect::<Result<Vec<_>, _>>()?
+        .try_into()
+        .map_err(|_| "expected 8 comma separated values".to_string())
+}
+
+fn parse_rfc3339(value: &str) -> Result<OffsetDateTime, ::time::error::Parse> {
+    OffsetDateTime::parse(value, &Rfc3339)
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ParseArgs {
+    /// Skip chunks with a bad crc instead of failing
+    #[clap(long)]
+    pub lenient: bool,
+
+    /// Don't check chunk crcs at all, for faster scanning of large files
+    #[clap(long, conflicts_with = "lenient")]
+    pub no_verify_crc: bool,
+}
+
+impl ParseArgs {
+    pub fn options(&self) -> ParseOptions {
+        ParseOptions {
+            crc_mode: if self.lenient {
+                CrcMode::Warn
+            } else {
+                CrcMode::Strict
+            },
+            verify_crc: !self.no_verify_crc,
+            ..Default::default()
+        }
+    }
+}
+
+// How pngs are written, for every command that writes one. Kept as a plain
+// comment, clap would use a doc comment as the about of each subcommand
+// flattening this in without one of its own
+#[derive(Args, Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// Keep the access and modification times of files rewritten in place
+    #[clap(long)]
+    pub preserve_times: bool,
+
+    /// Keep the whole mode of files rewritten in place, unix only. Read,
+    /// write and execute permissions are always kept, this also keeps the
+    /// setuid, setgid and sticky bits
+    #[clap(long)]
+    pub preserve_mode: bool,
+
+    /// Read back every png written, failing unless it parses with valid crcs
+    /// and holds exactly the chunks meant to be written
+    #[clap(long)]
+    pub verify: bool,
+}
+
+// Shared by every command that modifies or writes a png, so they all guard
+// against unwanted writes the same way
+#[derive(Args, Clone, Debug, Default)]
+pub struct GuardOptions {
+    /// Overwrite output files that already exist
+    #[clap(long)]
+    pub force: bool,
+
+    /// Report what would change without writing anything
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct BackupOptions {
+    /// Copy the original file to PNG_PATH + SUFFIX before modifying it in place
+    #[clap(
+        long,
+        value_name = "SUFFIX",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = ".bak",
+        env = "PNGME_BACKUP"
+    )]
+    pub backup: Option<String>,
+
+    /// Don't back up, even if PNGME_BACKUP or the config file asks to
+    #[clap(long, overrides_with = "backup")]
+    pub no_backup: bool,
+}
+
+/// A chunk to add with `encode --set`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkMessage {
+    pub chunk_type: ChunkType,
+    pub message: MessageSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageSource {
+    Text(String),
+    /// A file embedded as it is, or stdin for "-".
+    File(PathBuf),
+}
+
+impl MessageSource {
+    pub fn is_stdin(&self) -> bool {
+        matches!(self, MessageSource::File(path) if is_stdio(path))
+    }
+}
+
+fn parse_chunk_message(value: &str) -> Result<ChunkMessage, String> {
+    let (chunk_type, message) = value
+        .split_once('=')
+        .ok_or_else(|| "expected TYPE=MESSAGE or TYPE=@PATH".to_string())?;
+    let chunk_type = chunk_type
+        .parse::<ChunkType>()
+        .map_err(|error| format!("invalid chunk type `{}`: {}", chunk_type, error))?;
+    let message = match message.strip_prefix('@') {
+        Some(path) => MessageSource::File(PathBuf::from(path)),
+        None => MessageSource::Text(message.to_string()),
+    };
+
+    Ok(ChunkMessage {
+        chunk_type,
+        message,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Position {
+    Index(usize),
+    Before(String),
+    After(String),
+}
+
+fn parse_dimensions(value: &str) -> Result<(u32, u32), String> {
+    let error = || "expected WIDTHxHEIGHT, e.g. 512x512".to_string();
+    let (width, height) = value.split_once('x').ok_or_else(error)?;
+
+    Ok((
+        width.parse().map_err(|_| error())?,
+        height.parse().map_err(|_| error())?,
+    ))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Style {
+    Noise,
+    Solid([u8; 3]),
+}
+
+fn parse_style(value: &str) -> Result<Style, String> {
+    if value == "noise" {
+        return Ok(Style::Noise);
+    }
+
+    let error = || "expected noise or solid:#RRGGBB".to_string();
+    let color = value
+        .strip_prefix("solid:")
+        .and_then(|color| color.strip_prefix('#'))
+        .filter(|color| color.len() == 6 && color.is_ascii())
+        .ok_or_else(error)?;
+
+    let mut rgb = [0; 3];
+    for (index, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&color[index * 2..index * 2 + 2], 16).map_err(|_| error())?;
+    }
+
+    Ok(Style::Solid(rgb))
+}
+
+fn parse_position(value: &str) -> Result<Position, String> {
+    if let Some(chunk_type) = value.strip_prefix("before:") {
+        return Ok(Position::Before(chunk_type.to_string()));
+    }
+
+    if let Some(chunk_type) = value.strip_prefix("after:") {
+        return Ok(Position::After(chunk_type.to_string()));
+    }
+
+    value
+        .parse()
+        .map(Position::Index)
+        .map_err(|_| "expected an index, before:TYPE or after:TYPE".to_string())
+}
+
+fn parse_label(value: &str) -> Result<String, String> {
+    match label::is_valid_label(value) {
+        true => Ok(value.to_string()),
+        false => Err("labels must be 1 to 255 bytes long".to_string()),
+    }
+}
+
+/// Parses a byte count with an optional K, M or G suffix.
+fn parse_size(value: &str) -> Result<usize, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1 << 10),
+        Some((index, 'm' | 'M')) => (&value[..index], 1 << 20),
+        Some((index, 'g' | 'G')) => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|size| *size > 0)
+        .ok_or_else(|| "expected a positive size like 4096, 64K or 1M".to_string())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl Encode {
+    pub fn message_from_stdin(&self) -> bool {
+        self.message.as_deref() == Some(STDIO_PATH)
+            || self.message_file.as_deref().is_some_and(is_stdio)
+            || self.set.iter().any(|set| set.message.is_stdin())
+    }
+}
+
+impl BatchArgs for Encode {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Encode {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+
+    #[throws(anyhow::Error)]
+    fn validate_batch(&self) {
+        if self.output.is_some() || self.output_png_path.is_some() {
+            throw!(invalid_arguments(
+                "an output path can't be used when encoding multiple files"
+            ))
+        }
+
+        if !self.split_across.is_empty() {
+            throw!(invalid_arguments(
+                "--split-across can't be used when encoding multiple files"
+            ))
+        }
+
+        if self.message_from_stdin() {
+            throw!(invalid_arguments(
+                "the message can't be read from stdin when encoding multiple files"
+            ))
+        }
+
+        // Files may be processed on several threads, which can't all prompt
+        if self.encrypt && self.passphrase_file.is_none() {
+            throw!(invalid_arguments(
+                "use --passphrase-file to encrypt multiple files"
+            ))
+        }
+    }
+}
+
+impl BatchArgs for Decode {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Decode {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+
+    #[throws(anyhow::Error)]
+    fn validate_batch(&self) {
+        if self.output_file.is_some() || self.extract_dir.is_some() {
+            throw!(invalid_arguments(
+                "an output file or directory can't be used when decoding multiple files"
+            ))
+        }
+
+        if !self.join.is_empty() {
+            throw!(invalid_arguments(
+                "--join can't be used when decoding multiple files"
+            ))
+        }
+
+        // Files may be processed on several threads, which can't all prompt
+        if self.decrypt && self.passphrase_file.is_none() {
+            throw!(invalid_arguments(
+                "use --passphrase-file to decrypt multiple files"
+            ))
+        }
+    }
+}
+
+impl BatchArgs for Remove {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Remove {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+impl BatchArgs for Print {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Print {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+impl BatchArgs for Scan {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Scan {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+impl BatchArgs for Capacity {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Capacity {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+impl BatchArgs for Stats {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Stats {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+impl BatchArgs for Info {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Info {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+impl BatchArgs for Check {
+    fn png_path(&self) -> &Path {
+        &self.png_path
+    }
+
+    fn with_png_path(&self, png_path: PathBuf) -> Self {
+        Check {
+            png_path,
+            ..self.clone()
+        }
+    }
+
+    fn batch_options(&self) -> &BatchOptions {
+        &self.batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Command as ClapCommand, CommandFactory};
+
+    use super::*;
+
+    /// Every subcommand below `command`, depth first.
+    fn subcommands<'a>(command: &ClapCommand<'a>) -> Vec<ClapCommand<'a>> {
+        command
+            .get_subcommands()
+            .flat_map(|subcommand| {
+                let mut commands = vec![subcommand.clone()];
+                commands.extend(subcommands(subcommand));
+                commands
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_flattened_options_have_no_about() {
+        let flattened = [
+            WriteOptions::augment_args(ClapCommand::new("write")),
+            BackupOptions::augment_args(ClapCommand::new("backup")),
+            GuardOptions::augment_args(ClapCommand::new("guard")),
+            ParseArgs::augment_args(ClapCommand::new("parse")),
+            BatchOptions::augment_args(ClapCommand::new("batch")),
+        ];
+        for command in &flattened {
+            assert_eq!(command.get_about(), None, "{}", command.get_name());
+        }
+
+        let cli = Cli::command();
+        for command in subcommands(&cli) {
+            let about = command.get_about().unwrap_or_default();
+            assert!(
+                !about.contains("every command that writes"),
+                "{} has the write options as its about",
+                command.get_name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_options() {
+        let cli = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "image.png",
+            "ruSt",
+            "hello",
+            "--preserve-times",
+            "--preserve-mode",
+        ])
+        .unwrap();
+        let Command::Encode(encode) = cli.command else {
+            panic!("expected encode")
+        };
+        assert!(encode.write.preserve_times);
+        assert!(encode.write.preserve_mode);
+    }
+
+    #[test]
+    fn test_guard_options_are_shared() {
+        fn help<'a>(command: &ClapCommand<'a>, id: &str) -> Option<Option<&'a str>> {
+            command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .map(|arg| arg.get_help())
+        }
+
+        let guard = GuardOptions::augment_args(ClapCommand::new("guard"));
+        let cli = Cli::command();
+        let guarded: Vec<_> = subcommands(&cli)
+            .into_iter()
+            .filter(|command| help(command, "dry-run").is_some())
+            .collect();
+        assert!(guarded.len() > 1);
+        for command in &guarded {
+            for id in ["force", "dry-run"] {
+                assert_eq!(
+                    help(command, id),
+                    help(&guard, id),
+                    "{} --{}",
+                    command.get_name(),
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_batch() {
+        let validate = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Command::Encode(args) => args.validate_batch(),
+            Command::Decode(args) => args.validate_batch(),
+            _ => unreachable!("parsed an encode or decode command"),
+        };
+
+        assert!(validate(&["pngme", "encode", "dir", "ruSt", "hello"]).is_ok());
+        assert!(validate(&["pngme", "encode", "dir", "ruSt", "-"]).is_err());
+        assert!(validate(&["pngme", "encode", "dir", "ruSt", "hi", "--encrypt"]).is_err());
+        assert!(validate(&[
+            "pngme",
+            "encode",
+            "dir",
+            "ruSt",
+            "hi",
+            "--encrypt",
+            "--passphrase-file",
+            "passphrase.txt"
+        ])
+        .is_ok());
+
+        assert!(validate(&["pngme", "decode", "dir", "ruSt"]).is_ok());
+        assert!(validate(&["pngme", "decode", "dir", "ruSt", "--decrypt"]).is_err());
+    }
 }
@@ -0,0 +1,116 @@
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::color::{self, ChromaticitiesChunk, GammaChunk, SrgbChunk};
+use tracing::warn;
+
+use crate::{
+    cli::{Color, ColorSet, ColorShow, RenderingIntent},
+    output::{outln, Snapshot},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, resolve_output_path, save_png_or_preview,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Color) {
+    match command {
+        Color::Show(args) => show(args),
+        Color::Set(args) => set(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn show(args: ColorShow) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let mut found = false;
+
+    if let Some(chunk) = png.chunk_by_type("gAMA") {
+        let gamma = GammaChunk::try_from(chunk).context("invalid gAMA chunk")?;
+        outln!("gAMA: {}", gamma);
+        found = true;
+    }
+
+    if let Some(chunk) = png.chunk_by_type("sRGB") {
+        let srgb = SrgbChunk::try_from(chunk).context("invalid sRGB chunk")?;
+        outln!("sRGB: {}", srgb.rendering_intent());
+        found = true;
+    }
+
+    if let Some(chunk) = png.chunk_by_type("cHRM") {
+        let chrm = ChromaticitiesChunk::try_from(chunk).context("invalid cHRM chunk")?;
+        outln!("cHRM: {}", chrm);
+        found = true;
+    }
+
+    if !found {
+        outln!("No colour chunks found");
+    }
+
+    if let Err(err) = color::validate_color_chunks(png.chunks()) {
+        warn!("{}", err);
+    }
+}
+
+#[throws(anyhow::Error)]
+fn set(args: ColorSet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let mut chunks = vec![];
+
+    if let Some(gamma) = args.gamma {
+        chunks.push(GammaChunk::new(gamma).context("invalid gamma")?.to_chunk());
+    }
+
+    if let Some(intent) = args.srgb {
+        let intent = match intent {
+            RenderingIntent::Perceptual => color::RenderingIntent::Perceptual,
+            RenderingIntent::Relative => color::RenderingIntent::RelativeColorimetric,
+            RenderingIntent::Saturation => color::RenderingIntent::Saturation,
+            RenderingIntent::Absolute => color::RenderingIntent::AbsoluteColorimetric,
+        };
+        chunks.push(SrgbChunk::new(intent).to_chunk());
+    }
+
+    if let Some(values) = args.chromaticities {
+        let chrm = ChromaticitiesChunk::new(
+            (values[0], values[1]),
+            (values[2], values[3]),
+            (values[4], values[5]),
+            (values[6], values[7]),
+        )
+        .context("invalid chromaticities")?;
+        chunks.push(chrm.to_chunk());
+    }
+
+    let replaced: &[&[u8; 4]] = match (args.replace, args.srgb.is_some()) {
+        (false, _) => &[],
+        (true, true) => &[b"gAMA", b"cHRM"],
+        (true, false) => &[b"sRGB"],
+    };
+
+    png.retain_chunks(|chunk| {
+        let chunk_type = chunk.chunk_type().bytes();
+        !replaced.contains(&&chunk_type)
+            && !chunks
+                .iter()
+                .any(|new| new.chunk_type().bytes() == chunk_type)
+    });
+
+    for chunk in chunks {
+        png.insert_chunk_ordered(chunk);
+    }
+
+    color::validate_color_chunks(png.chunks())
+        .context("conflicting colour chunks, use --replace to remove them")?;
+
+    let saving = dry_run.is_none() && !is_stdio(&output_path);
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+
+    if saving {
+        outln!("Updated colour chunks");
+    }
+}
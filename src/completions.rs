@@ -0,0 +1,82 @@
+use std::io::Write;
+
+use anyhow::Context;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use fehler::throws;
+
+use crate::{
+    cli::{ChunkTypes, Cli, Completions},
+    output::outln,
+    util::parse_png_from_file,
+};
+
+/// Completes the chunk type after the png path of these subcommands with the
+/// types present in that png, using `pngme complete-chunk-types`.
+const BASH_CHUNK_TYPES: &str = r#"
+_pngme_chunk_types() {
+    if [[ ${COMP_CWORD} -eq 3 ]]; then
+        case "${COMP_WORDS[1]}" in
+            encode|decode|remove|extract|edit)
+                COMPREPLY=($(compgen -W "$(pngme complete-chunk-types "${COMP_WORDS[2]}" 2>/dev/null)" -- "${COMP_WORDS[3]}"))
+                [[ ${#COMPREPLY[@]} -gt 0 ]] && return 0
+                ;;
+        esac
+    fi
+    _pngme "$@"
+}
+
+complete -F _pngme_chunk_types -o bashdefault -o default pngme
+"#;
+
+const ZSH_CHUNK_TYPES: &str = r#"
+_pngme_chunk_types() {
+    if (( CURRENT == 4 )) && [[ ${words[2]} == (encode|decode|remove|extract|edit) ]]; then
+        local -a chunk_types
+        chunk_types=(${(f)"$(pngme complete-chunk-types ${words[3]} 2>/dev/null)"})
+        if (( ${#chunk_types} )); then
+            compadd -a chunk_types
+            return
+        fi
+    fi
+    _pngme "$@"
+}
+
+compdef _pngme_chunk_types pngme
+"#;
+
+const FISH_CHUNK_TYPES: &str = r#"
+complete -c pngme -n "__fish_seen_subcommand_from encode decode remove extract edit; and test (count (commandline -opc)) -eq 3" -f -a "(pngme complete-chunk-types (commandline -opc)[3] 2>/dev/null)"
+"#;
+
+#[throws(anyhow::Error)]
+pub fn run(args: Completions) {
+    let mut stdout = std::io::stdout().lock();
+    clap_complete::generate(args.shell, &mut Cli::command(), "pngme", &mut stdout);
+
+    let dynamic = match args.shell {
+        Shell::Bash => BASH_CHUNK_TYPES,
+        Shell::Zsh => ZSH_CHUNK_TYPES,
+        Shell::Fish => FISH_CHUNK_TYPES,
+        _ => "",
+    };
+
+    stdout
+        .write_all(dynamic.as_bytes())
+        .context("failed to write completions")?;
+}
+
+/// Prints the chunk types in a png once each, for the completion scripts.
+#[throws(anyhow::Error)]
+pub fn chunk_types(args: ChunkTypes) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let mut seen = vec![];
+
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if !seen.contains(&chunk_type) {
+            outln!("{}", chunk_type);
+            seen.push(chunk_type);
+        }
+    }
+}
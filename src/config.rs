@@ -0,0 +1,369 @@
+//! Defaults read from `~/.config/pngme/config.toml`, for example:
+//!
+//! ```toml
+//! # chunk type used when none is given, by commands where it comes last
+//! chunk-type = "ruSt"
+//! # text or json
+//! format = "json"
+//! # back up files before modifying them in place, see --backup
+//! backup = ".bak"
+//! # write a.pngme.png instead of modifying a.png in place
+//! output-suffix = ".pngme"
+//! # size budgets `pngme capacity` keeps messages within
+//! max-chunk-size = "64K"
+//! max-file-size = "1M"
+//!
+//! # selected with --profile watermark
+//! [profile.watermark]
+//! chunk-type = "wmRk"
+//! compress = "zstd"
+//! encrypt = true
+//! passphrase-file = "/etc/pngme/watermark.pass"
+//! ```
+//!
+//! Flags and environment variables always take precedence over the file, and
+//! a selected profile over the rest of the file.
+
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, Context};
+use clap::{Arg, Command, ValueEnum};
+use fehler::throws;
+use serde::Deserialize;
+
+use crate::cli::{Compression, OutputFormat};
+
+/// Subcommands whose chunk type can be left out, and the id of its argument.
+/// `encode` isn't one of them since its message follows the chunk type.
+const CHUNK_TYPE_ARGS: [(&str, &str); 5] = [
+    ("decode", "chunk-type"),
+    ("remove", "chunk-type"),
+    ("extract", "chunk-type"),
+    ("edit", "chunk-type"),
+    ("watch", "chunk"),
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub chunk_type: Option<String>,
+    pub format: Option<OutputFormat>,
+    pub backup: Option<String>,
+    pub output_suffix: Option<String>,
+    pub max_chunk_size: Option<String>,
+    pub max_file_size: Option<String>,
+    #[serde(rename = "profile")]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Embedding settings shared under a name, applied to `encode` and `decode`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pub chunk_type: Option<String>,
+    pub compress: Option<Compression>,
+    pub encrypt: bool,
+    pub passphrase_file: Option<String>,
+    pub recipients: Vec<String>,
+    pub identity: Option<String>,
+    pub sign: Option<String>,
+    pub verify: Option<String>,
+    pub hmac: Option<String>,
+}
+
+impl Config {
+    /// Adds the defaults from the file, and from the profile picked with
+    /// `--profile`, to the arguments of `command`.
+    #[throws(anyhow::Error)]
+    pub fn apply<'a>(&'a self, mut command: Command<'a>) -> Command<'a> {
+        command = command.arg(
+            Arg::new("profile")
+                .long("profile")
+                .global(true)
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Use the settings of a profile from the config file"),
+        );
+
+        if let Some(chunk_type) = &self.chunk_type {
+            command = with_chunk_type(command, chunk_type);
+        }
+
+        if let Some(format) = self
+            .format
+            .as_ref()
+            .and_then(|format| format.to_possible_value())
+        {
+            command = with_default(command, "format", format.get_name());
+        }
+
+        if let Some(backup) = &self.backup {
+            command = with_default(command, "backup", backup);
+        }
+
+        let sizes = [
+            ("max-chunk-size", &self.max_chunk_size),
+            ("max-file-size", &self.max_file_size),
+        ];
+        for (id, value) in sizes {
+            if let Some(value) = value {
+                command = with_default(command, id, value);
+            }
+        }
+
+        if let Some(name) = profile_name() {
+            let profile = self
+                .profiles
+                .get(&name)
+                .ok_or_else(|| anyhow!("no profile `{}` in the config file", name))?;
+            command = profile.apply(command);
+        }
+
+        command
+    }
+
+    /// Where to write a png read from `input` when no output path was given.
+    pub fn output_path(&self, input: &Path) -> PathBuf {
+        let Some(suffix) = &self.output_suffix else {
+            return input.to_path_buf();
+        };
+
+        let mut file_name = input.file_stem().unwrap_or_default().to_owned();
+        file_name.push(suffix);
+        if let Some(extension) = input.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+
+        input.with_file_name(file_name)
+    }
+}
+
+impl Profile {
+    fn apply<'a>(&'a self, mut command: Command<'a>) -> Command<'a> {
+        if let Some(chunk_type) = &self.chunk_type {
+            command = with_chunk_type(command, chunk_type);
+            // The message follows the chunk type, so it turns into an option
+            // to let the message take its place
+            command = command.mut_subcommand("encode", |encode| {
+                encode.mut_arg("chunk-type", |arg| {
+                    arg.long("chunk-type")
+                        .required(false)
+                        .default_value(chunk_type)
+                })
+            });
+        }
+
+        if let Some(compress) = self
+            .compress
+            .as_ref()
+            .and_then(|compress| compress.to_possible_value())
+        {
+            command = with_default(command, "compress", compress.get_name());
+        }
+
+        if self.encrypt {
+            command = with_default(command, "encrypt", "true");
+            command = with_default(command, "decrypt", "true");
+        }
+
+        if !self.recipients.is_empty() {
+            // clap borrows the list for as long as the command lives, which is
+            // the whole run
+            let recipients: &[&str] =
+                Vec::leak(self.recipients.iter().map(String::as_str).collect());
+            command = command.mut_subcommand("encode", |encode| {
+                encode.mut_arg("recipient", |arg| arg.default_values(recipients))
+            });
+        }
+
+        let paths = [
+            ("passphrase-file", &self.passphrase_file),
+            ("identity", &self.identity),
+            ("sign", &self.sign),
+            ("verify", &self.verify),
+            ("hmac", &self.hmac),
+        ];
+        for (id, value) in paths {
+            if let Some(value) = value {
+                command = with_default(command, id, value);
+            }
+        }
+
+        command
+    }
+}
+
+/// Sets the chunk type of the subcommands where it can be left out.
+fn with_chunk_type<'a>(mut command: Command<'a>, chunk_type: &'a str) -> Command<'a> {
+    for (subcommand, id) in CHUNK_TYPE_ARGS {
+        command = command.mut_subcommand(subcommand, |subcommand| {
+            subcommand.mut_arg(id, |arg| arg.required(false).default_value(chunk_type))
+        });
+    }
+
+    command
+}
+
+/// Sets the default of the argument `id` of every subcommand that has one.
+fn with_default<'a>(mut command: Command<'a>, id: &'static str, value: &'a str) -> Command<'a> {
+    for subcommand in command.get_subcommands_mut() {
+        if subcommand.get_arguments().any(|arg| arg.get_id() == id) {
+            *subcommand = std::mem::take(subcommand).mut_arg(id, |arg| arg.default_value(value));
+        }
+    }
+
+    command
+}
+
+/// Value of `--profile`, looked up before parsing the command line since the
+/// profile changes the defaults of other arguments.
+fn profile_name() -> Option<String> {
+    let mut args = env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .take_while(|arg| arg != "--");
+
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            return args.next();
+        }
+
+        if let Some(name) = arg.strip_prefix("--profile=") {
+            return Some(name.to_string());
+        }
+    }
+
+    None
+}
+
+/// `$PNGME_CONFIG`, or `pngme/config.toml` in `$XDG_CONFIG_HOME` or
+/// `~/.config`.
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("PNGME_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_dir.join("pngme").join("config.toml"))
+}
+
+/// Reads the config file, a missing file is the same as an empty one.
+#[throws(anyhow::Error)]
+pub fn load() -> &'static Config {
+    let config = match config_path() {
+        Some(path) if path.exists() => read(&path)?,
+        _ => Config::default(),
+    };
+
+    CONFIG.get_or_init(|| config)
+}
+
+#[throws(anyhow::Error)]
+fn read(path: &Path) -> Config {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("invalid config file {}", path.display()))?
+}
+
+/// The loaded config, or the defaults before [`load`] is called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches};
+
+    use super::*;
+    use crate::cli::{Cli, Command as CliCommand, Remove};
+
+    fn parse_remove(config: &Config, args: &[&str]) -> Remove {
+        // A variable of its own, other tests parse commands that read
+        // PNGME_BACKUP
+        let command = config
+            .apply(Cli::command())
+            .unwrap()
+            .mut_subcommand("remove", |remove| {
+                remove.mut_arg("backup", |arg| arg.env("PNGME_CONFIG_TEST_BACKUP"))
+            });
+        let matches = command
+            .try_get_matches_from([&["pngme", "remove"], args].concat())
+            .unwrap();
+        match Cli::from_arg_matches(&matches).unwrap().command {
+            CliCommand::Remove(remove) => remove,
+            _ => unreachable!("parsed a remove command"),
+        }
+    }
+
+    #[test]
+    fn test_flags_and_env_override_config() {
+        let config: Config = toml::from_str(r#"backup = ".config""#).unwrap();
+
+        let remove = parse_remove(&config, &["image.png", "ruSt", "--backup"]);
+        assert_eq!(remove.backup.backup.as_deref(), Some(".bak"));
+        let remove = parse_remove(&config, &["image.png", "ruSt"]);
+        assert_eq!(remove.backup.backup.as_deref(), Some(".config"));
+
+        env::set_var("PNGME_CONFIG_TEST_BACKUP", ".env");
+        let from_env = parse_remove(&config, &["image.png", "ruSt"]);
+        let from_flag = parse_remove(&config, &["image.png", "ruSt", "--backup=.flag"]);
+        env::remove_var("PNGME_CONFIG_TEST_BACKUP");
+
+        assert_eq!(from_env.backup.backup.as_deref(), Some(".env"));
+        assert_eq!(from_flag.backup.backup.as_deref(), Some(".flag"));
+    }
+
+    #[test]
+    fn test_chunk_type_default() {
+        let config: Config = toml::from_str(r#"chunk-type = "cfGt""#).unwrap();
+
+        let remove = parse_remove(&config, &["image.png"]);
+        assert_eq!(remove.chunk_type, "cfGt");
+        let remove = parse_remove(&config, &["image.png", "ruSt"]);
+        assert_eq!(remove.chunk_type, "ruSt");
+    }
+
+    #[test]
+    fn test_output_path() {
+        let config: Config = toml::from_str(r#"output-suffix = ".pngme""#).unwrap();
+        assert_eq!(
+            config.output_path(Path::new("dir/a.png")),
+            Path::new("dir/a.pngme.png")
+        );
+        assert_eq!(config.output_path(Path::new("a")), Path::new("a.pngme"));
+        assert_eq!(
+            Config::default().output_path(Path::new("a.png")),
+            Path::new("a.png")
+        );
+    }
+
+    #[test]
+    fn test_malformed_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        std::fs::write(&path, "chunk-type = ").unwrap();
+        let error = read(&path).unwrap_err();
+        assert!(error.to_string().starts_with("invalid config file"));
+
+        std::fs::write(&path, r#"chunk-tpye = "ruSt""#).unwrap();
+        assert!(read(&path).is_err());
+
+        std::fs::write(&path, r#"format = "yaml""#).unwrap();
+        assert!(read(&path).is_err());
+
+        std::fs::write(&path, "[profile.empty]\n").unwrap();
+        assert!(read(&path).unwrap().profiles.contains_key("empty"));
+    }
+}
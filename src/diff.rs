@@ -0,0 +1,73 @@
+use fehler::throws;
+use pngme_lib::{
+    chunk::Chunk,
+    diff::{self, ByteChange, ChunkChange},
+};
+
+use crate::{
+    cli::{Diff, OutputFormat},
+    output::{outln, print_json, ChangeReport},
+    util::{hex, parse_png_from_file_with},
+};
+
+fn describe(index: usize, chunk: &Chunk) -> String {
+    format!(
+        "[{}] {}: {} bytes, crc {:#010x}, sha256 {}",
+        index,
+        chunk.chunk_type(),
+        chunk.length(),
+        chunk.crc(),
+        diff::data_hash(chunk)
+    )
+}
+
+#[throws(anyhow::Error)]
+fn print_byte_changes(changes: &[ByteChange]) {
+    for change in changes {
+        outln!(
+            "    @{}: {} -> {}",
+            change.offset,
+            hex(change.old),
+            hex(change.new)
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+pub fn run(args: Diff) {
+    let old = parse_png_from_file_with(&args.old_png_path, args.parse.options())?;
+    let new = parse_png_from_file_with(&args.new_png_path, args.parse.options())?;
+    let changes = diff::diff(&old, &new);
+
+    if args.format == OutputFormat::Json {
+        let reports: Vec<ChangeReport> = changes
+            .iter()
+            .map(|change| ChangeReport::new(change, args.bytes))
+            .collect();
+        return print_json(&reports)?;
+    }
+
+    for change in &changes {
+        match *change {
+            ChunkChange::Removed { index, chunk } => outln!("- {}", describe(index, chunk)),
+            ChunkChange::Added { index, chunk } => outln!("+ {}", describe(index, chunk)),
+            ChunkChange::Modified {
+                old_index,
+                new_index,
+                old,
+                new,
+            } => {
+                outln!("- {}", describe(old_index, old));
+                outln!("+ {}", describe(new_index, new));
+
+                if args.bytes {
+                    print_byte_changes(&diff::byte_changes(old.data(), new.data()))?;
+                }
+            }
+        }
+    }
+
+    if changes.is_empty() {
+        outln!("No differences");
+    }
+}
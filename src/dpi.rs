@@ -0,0 +1,57 @@
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::phys::PhysChunk;
+
+use crate::{
+    cli::{Dpi, DpiGet, DpiSet},
+    error::not_found,
+    output::{outln, Snapshot},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, resolve_output_path, save_png_or_preview,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Dpi) {
+    match command {
+        Dpi::Get(args) => get(args),
+        Dpi::Set(args) => set(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn get(args: DpiGet) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let chunk = png
+        .chunk_by_type("pHYs")
+        .ok_or_else(|| not_found("no physical dimensions found"))?;
+    let phys = PhysChunk::try_from(chunk).context("invalid pHYs chunk")?;
+
+    match phys.dpi() {
+        Some((x, y)) if x == y => outln!("{} dpi", x),
+        Some((x, y)) => outln!("{}x{} dpi", x, y),
+        None => outln!(
+            "unknown unit, aspect ratio {}:{}",
+            phys.x_pixels_per_unit(),
+            phys.y_pixels_per_unit()
+        ),
+    }
+}
+
+#[throws(anyhow::Error)]
+fn set(args: DpiSet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    png.retain_chunks(|chunk| chunk.chunk_type().bytes() != *b"pHYs");
+    png.insert_chunk_ordered(PhysChunk::from_dpi(args.dpi).to_chunk());
+
+    if !is_stdio(&output_path) {
+        outln!("Set resolution to {} dpi", args.dpi);
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
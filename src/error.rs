@@ -0,0 +1,141 @@
+use std::process::ExitCode;
+
+use pngme_lib::{
+    ancillary::AncillaryError,
+    apng::ApngError,
+    chunk::ChunkParseError,
+    chunk_type::ChunkTypeParseError,
+    compression::CompressionError,
+    crypto::CryptoError,
+    fragment::FragmentError,
+    kv::KvError,
+    label::LabelError,
+    mac::MacError,
+    palette::PaletteError,
+    png::{PngChunkRemoveError, PngParseError},
+    sign::SignError,
+};
+use thiserror::Error;
+
+/// Listed in `--help`, keep in sync with [`Failure`].
+pub const EXIT_CODES: &str = "EXIT CODES:
+    0  success
+    1  other failure
+    2  chunk, keyword or file not found
+    3  corrupt png or payload
+    4  io error
+    5  invalid arguments
+    6  signature, integrity tag or decryption check failed";
+
+/// Failures detected by the cli itself, as opposed to the library errors
+/// wrapped in [`anyhow::Error`].
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    InvalidArguments(String),
+}
+
+pub fn not_found(message: impl Into<String>) -> CliError {
+    CliError::NotFound(message.into())
+}
+
+pub fn invalid_arguments(message: impl Into<String>) -> CliError {
+    CliError::InvalidArguments(message.into())
+}
+
+/// Classes of failure, each exiting with its own stable code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Other = 1,
+    NotFound = 2,
+    Parse = 3,
+    Io = 4,
+    InvalidArguments = 5,
+    Verification = 6,
+}
+
+impl Failure {
+    fn of(cause: &(dyn std::error::Error + 'static)) -> Option<Failure> {
+        if let Some(error) = cause.downcast_ref::<CliError>() {
+            return Some(match error {
+                CliError::NotFound(_) => Failure::NotFound,
+                CliError::InvalidArguments(_) => Failure::InvalidArguments,
+            });
+        }
+
+        if let Some(error) = cause.downcast_ref::<PngParseError>() {
+            return Some(match error {
+                PngParseError::Io(_) => Failure::Io,
+                _ => Failure::Parse,
+            });
+        }
+
+        if let Some(error) = cause.downcast_ref::<PngChunkRemoveError>() {
+            return Some(match error {
+                PngChunkRemoveError::ChunkNotFound => Failure::NotFound,
+                PngChunkRemoveError::ChunkTypeParsingError(_) => Failure::InvalidArguments,
+            });
+        }
+
+        if let Some(error) = cause.downcast_ref::<ApngError>() {
+            return Some(match error {
+                ApngError::NotAnimated => Failure::NotFound,
+                _ => Failure::Parse,
+            });
+        }
+
+        if cause.is::<AncillaryError>()
+            || cause.is::<ChunkParseError>()
+            || cause.is::<CompressionError>()
+            || cause.is::<FragmentError>()
+            || cause.is::<KvError>()
+            || cause.is::<LabelError>()
+            || cause.is::<PaletteError>()
+            || cause.is::<std::str::Utf8Error>()
+        {
+            return Some(Failure::Parse);
+        }
+
+        if cause.is::<SignError>() || cause.is::<MacError>() || cause.is::<CryptoError>() {
+            return Some(Failure::Verification);
+        }
+
+        if cause.is::<ChunkTypeParseError>() || cause.is::<clap::Error>() {
+            return Some(Failure::InvalidArguments);
+        }
+
+        if cause.is::<std::io::Error>() {
+            return Some(Failure::Io);
+        }
+
+        None
+    }
+
+    /// Classifies `error` by the outermost cause in its chain that has a
+    /// class, since inner causes like io errors from a decompressor may not
+    /// say what went wrong.
+    pub fn classify(error: &anyhow::Error) -> Failure {
+        error
+            .chain()
+            .find_map(Failure::of)
+            .unwrap_or(Failure::Other)
+    }
+}
+
+/// Whether `error` comes from writing to a pipe whose reader went away.
+pub fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|error| error.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::exif::ExifChunk;
+
+use crate::{
+    cli::{Exif, ExifDump, ExifImport},
+    error::not_found,
+    output::{outln, Snapshot},
+    util::{check_output_path, parse_png_from_file, resolve_output_path, save_png_or_preview},
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Exif) {
+    match command {
+        Exif::Dump(args) => dump(args),
+        Exif::Import(args) => import(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn dump(args: ExifDump) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let chunk = png
+        .chunk_by_type("eXIf")
+        .ok_or_else(|| not_found("no exif data found"))?;
+    let exif = ExifChunk::try_from(chunk)?
+        .parse()
+        .context("failed to parse exif data")?;
+
+    for entry in exif.entries() {
+        let name = entry
+            .name()
+            .map_or_else(|| format!("{:#06x}", entry.tag), str::to_string);
+
+        outln!("{} {}: {}", entry.ifd, name, entry.value);
+    }
+}
+
+#[throws(anyhow::Error)]
+fn import(args: ExifImport) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let data = std::fs::read(&args.exif_path).context("failed to read exif file")?;
+    let exif = ExifChunk::new(&data).context("invalid exif data")?;
+
+    // Only one eXIf chunk is allowed per file
+    png.retain_chunks(|chunk| chunk.chunk_type().bytes() != *b"eXIf");
+    png.insert_chunk_ordered(exif.to_chunk());
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
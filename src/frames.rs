@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use anyhow::Context;
+use fehler::{throw, throws};
+use pngme_lib::{
+    apng::{self, Animation},
+    png::Png,
+};
+
+use crate::{
+    cli::{Frames, FramesCommand, FramesExtract, OutputFormat, ParseArgs},
+    error::not_found,
+    output::{outln, print_json, FramesReport},
+    util::{check_output_path, parse_png_from_file_with, save_png_to_file},
+};
+
+#[throws(anyhow::Error)]
+pub fn run(args: Frames) {
+    match args.command {
+        Some(FramesCommand::Extract(args)) => extract(args)?,
+        None => list(&args)?,
+    }
+}
+
+#[throws(anyhow::Error)]
+fn read_animation(png_path: &Path, parse: &ParseArgs) -> (Png, Animation) {
+    let png = parse_png_from_file_with(png_path, parse.options())?;
+    if !apng::is_animated(&png) {
+        throw!(not_found("png isn't animated"))
+    }
+    let animation = Animation::from_png(&png).context("invalid animation")?;
+
+    (png, animation)
+}
+
+#[throws(anyhow::Error)]
+fn list(args: &Frames) {
+    let png_path = args
+        .png_path
+        .as_deref()
+        .expect("required without a subcommand");
+    let (_, animation) = read_animation(png_path, &args.parse)?;
+
+    if args.format == OutputFormat::Json {
+        return print_json(&FramesReport::from(&animation))?;
+    }
+
+    let plays = match animation.control.num_plays() {
+        0 => "forever".to_string(),
+        plays => plays.to_string(),
+    };
+    outln!("Frames: {}", animation.frames.len());
+    outln!("Plays: {}", plays);
+    outln!("Duration: {} ms", animation.duration().as_millis());
+
+    for (index, frame) in animation.frames.iter().enumerate() {
+        let control = &frame.control;
+        let default_image = match frame.is_default_image {
+            true => " (default image)",
+            false => "",
+        };
+        outln!(
+            "  [{}] {}x{} at ({}, {}), {} ms, {} bytes, dispose {}, blend {}{}",
+            index,
+            control.width,
+            control.height,
+            control.x_offset,
+            control.y_offset,
+            control.delay().as_millis(),
+            frame.data.len(),
+            control.dispose_op,
+            control.blend_op,
+            default_image
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+fn extract(args: FramesExtract) {
+    let (png, animation) = read_animation(&args.png_path, &args.parse)?;
+
+    std::fs::create_dir_all(&args.out_dir)
+        .with_context(|| format!("failed to create {}", args.out_dir.display()))?;
+
+    // Check every path first so a refusal doesn't leave half the frames
+    let paths: Vec<_> = (0..animation.frames.len())
+        .map(|index| args.out_dir.join(format!("frame-{:03}.png", index)))
+        .collect();
+    for path in &paths {
+        check_output_path(&args.png_path, path, args.force)?;
+    }
+
+    for (index, (frame, path)) in animation.frames.iter().zip(&paths).enumerate() {
+        let frame_png = frame
+            .to_png(&png)
+            .with_context(|| format!("failed to rebuild frame {}", index))?;
+        save_png_to_file(frame_png, path, &args.write)?;
+        outln!("{}", path.display());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{borrow::Cow, path::PathBuf};
+
+use fehler::{throw, throws};
+use pngme_lib::{chunk::Chunk, compression, label, text::TextualChunk};
+use regex::{Regex, RegexBuilder};
+use tracing::warn;
+
+use crate::{
+    batch::{expand_batch_path, is_batch_path},
+    cli::Grep,
+    error::{invalid_arguments, not_found},
+    output::outln,
+    util::parse_png_from_file,
+};
+
+/// Characters of context kept on each side of a match.
+const CONTEXT: usize = 30;
+
+#[throws(anyhow::Error)]
+pub fn run(args: Grep) {
+    let regex = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|error| invalid_arguments(format!("invalid pattern: {}", error)))?;
+
+    let mut paths: Vec<PathBuf> = vec![];
+    for path in &args.png_paths {
+        match is_batch_path(path) {
+            true => paths.extend(expand_batch_path(path)?),
+            false => paths.push(path.clone()),
+        }
+    }
+
+    let mut matched = false;
+    for path in &paths {
+        // Like grep, a file that can't be read doesn't stop the search
+        let png = match parse_png_from_file(path) {
+            Ok(png) => png,
+            Err(error) => {
+                warn!("{}: {:#}", path.display(), error);
+                continue;
+            }
+        };
+
+        let chunks = png.chunks().iter().filter(|chunk| {
+            !chunk.chunk_type().is_critical()
+                && args
+                    .chunk_type
+                    .as_ref()
+                    .is_none_or(|chunk_type| chunk.chunk_type().to_string() == *chunk_type)
+        });
+
+        for chunk in chunks {
+            let text = decoded_text(chunk);
+            for line in text.lines() {
+                if let Some(excerpt) = excerpt(&regex, line) {
+                    outln!("{}: {}: {}", path.display(), chunk.chunk_type(), excerpt);
+                    matched = true;
+                }
+            }
+        }
+    }
+
+    if !matched {
+        throw!(not_found("no matches"))
+    }
+}
+
+/// Chunk data as text, with text chunks, labels and compression decoded
+/// where possible.
+fn decoded_text(chunk: &Chunk) -> Cow<'_, str> {
+    if TextualChunk::is_textual(chunk) {
+        if let Ok(text) = TextualChunk::try_from(chunk) {
+            return Cow::Owned(format!("{}: {}", text.keyword(), text.text()));
+        }
+    }
+
+    let mut data = chunk.data();
+    if let Ok((_, payload)) = label::split(data) {
+        data = payload;
+    }
+
+    if compression::is_compressed(data) {
+        if let Ok(decompressed) = compression::decompress(data) {
+            return Cow::Owned(String::from_utf8_lossy(&decompressed).into_owned());
+        }
+    }
+
+    String::from_utf8_lossy(data)
+}
+
+/// The first match in `line` with some context around it, if there is one.
+fn excerpt(regex: &Regex, line: &str) -> Option<String> {
+    let found = regex.find(line)?;
+
+    let start = line[..found.start()]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT - 1)
+        .map_or(0, |(index, _)| index);
+    let end = line[found.end()..]
+        .char_indices()
+        .nth(CONTEXT)
+        .map_or(line.len(), |(index, _)| found.end() + index);
+
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < line.len() { "..." } else { "" };
+
+    Some(format!("{}{}{}", prefix, &line[start..end], suffix))
+}
@@ -0,0 +1,73 @@
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::icc::IccProfileChunk;
+use tracing::warn;
+
+use crate::{
+    cli::{Icc, IccEmbed, IccExtract},
+    error::not_found,
+    output::{outln, Snapshot},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, read_input_file, resolve_output_path,
+        save_png_or_preview, write_output_file,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Icc) {
+    match command {
+        Icc::Extract(args) => extract(args),
+        Icc::Embed(args) => embed(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn extract(args: IccExtract) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let chunk = png
+        .chunk_by_type("iCCP")
+        .ok_or_else(|| not_found("no icc profile found"))?;
+    let icc = IccProfileChunk::try_from(chunk).context("invalid iCCP chunk")?;
+
+    write_output_file(&args.icc_path, icc.profile())?;
+
+    if !is_stdio(&args.icc_path) {
+        outln!(
+            "Extracted icc profile \"{}\" ({} bytes)",
+            icc.name(),
+            icc.profile().len()
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+fn embed(args: IccEmbed) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => args
+            .icc_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !is_stdio(stem.as_ref()))
+            .unwrap_or("ICC Profile")
+            .to_string(),
+    };
+
+    let profile = read_input_file(&args.icc_path)?;
+    let icc = IccProfileChunk::new(&name, profile).context("invalid icc profile")?;
+
+    if png.chunk_by_type("sRGB").is_some() {
+        warn!("the png also has an sRGB chunk, which should not be combined with iCCP");
+    }
+
+    png.retain_chunks(|chunk| chunk.chunk_type().bytes() != *b"iCCP");
+    png.insert_chunk_ordered(icc.to_chunk()?);
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
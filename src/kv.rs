@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::{kv::KvStore, png::Png};
+
+use crate::{
+    cli::{Kv, KvDel, KvGet, KvList, KvSet, OutputFormat},
+    error::not_found,
+    output::{outln, print_json, Snapshot},
+    util::{check_output_path, parse_png_from_file, resolve_output_path, save_png_or_preview},
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Kv) {
+    match command {
+        Kv::Set(args) => set(args),
+        Kv::Get(args) => get(args),
+        Kv::List(args) => list(args),
+        Kv::Del(args) => del(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn read_store(png: &Png) -> KvStore {
+    KvStore::from_png(png).context("failed to read key-value store")?
+}
+
+/// Replaces the store chunk of `png`, dropping it once the store is empty.
+fn write_store(png: &mut Png, store: &KvStore) {
+    let chunk_type = pngme_lib::kv::chunk_type();
+    png.retain_chunks(|chunk| *chunk.chunk_type() != chunk_type);
+
+    if !store.is_empty() {
+        png.insert_chunk_ordered(store.to_chunk());
+    }
+}
+
+#[throws(anyhow::Error)]
+fn set(args: KvSet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let mut store = read_store(&png)?;
+    store.set(&args.key, &args.value);
+    write_store(&mut png, &store);
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
+
+#[throws(anyhow::Error)]
+fn get(args: KvGet) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let store = read_store(&png)?;
+
+    let value = store
+        .get(&args.key)
+        .ok_or_else(|| not_found("key not found"))?;
+
+    outln!("{}", value);
+}
+
+#[throws(anyhow::Error)]
+fn list(args: KvList) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let store = read_store(&png)?;
+
+    if args.format == OutputFormat::Json {
+        return print_json(&store.iter().collect::<BTreeMap<_, _>>())?;
+    }
+
+    for (key, value) in store.iter() {
+        outln!("{}={}", key, value);
+    }
+}
+
+#[throws(anyhow::Error)]
+fn del(args: KvDel) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let mut store = read_store(&png)?;
+    store
+        .remove(&args.key)
+        .ok_or_else(|| not_found("key not found"))?;
+    write_store(&mut png, &store);
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
@@ -1,77 +1,1388 @@
+mod ancillary;
+mod batch;
+mod bundle;
 mod cli;
+mod color;
+mod completions;
+mod config;
+mod diff;
+mod dpi;
+mod error;
+mod exif;
+mod frames;
+mod grep;
+mod icc;
+mod kv;
+mod manifest;
+mod output;
+mod palette;
+mod progress;
+mod secret;
+mod serve;
+mod splt;
+mod text;
+mod time;
 mod util;
+mod watch;
+mod xmp;
 
-use std::str::FromStr;
+use std::{
+    borrow::Cow,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+};
 
-use anyhow::Context;
-use clap::Parser;
-use cli::{Cli, Decode, Encode, Print, Remove};
-use fehler::throws;
-use pngme_lib::{chunk::Chunk, chunk_type::ChunkType};
-use util::{parse_png_from_file, save_png_to_file};
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{CommandFactory, FromArgMatches};
+use cli::{
+    Capacity, Check, Cli, Command, Compression, CopyChunks, Decode, Duplicates, Edit, Encode,
+    Encoding, Extract, GenType, Generate, GroupBy, Info, Keygen, MessageSource, Mode, Optimize,
+    OutputFormat, Position, Print, Remove, Repair, Scan, SortKey, Style,
+};
+use error::{invalid_arguments, not_found, Failure};
+use fehler::{throw, throws};
+use output::{
+    outln, print_json, CapacityReport, ChunkReport, DecodeReport, GroupReport, InfoReport,
+    Snapshot, StatsReport,
+};
+use pngme_lib::{
+    apng,
+    builder::PngBuilder,
+    chunk::Chunk,
+    chunk_type::{ChunkCategory, ChunkType},
+    codec::CodecRegistry,
+    compression::{self, CompressionMethod},
+    crypto, fragment, label, mac,
+    png::{ChunkRow, DuplicatePolicy, ParseOptions, Png},
+    repair, scan, sign,
+    sniff::{sniff, ContentType},
+    stats::Stats,
+    stego, validate,
+};
+use tracing::{warn, Level};
+use util::{
+    backup_file, check_output_path, hex, is_stdio, open_chunk_reader, parse_png_from_file,
+    parse_png_from_file_with, read_clipboard, read_input_file, read_key_file, read_message_file,
+    read_passphrase, resolve_output_path, save_png_or_preview, save_png_to_file, write_clipboard,
+    write_output_file, write_private_file, STDIO_PATH,
+};
+
+fn main() -> ExitCode {
+    let Err(error) = run() else {
+        return ExitCode::SUCCESS;
+    };
+
+    if let Some(error) = error.downcast_ref::<clap::Error>() {
+        // Help and version output come through here as well
+        let _ = error.print();
+        if !error.use_stderr() {
+            return ExitCode::SUCCESS;
+        }
+    } else if error::is_broken_pipe(&error) {
+        // The reader, like `head`, has seen all it wanted
+        return ExitCode::SUCCESS;
+    } else {
+        eprintln!("Error: {:?}", error);
+    }
+
+    Failure::classify(&error).into()
+}
 
 #[throws(anyhow::Error)]
-fn main() {
-    match Cli::parse() {
-        Cli::Encode(args) => encode(args),
-        Cli::Decode(args) => decode(args),
-        Cli::Remove(args) => remove(args),
-        Cli::Print(args) => print(args),
+fn run() {
+    let config = config::load()?;
+    let matches = config.apply(Cli::command())?.try_get_matches()?;
+    let cli = Cli::from_arg_matches(&matches)?;
+    init_logging(&cli);
+    progress::init(cli.quiet);
+
+    match cli.command {
+        Command::Encode(args) => batch::dispatch(*args, encode),
+        Command::Decode(args) => batch::dispatch(args, decode),
+        Command::Remove(args) => batch::dispatch(args, remove),
+        Command::Print(args) => batch::dispatch(args, print),
+        Command::Info(args) => batch::dispatch(args, info),
+        Command::Stats(args) => batch::dispatch(args, stats),
+        Command::Check(args) => batch::dispatch(args, check),
+        Command::Grep(args) => grep::run(args),
+        Command::Frames(args) => frames::run(args),
+        Command::Palette(args) => palette::run(args),
+        Command::Scan(args) => batch::dispatch(args, scan),
+        Command::Capacity(args) => batch::dispatch(args, capacity),
+        Command::Generate(args) => generate(args),
+        Command::Diff(args) => diff::run(args),
+        Command::Copy(args) => copy(args),
+        Command::Watch(args) => watch::run(args),
+        Command::Apply(args) => manifest::run(args),
+        Command::Serve(args) => serve::run(args),
+        Command::Repair(args) => repair(args),
+        Command::Optimize(args) => optimize(args),
+        Command::Extract(args) => extract(args),
+        Command::Edit(args) => edit(args),
+        Command::Keygen(args) => keygen(args),
+        Command::GenType(args) => gen_type(args),
+        Command::SplitSecret(args) => secret::split(args),
+        Command::JoinSecret(args) => secret::join(args),
+        Command::Completions(args) => completions::run(args),
+        Command::ChunkTypes(args) => completions::chunk_types(args),
+        Command::Text(command) => text::run(command),
+        Command::Exif(command) => exif::run(command),
+        Command::Xmp(command) => xmp::run(command),
+        Command::Icc(command) => icc::run(command),
+        Command::Time(command) => time::run(command),
+        Command::Dpi(command) => dpi::run(command),
+        Command::Color(command) => color::run(command),
+        Command::Ancillary(command) => ancillary::run(command),
+        Command::Splt(command) => splt::run(command),
+        Command::Kv(command) => kv::run(command),
     }?
 }
 
+/// Logs to stderr, warnings by default.
+fn init_logging(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
 #[throws(anyhow::Error)]
 fn encode(args: Encode) {
     let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(
+        &args.png_path,
+        args.output.as_deref().or(args.output_png_path.as_deref()),
+    );
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
 
-    let chunk_type = ChunkType::from_str(&args.chunk_type).context("invalid chunk type")?;
-    let data = args.message.into_bytes();
-    let chunk = Chunk::new(chunk_type, data);
+    // Read and check the other pngs up front. Like the main png they are
+    // only written once every chunk has been placed in all of them, so none
+    // is left modified when a later step fails
+    let mut others = vec![];
+    for path in &args.split_across {
+        let other = parse_png_from_file(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        check_output_path(path, path, args.guard.force)?;
+        let dry_run = args.guard.dry_run.then(|| Snapshot::new(&other));
+        others.push((other, path, dry_run, vec![]));
+    }
+
+    let authenticated =
+        args.encrypt || !args.recipient.is_empty() || args.hmac.is_some() || args.sign.is_some();
+    if args.decoys.is_some() && !authenticated {
+        throw!(invalid_arguments(
+            "decoys need --encrypt, --recipient, --hmac or --sign to tell the message apart"
+        ))
+    }
 
-    png.append_chunk(chunk);
+    if args.message_from_stdin() && is_stdio(&args.png_path) {
+        throw!(invalid_arguments(
+            "the png and the message can't both be read from stdin"
+        ))
+    }
+    if args.set.iter().filter(|set| set.message.is_stdin()).count() > 1 {
+        throw!(invalid_arguments("only one message can be read from stdin"))
+    }
+    if args.mode == Mode::Lsb && args.set.len() > 1 {
+        throw!(invalid_arguments(
+            "the pixel data can only hide one message, use a single --set"
+        ))
+    }
 
-    let output_path = if let Some(path) = args.output_png_path {
-        path
+    // Every message is read before anything is encoded, so a missing file
+    // doesn't leave the png half written
+    let messages = if args.set.is_empty() {
+        let chunk_type = args.chunk_type.as_deref().expect("enforced by clap");
+        let chunk_type = ChunkType::from_str(chunk_type).context("invalid chunk type")?;
+        vec![(chunk_type, read_message(&args)?)]
     } else {
-        args.png_path
+        args.set
+            .iter()
+            .map(|set| {
+                let data = match &set.message {
+                    MessageSource::Text(text) => text.clone().into_bytes(),
+                    MessageSource::File(path) => read_input_file(path)?,
+                };
+                Ok((set.chunk_type, data))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
     };
 
-    save_png_to_file(png, &output_path)?;
+    let passphrase = match args.encrypt {
+        true => Some(read_passphrase(args.passphrase_file.as_deref(), true)?),
+        false => None,
+    };
+    let secret_key = args.sign.as_deref().map(read_key_file).transpose()?;
+
+    let mut chunks = vec![];
+    for (chunk_type, data) in messages {
+        let data = seal_message(data, &chunk_type, passphrase.as_deref(), &secret_key, &args)?;
+
+        if args.mode == Mode::Lsb {
+            let data = with_label(args.label.as_deref(), data)?;
+            stego::embed(&mut png, &data).context("failed to hide message in pixel data")?;
+            continue;
+        }
+
+        let mut payloads = split_payloads(data, others.len() + 1, &args)?;
+        let other_payloads = match others.is_empty() {
+            true => vec![],
+            false => payloads.split_off(1),
+        };
+        chunks.extend(
+            payloads
+                .into_iter()
+                .map(|payload| Chunk::new(chunk_type, payload)),
+        );
+
+        for ((_, _, _, other_chunks), data) in others.iter_mut().zip(other_payloads) {
+            other_chunks.push(Chunk::new(chunk_type, data));
+        }
+    }
+
+    for (other, path, _, other_chunks) in &mut others {
+        place_chunks(other, std::mem::take(other_chunks), &args)
+            .with_context(|| format!("failed to encode into {}", path.display()))?;
+    }
+    if !chunks.is_empty() {
+        place_chunks(&mut png, chunks, &args)?;
+    }
+
+    for (other, path, dry_run, _) in others {
+        if dry_run.is_none() {
+            backup_file(path, &args.backup)?;
+        }
+        save_png_or_preview(other, path, dry_run, &args.write)?;
+    }
+
+    if output_path == args.png_path && dry_run.is_none() {
+        backup_file(&args.png_path, &args.backup)?;
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
+
+/// The single message of `encode`, from wherever the arguments say.
+#[throws(anyhow::Error)]
+fn read_message(args: &Encode) -> Vec<u8> {
+    if let Some(dir) = &args.input_dir {
+        return bundle::pack(dir)?;
+    }
+
+    match (&args.message, &args.input_file, &args.message_file) {
+        (_, Some(path), _) => std::fs::read(path).context("failed to read input file")?,
+        (_, None, Some(path)) => read_message_file(path)?.into_bytes(),
+        (Some(message), None, None) if message == STDIO_PATH => {
+            read_message_file(Path::new(STDIO_PATH))?.into_bytes()
+        }
+        (Some(message), None, None) => message.clone().into_bytes(),
+        (None, None, None) if args.message_from_clipboard => read_clipboard()?.into_bytes(),
+        (None, None, None) => unreachable!("enforced by clap"),
+    }
+}
+
+/// Compresses, encrypts, tags and signs a message, as the encode arguments
+/// ask.
+#[throws(anyhow::Error)]
+fn seal_message(
+    mut data: Vec<u8>,
+    chunk_type: &ChunkType,
+    passphrase: Option<&str>,
+    secret_key: &Option<[u8; 32]>,
+    args: &Encode,
+) -> Vec<u8> {
+    // Archives compress well, so directories are compressed by default
+    let compress = args
+        .compress
+        .or(args.input_dir.is_some().then_some(Compression::Zstd));
+    if let Some(method) = compress {
+        let method = match method {
+            Compression::Zlib => CompressionMethod::Zlib,
+            Compression::Zstd => CompressionMethod::Zstd,
+        };
+
+        data = compression::compress(method, &data).context("failed to compress message")?;
+    }
+
+    if let Some(passphrase) = passphrase {
+        data =
+            crypto::encrypt(passphrase.as_bytes(), &data).context("failed to encrypt message")?;
+    }
+
+    if !args.recipient.is_empty() {
+        let recipients: Vec<&str> = args.recipient.iter().map(String::as_str).collect();
+        data = crypto::encrypt_to_recipients(&recipients, &data)
+            .context("failed to encrypt message")?;
+    }
+
+    if let Some(key) = &args.hmac {
+        data = mac::tag(key.as_bytes(), chunk_type, &data);
+    }
+
+    if let Some(secret_key) = secret_key {
+        data = sign::sign(secret_key, &data);
+    }
+
+    data
+}
+
+/// Splits an encoded message into `parts` payloads for `--split-across`, or
+/// as the fragment and decoy options ask, each wrapped in the label.
+#[throws(anyhow::Error)]
+fn split_payloads(data: Vec<u8>, parts: usize, args: &Encode) -> Vec<Vec<u8>> {
+    if parts > 1 {
+        fragment::split_into(&data, parts)
+    } else if let Some(decoys) = args.decoys {
+        crypto::with_decoys(&data, decoys)
+    } else if let Some(size) = args.max_fragment_size {
+        fragment::split(&data, size).context("failed to fragment message")?
+    } else {
+        vec![data]
+    }
+    .into_iter()
+    .map(|payload| with_label(args.label.as_deref(), payload))
+    .collect::<anyhow::Result<Vec<_>>>()?
+}
+
+/// Wraps an encoded payload in an envelope named `label`, if there is one.
+#[throws(anyhow::Error)]
+fn with_label(label: Option<&str>, payload: Vec<u8>) -> Vec<u8> {
+    match label {
+        Some(name) => label::label(name, &payload).context("invalid label")?,
+        None => payload,
+    }
+}
+
+/// Label of the message in a chunk, if it has a valid one.
+fn chunk_label(chunk: &Chunk) -> Option<&str> {
+    label::split(chunk.data()).ok().map(|(label, _)| label)
+}
+
+/// The chunk with its label removed, if it matches `label`. Without a label
+/// every message matches, labelled or not.
+fn select_label(chunk: Chunk, label: Option<&str>) -> Option<Chunk> {
+    let Ok((name, payload)) = label::split(chunk.data()) else {
+        return label.is_none().then_some(chunk);
+    };
+
+    label
+        .is_none_or(|label| label == name)
+        .then(|| Chunk::new(*chunk.chunk_type(), payload.to_vec()))
+}
+
+/// Inserts encoded chunks where the encode arguments ask for them.
+#[throws(anyhow::Error)]
+fn place_chunks(png: &mut Png, chunks: Vec<Chunk>, args: &Encode) {
+    if let Some(position) = &args.position {
+        let index = match position {
+            Position::Index(index) => *index,
+            Position::Before(chunk_type) => chunk_position(png, chunk_type)?,
+            Position::After(chunk_type) => chunk_position(png, chunk_type)? + 1,
+        };
+        if apng::splits_frame(png.chunks(), index) {
+            throw!(invalid_arguments(
+                "position is inside an animation frame, which would break its sequence"
+            ))
+        }
+        for (offset, chunk) in chunks.into_iter().enumerate() {
+            png.insert_chunk(index + offset, chunk)?;
+        }
+    } else if args.replace {
+        // Drop every old chunk of the types being added, so no stale
+        // fragments are left, but keep the messages stored under other labels
+        let chunk_types: Vec<ChunkType> = chunks.iter().map(|chunk| *chunk.chunk_type()).collect();
+        let is_old = |chunk: &Chunk| {
+            chunk_types.contains(chunk.chunk_type()) && args.label.as_deref() == chunk_label(chunk)
+        };
+        match png.chunks().iter().position(is_old) {
+            Some(index) => {
+                png.retain_chunks(|chunk| !is_old(chunk));
+                for (offset, chunk) in chunks.into_iter().enumerate() {
+                    png.insert_chunk(index + offset, chunk)?;
+                }
+            }
+            None => chunks
+                .into_iter()
+                .for_each(|chunk| png.insert_chunk_ordered(chunk)),
+        }
+    } else if args.append_raw {
+        chunks.into_iter().for_each(|chunk| png.append_chunk(chunk));
+    } else {
+        chunks
+            .into_iter()
+            .for_each(|chunk| png.insert_chunk_ordered(chunk));
+    }
+
+    let policy = match args.duplicates {
+        Duplicates::Reject => DuplicatePolicy::Reject,
+        Duplicates::Warn => DuplicatePolicy::Warn,
+        Duplicates::Dedupe => DuplicatePolicy::Deduplicate,
+    };
+    let duplicates = png
+        .enforce_unique_chunks(policy)
+        .context("png would contain duplicate chunks")?;
+    if args.duplicates == Duplicates::Warn {
+        for chunk_type in duplicates {
+            warn!("`{}` chunk appears more than once", chunk_type);
+        }
+    }
+}
+
+/// Index of the first chunk of type `chunk_type`.
+#[throws(anyhow::Error)]
+fn chunk_position(png: &Png, chunk_type: &str) -> usize {
+    png.chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
+        .with_context(|| format!("no `{}` chunk to position against", chunk_type))?
 }
 
 #[throws(anyhow::Error)]
 fn decode(args: Decode) {
-    let png = parse_png_from_file(&args.png_path)?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type).context("invalid chunk type")?;
+    let is_match = |chunk: &Chunk| *chunk.chunk_type() == chunk_type;
+    let authenticated =
+        args.decrypt || args.identity.is_some() || args.verify.is_some() || args.hmac.is_some();
 
-    let chunk = png
-        .chunk_by_type(&args.chunk_type)
-        .context("chunk not found")?;
+    let label = args.label.as_deref();
+    let mut chunks: Vec<Chunk> = vec![];
+    if args.mode == Mode::Lsb {
+        // Treat the hidden payload as if it were stored in a chunk, so it
+        // goes through the same verification and decryption
+        let png = parse_png_from_file_with(&args.png_path, args.parse.options())?;
+        let payload = stego::extract(&png).context("failed to read message from pixel data")?;
+        chunks.extend(select_label(Chunk::new(chunk_type, payload), label));
+    } else if args.parse.lenient {
+        let png = parse_png_from_file_with(&args.png_path, args.parse.options())?;
+        chunks.extend(
+            png.into_chunks()
+                .into_iter()
+                .filter(is_match)
+                .filter_map(|chunk| select_label(chunk, label)),
+        );
+    } else {
+        for chunk in open_chunk_reader(&args.png_path, !args.parse.no_verify_crc)? {
+            let chunk = chunk.context("failed to parse png file")?;
+            if !is_match(&chunk) {
+                continue;
+            }
+
+            if let Some(chunk) = select_label(chunk, label) {
+                // The other fragments of a message may be anywhere in the
+                // file, and decoys may come before the real message
+                let is_fragment = fragment::is_fragment(chunk.data());
+                chunks.push(chunk);
+                if !args.all && !is_fragment && !authenticated {
+                    break;
+                }
+            }
+        }
+    }
+
+    for path in &args.join {
+        let png = parse_png_from_file_with(path, args.parse.options())
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        chunks.extend(
+            png.into_chunks()
+                .into_iter()
+                .filter(is_match)
+                .filter_map(|chunk| select_label(chunk, label)),
+        );
+    }
+
+    let payloads =
+        fragment::reassemble(&chunks).context("failed to reassemble fragmented message")?;
+
+    if payloads.is_empty() {
+        throw!(not_found("chunk not found"))
+    }
+
+    let key = if args.decrypt {
+        Some(DecryptionKey::Passphrase(read_passphrase(
+            args.passphrase_file.as_deref(),
+            false,
+        )?))
+    } else if let Some(path) = &args.identity {
+        Some(DecryptionKey::Identity(
+            std::fs::read(path).context("failed to read identity file")?,
+        ))
+    } else {
+        None
+    };
+
+    let public_key = match &args.verify {
+        Some(path) => Some(read_key_file(path)?),
+        None => None,
+    };
+
+    // Decoys can only be told apart from the real message by failing to
+    // authenticate, so every candidate is tried when a key is given
+    let mut messages = vec![];
+    let mut last_error = None;
+    for (chunk, payload) in &payloads {
+        let message = decode_message(
+            chunk.chunk_type(),
+            payload.clone(),
+            key.as_ref(),
+            public_key.as_ref(),
+            args.hmac.as_deref(),
+        );
+
+        match message {
+            Ok(message) => messages.push((*chunk, message)),
+            Err(error) if authenticated => last_error = Some(error),
+            Err(error) => throw!(error),
+        }
+
+        if !args.all && !messages.is_empty() {
+            break;
+        }
+    }
+
+    if let Some(error) = last_error.filter(|_| messages.is_empty()) {
+        throw!(error)
+    }
+
+    if let Some(path) = args.output_file {
+        write_output_file(&path, &messages[0].1)?;
+    } else if let Some(dir) = &args.extract_dir {
+        let files = bundle::unpack(&messages[0].1, dir)?;
+        eprintln!("Extracted {} files to {}", files, dir.display());
+    } else if args.to_clipboard {
+        let message = String::from_utf8(messages[0].1.clone())
+            .context("only text messages can be copied to the clipboard")?;
+        write_clipboard(message)?;
+        eprintln!("Copied message to the clipboard");
+    } else if let Some(encoding) = args.encoding {
+        let count = if args.all { messages.len() } else { 1 };
+        for (_, message) in &messages[..count] {
+            print_encoded(message, encoding)?;
+        }
+    } else if args.auto {
+        let count = if args.all { messages.len() } else { 1 };
+        for (_, message) in &messages[..count] {
+            print_auto(message, args.force)?;
+        }
+    } else if args.format == OutputFormat::Json && args.all {
+        let reports: Vec<DecodeReport> = messages
+            .iter()
+            .map(|(chunk, message)| DecodeReport::new(chunk, message))
+            .collect();
+        print_json(&reports)?;
+    } else if args.format == OutputFormat::Json {
+        print_json(&DecodeReport::new(messages[0].0, &messages[0].1))?;
+    } else if args.all {
+        for (index, (_, message)) in messages.iter().enumerate() {
+            outln!(
+                "{}: Found chunk: \"{}\"",
+                index,
+                message_text(message, args.strict_utf8)?
+            );
+        }
+    } else {
+        outln!(
+            "Found chunk: \"{}\"",
+            message_text(&messages[0].1, args.strict_utf8)?
+        );
+    }
+
+    if args.format == OutputFormat::Text
+        && args.encoding.is_none()
+        && !args.auto
+        && args.extract_dir.is_none()
+    {
+        let content_type = sniff(&messages[0].1);
+        if !content_type.is_text() {
+            warn!(
+                "the message looks like {} data, use --auto or --encoding to print it intact",
+                content_type
+            );
+        }
+    }
+}
+
+/// Prints a message according to what it looks like: json pretty-printed,
+/// text as is, and binary data raw unless that would go to a terminal.
+#[throws(anyhow::Error)]
+fn print_auto(message: &[u8], force: bool) {
+    match sniff(message) {
+        ContentType::Json => match serde_json::from_slice::<serde_json::Value>(message) {
+            Ok(json) => print_json(&json)?,
+            Err(_) => outln!("{}", String::from_utf8_lossy(message)),
+        },
+        ContentType::Text => outln!("{}", String::from_utf8_lossy(message)),
+        content_type => {
+            if std::io::stdout().is_terminal() && !force {
+                throw!(invalid_arguments(format!(
+                    "refusing to print {} data to a terminal, use --force or --output-file",
+                    content_type
+                )))
+            }
+            print_encoded(message, Encoding::Raw)?
+        }
+    }
+}
+
+/// A message as text, with invalid utf-8 replaced unless `strict` is set.
+#[throws(anyhow::Error)]
+fn message_text(message: &[u8], strict: bool) -> Cow<'_, str> {
+    if strict {
+        Cow::Borrowed(std::str::from_utf8(message).context("message is not valid utf-8")?)
+    } else {
+        String::from_utf8_lossy(message)
+    }
+}
+
+/// Writes a message to stdout, followed by a newline unless written raw.
+#[throws(anyhow::Error)]
+fn print_encoded(message: &[u8], encoding: Encoding) {
+    let mut stdout = output::stdout();
+    match encoding {
+        Encoding::Raw => stdout.write_all(message),
+        Encoding::Base64 => writeln!(stdout, "{}", STANDARD.encode(message)),
+        Encoding::Hex => writeln!(stdout, "{}", hex(message)),
+    }
+    .context("failed to write stdout")?;
+}
+
+/// What to decrypt a message with.
+enum DecryptionKey {
+    Passphrase(String),
+    /// Contents of an age identity file.
+    Identity(Vec<u8>),
+}
+
+/// Verifies, decrypts and decompresses a message according to its payload
+/// headers.
+#[throws(anyhow::Error)]
+fn decode_message(
+    chunk_type: &ChunkType,
+    data: Vec<u8>,
+    key: Option<&DecryptionKey>,
+    public_key: Option<&[u8; sign::PUBLIC_KEY_LENGTH]>,
+    hmac_key: Option<&str>,
+) -> Vec<u8> {
+    decode_message_limited(
+        chunk_type,
+        data,
+        key,
+        public_key,
+        hmac_key,
+        compression::MAX_DECOMPRESSED_SIZE,
+    )?
+}
+
+/// Like [`decode_message`], failing if a compressed message decompresses to
+/// more than `max_size` bytes.
+#[throws(anyhow::Error)]
+fn decode_message_limited(
+    chunk_type: &ChunkType,
+    mut data: Vec<u8>,
+    key: Option<&DecryptionKey>,
+    public_key: Option<&[u8; sign::PUBLIC_KEY_LENGTH]>,
+    hmac_key: Option<&str>,
+    max_size: u64,
+) -> Vec<u8> {
+    if let Some(public_key) = public_key {
+        data = sign::verify(public_key, &data).context("failed to verify message")?;
+    } else if sign::is_signed(&data) {
+        data = sign::strip_signature(&data).context("failed to read signed message")?;
+    }
+
+    if let Some(hmac_key) = hmac_key {
+        data = mac::verify(hmac_key.as_bytes(), chunk_type, &data)
+            .context("failed to verify message integrity")?;
+    } else if mac::is_tagged(&data) {
+        data = mac::strip_tag(&data).context("failed to read tagged message")?;
+    }
+
+    match key {
+        Some(DecryptionKey::Passphrase(passphrase)) => {
+            data = crypto::decrypt(passphrase.as_bytes(), &data)
+                .context("failed to decrypt message")?;
+        }
+        Some(DecryptionKey::Identity(identity)) => {
+            data = crypto::decrypt_with_identities(identity, &data)
+                .context("failed to decrypt message")?;
+        }
+        None => {}
+    }
 
-    println!("Found chunk: \"{}\"", chunk.data_as_string());
+    if compression::is_compressed(&data) {
+        data = compression::decompress_limited(&data, max_size)
+            .context("failed to decompress message")?;
+    }
+
+    data
 }
 
 #[throws(anyhow::Error)]
 fn remove(args: Remove) {
+    check_output_path(&args.png_path, &args.png_path, args.guard.force)?;
     let mut png = parse_png_from_file(&args.png_path)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
 
-    let chunk = png
-        .remove_chunk(&args.chunk_type)
-        .context("chunk not found")?;
+    let chunks = if args.all {
+        png.remove_chunks(&args.chunk_type)
+            .context("invalid chunk type")?
+    } else if let Some(index) = args.index {
+        let chunk_type = ChunkType::from_str(&args.chunk_type).context("invalid chunk type")?;
+        let position = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| *chunk.chunk_type() == chunk_type)
+            .nth(index)
+            .map(|(position, _)| position)
+            .ok_or_else(|| not_found("chunk not found"))?;
+
+        vec![png.remove_chunk_at(position)?]
+    } else {
+        vec![png
+            .remove_chunk(&args.chunk_type)
+            .context("chunk not found")?]
+    };
 
-    println!("Removed chunk with message: \"{}\"", chunk.data_as_string());
+    if chunks.is_empty() {
+        throw!(not_found("chunk not found"))
+    }
 
-    save_png_to_file(png, &args.png_path)?;
+    for chunk in chunks {
+        // Keep stdout clean for the png when it is being piped
+        if is_stdio(&args.png_path) {
+            eprintln!("Removed chunk with message: \"{}\"", chunk.data_as_string());
+        } else {
+            outln!("Removed chunk with message: \"{}\"", chunk.data_as_string());
+        }
+    }
+
+    if dry_run.is_none() {
+        backup_file(&args.png_path, &args.backup)?;
+    }
+
+    save_png_or_preview(png, &args.png_path, dry_run, &args.write)?;
 }
 
 #[throws(anyhow::Error)]
 fn print(args: Print) {
-    let png = parse_png_from_file(&args.png_path)?;
+    let is_shown = |chunk: &Chunk| {
+        let chunk_type = chunk.chunk_type();
+        (args.types.is_empty() || args.types.contains(&chunk_type.to_string()))
+            && (!args.critical || chunk_type.is_critical())
+            && (!args.ancillary || !chunk_type.is_critical())
+            && (!args.unknown_only || !chunk_type.is_standard())
+    };
+    let registry = CodecRegistry::new();
+    let decode = |chunk: &Chunk| {
+        let codec = registry
+            .codec(chunk.chunk_type())
+            .filter(|_| args.decoded)?;
+        Some(codec.decode(chunk).map_err(anyhow::Error::from))
+    };
+    let print_row = |index: usize, chunk: &Chunk| -> anyhow::Result<()> {
+        outln!("{:#}", ChunkRow::new(index, chunk));
+        match decode(chunk) {
+            Some(Ok(value)) => outln!("{:7}{}", "", value),
+            Some(Err(err)) => outln!("{:7}invalid: {:#}", "", err),
+            None => {}
+        }
+        Ok(())
+    };
 
-    for chunk in png.chunks() {
-        println!(
-            "Chunk \"{}\": \"{}\"",
+    let verify_crc = !args.parse.no_verify_crc;
+    let in_file_order = args.sort.is_none() && args.group_by.is_none();
+    let chunks: Vec<Chunk> = if args.parse.lenient {
+        parse_png_from_file_with(&args.png_path, args.parse.options())?.into_chunks()
+    } else if args.format == OutputFormat::Json || !in_file_order {
+        open_chunk_reader(&args.png_path, verify_crc)?
+            .collect::<Result<_, _>>()
+            .context("failed to parse png file")?
+    } else {
+        // Print as we go so huge files don't have to be held in memory. The
+        // header waits for the first chunk, so nothing is printed for files
+        // that can't be read at all
+        let reader = open_chunk_reader(&args.png_path, verify_crc)?;
+        for (index, chunk) in reader.enumerate() {
+            let chunk = chunk.context("failed to parse png file")?;
+            if index == 0 {
+                outln!("{}", ChunkRow::header(true));
+            }
+            if is_shown(&chunk) {
+                print_row(index, &chunk)?;
+            }
+        }
+        return;
+    };
+
+    // Indices are kept so rows show where each chunk sits in the file
+    let mut chunks: Vec<(usize, &Chunk)> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| is_shown(chunk))
+        .collect();
+    match args.sort {
+        Some(SortKey::Size) => chunks.sort_by_key(|(_, chunk)| std::cmp::Reverse(chunk.size())),
+        Some(SortKey::Type) => chunks.sort_by_key(|(_, chunk)| chunk.chunk_type().to_string()),
+        Some(SortKey::Offset) | None => {}
+    }
+
+    let groups: Vec<(String, Vec<(usize, &Chunk)>)> = match args.group_by {
+        Some(GroupBy::Category) => [
+            ChunkCategory::CriticalStandard,
+            ChunkCategory::AncillaryStandard,
+            ChunkCategory::Private,
+            ChunkCategory::Invalid,
+        ]
+        .into_iter()
+        .map(|category| {
+            let group: Vec<(usize, &Chunk)> = chunks
+                .iter()
+                .filter(|(_, chunk)| chunk.chunk_type().category() == category)
+                .copied()
+                .collect();
+            (category.to_string(), group)
+        })
+        .filter(|(_, group)| !group.is_empty())
+        .collect(),
+        None => vec![(String::new(), chunks)],
+    };
+
+    if args.format == OutputFormat::Json {
+        let reports: Vec<GroupReport> = groups
+            .into_iter()
+            .map(|(category, group)| GroupReport {
+                category,
+                chunks: group
+                    .into_iter()
+                    .map(|(_, chunk)| {
+                        let mut report = ChunkReport::from(chunk);
+                        report.decoded = match decode(chunk) {
+                            Some(Ok(value)) => Some(value),
+                            Some(Err(err)) => {
+                                warn!("failed to decode {} chunk: {:#}", chunk.chunk_type(), err);
+                                None
+                            }
+                            None => None,
+                        };
+                        report
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        return match args.group_by {
+            Some(_) => print_json(&reports)?,
+            None => print_json(&reports[0].chunks)?,
+        };
+    }
+
+    for (category, group) in &groups {
+        if args.group_by.is_some() {
+            outln!("{} chunks:", category);
+        }
+        outln!("{}", ChunkRow::header(true));
+        for (index, chunk) in group {
+            print_row(*index, chunk)?;
+        }
+    }
+}
+
+#[throws(anyhow::Error)]
+fn info(args: Info) {
+    let png = parse_png_from_file_with(&args.png_path, args.parse.options())?;
+    let header = png.header().context("invalid image header")?;
+
+    if args.format == OutputFormat::Json {
+        return print_json(&InfoReport::new(&header, png.chunks().len()))?;
+    }
+
+    outln!("Dimensions: {}x{}", header.width(), header.height());
+    outln!("Bit depth: {}", header.bit_depth());
+    outln!("Color type: {}", header.color_type());
+    outln!("Interlace: {}", header.interlace());
+    outln!("Chunks: {}", png.chunks().len());
+}
+
+#[throws(anyhow::Error)]
+fn stats(args: cli::Stats) {
+    let png = parse_png_from_file_with(&args.png_path, args.parse.options())?;
+    let stats = Stats::of(&png);
+
+    if args.format == OutputFormat::Json {
+        return print_json(&StatsReport::new(&png, &stats, args.top))?;
+    }
+
+    outln!("Size: {} bytes", stats.total_bytes);
+    outln!("Critical chunks: {} bytes", stats.critical_bytes);
+    outln!("Ancillary chunks: {} bytes", stats.ancillary_bytes);
+    outln!("Image data: {} bytes", stats.idat_bytes);
+    outln!("Metadata overhead: {:.1}%", stats.metadata_overhead());
+
+    outln!("Chunk types:");
+    for types in &stats.types {
+        let description = types
+            .description
+            .map(|description| format!(" ({})", description))
+            .unwrap_or_default();
+        outln!(
+            "  {}{}: {} chunk(s), {} bytes",
+            types.chunk_type,
+            description,
+            types.count,
+            types.bytes
+        );
+    }
+
+    outln!("Largest chunks:");
+    for (index, chunk) in stats.largest_chunks(&png, args.top) {
+        outln!(
+            "  [{}] {}: {} bytes",
+            index,
             chunk.chunk_type(),
-            chunk.data_as_string()
+            chunk.size()
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+fn check(args: Check) {
+    let bytes = read_input_file(&args.png_path)?;
+    let diagnostics = validate::validate(&bytes);
+
+    for diagnostic in &diagnostics {
+        outln!("{}", diagnostic);
+    }
+
+    if !diagnostics.is_empty() {
+        bail!("found {} problem(s)", diagnostics.len())
+    }
+
+    outln!("No problems found");
+}
+
+#[throws(anyhow::Error)]
+fn scan(args: Scan) {
+    let bytes = read_input_file(&args.png_path)?;
+    let findings = scan::scan(&bytes);
+
+    for finding in &findings {
+        outln!("{}", finding);
+    }
+
+    if !findings.is_empty() {
+        bail!("found {} suspicious item(s)", findings.len())
+    }
+
+    outln!("Nothing suspicious found");
+}
+
+#[throws(anyhow::Error)]
+fn capacity(args: Capacity) {
+    let png = parse_png_from_file_with(&args.png_path, args.parse.options())?;
+
+    // A message in a chunk is limited by the size budgets, in the pixels by
+    // the number of colour samples
+    let (mode, capacity) = match args.mode {
+        Mode::Chunk => {
+            let mut capacity = args
+                .max_chunk_size
+                .unwrap_or(ParseOptions::DEFAULT_MAX_CHUNK_SIZE as usize);
+            if let Some(max_file_size) = args.max_file_size {
+                // The chunk's length, type and crc take 12 bytes as well
+                let file_size = 8 + png.chunks().iter().map(Chunk::size).sum::<usize>();
+                capacity = capacity.min(max_file_size.saturating_sub(file_size + 12));
+            }
+            ("chunk", capacity)
+        }
+        Mode::Lsb => (
+            "lsb",
+            stego::capacity(&png).context("can't hide a message in the pixels")?,
+        ),
+    };
+    let capacity = match args.encrypt {
+        true => capacity.saturating_sub(crypto::OVERHEAD),
+        false => capacity,
+    };
+
+    if args.format == OutputFormat::Json {
+        return print_json(&CapacityReport {
+            mode: mode.to_string(),
+            capacity,
+        })?;
+    }
+
+    outln!("Capacity: {} bytes", capacity);
+}
+
+#[throws(anyhow::Error)]
+fn generate(args: Generate) {
+    if !args.guard.force && !is_stdio(&args.out) && args.out.exists() {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            args.out.display()
         )
     }
+
+    let (width, height) = match args.capacity {
+        Some(capacity) => {
+            let pixels = stego::pixels_needed(capacity);
+            let side = (1..)
+                .find(|side| side * side >= pixels)
+                .expect("some side fits");
+            let side = u32::try_from(side).context("capacity is too large for an image")?;
+            (side, side)
+        }
+        None => args.size,
+    };
+
+    let builder = PngBuilder::new(width, height);
+    let png = match args.style {
+        Style::Noise => builder.noise(),
+        Style::Solid(rgb) => builder.solid(&rgb),
+    }
+    .build()
+    .context("failed to generate image")?;
+    let capacity = stego::capacity(&png).context("can't hide a message in the pixels")?;
+
+    if !args.guard.dry_run {
+        save_png_to_file(png, &args.out, &args.write)?;
+    }
+
+    let summary = format!(
+        "{} a {}x{} image that hides up to {} bytes with --mode lsb",
+        if args.guard.dry_run {
+            "Would generate"
+        } else {
+            "Generated"
+        },
+        width,
+        height,
+        capacity
+    );
+    match is_stdio(&args.out) && !args.guard.dry_run {
+        true => eprintln!("{}", summary),
+        false => outln!("{}", summary),
+    }
+}
+
+#[throws(anyhow::Error)]
+fn copy(args: CopyChunks) {
+    let source = parse_png_from_file(&args.from)?;
+    let mut png = parse_png_from_file(&args.to)?;
+    let output_path = resolve_output_path(&args.to, args.output.as_deref());
+    check_output_path(&args.to, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let chunk_types = args
+        .chunk_types
+        .iter()
+        .map(|chunk_type| ChunkType::from_str(chunk_type).context("invalid chunk type"))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let select = |chunk: &Chunk| match args.all_ancillary {
+        true => !chunk.chunk_type().is_critical(),
+        false => chunk_types.contains(chunk.chunk_type()),
+    };
+
+    if !source.chunks().iter().any(select) {
+        throw!(not_found(format!(
+            "no matching chunks in {}",
+            args.from.display()
+        )))
+    }
+
+    let skipped = png
+        .copy_chunks_from(&source, select)
+        .context("failed to copy chunks")?;
+    for chunk_type in skipped {
+        warn!(
+            "skipped `{}`, it isn't safe to copy to a different image",
+            chunk_type
+        );
+    }
+
+    if output_path == args.to && dry_run.is_none() {
+        backup_file(&args.to, &args.backup)?;
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
+
+#[throws(anyhow::Error)]
+fn repair(args: Repair) {
+    let bytes = read_input_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let (png, fixes) = repair::repair(&bytes).context("failed to repair png")?;
+
+    let report = |line: &dyn std::fmt::Display| -> anyhow::Result<()> {
+        if is_stdio(&output_path) && !args.guard.dry_run {
+            eprintln!("{}", line);
+        } else {
+            outln!("{}", line);
+        }
+        Ok(())
+    };
+
+    for fix in &fixes {
+        report(fix)?;
+    }
+
+    if fixes.is_empty() {
+        return report(&"Nothing to repair")?;
+    }
+
+    if !args.guard.dry_run {
+        save_png_to_file(png, &output_path, &args.write)?;
+    }
+}
+
+#[throws(anyhow::Error)]
+fn optimize(args: Optimize) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+
+    let file_size = |png: &Png| 8 + png.chunks().iter().map(Chunk::size).sum::<usize>();
+    let (old_size, old_idats) = (file_size(&png), png.chunks_by_type("IDAT").count());
+
+    png.recompress_image_data(args.level)
+        .context("failed to recompress image data")?;
+    let (new_size, new_idats) = (file_size(&png), png.chunks_by_type("IDAT").count());
+
+    let report = |line: &dyn std::fmt::Display| -> anyhow::Result<()> {
+        if is_stdio(&output_path) && !args.guard.dry_run {
+            eprintln!("{}", line);
+        } else {
+            outln!("{}", line);
+        }
+        Ok(())
+    };
+
+    if new_size >= old_size {
+        return report(&format!(
+            "Level {} doesn't make the file any smaller, leaving it as is",
+            args.level
+        ))?;
+    }
+
+    report(&format!(
+        "{} -> {} bytes ({:.1}% smaller), {} IDAT chunk(s) -> {}",
+        old_size,
+        new_size,
+        (old_size - new_size) as f64 * 100.0 / old_size as f64,
+        old_idats,
+        new_idats
+    ))?;
+
+    if !args.guard.dry_run {
+        save_png_to_file(png, &output_path, &args.write)?;
+    }
+}
+
+#[throws(anyhow::Error)]
+fn extract(args: Extract) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let chunk = png
+        .chunk_by_type(&args.chunk_type)
+        .ok_or_else(|| not_found("chunk not found"))?;
+
+    write_output_file(&args.out, chunk.data())?;
+}
+
+/// The user's editor command, split into the program and its arguments.
+fn editor_command() -> Vec<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    editor.split_whitespace().map(String::from).collect()
+}
+
+#[throws(anyhow::Error)]
+fn edit(args: Edit) {
+    if is_stdio(&args.png_path) {
+        throw!(invalid_arguments("can't edit a png read from stdin"))
+    }
+
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type).context("invalid chunk type")?;
+    let original = png
+        .chunk_by_type(&args.chunk_type)
+        .map(|chunk| chunk.data().to_vec())
+        .unwrap_or_default();
+
+    let file = tempfile::Builder::new()
+        .prefix("pngme-")
+        .suffix(".txt")
+        .tempfile()
+        .context("failed to create temporary file")?;
+    std::fs::write(file.path(), &original).context("failed to write temporary file")?;
+
+    let command = editor_command();
+    let (program, editor_args) = command.split_first().context("editor command is empty")?;
+    let status = std::process::Command::new(program)
+        .args(editor_args)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("failed to run editor `{}`", program))?;
+    if !status.success() {
+        bail!("editor exited with {}, leaving the png unchanged", status)
+    }
+
+    let edited = std::fs::read(file.path()).context("failed to read edited file")?;
+    if edited == original {
+        outln!("No changes");
+        return;
+    }
+
+    png.replace_chunk(Chunk::new(chunk_type, edited));
+    backup_file(&args.png_path, &args.backup)?;
+    save_png_to_file(png, &args.png_path, &args.write)?;
+
+    outln!("Updated `{}` chunk", args.chunk_type);
+}
+
+#[throws(anyhow::Error)]
+fn keygen(args: Keygen) {
+    let mut public_key_path = args.key_path.clone().into_os_string();
+    public_key_path.push(".pub");
+    let public_key_path = PathBuf::from(public_key_path);
+
+    for path in [&args.key_path, &public_key_path] {
+        if !args.force && path.exists() {
+            bail!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            )
+        }
+    }
+
+    let (secret_key, public_key) = sign::generate_keypair();
+    let encode = |key: &[u8]| format!("{}\n", STANDARD.encode(key));
+
+    write_private_file(&args.key_path, encode(&secret_key).as_bytes())?;
+    write_output_file(&public_key_path, encode(&public_key).as_bytes())?;
+
+    outln!("Secret key: {}", args.key_path.display());
+    outln!("Public key: {}", public_key_path.display());
+}
+
+#[throws(anyhow::Error)]
+fn gen_type(args: GenType) {
+    let chunk_type = match &args.label {
+        Some(label) => {
+            ChunkType::new_private(label).context("label needs at least four ascii letters")?
+        }
+        None => ChunkType::random_private(),
+    };
+
+    outln!("{}", chunk_type);
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use pngme_lib::builder::PngBuilder;
+
+    use super::*;
+
+    fn encode_args(args: &[&str]) -> Encode {
+        let cli = Cli::try_parse_from(["pngme", "encode"].iter().chain(args)).unwrap();
+        match cli.command {
+            Command::Encode(args) => *args,
+            _ => unreachable!("parsed an encode command"),
+        }
+    }
+
+    #[test]
+    fn test_encode_split_across() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        for name in ["a.png", "b.png"] {
+            let png = PngBuilder::new(4, 4).build().unwrap();
+            std::fs::write(path(name), png.as_bytes()).unwrap();
+        }
+
+        encode(encode_args(&[
+            &path("a.png"),
+            "ruSt",
+            "hello",
+            "--split-across",
+            &path("b.png"),
+        ]))
+        .unwrap();
+
+        for name in ["a.png", "b.png"] {
+            let png = parse_png_from_file(Path::new(&path(name))).unwrap();
+            assert_eq!(png.chunks_by_type("ruSt").count(), 1, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_encode_split_across_leaves_pngs_untouched_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+
+        // Rejected only once the main png's chunks are placed, after the
+        // message has been split across both
+        let mut png = PngBuilder::new(4, 4).build().unwrap();
+        png.insert_chunk_ordered(Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![0; 7]));
+        png.insert_chunk_ordered(Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![0; 7]));
+        std::fs::write(path("a.png"), png.as_bytes()).unwrap();
+        let other = PngBuilder::new(4, 4).build().unwrap().as_bytes();
+        std::fs::write(path("b.png"), &other).unwrap();
+
+        let result = encode(encode_args(&[
+            &path("a.png"),
+            "ruSt",
+            "hello",
+            "--split-across",
+            &path("b.png"),
+            "--duplicates",
+            "reject",
+        ]));
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(path("a.png")).unwrap(), png.as_bytes());
+        assert_eq!(std::fs::read(path("b.png")).unwrap(), other);
+    }
+
+    #[test]
+    fn test_repair_and_optimize_output_is_buffered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let png = PngBuilder::new(4, 4).build().unwrap();
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        let cli = Cli::try_parse_from(["pngme", "repair", path.to_str().unwrap(), "--dry-run"]);
+        let Command::Repair(args) = cli.unwrap().command else {
+            unreachable!("parsed a repair command")
+        };
+        let (result, output) = output::buffered(|| repair(args));
+        result.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Nothing to repair\n");
+
+        let cli = Cli::try_parse_from(["pngme", "optimize", path.to_str().unwrap(), "--dry-run"]);
+        let Command::Optimize(args) = cli.unwrap().command else {
+            unreachable!("parsed an optimize command")
+        };
+        let (result, output) = output::buffered(|| optimize(args));
+        result.unwrap();
+        assert!(!output.is_empty());
+    }
 }
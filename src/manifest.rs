@@ -0,0 +1,308 @@
+//! Operations on many pngs described by a manifest, so an asset pipeline can
+//! be checked in and rerun. For example:
+//!
+//! ```toml
+//! # paths are relative to the manifest
+//! [[file]]
+//! path = "assets/*.png"
+//! # write the results here instead of modifying the files in place
+//! output-dir = "dist"
+//! operations = [
+//!     { op = "strip", keep = ["sRGB", "gAMA"] },
+//!     { op = "text-set", keyword = "Author", text = "Jane Doe" },
+//!     { op = "encode", chunk-type = "ruSt", message-file = "VERSION" },
+//! ]
+//!
+//! [[file]]
+//! path = "icons/app.png"
+//! operations = [{ op = "remove", chunk-type = "tIME" }]
+//! ```
+//!
+//! Operations run in order, and every file is read and changed in memory
+//! before any is written, so a broken manifest leaves all of them untouched.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use fehler::throws;
+use pngme_lib::{chunk::Chunk, chunk_type::ChunkType, label, png::Png};
+use serde::Deserialize;
+
+use crate::{
+    batch::{expand_batch_path, is_batch_path},
+    cli::Apply,
+    output::{outln, Snapshot},
+    text::set_text,
+    util::{check_output_path, parse_png_from_file, read_input_file, save_png_to_file},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(rename = "file", default)]
+    files: Vec<FileEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct FileEntry {
+    /// A png, a directory of them or a glob pattern.
+    path: PathBuf,
+    output_dir: Option<PathBuf>,
+    operations: Vec<Operation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(
+    tag = "op",
+    deny_unknown_fields,
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case"
+)]
+enum Operation {
+    /// Adds a chunk holding a message, or the contents of a file.
+    Encode {
+        chunk_type: ChunkType,
+        message: Option<String>,
+        message_file: Option<PathBuf>,
+        label: Option<String>,
+        /// Drops the chunks of the type with the same label first.
+        #[serde(default)]
+        replace: bool,
+    },
+    /// Removes every chunk of a type, if there are any.
+    Remove { chunk_type: ChunkType },
+    /// Removes every ancillary chunk, except those of the kept types.
+    Strip {
+        #[serde(default)]
+        keep: Vec<ChunkType>,
+    },
+    /// Sets the text stored under a keyword, like `text set`.
+    TextSet {
+        keyword: String,
+        text: String,
+        #[serde(default)]
+        compressed: bool,
+        language: Option<String>,
+        translated_keyword: Option<String>,
+    },
+}
+
+impl Operation {
+    /// Applies the operation to `png`, reading any files relative to `base`.
+    #[throws(anyhow::Error)]
+    fn apply(&self, png: &mut Png, base: &Path) {
+        match self {
+            Operation::Encode {
+                chunk_type,
+                message,
+                message_file,
+                label,
+                replace,
+            } => {
+                let data = match (message, message_file) {
+                    (Some(message), None) => message.clone().into_bytes(),
+                    (None, Some(path)) => read_input_file(&base.join(path))?,
+                    _ => bail!("encode needs either a message or a message-file"),
+                };
+                let data = match label {
+                    Some(name) => label::label(name, &data).context("invalid label")?,
+                    None => data,
+                };
+
+                if *replace {
+                    png.retain_chunks(|chunk| {
+                        chunk.chunk_type() != chunk_type
+                            || label::split(chunk.data()).ok().map(|(name, _)| name)
+                                != label.as_deref()
+                    });
+                }
+                png.insert_chunk_ordered(Chunk::new(*chunk_type, data));
+            }
+            Operation::Remove { chunk_type } => {
+                png.retain_chunks(|chunk| chunk.chunk_type() != chunk_type);
+            }
+            Operation::Strip { keep } => {
+                png.retain_chunks(|chunk| {
+                    chunk.chunk_type().is_critical() || keep.contains(chunk.chunk_type())
+                });
+            }
+            Operation::TextSet {
+                keyword,
+                text,
+                compressed,
+                language,
+                translated_keyword,
+            } => {
+                let language = (language.is_some() || translated_keyword.is_some()).then(|| {
+                    (
+                        language.as_deref().unwrap_or_default(),
+                        translated_keyword.as_deref().unwrap_or_default(),
+                    )
+                });
+                set_text(png, keyword, text, *compressed, language)?;
+            }
+        }
+    }
+}
+
+#[throws(anyhow::Error)]
+pub fn run(args: Apply) {
+    let contents = std::fs::read_to_string(&args.manifest_path)
+        .with_context(|| format!("failed to read {}", args.manifest_path.display()))?;
+    let manifest: Manifest = toml::from_str(&contents)
+        .with_context(|| format!("invalid manifest {}", args.manifest_path.display()))?;
+    let base = args.manifest_path.parent().unwrap_or(Path::new(""));
+
+    let mut results = vec![];
+    for entry in &manifest.files {
+        let path = base.join(&entry.path);
+        let paths = match is_batch_path(&path) {
+            true => expand_batch_path(&path)?,
+            false => vec![path],
+        };
+
+        for path in paths {
+            let mut png = parse_png_from_file(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+            for (index, operation) in entry.operations.iter().enumerate() {
+                operation.apply(&mut png, base).with_context(|| {
+                    format!("operation {} failed on {}", index + 1, path.display())
+                })?;
+            }
+
+            let output_path = match &entry.output_dir {
+                Some(dir) => base
+                    .join(dir)
+                    .join(path.file_name().context("png path has no file name")?),
+                None => path.clone(),
+            };
+            check_output_path(&path, &output_path, args.guard.force)?;
+            results.push((output_path, png, dry_run));
+        }
+    }
+
+    for (output_path, png, dry_run) in results {
+        match dry_run {
+            Some(snapshot) => {
+                outln!("{}:", output_path.display());
+                snapshot.print_changes(&png)?;
+            }
+            None => {
+                if let Some(dir) = output_path.parent().filter(|dir| !dir.exists()) {
+                    std::fs::create_dir_all(dir)
+                        .with_context(|| format!("failed to create {}", dir.display()))?;
+                }
+                save_png_to_file(png, &output_path, &args.write)?;
+                outln!("Wrote {}", output_path.display());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use pngme_lib::builder::PngBuilder;
+
+    use super::*;
+    use crate::cli::{Cli, Command};
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [[file]]
+            path = "assets/*.png"
+            output-dir = "dist"
+            operations = [
+                { op = "strip", keep = ["sRGB"] },
+                { op = "encode", chunk-type = "ruSt", message = "hi", replace = true },
+            ]
+
+            [[file]]
+            path = "icon.png"
+            operations = [{ op = "remove", chunk-type = "tIME" }]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.files.len(), 2);
+        let [assets, icon] = &manifest.files[..] else {
+            unreachable!("two files")
+        };
+        assert_eq!(assets.path, Path::new("assets/*.png"));
+        assert_eq!(assets.output_dir.as_deref(), Some(Path::new("dist")));
+        assert!(matches!(
+            &assets.operations[..],
+            [Operation::Strip { keep }, Operation::Encode { replace: true, .. }]
+                if keep.len() == 1 && keep[0].to_string() == "sRGB"
+        ));
+        assert_eq!(icon.output_dir, None);
+        assert!(matches!(
+            &icon.operations[..],
+            [Operation::Remove { chunk_type }] if chunk_type.to_string() == "tIME"
+        ));
+    }
+
+    #[test]
+    fn test_unknown_operation() {
+        let manifest = toml::from_str::<Manifest>(
+            r#"
+            [[file]]
+            path = "a.png"
+            operations = [{ op = "frobnicate" }]
+            "#,
+        );
+        assert!(manifest.is_err());
+    }
+
+    #[test]
+    fn test_apply_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let png_path = dir.path().join("a.png");
+        let png = PngBuilder::new(4, 4).build().unwrap();
+        std::fs::write(&png_path, png.as_bytes()).unwrap();
+
+        let manifest_path = dir.path().join("manifest.toml");
+        std::fs::write(
+            &manifest_path,
+            r#"
+            [[file]]
+            path = "a.png"
+            output-dir = "dist"
+            operations = [
+                { op = "text-set", keyword = "Author", text = "Jane Doe" },
+                { op = "encode", chunk-type = "ruSt", message = "hello" },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let apply = |args: &[&str]| {
+            let manifest_path = manifest_path.to_str().unwrap();
+            let cli = Cli::try_parse_from([&["pngme", "apply", manifest_path], args].concat());
+            match cli.unwrap().command {
+                Command::Apply(args) => run(args).unwrap(),
+                _ => unreachable!("parsed an apply command"),
+            }
+        };
+        let output_path = dir.path().join("dist").join("a.png");
+
+        apply(&["--dry-run"]);
+        assert!(!output_path.exists());
+
+        apply(&[]);
+        let output = parse_png_from_file(&output_path).unwrap();
+        let text = output.chunk_by_type("tEXt").unwrap();
+        assert_eq!(text.data(), b"Author\0Jane Doe");
+        let message = output.chunk_by_type("ruSt").unwrap();
+        assert_eq!(message.data(), b"hello");
+        assert_eq!(
+            parse_png_from_file(&png_path).unwrap().as_bytes(),
+            png.as_bytes()
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fehler::throws;
+use pngme_lib::{
+    apng::Animation,
+    chunk::Chunk,
+    codec::TypedValue,
+    diff::{self, ChunkChange},
+    ihdr::Ihdr,
+    palette::{SuggestedPaletteChunk, SuggestedPaletteEntry},
+    png::Png,
+    sniff::sniff,
+    stats::Stats,
+};
+use serde::{Deserialize, Serialize};
+
+thread_local! {
+    /// What the current thread printed, while its output is being buffered.
+    static BUFFER: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Like `println!`, but written through [`stdout`] so it can be buffered, and
+/// failing instead of panicking when stdout can't be written.
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        use anyhow::Context as _;
+        use std::io::Write as _;
+        writeln!($crate::output::stdout(), $($arg)*).context("failed to write stdout")?
+    }};
+}
+pub(crate) use outln;
+
+/// Stdout, or the current thread's buffer inside [`buffered`].
+pub struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        BUFFER.with_borrow_mut(|buffer| match buffer {
+            Some(buffer) => buffer.write(buf),
+            None => io::stdout().write(buf),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        BUFFER.with_borrow(|buffer| match buffer {
+            Some(_) => Ok(()),
+            None => io::stdout().flush(),
+        })
+    }
+}
+
+pub fn stdout() -> Stdout {
+    Stdout
+}
+
+/// Runs `f`, keeping what it prints with [`stdout`] instead of printing it,
+/// so work done on several threads can be printed one piece at a time.
+pub fn buffered<T>(f: impl FnOnce() -> T) -> (T, Vec<u8>) {
+    BUFFER.set(Some(vec![]));
+    let result = f();
+    (result, BUFFER.take().unwrap_or_default())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkReport<'a> {
+    #[serde(flatten)]
+    pub chunk: &'a Chunk,
+    pub offset: Option<u64>,
+    pub size: usize,
+    pub description: Option<&'static str>,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<TypedValue>,
+}
+
+impl<'a> From<&'a Chunk> for ChunkReport<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        let chunk_type = chunk.chunk_type();
+
+        ChunkReport {
+            chunk,
+            offset: chunk.offset(),
+            size: chunk.size(),
+            description: chunk_type.known_description(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            decoded: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupReport<'a> {
+    pub category: String,
+    pub chunks: Vec<ChunkReport<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecodeReport<'a> {
+    #[serde(flatten)]
+    pub chunk: ChunkReport<'a>,
+    pub message: String,
+    pub content_type: String,
+}
+
+impl<'a> DecodeReport<'a> {
+    pub fn new(chunk: &'a Chunk, message: &[u8]) -> Self {
+        DecodeReport {
+            chunk: chunk.into(),
+            message: STANDARD.encode(message),
+            content_type: sniff(message).to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct InfoReport {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: String,
+    pub interlace: String,
+    pub chunks: usize,
+}
+
+impl InfoReport {
+    pub fn new(header: &Ihdr, chunks: usize) -> Self {
+        InfoReport {
+            width: header.width(),
+            height: header.height(),
+            bit_depth: header.bit_depth(),
+            color_type: header.color_type().to_string(),
+            interlace: header.interlace().to_string(),
+            chunks,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsReport {
+    pub total_bytes: usize,
+    pub critical_bytes: usize,
+    pub ancillary_bytes: usize,
+    pub idat_bytes: usize,
+    pub metadata_overhead: f64,
+    pub types: Vec<TypeReport>,
+    pub largest: Vec<ChunkSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeReport {
+    pub chunk_type: String,
+    pub description: Option<&'static str>,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl StatsReport {
+    pub fn new(png: &Png, stats: &Stats, top: usize) -> Self {
+        StatsReport {
+            total_bytes: stats.total_bytes,
+            critical_bytes: stats.critical_bytes,
+            ancillary_bytes: stats.ancillary_bytes,
+            idat_bytes: stats.idat_bytes,
+            metadata_overhead: stats.metadata_overhead(),
+            types: stats
+                .types
+                .iter()
+                .map(|types| TypeReport {
+                    chunk_type: types.chunk_type.clone(),
+                    description: types.description,
+                    count: types.count,
+                    bytes: types.bytes,
+                })
+                .collect(),
+            largest: stats
+                .largest_chunks(png, top)
+                .map(|(index, chunk)| ChunkSummary::new(index, chunk))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaletteEntry {
+    pub index: usize,
+    pub hex: String,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl PaletteEntry {
+    pub fn new(index: usize, [red, green, blue]: [u8; 3], alpha: u8) -> Self {
+        PaletteEntry {
+            index,
+            hex: format!("#{:02x}{:02x}{:02x}", red, green, blue),
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+}
+
+/// An sPLT chunk as `splt export` writes it and `splt import` reads it back.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuggestedPalette {
+    pub name: String,
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedPaletteColor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuggestedPaletteColor {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+impl From<&SuggestedPaletteChunk> for SuggestedPalette {
+    fn from(splt: &SuggestedPaletteChunk) -> Self {
+        let entries = splt
+            .entries()
+            .iter()
+            .map(|entry| SuggestedPaletteColor {
+                red: entry.red,
+                green: entry.green,
+                blue: entry.blue,
+                alpha: entry.alpha,
+                frequency: entry.frequency,
+            })
+            .collect();
+
+        SuggestedPalette {
+            name: splt.name().to_string(),
+            sample_depth: splt.sample_depth(),
+            entries,
+        }
+    }
+}
+
+impl SuggestedPalette {
+    pub fn entries(&self) -> Vec<SuggestedPaletteEntry> {
+        self.entries
+            .iter()
+            .map(|color| SuggestedPaletteEntry {
+                red: color.red,
+                green: color.green,
+                blue: color.blue,
+                alpha: color.alpha,
+                frequency: color.frequency,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FramesReport {
+    pub num_frames: u32,
+    /// 0 plays forever.
+    pub num_plays: u32,
+    pub duration_ms: u128,
+    pub frames: Vec<FrameReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FrameReport {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_ms: u128,
+    pub dispose: String,
+    pub blend: String,
+    pub bytes: usize,
+    pub default_image: bool,
+}
+
+impl From<&Animation> for FramesReport {
+    fn from(animation: &Animation) -> Self {
+        FramesReport {
+            num_frames: animation.control.num_frames(),
+            num_plays: animation.control.num_plays(),
+            duration_ms: animation.duration().as_millis(),
+            frames: animation
+                .frames
+                .iter()
+                .map(|frame| FrameReport {
+                    sequence_number: frame.control.sequence_number,
+                    width: frame.control.width,
+                    height: frame.control.height,
+                    x_offset: frame.control.x_offset,
+                    y_offset: frame.control.y_offset,
+                    delay_ms: frame.control.delay().as_millis(),
+                    dispose: frame.control.dispose_op.to_string(),
+                    blend: frame.control.blend_op.to_string(),
+                    bytes: frame.data.len(),
+                    default_image: frame.is_default_image,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CapacityReport {
+    pub mode: String,
+    pub capacity: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkSummary {
+    pub index: usize,
+    pub chunk_type: String,
+    pub length: usize,
+    pub crc: u32,
+    pub sha256: String,
+}
+
+impl ChunkSummary {
+    pub fn new(index: usize, chunk: &Chunk) -> Self {
+        ChunkSummary {
+            index,
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            sha256: diff::data_hash(chunk),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ByteChangeReport {
+    pub offset: usize,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ChangeReport {
+    Removed {
+        old: ChunkSummary,
+    },
+    Added {
+        new: ChunkSummary,
+    },
+    Modified {
+        old: ChunkSummary,
+        new: ChunkSummary,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bytes: Option<Vec<ByteChangeReport>>,
+    },
+}
+
+impl ChangeReport {
+    pub fn new(change: &ChunkChange, with_bytes: bool) -> Self {
+        match *change {
+            ChunkChange::Removed { index, chunk } => ChangeReport::Removed {
+                old: ChunkSummary::new(index, chunk),
+            },
+            ChunkChange::Added { index, chunk } => ChangeReport::Added {
+                new: ChunkSummary::new(index, chunk),
+            },
+            ChunkChange::Modified {
+                old_index,
+                new_index,
+                old,
+                new,
+            } => ChangeReport::Modified {
+                old: ChunkSummary::new(old_index, old),
+                new: ChunkSummary::new(new_index, new),
+                bytes: with_bytes.then(|| {
+                    diff::byte_changes(old.data(), new.data())
+                        .into_iter()
+                        .map(|change| ByteChangeReport {
+                            offset: change.offset,
+                            old: STANDARD.encode(change.old),
+                            new: STANDARD.encode(change.new),
+                        })
+                        .collect()
+                }),
+            },
+        }
+    }
+}
+
+/// The chunks of a png before it is modified, so a dry run can report what
+/// would change.
+#[derive(Debug)]
+pub struct Snapshot {
+    chunks: Vec<(String, usize, u32)>,
+    size: usize,
+}
+
+impl Snapshot {
+    pub fn new(png: &Png) -> Self {
+        Snapshot {
+            chunks: png.chunks().iter().map(Snapshot::entry).collect(),
+            size: png.as_bytes().len(),
+        }
+    }
+
+    fn entry(chunk: &Chunk) -> (String, usize, u32) {
+        (chunk.chunk_type().to_string(), chunk.length(), chunk.crc())
+    }
+
+    /// Prints the chunks removed from and added to `png` since the snapshot.
+    #[throws(anyhow::Error)]
+    pub fn print_changes(&self, png: &Png) {
+        let mut removed = self.chunks.clone();
+        let mut added = vec![];
+
+        for chunk in png.chunks().iter().map(Snapshot::entry) {
+            match removed.iter().position(|entry| *entry == chunk) {
+                Some(index) => {
+                    removed.remove(index);
+                }
+                None => added.push(chunk),
+            }
+        }
+
+        for (chunk_type, length, _) in &removed {
+            outln!("- {} ({} bytes)", chunk_type, length);
+        }
+
+        for (chunk_type, length, _) in &added {
+            outln!("+ {} ({} bytes)", chunk_type, length);
+        }
+
+        if removed.is_empty() && added.is_empty() {
+            outln!("No changes");
+        }
+
+        outln!(
+            "File size: {} bytes (was {})",
+            png.as_bytes().len(),
+            self.size
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+pub fn print_json(value: &impl Serialize) {
+    let json = serde_json::to_string_pretty(value).context("failed to serialize output")?;
+    outln!("{}", json);
+}
@@ -0,0 +1,65 @@
+use std::io::IsTerminal;
+
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::palette::{PaletteChunk, TransparencyChunk};
+
+use crate::{
+    cli::{OutputFormat, Palette},
+    error::not_found,
+    output::{outln, print_json, PaletteEntry},
+    util::parse_png_from_file,
+};
+
+#[throws(anyhow::Error)]
+pub fn run(args: Palette) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let color_type = png.color_type().context("invalid image header")?;
+
+    let palette = png
+        .chunk_by_type("PLTE")
+        .ok_or_else(|| not_found("png has no palette"))?;
+    let palette = PaletteChunk::try_from(palette).context("invalid PLTE chunk")?;
+    let transparency = png
+        .chunk_by_type("tRNS")
+        .map(|chunk| TransparencyChunk::parse(chunk, color_type))
+        .transpose()
+        .context("invalid tRNS chunk")?;
+
+    let entries: Vec<PaletteEntry> = palette
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(index, rgb)| {
+            let alpha = transparency
+                .as_ref()
+                .map_or(u8::MAX, |transparency| transparency.alpha(index));
+            PaletteEntry::new(index, *rgb, alpha)
+        })
+        .collect();
+
+    if args.format == OutputFormat::Json {
+        return print_json(&entries)?;
+    }
+
+    // Only draw swatches where there's a terminal to show the colours
+    let swatches = std::io::stdout().is_terminal();
+
+    outln!("Palette: {} entries ({})", entries.len(), color_type);
+    for entry in &entries {
+        let swatch = match swatches {
+            true => format!(
+                "\x1b[38;2;{};{};{}m\u{2588}\u{2588}\x1b[0m ",
+                entry.red, entry.green, entry.blue
+            ),
+            false => String::new(),
+        };
+        outln!(
+            "  [{:>3}] {}{} alpha {}",
+            entry.index,
+            swatch,
+            entry.hex,
+            entry.alpha
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! Progress bars for batches and large files, drawn on stderr.
+
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+
+/// Files smaller than this are read and written without a progress bar.
+pub const LARGE_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Shows progress bars from now on, unless `quiet` is set or stdout isn't a
+/// terminal, in which case the output is likely read by another program.
+pub fn init(quiet: bool) {
+    ENABLED.store(!quiet && std::io::stdout().is_terminal(), Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn bar(len: u64, template: &str) -> ProgressBar {
+    if !is_enabled() {
+        return ProgressBar::hidden();
+    }
+
+    let style = ProgressStyle::with_template(template).expect("invalid progress template");
+    ProgressBar::new(len)
+        .with_style(style)
+        .with_finish(ProgressFinish::AndClear)
+}
+
+/// Progress through the files of a batch.
+pub fn files(count: usize) -> ProgressBar {
+    bar(
+        count as u64,
+        "{wide_bar} {pos}/{len} files, {per_sec}, ETA {eta}",
+    )
+}
+
+/// Progress through `len` bytes of a file, hidden unless the file is large.
+pub fn bytes(len: u64) -> ProgressBar {
+    if len < LARGE_FILE_SIZE {
+        return ProgressBar::hidden();
+    }
+
+    bar(
+        len,
+        "{wide_bar} {bytes}/{total_bytes}, {bytes_per_sec}, ETA {eta}",
+    )
+}
@@ -0,0 +1,76 @@
+use anyhow::Context;
+use fehler::{throw, throws};
+use pngme_lib::shamir::{self, Share};
+
+use crate::{
+    cli::{JoinSecret, SplitSecret},
+    error::invalid_arguments,
+    output::{outln, Snapshot},
+    util::{check_output_path, parse_png_from_file, save_png_or_preview, write_output_file},
+};
+
+#[throws(anyhow::Error)]
+pub fn split(args: SplitSecret) {
+    if args.png_paths.len() != args.shares as usize {
+        throw!(invalid_arguments(format!(
+            "{} shares need {} pngs, got {}",
+            args.shares,
+            args.shares,
+            args.png_paths.len()
+        )))
+    }
+
+    let shares = shamir::split(args.message.as_bytes(), args.threshold, args.shares)
+        .context("failed to split secret")?;
+
+    // Check every png before writing any, so a failure doesn't leave only
+    // some of the shares written
+    let mut outputs = vec![];
+    for png_path in &args.png_paths {
+        let png = parse_png_from_file(png_path)
+            .with_context(|| format!("failed to read {}", png_path.display()))?;
+
+        let output_path = match &args.out_dir {
+            Some(dir) => dir.join(png_path.file_name().context("png path has no file name")?),
+            None => png_path.clone(),
+        };
+        check_output_path(png_path, &output_path, args.guard.force)?;
+
+        outputs.push((png, output_path));
+    }
+
+    for ((mut png, output_path), share) in outputs.into_iter().zip(&shares) {
+        let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+        png.retain_chunks(|chunk| chunk.chunk_type().bytes() != shamir::SHARE);
+        png.insert_chunk_ordered(share.to_chunk());
+
+        outln!(
+            "Stored share {} of {} in {}",
+            share.index(),
+            args.shares,
+            output_path.display()
+        );
+        save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+    }
+}
+
+#[throws(anyhow::Error)]
+pub fn join(args: JoinSecret) {
+    let mut shares = vec![];
+    for png_path in &args.png_paths {
+        let png = parse_png_from_file(png_path)?;
+        let chunk = png
+            .chunk_by_type("shAr")
+            .with_context(|| format!("no share found in {}", png_path.display()))?;
+
+        shares.push(Share::try_from(chunk).context("invalid share chunk")?);
+    }
+
+    let secret = shamir::combine(&shares).context("failed to recover secret")?;
+
+    match args.output_file {
+        Some(path) => write_output_file(&path, &secret)?,
+        None => outln!("Secret: \"{}\"", String::from_utf8_lossy(&secret)),
+    }
+}
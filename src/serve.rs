@@ -0,0 +1,266 @@
+use std::{io::Read, str::FromStr};
+
+use anyhow::{anyhow, Context};
+use fehler::{throw, throws};
+use pngme_lib::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::CompressionError,
+    png::{ParseOptions, Png},
+};
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    cli::Serve,
+    decode_message_limited,
+    error::invalid_arguments,
+    output::{ChunkReport, DecodeReport},
+};
+
+/// Largest png accepted in a request body.
+const MAX_UPLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Most chunks accepted in an uploaded png, far fewer than the command line
+/// allows since every one of them may need decoding.
+const MAX_CHUNK_COUNT: usize = 10_000;
+
+/// Most bytes all the messages of a single request may decode to together,
+/// so many small compressed chunks can't add up to a decompression bomb.
+const MAX_DECODED_SIZE: u64 = 64 * 1024 * 1024;
+
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+/// A failed request, with the status code to answer it with.
+struct HttpError {
+    status: u16,
+    error: anyhow::Error,
+}
+
+impl From<anyhow::Error> for HttpError {
+    fn from(error: anyhow::Error) -> Self {
+        HttpError { status: 400, error }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    error: String,
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("valid header")
+}
+
+fn json_response(status: u16, value: &impl Serialize) -> HttpResponse {
+    let json = serde_json::to_vec_pretty(value).expect("reports serialize to json");
+
+    Response::from_data(json)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+/// Value of the query parameter `name` of the request url.
+fn query_param(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+#[throws(anyhow::Error)]
+fn required_param(url: &str, name: &str) -> String {
+    query_param(url, name).with_context(|| format!("missing `{}` query parameter", name))?
+}
+
+fn read_png(request: &mut Request) -> Result<Png, HttpError> {
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(MAX_UPLOAD_SIZE + 1)
+        .read_to_end(&mut body)
+        .context("failed to read request body")?;
+
+    if body.len() as u64 > MAX_UPLOAD_SIZE {
+        return Err(HttpError {
+            status: 413,
+            error: anyhow!("png is larger than {} bytes", MAX_UPLOAD_SIZE),
+        });
+    }
+
+    let options = ParseOptions {
+        max_chunk_count: MAX_CHUNK_COUNT,
+        ..ParseOptions::default()
+    };
+    let (png, _) = Png::parse(&body, options).context("failed to parse png")?;
+    Ok(png)
+}
+
+/// `POST /encode?chunk_type=TYPE&message=MESSAGE`, answers with the png
+/// from the body with the message added.
+fn encode(request: &mut Request) -> Result<HttpResponse, HttpError> {
+    let chunk_type = required_param(request.url(), "chunk_type")?;
+    let chunk_type = ChunkType::from_str(&chunk_type).context("invalid chunk type")?;
+    let message = required_param(request.url(), "message")?;
+
+    let mut png = read_png(request)?;
+    png.insert_chunk_ordered(Chunk::new(chunk_type, message.into_bytes()));
+
+    Ok(Response::from_data(png.as_bytes()).with_header(header("Content-Type", "image/png")))
+}
+
+/// Decodes the message of each chunk, failing once they add up to more than
+/// [`MAX_DECODED_SIZE`] bytes.
+fn decode_messages(chunks: &[&Chunk]) -> Result<Vec<Vec<u8>>, HttpError> {
+    let too_large = || HttpError {
+        status: 413,
+        error: anyhow!(
+            "messages decode to more than {} bytes together",
+            MAX_DECODED_SIZE
+        ),
+    };
+    let mut remaining = MAX_DECODED_SIZE;
+    let mut messages = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let data = chunk.data().to_vec();
+        let message = decode_message_limited(chunk.chunk_type(), data, None, None, None, remaining)
+            .map_err(|error| match error.downcast_ref::<CompressionError>() {
+                Some(CompressionError::DecompressedTooLarge { .. }) => too_large(),
+                _ => HttpError::from(error),
+            })?;
+
+        remaining = remaining
+            .checked_sub(message.len() as u64)
+            .ok_or_else(too_large)?;
+        messages.push(message);
+    }
+
+    Ok(messages)
+}
+
+/// `POST /decode?chunk_type=TYPE`, answers with every message of that type
+/// in the png from the body.
+fn decode(request: &mut Request) -> Result<HttpResponse, HttpError> {
+    let chunk_type = required_param(request.url(), "chunk_type")?;
+    let chunk_type = ChunkType::from_str(&chunk_type).context("invalid chunk type")?;
+    let png = read_png(request)?;
+
+    let chunks: Vec<&Chunk> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| *chunk.chunk_type() == chunk_type)
+        .collect();
+    if chunks.is_empty() {
+        return Err(HttpError {
+            status: 404,
+            error: anyhow!("chunk not found"),
+        });
+    }
+
+    let messages = decode_messages(&chunks)?;
+    let reports: Vec<DecodeReport> = chunks
+        .iter()
+        .zip(&messages)
+        .map(|(chunk, message)| DecodeReport::new(chunk, message))
+        .collect();
+
+    Ok(json_response(200, &reports))
+}
+
+/// `POST /chunks`, answers with every chunk of the png from the body.
+fn chunks(request: &mut Request) -> Result<HttpResponse, HttpError> {
+    let png = read_png(request)?;
+    let reports: Vec<ChunkReport> = png.chunks().iter().map(ChunkReport::from).collect();
+
+    Ok(json_response(200, &reports))
+}
+
+fn handle(request: &mut Request) -> HttpResponse {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let result = match (request.method(), path.as_str()) {
+        (Method::Post, "/encode") => encode(request),
+        (Method::Post, "/decode") => decode(request),
+        (Method::Post, "/chunks") => chunks(request),
+        (_, "/encode" | "/decode" | "/chunks") => Err(HttpError {
+            status: 405,
+            error: anyhow!("only POST is supported"),
+        }),
+        _ => Err(HttpError {
+            status: 404,
+            error: anyhow!("no such endpoint `{}`", path),
+        }),
+    };
+
+    result.unwrap_or_else(|HttpError { status, error }| {
+        json_response(
+            status,
+            &ErrorReport {
+                error: format!("{:#}", error),
+            },
+        )
+    })
+}
+
+#[throws(anyhow::Error)]
+pub fn run(args: Serve) {
+    if args.threads == 0 {
+        throw!(invalid_arguments("need at least one thread"))
+    }
+
+    let address = (args.host.as_str(), args.port);
+    let server = Server::http(address)
+        .map_err(|error| anyhow!(error))
+        .with_context(|| format!("failed to listen on {}:{}", args.host, args.port))?;
+
+    eprintln!("Listening on http://{}:{}", args.host, args.port);
+
+    std::thread::scope(|scope| {
+        for _ in 0..args.threads {
+            scope.spawn(|| {
+                for mut request in server.incoming_requests() {
+                    let response = handle(&mut request);
+                    if let Err(error) = request.respond(response) {
+                        eprintln!("error: failed to send response: {}", error);
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use pngme_lib::compression::{self, CompressionMethod};
+
+    use super::*;
+
+    fn compressed_chunk(size: usize) -> Chunk {
+        let data = compression::compress(CompressionMethod::Zstd, &vec![0; size]).unwrap();
+        Chunk::new(ChunkType::from_str("ruSt").unwrap(), data)
+    }
+
+    #[test]
+    fn test_decode_messages() {
+        let chunks = [compressed_chunk(1024), compressed_chunk(2048)];
+        let messages = decode_messages(&chunks.iter().collect::<Vec<_>>())
+            .unwrap_or_else(|error| panic!("{:#}", error.error));
+        assert_eq!(messages[0].len(), 1024);
+        assert_eq!(messages[1].len(), 2048);
+    }
+
+    #[test]
+    fn test_decode_messages_total_limit() {
+        // Each one is well within the per message limit, not all of them
+        let chunk = compressed_chunk(MAX_DECODED_SIZE as usize / 4);
+        let chunks = vec![&chunk; 5];
+        let error = decode_messages(&chunks).err().unwrap();
+        assert_eq!(error.status, 413);
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Context;
+use fehler::{throw, throws};
+use pngme_lib::{palette::SuggestedPaletteChunk, png::Png};
+
+use crate::{
+    cli::{OutputFormat, Splt, SpltExport, SpltImport, SpltList},
+    error::{invalid_arguments, not_found},
+    output::{outln, print_json, Snapshot, SuggestedPalette},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, read_input_file, resolve_output_path,
+        save_png_or_preview, write_output_file,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Splt) {
+    match command {
+        Splt::List(args) => list(args),
+        Splt::Export(args) => export(args),
+        Splt::Import(args) => import(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn suggested_palettes(png: &Png) -> Vec<SuggestedPaletteChunk> {
+    png.chunks_by_type("sPLT")
+        .map(SuggestedPaletteChunk::try_from)
+        .collect::<Result<_, _>>()
+        .context("invalid sPLT chunk")?
+}
+
+#[throws(anyhow::Error)]
+fn list(args: SpltList) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let palettes = suggested_palettes(&png)?;
+
+    if args.format == OutputFormat::Json {
+        let palettes: Vec<SuggestedPalette> = palettes.iter().map(SuggestedPalette::from).collect();
+        return print_json(&palettes)?;
+    }
+
+    if palettes.is_empty() {
+        outln!("No suggested palettes found");
+    }
+
+    for splt in &palettes {
+        outln!(
+            "{}: {} entries, {}-bit samples",
+            splt.name(),
+            splt.entries().len(),
+            splt.sample_depth()
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+fn export(args: SpltExport) {
+    let png = parse_png_from_file(&args.png_path)?;
+    let mut palettes = suggested_palettes(&png)?;
+
+    let splt = match &args.name {
+        Some(name) => palettes
+            .into_iter()
+            .find(|splt| splt.name() == name)
+            .ok_or_else(|| not_found(format!("no suggested palette named \"{}\"", name)))?,
+        None if palettes.len() > 1 => throw!(invalid_arguments(
+            "png has more than one suggested palette, pick one with --name"
+        )),
+        None => palettes
+            .pop()
+            .ok_or_else(|| not_found("no suggested palettes found"))?,
+    };
+
+    let json = serde_json::to_string_pretty(&SuggestedPalette::from(&splt))
+        .context("failed to serialize palette")?;
+    write_output_file(&args.json_path, format!("{}\n", json).as_bytes())?;
+
+    if !is_stdio(&args.json_path) {
+        outln!(
+            "Exported suggested palette \"{}\" ({} entries)",
+            splt.name(),
+            splt.entries().len()
+        );
+    }
+}
+
+#[throws(anyhow::Error)]
+fn import(args: SpltImport) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let json = read_input_file(&args.json_path)?;
+    let palette: SuggestedPalette =
+        serde_json::from_slice(&json).context("failed to parse palette json")?;
+    let splt = SuggestedPaletteChunk::new(&palette.name, palette.sample_depth, palette.entries())
+        .context("invalid suggested palette")?;
+
+    // Names are unique within a png, so an import replaces its namesake
+    png.retain_chunks(|chunk| {
+        chunk.chunk_type().bytes() != *b"sPLT"
+            || SuggestedPaletteChunk::try_from(chunk).map_or(true, |old| old.name() != splt.name())
+    });
+    png.insert_chunk_ordered(splt.to_chunk()?);
+
+    if !is_stdio(&output_path) {
+        outln!(
+            "Imported suggested palette \"{}\" ({} entries)",
+            splt.name(),
+            splt.entries().len()
+        );
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
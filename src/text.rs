@@ -0,0 +1,147 @@
+use anyhow::Context;
+use fehler::{throw, throws};
+use pngme_lib::{
+    chunk::Chunk,
+    png::Png,
+    text::{CompressedTextChunk, InternationalTextChunk, TextChunk, TextualChunk},
+};
+
+use crate::{
+    cli::{Text, TextGet, TextList, TextRemove, TextSet},
+    error::not_found,
+    output::{outln, Snapshot},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, resolve_output_path, save_png_or_preview,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Text) {
+    match command {
+        Text::Set(args) => set(args),
+        Text::Get(args) => get(args),
+        Text::List(args) => list(args),
+        Text::Remove(args) => remove(args),
+    }?
+}
+
+/// Textual chunks of the png, skipping any that fail to decode.
+fn textual_chunks(png: &Png) -> impl Iterator<Item = TextualChunk> + '_ {
+    png.chunks()
+        .iter()
+        .filter(|chunk| TextualChunk::is_textual(chunk))
+        .filter_map(|chunk| TextualChunk::try_from(chunk).ok())
+}
+
+fn has_keyword(chunk: &Chunk, keyword: &str) -> bool {
+    TextualChunk::is_textual(chunk)
+        && TextualChunk::try_from(chunk).is_ok_and(|text| text.keyword() == keyword)
+}
+
+#[throws(anyhow::Error)]
+fn set(args: TextSet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let international =
+        args.international || args.language.is_some() || args.translated_keyword.is_some();
+    let language = international.then(|| {
+        (
+            args.language.as_deref().unwrap_or_default(),
+            args.translated_keyword.as_deref().unwrap_or_default(),
+        )
+    });
+
+    set_text(
+        &mut png,
+        &args.keyword,
+        &args.text,
+        args.compressed,
+        language,
+    )?;
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
+
+/// Replaces the text stored under `keyword`, as a `tEXt` chunk, a `zTXt`
+/// chunk when compressed, or an `iTXt` chunk when given a language and
+/// translated keyword, which may be empty.
+#[throws(anyhow::Error)]
+pub fn set_text(
+    png: &mut Png,
+    keyword: &str,
+    text: &str,
+    compressed: bool,
+    language: Option<(&str, &str)>,
+) {
+    let chunk = if let Some((language, translated_keyword)) = language {
+        InternationalTextChunk::new(keyword, text)
+            .and_then(|text| text.with_language(language, translated_keyword))
+            .map(|text| text.with_compression(compressed))
+            .and_then(|text| text.to_chunk())
+    } else if compressed {
+        CompressedTextChunk::new(keyword, text).and_then(|text| text.to_chunk())
+    } else {
+        TextChunk::new(keyword, text).map(|text| text.to_chunk())
+    }
+    .context("invalid text chunk, use --international for non latin-1 text")?;
+
+    png.retain_chunks(|chunk| !has_keyword(chunk, keyword));
+    png.insert_chunk_ordered(chunk);
+}
+
+#[throws(anyhow::Error)]
+fn get(args: TextGet) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let text = textual_chunks(&png)
+        .find(|text| text.keyword() == args.keyword)
+        .ok_or_else(|| not_found("keyword not found"))?;
+
+    outln!("{}", text.text());
+}
+
+#[throws(anyhow::Error)]
+fn list(args: TextList) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    for text in textual_chunks(&png) {
+        match &text {
+            TextualChunk::International(chunk) if !chunk.language_tag().is_empty() => outln!(
+                "{} [{}]: {}",
+                text.keyword(),
+                chunk.language_tag(),
+                text.text()
+            ),
+            _ => outln!("{}: {}", text.keyword(), text.text()),
+        }
+    }
+}
+
+#[throws(anyhow::Error)]
+fn remove(args: TextRemove) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let count = png.chunks().len();
+    png.retain_chunks(|chunk| !has_keyword(chunk, &args.keyword));
+    let removed = count - png.chunks().len();
+
+    if removed == 0 {
+        throw!(not_found("keyword not found"))
+    }
+
+    if !is_stdio(&output_path) {
+        outln!(
+            "Removed {} text chunk(s) with keyword \"{}\"",
+            removed,
+            args.keyword
+        );
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
@@ -0,0 +1,47 @@
+use ::time::OffsetDateTime;
+use anyhow::Context;
+use fehler::throws;
+use pngme_lib::time::TimeChunk;
+
+use crate::{
+    cli::{Time, TimeGet, TimeSet},
+    error::not_found,
+    output::{outln, Snapshot},
+    util::{check_output_path, parse_png_from_file, resolve_output_path, save_png_or_preview},
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Time) {
+    match command {
+        Time::Get(args) => get(args),
+        Time::Set(args) => set(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn get(args: TimeGet) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let chunk = png
+        .chunk_by_type("tIME")
+        .ok_or_else(|| not_found("no modification time found"))?;
+    let time = TimeChunk::try_from(chunk).context("invalid tIME chunk")?;
+
+    outln!("{}", time);
+}
+
+#[throws(anyhow::Error)]
+fn set(args: TimeSet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let datetime = args.at.unwrap_or_else(OffsetDateTime::now_utc);
+    let time = TimeChunk::from(datetime);
+
+    png.retain_chunks(|chunk| chunk.chunk_type().bytes() != *b"tIME");
+    png.insert_chunk_ordered(time.to_chunk());
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
@@ -1,27 +1,601 @@
-use std::path::Path;
+use std::{
+    fs::{File, FileTimes},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
 
-use anyhow::{bail, Context};
-use fehler::throws;
-use pngme_lib::png::Png;
+use anyhow::{anyhow, bail, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fehler::{throw, throws};
+use pngme_lib::{
+    chunk::Chunk,
+    png::{ChunkReader, ParseOptions, Png},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    cli::{BackupOptions, WriteOptions},
+    config,
+    error::not_found,
+    output::Snapshot,
+    progress,
+};
+
+/// Path used on the command line to refer to stdin or stdout.
+pub const STDIO_PATH: &str = "-";
+
+/// Files larger than this are memory-mapped instead of read into memory.
+const MMAP_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Whether `path` is an http or https URL, which read-only commands accept
+/// in place of a file.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("https://") || path.starts_with("http://"))
+}
+
+/// Fetches `url`, streaming the response body.
+#[cfg(feature = "remote")]
+#[throws(anyhow::Error)]
+pub fn open_url(url: &Path) -> Box<dyn Read> {
+    let url = url.to_str().context("URL is not valid utf8")?;
+    let response = reqwest::blocking::Client::builder()
+        .user_agent(concat!("pngme/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .and_then(|client| client.get(url).send())
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to fetch {}", url))?;
+
+    let size = response.content_length().unwrap_or(0);
+    let reader: Box<dyn Read> = Box::new(progress::bytes(size).wrap_read(response));
+    reader
+}
+
+#[cfg(not(feature = "remote"))]
+pub fn open_url(_url: &Path) -> anyhow::Result<Box<dyn Read>> {
+    bail!("pngme was built without URL support, enable the `remote` feature")
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
 
 #[throws(anyhow::Error)]
 pub fn validate_png_path(path: &Path) {
     if !path.is_file() {
-        bail!("Entered path is not a valid file.")
+        throw!(not_found("Entered path is not a valid file."))
     }
 }
 
+#[throws(anyhow::Error)]
+pub fn parse_png_from_reader(mut reader: impl Read) -> Png {
+    let mut png_file = vec![];
+    reader
+        .read_to_end(&mut png_file)
+        .context("failed to read png")?;
+
+    Png::try_from(png_file.as_slice()).context("failed to parse png")?
+}
+
 #[throws(anyhow::Error)]
 pub fn parse_png_from_file(path: &Path) -> Png {
+    if is_stdio(path) {
+        return parse_png_from_reader(std::io::stdin().lock())?;
+    }
+
+    if is_url(path) {
+        return parse_png_from_reader(open_url(path)?)?;
+    }
+
     validate_png_path(path)?;
 
+    let size = std::fs::metadata(path)
+        .context("failed to read png file")?
+        .len();
+    if size > MMAP_THRESHOLD && !progress::is_enabled() {
+        return Png::from_file_mmap(path).context("failed to parse png file")?;
+    }
+
+    if size >= progress::LARGE_FILE_SIZE {
+        // Streamed chunk by chunk so the progress bar can follow along
+        let chunks = open_chunk_reader(path, true)?
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to parse png file")?;
+        return Png::from_chunks(chunks);
+    }
+
     let png_file = std::fs::read(path).context("failed to read png file")?;
     let png = Png::try_from(png_file.as_slice()).context("failed to parse png file")?;
 
     png
 }
 
+/// Opens a png file, stdin if `path` is "-", or a URL, for streaming its
+/// chunks.
+#[throws(anyhow::Error)]
+pub fn open_chunk_reader(path: &Path, verify_crc: bool) -> ChunkReader<Box<dyn Read>> {
+    let reader: Box<dyn Read> = if is_stdio(path) {
+        Box::new(BufReader::new(std::io::stdin()))
+    } else if is_url(path) {
+        Box::new(BufReader::new(open_url(path)?))
+    } else {
+        validate_png_path(path)?;
+        let file = File::open(path).context("failed to read png file")?;
+        let size = file.metadata().context("failed to read png file")?.len();
+        Box::new(progress::bytes(size).wrap_read(BufReader::new(file)))
+    };
+
+    ChunkReader::new(reader).verify_crc(verify_crc)
+}
+
+/// Parses a png with custom options, reporting skipped chunks on stderr.
+#[throws(anyhow::Error)]
+pub fn parse_png_from_file_with(path: &Path, options: ParseOptions) -> Png {
+    if options == ParseOptions::default() {
+        return parse_png_from_file(path)?;
+    }
+
+    let png_file = read_input_file(path)?;
+    let (png, warnings) = Png::parse(&png_file, options).context("failed to parse png file")?;
+
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+
+    png
+}
+
+/// Reads a whole file, stdin if `path` is "-", or a URL.
+#[throws(anyhow::Error)]
+pub fn read_input_file(path: &Path) -> Vec<u8> {
+    if is_stdio(path) {
+        let mut input = vec![];
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut input)
+            .context("failed to read stdin")?;
+        return input;
+    }
+
+    if is_url(path) {
+        let mut input = vec![];
+        open_url(path)?
+            .read_to_end(&mut input)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        return input;
+    }
+
+    let read = || {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut input = Vec::with_capacity(size as usize);
+        progress::bytes(size)
+            .wrap_read(file)
+            .read_to_end(&mut input)?;
+        Ok::<_, std::io::Error>(input)
+    };
+
+    read().with_context(|| format!("failed to read {}", path.display()))?
+}
+
+/// Reads a text message from a file, or stdin if `path` is "-".
+#[throws(anyhow::Error)]
+pub fn read_message_file(path: &Path) -> String {
+    String::from_utf8(read_input_file(path)?)
+        .context("message is not valid utf8, use --input-file for binary data")?
+}
+
+/// Writes `data` to a file, or stdout if `path` is "-".
+#[throws(anyhow::Error)]
+pub fn write_output_file(path: &Path, data: &[u8]) {
+    if is_stdio(path) {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(data).context("failed to write stdout")?;
+        return stdout.flush().context("failed to write stdout")?;
+    }
+
+    write_file_atomic(path, &WriteOptions::default(), false, |writer| {
+        writer.write_all(data)
+    })?;
+}
+
+/// Writes `data` to a file only its owner can read, for secret keys.
+#[throws(anyhow::Error)]
+pub fn write_private_file(path: &Path, data: &[u8]) {
+    write_file_atomic(path, &WriteOptions::default(), true, |writer| {
+        writer.write_all(data)
+    })?;
+}
+
+#[throws(anyhow::Error)]
+pub fn save_png_to_writer(png: Png, writer: impl Write) {
+    let mut writer = BufWriter::new(writer);
+    png.write_to(&mut writer).context("failed to write png")?;
+    writer.flush().context("failed to write png")?;
+}
+
+#[throws(anyhow::Error)]
+pub fn save_png_to_file(png: Png, path: &Path, options: &WriteOptions) {
+    if is_stdio(path) {
+        return save_png_to_writer(png, std::io::stdout().lock())?;
+    }
+
+    let size = 8 + png
+        .chunks()
+        .iter()
+        .map(|chunk| chunk.length() as u64 + 12)
+        .sum::<u64>();
+    let bar = progress::bytes(size);
+
+    write_file_atomic(path, options, false, |writer| {
+        png.write_to(&mut bar.wrap_write(writer))
+    })
+    .context("failed to write png file")?;
+
+    if options.verify {
+        verify_written(&png, path)?;
+    }
+}
+
+/// Reads back the png just written to `path`, failing unless every crc is
+/// valid and it holds the same chunks as `png`, in the same order.
+#[throws(anyhow::Error)]
+fn verify_written(png: &Png, path: &Path) {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("failed to read back {} to verify it", path.display()))?;
+    let written = Png::try_from(bytes.as_slice()).with_context(|| {
+        format!(
+            "verification of {} failed, it doesn't parse",
+            path.display()
+        )
+    })?;
+
+    let (expected, actual) = (png.chunks(), written.chunks());
+    let describe = |chunk: Option<&Chunk>| match chunk {
+        Some(chunk) => format!("{} ({} bytes)", chunk.chunk_type(), chunk.length()),
+        None => "missing".to_string(),
+    };
+    if let Some(index) = (0..expected.len().max(actual.len()))
+        .find(|&index| expected.get(index) != actual.get(index))
+    {
+        bail!(
+            "verification of {} failed, chunk {} is {} instead of {}",
+            path.display(),
+            index,
+            describe(actual.get(index)),
+            describe(expected.get(index))
+        )
+    }
+
+    debug!(path = %path.display(), chunks = actual.len(), "verified written png");
+}
+
+/// Where to write a png read from `input`: `output` if given, otherwise
+/// `input` itself or a suffixed copy, as set in the config file.
+pub fn resolve_output_path(input: &Path, output: Option<&Path>) -> PathBuf {
+    match output {
+        Some(output) => output.to_path_buf(),
+        None if is_stdio(input) => input.to_path_buf(),
+        None => config::get().output_path(input),
+    }
+}
+
+/// Refuses to overwrite an existing file other than `input` unless `force`
+/// is set.
+#[throws(anyhow::Error)]
+pub fn check_output_path(input: &Path, output: &Path, force: bool) {
+    if is_url(output) {
+        bail!(
+            "can't write to {}, give an output path instead",
+            output.display()
+        )
+    }
+
+    if force || is_stdio(output) || !output.exists() {
+        return;
+    }
+
+    let same_file = match (std::fs::canonicalize(input), std::fs::canonicalize(output)) {
+        (Ok(input), Ok(output)) => input == output,
+        _ => false,
+    };
+
+    if !same_file {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            output.display()
+        )
+    }
+}
+
+/// Writes `png` to `path`, or only prints what changed since `dry_run` was
+/// taken when doing a dry run.
+#[throws(anyhow::Error)]
+pub fn save_png_or_preview(
+    png: Png,
+    path: &Path,
+    dry_run: Option<Snapshot>,
+    options: &WriteOptions,
+) {
+    match dry_run {
+        Some(snapshot) => snapshot.print_changes(&png)?,
+        None => save_png_to_file(png, path, options)?,
+    }
+}
+
+/// Streams the output of `write` to a temporary file next to `path` and
+/// renames it over `path`, so a crash midway never leaves a truncated file
+/// behind. Symlinks are followed and existing permissions are kept, as are
+/// special mode bits and times when `options` ask for them. New files get the
+/// usual permissions left by the umask, or owner-only ones when `private` is
+/// set.
+#[throws(anyhow::Error)]
+pub fn write_file_atomic(
+    path: &Path,
+    options: &WriteOptions,
+    private: bool,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) {
+    if is_url(path) {
+        bail!("can't write to {}", path.display())
+    }
+
+    let path = match std::fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(_) => path.to_path_buf(),
+    };
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut builder = tempfile::Builder::new();
+    // Opened with 0666 the same way `File::create` is, so the umask applies
+    #[cfg(unix)]
+    if !private {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+
+    let file = builder
+        .tempfile_in(dir)
+        .with_context(|| format!("failed to create temporary file in {}", dir.display()))?;
+
+    let mut writer = BufWriter::new(file);
+    write(&mut writer).context("failed to write temporary file")?;
+    let file = writer
+        .into_inner()
+        .map_err(|err| err.into_error())
+        .context("failed to write temporary file")?;
+
+    file.as_file()
+        .sync_all()
+        .context("failed to write temporary file")?;
+
+    if let Ok(metadata) = std::fs::metadata(&path) {
+        if !private {
+            let mut permissions = metadata.permissions();
+            // Like a write by anyone but root, a rewrite drops the setuid,
+            // setgid and sticky bits unless the whole mode is asked for
+            #[cfg(unix)]
+            if !options.preserve_mode {
+                use std::os::unix::fs::PermissionsExt;
+                permissions.set_mode(permissions.mode() & 0o777);
+            }
+            file.as_file()
+                .set_permissions(permissions)
+                .context("failed to copy permissions")?;
+        }
+
+        if options.preserve_times {
+            let times = FileTimes::new()
+                .set_accessed(metadata.accessed().context("failed to read file times")?)
+                .set_modified(metadata.modified().context("failed to read file times")?);
+            file.as_file()
+                .set_times(times)
+                .context("failed to copy file times")?;
+        }
+
+        #[cfg(not(unix))]
+        if options.preserve_mode {
+            warn!("--preserve-mode is only supported on unix, special bits aren't kept");
+        }
+    }
+
+    file.persist(&path)
+        .with_context(|| format!("failed to replace {}", path.display()))?;
+}
+
+/// Copies `path` to `path` + the backup suffix, if a backup was requested.
+#[throws(anyhow::Error)]
+pub fn backup_file(path: &Path, options: &BackupOptions) {
+    if let Some(suffix) = options
+        .backup
+        .as_ref()
+        .filter(|_| !options.no_backup && !is_stdio(path))
+    {
+        let mut backup_path = path.as_os_str().to_owned();
+        backup_path.push(suffix);
+        let backup_path = Path::new(&backup_path);
+
+        std::fs::copy(path, backup_path)
+            .with_context(|| format!("failed to back up to {}", backup_path.display()))?;
+    }
+}
+
+/// Reads a base64 encoded key of `N` bytes, as written by `pngme keygen`.
+#[throws(anyhow::Error)]
+pub fn read_key_file<const N: usize>(path: &Path) -> [u8; N] {
+    let key = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read key file {}", path.display()))?;
+    let key = STANDARD
+        .decode(key.trim())
+        .with_context(|| format!("{} is not a valid key file", path.display()))?;
+
+    key.try_into()
+        .map_err(|_| anyhow!("{} is not a valid key file", path.display()))?
+}
+
 #[throws(anyhow::Error)]
-pub fn save_png_to_file(png: Png, path: &Path) {
-    std::fs::write(path, png.as_bytes()).context("failed to write png file")?;
+pub fn read_passphrase(path: Option<&Path>, confirm: bool) -> String {
+    if let Some(path) = path {
+        let passphrase = std::fs::read_to_string(path).context("failed to read passphrase file")?;
+        return passphrase.trim_end_matches(&['\r', '\n'][..]).to_string();
+    }
+
+    let passphrase =
+        rpassword::prompt_password("Passphrase: ").context("failed to read passphrase")?;
+
+    if confirm {
+        let confirmation = rpassword::prompt_password("Confirm passphrase: ")
+            .context("failed to read passphrase")?;
+
+        if passphrase != confirmation {
+            bail!("passphrases do not match")
+        }
+    }
+
+    passphrase
+}
+
+#[cfg(feature = "clipboard")]
+#[throws(anyhow::Error)]
+pub fn read_clipboard() -> String {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .context("failed to read clipboard")?
+}
+
+#[cfg(feature = "clipboard")]
+#[throws(anyhow::Error)]
+pub fn write_clipboard(text: String) {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .context("failed to write clipboard")?
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn read_clipboard() -> anyhow::Result<String> {
+    bail!("pngme was built without clipboard support, enable the `clipboard` feature")
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn write_clipboard(_text: String) -> anyhow::Result<()> {
+    bail!("pngme was built without clipboard support, enable the `clipboard` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        time::{Duration, SystemTime},
+    };
+
+    use pngme_lib::{builder::PngBuilder, chunk_type::ChunkType};
+
+    use super::*;
+
+    /// Adds a message to the png at `path` in place, like `encode` does.
+    fn encode_in_place(path: &Path, options: &WriteOptions) {
+        let mut png = parse_png_from_file(path).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        png.insert_chunk_ordered(Chunk::new(chunk_type, b"hello".to_vec()));
+        save_png_to_file(png, path, options).unwrap();
+    }
+
+    #[test]
+    fn test_preserve_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let png = PngBuilder::new(4, 4).build().unwrap();
+        save_png_to_file(png, &path, &WriteOptions::default()).unwrap();
+
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let set_modified = || {
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap()
+        };
+        let modified_time = || std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        set_modified();
+        let options = WriteOptions {
+            preserve_times: true,
+            verify: true,
+            ..WriteOptions::default()
+        };
+        encode_in_place(&path, &options);
+        assert_eq!(modified_time(), modified);
+        assert_eq!(parse_png_from_file(&path).unwrap().chunks().len(), 4);
+
+        set_modified();
+        encode_in_place(&path, &WriteOptions::default());
+        assert_ne!(modified_time(), modified);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_new_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mode = |name: &str| {
+            let metadata = std::fs::metadata(dir.path().join(name)).unwrap();
+            metadata.permissions().mode() & 0o777
+        };
+
+        File::create(dir.path().join("expected")).unwrap();
+        write_output_file(&dir.path().join("output"), b"hello").unwrap();
+        let png = PngBuilder::new(4, 4).build().unwrap();
+        save_png_to_file(png, &dir.path().join("image.png"), &WriteOptions::default()).unwrap();
+        assert_eq!(mode("output"), mode("expected"));
+        assert_eq!(mode("image.png"), mode("expected"));
+
+        write_private_file(&dir.path().join("key"), b"secret").unwrap();
+        assert_eq!(mode("key"), 0o600);
+
+        // An existing secret key overwritten with --force is narrowed too
+        std::fs::set_permissions(
+            dir.path().join("key"),
+            std::fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        write_private_file(&dir.path().join("key"), b"secret").unwrap();
+        assert_eq!(mode("key"), 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserve_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let png = PngBuilder::new(4, 4).build().unwrap();
+        save_png_to_file(png, &path, &WriteOptions::default()).unwrap();
+
+        let set_mode =
+            |mode| std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        let mode = || std::fs::metadata(&path).unwrap().permissions().mode() & 0o7777;
+
+        set_mode(0o2640);
+        encode_in_place(&path, &WriteOptions::default());
+        assert_eq!(mode(), 0o640);
+
+        set_mode(0o2640);
+        let options = WriteOptions {
+            preserve_mode: true,
+            ..WriteOptions::default()
+        };
+        encode_in_place(&path, &options);
+        assert_eq!(mode(), 0o2640);
+    }
 }
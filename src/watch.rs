@@ -0,0 +1,107 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use fehler::throws;
+use notify::{event::EventKind, RecursiveMode, Watcher};
+use pngme_lib::{chunk::Chunk, chunk_type::ChunkType};
+
+use crate::{
+    batch::expand_batch_path,
+    cli::Watch,
+    output::outln,
+    util::{parse_png_from_file, save_png_to_file},
+};
+
+/// How long to wait for more changes before embedding, exporters often write
+/// a file in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+/// Embeds the message in the png at `path`, unless it is already there.
+#[throws(anyhow::Error)]
+fn embed(args: &Watch, path: &Path) {
+    let message = std::fs::read(&args.message_file).context("failed to read message file")?;
+    let chunk_type = ChunkType::from_str(&args.chunk).context("invalid chunk type")?;
+    let mut png = parse_png_from_file(path)?;
+
+    // Writing the png triggers another change, which ends up here
+    let existing: Vec<&Chunk> = png.chunks_by_type(&args.chunk).collect();
+    if matches!(existing[..], [chunk] if chunk.data() == message) {
+        return;
+    }
+
+    png.retain_chunks(|chunk| *chunk.chunk_type() != chunk_type);
+    png.insert_chunk_ordered(Chunk::new(chunk_type, message));
+    save_png_to_file(png, path, &args.write)?;
+
+    outln!("Embedded message in {}", path.display());
+}
+
+fn embed_all(args: &Watch, paths: impl IntoIterator<Item = PathBuf>) {
+    for path in paths {
+        if let Err(error) = embed(args, &path) {
+            eprintln!("error: {}: {:#}", path.display(), error);
+        }
+    }
+}
+
+#[throws(anyhow::Error)]
+pub fn run(args: Watch) {
+    let message_file = args
+        .message_file
+        .canonicalize()
+        .context("failed to read message file")?;
+
+    // A directory without pngs yet is fine, they may show up later
+    embed_all(&args, expand_batch_path(&args.dir).unwrap_or_default());
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("failed to start watching")?;
+    watcher
+        .watch(&args.dir, RecursiveMode::Recursive)
+        .with_context(|| format!("failed to watch {}", args.dir.display()))?;
+    watcher
+        .watch(&message_file, RecursiveMode::NonRecursive)
+        .context("failed to watch message file")?;
+
+    eprintln!(
+        "Watching {} for changes, press Ctrl-C to stop",
+        args.dir.display()
+    );
+
+    for event in &receiver {
+        let mut events = vec![event];
+        while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+            events.push(event);
+        }
+
+        let mut paths = BTreeSet::new();
+        for event in events {
+            let event = event.context("failed to watch for changes")?;
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                paths.extend(event.paths);
+            }
+        }
+
+        if paths.contains(&message_file) {
+            embed_all(&args, expand_batch_path(&args.dir).unwrap_or_default());
+        } else {
+            embed_all(
+                &args,
+                paths
+                    .into_iter()
+                    .filter(|path| is_png(path) && path.is_file()),
+            );
+        }
+    }
+}
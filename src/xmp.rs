@@ -0,0 +1,74 @@
+use anyhow::Context;
+use fehler::{throw, throws};
+use pngme_lib::xmp::XmpChunk;
+
+use crate::{
+    cli::{Xmp, XmpGet, XmpRemove, XmpSet},
+    error::not_found,
+    output::{outln, Snapshot},
+    util::{
+        check_output_path, is_stdio, parse_png_from_file, read_input_file, resolve_output_path,
+        save_png_or_preview,
+    },
+};
+
+#[throws(anyhow::Error)]
+pub fn run(command: Xmp) {
+    match command {
+        Xmp::Get(args) => get(args),
+        Xmp::Set(args) => set(args),
+        Xmp::Remove(args) => remove(args),
+    }?
+}
+
+#[throws(anyhow::Error)]
+fn get(args: XmpGet) {
+    let png = parse_png_from_file(&args.png_path)?;
+
+    let chunk = png
+        .chunks()
+        .iter()
+        .find(|chunk| XmpChunk::is_xmp(chunk))
+        .ok_or_else(|| not_found("no xmp packet found"))?;
+    let xmp = XmpChunk::try_from(chunk).context("invalid xmp chunk")?;
+
+    outln!("{}", xmp.packet());
+}
+
+#[throws(anyhow::Error)]
+fn set(args: XmpSet) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let packet = String::from_utf8(read_input_file(&args.xmp_path)?)
+        .context("xmp packet is not valid utf8")?;
+    let xmp = XmpChunk::new(&packet).context("invalid xmp packet")?;
+
+    png.retain_chunks(|chunk| !XmpChunk::is_xmp(chunk));
+    png.insert_chunk_ordered(xmp.to_chunk());
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}
+
+#[throws(anyhow::Error)]
+fn remove(args: XmpRemove) {
+    let mut png = parse_png_from_file(&args.png_path)?;
+    let output_path = resolve_output_path(&args.png_path, args.output.as_deref());
+    check_output_path(&args.png_path, &output_path, args.guard.force)?;
+    let dry_run = args.guard.dry_run.then(|| Snapshot::new(&png));
+
+    let count = png.chunks().len();
+    png.retain_chunks(|chunk| !XmpChunk::is_xmp(chunk));
+
+    if png.chunks().len() == count {
+        throw!(not_found("no xmp packet found"))
+    }
+
+    if !is_stdio(&output_path) {
+        outln!("Removed xmp packet");
+    }
+
+    save_png_or_preview(png, &output_path, dry_run, &args.write)?;
+}